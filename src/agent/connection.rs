@@ -428,6 +428,7 @@ impl AgentConnection {
                                             let log_file = match kind.as_str() {
                                                 "out" | "stdout" => process.logs().out,
                                                 "error" | "stderr" => process.logs().error,
+                                                "combined" => process.logs().combined,
                                                 _ => process.logs().out,
                                             };
 
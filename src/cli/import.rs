@@ -1,20 +1,29 @@
 use super::{Item, Items};
 use colored::Colorize;
+use lazy_static::lazy_static;
 use macros_rs::{crashln, string};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     fs::{self, OpenOptions},
     io::prelude::*,
+    path::PathBuf,
 };
 
 use opm::{
     file::Exists,
     helpers,
-    process::{Env, Runner},
+    process::{balancer::Balancer, capture::Capture, deps::{self, Condition, Dependency}, environment::Environment, group::Group, listen::{self, Listen}, health::{Liveness, Readiness}, job::Job, restart::{Backoff, Restart, RestartPolicy}, rotation::Rotation, schedule, threshold::{ThresholdAction, Thresholds}, unix::rlimits::Limits, watch::WatchAction, Env, Exec, ExecMode, KillMode, Resources, Runner, StopPolicy},
+    secret::Secrets,
 };
 
+lazy_static! {
+    /// `${` not already escaped as `$${`
+    static ref TEMPLATE: Regex = Regex::new(r"(^|[^$])\$\{").unwrap();
+}
+
 #[derive(Deserialize, Debug)]
 struct ProcessWrapper {
     #[serde(alias = "process")]
@@ -28,21 +37,206 @@ struct Process {
     watch: Option<Watch>,
     #[serde(default)]
     env: Env,
+    env_file: Option<Vec<PathBuf>>,
     max_memory: Option<String>,
+    max_memory_for_ms: Option<u64>,
+    max_cpu: Option<f64>,
+    max_cpu_window_ms: Option<u64>,
+    threshold_action: Option<ThresholdAction>,
+    log_rotation: Option<Rotation>,
+    log_capture: Option<Capture>,
+    cwd: Option<PathBuf>,
+    user: Option<String>,
+    group: Option<String>,
+    umask: Option<String>,
+    memory_max: Option<String>,
+    cpu_max: Option<String>,
+    pids_max: Option<u64>,
+    limits: Option<Limits>,
+    exec_mode: Option<ExecMode>,
+    stop_signal: Option<String>,
+    kill_timeout_ms: Option<u64>,
+    kill_mode: Option<KillMode>,
+    ready: Option<Readiness>,
+    liveness: Option<Liveness>,
+    restart_policy: Option<RestartPolicy>,
+    success_exit_codes: Option<Vec<i32>>,
+    max_restarts: Option<u64>,
+    min_uptime_ms: Option<u64>,
+    backoff: Option<Backoff>,
+    cron_restart: Option<String>,
+    job: Option<Job>,
+    #[serde(default)]
+    depends_on: Vec<Dependency>,
+    #[serde(default)]
+    listen: Vec<Listen>,
+    instances: Option<usize>,
+    port_range: Option<String>,
+    max_unavailable: Option<usize>,
+    balance: Option<Balancer>,
+    /// Named environments, written as `env_<name>` blocks
+    #[serde(flatten)]
+    overlays: BTreeMap<String, hcl::Value>,
 }
 
+/// Written as `path` for a single path, `paths` for several
 #[derive(Serialize, Deserialize, Debug)]
 struct Watch {
-    path: String,
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    paths: Option<Vec<String>>,
+    #[serde(default)]
+    ignore: Option<Vec<String>>,
+    #[serde(default)]
+    debounce_ms: Option<u64>,
+    #[serde(default)]
+    action: Option<WatchAction>,
 }
 
 impl Process {
-    fn get_watch_path(&self) -> Option<String> {
-        self.watch.as_ref().and_then(|w| Some(w.path.clone()))
+    fn get_watch(&self, name: &str) -> Option<opm::process::Watch> {
+        let watch = self.watch.as_ref()?;
+        let watch = opm::process::Watch {
+            enabled: true,
+            paths: watch.path.iter().chain(watch.paths.iter().flatten()).cloned().collect(),
+            ignore: watch.ignore.clone(),
+            debounce_ms: watch.debounce_ms,
+            action: watch.action.unwrap_or_default(),
+        };
+        if let Err(err) = watch.validate() {
+            crashln!("{} Invalid watch for ({name}): {err}", *helpers::FAIL);
+        }
+        Some(watch)
+    }
+
+    fn get_environment(&self, name: &str, selected: &Option<String>) -> Environment {
+        let mut overlays = BTreeMap::new();
+        for (key, value) in &self.overlays {
+            let Some(overlay) = key.strip_prefix("env_") else {
+                continue;
+            };
+            let hcl::Value::Object(vars) = value else {
+                crashln!("{} Invalid {key} for ({name}): expected a block of variables", *helpers::FAIL);
+            };
+            let vars = vars.iter().map(|(var, value)| match value {
+                hcl::Value::String(value) => (var.clone(), value.clone()),
+                hcl::Value::Number(_) | hcl::Value::Bool(_) => (var.clone(), value.to_string()),
+                _ => crashln!("{} Invalid {key}.{var} for ({name}): expected a string", *helpers::FAIL),
+            });
+            overlays.insert(overlay.to_string(), vars.collect());
+        }
+
+        let environment = Environment {
+            vars: self.env.clone(),
+            files: self.env_file.clone().unwrap_or_default(),
+            overlays,
+            selected: selected.clone(),
+        };
+        if let Err(err) = environment.validate() {
+            crashln!("{} Invalid --env for ({name}): {err}", *helpers::FAIL);
+        }
+        environment
+    }
+
+    fn get_exec(&self, name: &str, selected: &Option<String>) -> Exec {
+        let umask = match self.umask.as_deref().map(helpers::parse_umask).transpose() {
+            Ok(umask) => umask,
+            Err(err) => crashln!("{} Invalid umask for ({name}): {err}", *helpers::FAIL),
+        };
+
+        let resources = match Resources::parse(
+            self.memory_max.as_deref(),
+            self.cpu_max.as_deref(),
+            self.pids_max,
+        ) {
+            Ok(resources) => resources,
+            Err(err) => crashln!("{} Invalid resource limit for ({name}): {err}", *helpers::FAIL),
+        };
+
+        let stop = match StopPolicy::parse(
+            self.stop_signal.as_deref(),
+            self.kill_timeout_ms,
+            self.kill_mode,
+        ) {
+            Ok(stop) => stop,
+            Err(err) => crashln!("{} Invalid stop policy for ({name}): {err}", *helpers::FAIL),
+        };
+
+        if let Some(Err(err)) = self.ready.as_ref().map(Readiness::validate) {
+            crashln!("{} Invalid readiness check for ({name}): {err}", *helpers::FAIL);
+        }
+        if let Some(Err(err)) = self.liveness.as_ref().map(Liveness::validate) {
+            crashln!("{} Invalid liveness probe for ({name}): {err}", *helpers::FAIL);
+        }
+
+        let restart = Restart {
+            policy: self.restart_policy.unwrap_or_default(),
+            success_exit_codes: self.success_exit_codes.clone().unwrap_or_default(),
+            max_restarts: self.max_restarts,
+            min_uptime_ms: self.min_uptime_ms,
+            backoff: self.backoff.clone().unwrap_or_default(),
+        };
+        if let Err(err) = restart.validate() {
+            crashln!("{} Invalid restart policy for ({name}): {err}", *helpers::FAIL);
+        }
+
+        if let Some(Err(err)) = self.cron_restart.as_deref().map(schedule::validate) {
+            crashln!("{} Invalid cron_restart for ({name}): {err}", *helpers::FAIL);
+        }
+
+        if let Some(Err(err)) = self.job.as_ref().map(Job::validate) {
+            crashln!("{} Invalid job for ({name}): {err}", *helpers::FAIL);
+        }
+
+        if let Err(err) = deps::validate(name, &self.depends_on) {
+            crashln!("{} Invalid depends_on for ({name}): {err}", *helpers::FAIL);
+        }
+
+        if let Err(err) = listen::validate(&self.listen, false) {
+            crashln!("{} Invalid listen for ({name}): {err}", *helpers::FAIL);
+        }
+
+        let thresholds = Thresholds {
+            action: self.threshold_action.clone().unwrap_or_default(),
+            memory_for_ms: self.max_memory_for_ms,
+            max_cpu: self.max_cpu,
+            cpu_window_ms: self.max_cpu_window_ms,
+        };
+        if let Err(err) = thresholds.validate() {
+            crashln!("{} Invalid thresholds for ({name}): {err}", *helpers::FAIL);
+        }
+
+        if let Some(Err(err)) = self.log_rotation.as_ref().map(Rotation::validate) {
+            crashln!("{} Invalid log_rotation for ({name}): {err}", *helpers::FAIL);
+        }
+
+        Exec {
+            cwd: self.cwd.clone(),
+            user: self.user.clone(),
+            group: self.group.clone(),
+            umask,
+            resources,
+            limits: self.limits.clone().unwrap_or_default(),
+            mode: self.exec_mode,
+            stop,
+            ready: self.ready.clone(),
+            liveness: self.liveness.clone(),
+            restart,
+            cron_restart: self.cron_restart.clone(),
+            job: self.job.clone(),
+            depends_on: self.depends_on.clone(),
+            listen: self.listen.clone(),
+            environment: self.get_environment(name, selected),
+            thresholds,
+            log_rotation: self.log_rotation.clone(),
+            log_capture: self.log_capture.clone(),
+            instance: None,
+        }
     }
 }
 
-pub fn read_hcl(path: &String) {
+pub fn read_hcl(path: &String, environment: &Option<String>) {
     let mut servers: Vec<String> = vec![];
 
     println!("{} Applying action importProcess", *helpers::SUCCESS);
@@ -56,6 +250,8 @@ pub fn read_hcl(path: &String) {
         ),
     };
 
+    // ${VAR} is expanded by opm when the process starts, HCL only has to keep it as text
+    let contents = TEMPLATE.replace_all(&contents, "${1}$$$${");
     let hcl_parsed: ProcessWrapper = match hcl::from_str(&contents) {
         Ok(hcl) => hcl,
         Err(err) => crashln!(
@@ -65,36 +261,84 @@ pub fn read_hcl(path: &String) {
         ),
     };
 
-    for (name, item) in hcl_parsed.list {
+    // Import in dependency order, a dependency outside of the file must already exist
+    let existing = Runner::new();
+    let mut list: Vec<(String, Process)> = hcl_parsed.list.into_iter().collect();
+    list.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (name, item) in &list {
+        let unknown = item.depends_on.iter().find(|dependency| {
+            !list.iter().any(|(other, _)| *other == dependency.name) && !existing.list.values().any(|p| p.name == dependency.name)
+        });
+        if let Some(dependency) = unknown {
+            crashln!("{} Process ({name}) depends on unknown process ({})", *helpers::FAIL, dependency.name);
+        }
+    }
+
+    let nodes: Vec<(usize, &str, &[Dependency])> = list
+        .iter()
+        .enumerate()
+        .map(|(index, (name, item))| (index, name.as_str(), item.depends_on.as_slice()))
+        .collect();
+    let order = match deps::start_order(&nodes) {
+        Ok(order) => order,
+        Err(err) => crashln!("{} Cannot import processes: {err}", *helpers::FAIL),
+    };
+    let mut list: Vec<Option<(String, Process)>> = list.into_iter().map(Some).collect();
+
+    for (name, item) in order.into_iter().filter_map(|index| list[index].take()) {
+        for dependency in item.depends_on.iter().filter(|dependency| dependency.condition == Condition::Ready) {
+            if !super::internal::wait_for_dependency(dependency) {
+                println!("{} Dependency ({dependency}) of ({name}) is not ready, starting it anyway", *helpers::WARN);
+            }
+        }
+
         let mut runner = Runner::new();
         let server_name = &item.server.clone().unwrap_or("local".into());
         let (kind, list_name) = super::format(server_name);
 
-        runner = super::Internal {
+        let internal = super::Internal {
             id: 0,
             server_name,
             kind: kind.clone(),
             runner: runner.clone(),
-        }
-        .create(
-            &item.script,
-            &Some(name.clone()),
-            &item.get_watch_path(),
-            &item.max_memory,
-            true,
-        );
+        };
+        runner = match item.instances {
+            Some(instances) => {
+                let ports = item.port_range.as_deref().map(super::parse_port_range).unwrap_or_default();
+                let group = match Group::new(instances, &ports) {
+                    Ok(group) => Group {
+                        max_unavailable: item.max_unavailable,
+                        balancer: item.balance.clone(),
+                        ..group
+                    },
+                    Err(err) => crashln!("{} Invalid instances for ({name}): {err}", *helpers::FAIL),
+                };
+                internal.create_group(
+                    &item.script,
+                    &name,
+                    &group,
+                    &item.get_watch(&name),
+                    &item.max_memory,
+                    &item.get_exec(&name, environment),
+                )
+            }
+            None => internal.create(
+                &item.script,
+                &Some(name.clone()),
+                &item.get_watch(&name),
+                &item.max_memory,
+                &item.get_exec(&name, environment),
+                true,
+            ),
+        };
 
         println!("{} Imported {kind}process {name}", *helpers::SUCCESS);
 
-        match runner.find(&name, server_name) {
-            Some(id) => {
-                let mut p = runner.get(id);
-                p.stop();
-                p.set_env(item.env);
-                p.restart(false);
-            }
-            None => crashln!("{} Failed to write to ({name})", *helpers::FAIL),
+        if runner.find_all(&name, server_name).is_empty() {
+            crashln!("{} Failed to write to ({name})", *helpers::FAIL);
         }
+        runner.save();
 
         if !servers.contains(&list_name) {
             servers.push(list_name);
@@ -131,9 +375,9 @@ pub fn export_hcl(items: &Items, path: &Option<String>) {
         for item in &items.items {
             match item {
                 Item::Id(id) => process_ids.push(*id),
-                Item::Name(name) => match runner.find(&name, &string!("internal")) {
-                    Some(id) => process_ids.push(id),
-                    None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
+                Item::Name(name) => match runner.find_all(&name, &string!("internal")) {
+                    ids if !ids.is_empty() => process_ids.extend(ids),
+                    _ => crashln!("{} Process ({name}) not found", *helpers::FAIL),
                 },
             }
         }
@@ -164,22 +408,50 @@ pub fn export_hcl(items: &Items, path: &Option<String>) {
         }
     }
 
-    // Export each process
-    let count = process_ids.len();
+    // Export each process, a group once with its instance count
+    let mut exported_groups = HashSet::new();
+    let mut count = 0;
+    let secrets = Secrets::redactor();
     for id in &process_ids {
-        let process = runner.try_info(*id);
+        let mut process = runner.try_info(*id).clone();
+        process.redact(&secrets);
+        let group = process
+            .exec
+            .instance
+            .as_ref()
+            .and_then(|instance| instance.group.as_ref())
+            .and_then(|name| runner.groups.get(name).map(|group| (name.clone(), group.clone())));
+        if let Some((name, _)) = &group {
+            if !exported_groups.insert(name.clone()) {
+                continue;
+            }
+        }
+        count += 1;
+
         let mut watch_parsed = None;
         let mut env_parsed = HashMap::new();
 
         let current_env: HashMap<String, String> = std::env::vars().collect();
 
         if process.watch.enabled {
+            let watch = &process.watch;
+            let single = watch.paths.len() == 1;
             watch_parsed = Some(Watch {
-                path: process.watch.path.clone(),
+                path: single.then(|| watch.paths[0].clone()),
+                paths: (!single).then(|| watch.paths.clone()),
+                ignore: watch.ignore.clone(),
+                debounce_ms: watch.debounce_ms,
+                action: (watch.action != WatchAction::Restart).then_some(watch.action),
             })
         }
 
+        // Every instance is given its own position and port again on import
+        let instance_env = process.exec.instance.as_ref().map(|instance| instance.env()).unwrap_or_default();
+
         for (key, value) in process.env.clone() {
+            if instance_env.contains_key(&key) {
+                continue;
+            }
             if let Some(current_value) = current_env.get(&key) {
                 if current_value != &value {
                     env_parsed.insert(key, value);
@@ -189,6 +461,9 @@ pub fn export_hcl(items: &Items, path: &Option<String>) {
             }
         }
 
+        // Declared variables are exported as written, templates included
+        env_parsed.extend(process.exec.environment.vars.clone());
+
         // Format max_memory for export (convert bytes to human-readable format)
         let max_memory_str = if process.max_memory > 0 {
             Some(helpers::format_memory(process.max_memory))
@@ -196,15 +471,61 @@ pub fn export_hcl(items: &Items, path: &Option<String>) {
             None
         };
 
-        let data = hcl::block! {
-            process (process.name.clone()) {
+        let (name, instances, port_range, max_unavailable, balance) = match group {
+            Some((name, group)) => (name, Some(group.instances), group.port_range(), group.max_unavailable, group.balancer),
+            None => (process.name.clone(), None, None, None, None),
+        };
+
+        let environment = &process.exec.environment;
+        let thresholds = &process.exec.thresholds;
+        let mut data = hcl::block! {
+            process (name) {
                 script = (process.script.clone())
                 watch = (watch_parsed)
                 env = (env_parsed)
+                env_file = ((!environment.files.is_empty()).then(|| environment.files.clone()))
                 max_memory = (max_memory_str)
+                max_memory_for_ms = (thresholds.memory_for_ms)
+                max_cpu = (thresholds.max_cpu)
+                max_cpu_window_ms = (thresholds.cpu_window_ms)
+                threshold_action = ((thresholds.action != ThresholdAction::Stop).then(|| thresholds.action.to_string()))
+                log_rotation = (process.exec.log_rotation.clone())
+                log_capture = (process.exec.log_capture.clone())
+                cwd = (process.exec.cwd.as_ref().map(|cwd| cwd.to_string_lossy().into_owned()))
+                user = (process.exec.user.clone())
+                group = (process.exec.group.clone())
+                umask = (process.exec.format_umask())
+                memory_max = (process.exec.resources.memory_max.map(helpers::format_memory))
+                cpu_max = (process.exec.resources.format_cpu_max())
+                pids_max = (process.exec.resources.pids_max)
+                limits = ((!process.exec.limits.is_empty()).then(|| process.exec.limits.clone()))
+                exec_mode = (process.exec.mode.map(|mode| mode.to_string()))
+                stop_signal = (process.exec.stop.stop_signal.clone())
+                kill_timeout_ms = (process.exec.stop.kill_timeout_ms)
+                kill_mode = ((process.exec.stop.kill_mode != KillMode::Group).then(|| process.exec.stop.kill_mode.to_string()))
+                ready = (process.exec.ready.clone())
+                liveness = (process.exec.liveness.clone())
+                restart_policy = ((process.exec.restart.policy != RestartPolicy::OnFailure).then(|| process.exec.restart.policy.to_string()))
+                success_exit_codes = ((!process.exec.restart.success_exit_codes.is_empty()).then(|| process.exec.restart.success_exit_codes.clone()))
+                max_restarts = (process.exec.restart.max_restarts)
+                min_uptime_ms = (process.exec.restart.min_uptime_ms)
+                backoff = ((process.exec.restart.backoff != Backoff::default()).then(|| process.exec.restart.backoff.clone()))
+                cron_restart = (process.exec.cron_restart.clone())
+                job = (process.exec.job.clone())
+                depends_on = (process.exec.depends_on.clone())
+                listen = (process.exec.listen.clone())
+                instances = (instances)
+                port_range = (port_range)
+                max_unavailable = (max_unavailable)
+                balance = (balance)
             }
         };
 
+        for (overlay, vars) in &environment.overlays {
+            let vars = hcl::to_expression(vars).unwrap();
+            data.body.0.push(hcl::Attribute::new(format!("env_{overlay}"), vars).into());
+        }
+
         let serialized = hcl::to_string(&data).unwrap();
 
         // Append to file
@@ -238,3 +559,31 @@ pub fn export_hcl(items: &Items, path: &Option<String>) {
         output_path
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn imported(block: hcl::Block) -> Process {
+        let exported = hcl::to_string(&block).unwrap();
+        let mut wrapper: ProcessWrapper = hcl::from_str(&exported).unwrap_or_else(|err| panic!("{err}\n{exported}"));
+        wrapper.list.remove("api").unwrap()
+    }
+
+    #[test]
+    fn test_env_file_round_trip() {
+        let absent = imported(hcl::block! { process "api" { script = "node server.js" } });
+        assert_eq!(absent.env_file, None);
+
+        // Export writes `env_file = null` for a process without env files
+        let none: Option<Vec<PathBuf>> = None;
+        let null = imported(hcl::block! { process "api" { script = "node server.js" env_file = (none) } });
+        assert_eq!(null.env_file, None);
+        assert!(null.get_environment("api", &None).files.is_empty());
+
+        let files = vec![PathBuf::from(".env"), PathBuf::from(".env.local")];
+        let list = imported(hcl::block! { process "api" { script = "node server.js" env_file = (Some(files.clone())) } });
+        assert_eq!(list.env_file.as_ref(), Some(&files));
+        assert_eq!(list.get_environment("api", &None).files, files);
+    }
+}
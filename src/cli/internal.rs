@@ -17,8 +17,10 @@ use opm::{
     log,
     process::{
         extract_search_pattern_from_command, get_process_cpu_usage_with_children_from_process, http,
-        is_any_descendant_alive, is_pid_alive, ItemSingle, Runner,
+        entry::Filter, deps::{Condition, Dependency}, group::Group, is_any_descendant_alive, is_pid_alive, job::JobRun, listen, restart::{CrashRecord, RestartPolicy}, rotation, schedule, unix::rlimits::Limits, Exec, Watch, ItemSingle, Resources,
+        Env, Runner,
     },
+    secret::Secrets,
 };
 
 use tabled::{
@@ -42,6 +44,181 @@ fn format_last_restart_attempt(item: &opm::process::Process) -> String {
         .unwrap_or_else(|| "never".to_string())
 }
 
+fn exec_identity(value: &Option<String>) -> String {
+    value.clone().unwrap_or_else(|| "inherited".to_string())
+}
+
+/// Readiness check and whether the current instance passed it, for `opm info`
+fn readiness(item: &opm::process::Process) -> String {
+    match &item.exec.ready {
+        Some(ready) if item.launching.is_some() => format!("{}, waiting  ", ready.describe()),
+        Some(ready) => format!("{}  ", ready.describe()),
+        None => string!("none  "),
+    }
+}
+
+/// Liveness probe and the result of its last run, for `opm info`
+fn liveness(item: &opm::process::Process) -> String {
+    let Some(liveness) = &item.exec.liveness else {
+        return string!("none  ");
+    };
+    match &item.health.last_error {
+        Some(err) => format!("{}, {} ({})  ", liveness.describe(), item.health.status, err),
+        None => format!("{}, {}  ", liveness.describe(), item.health.status),
+    }
+}
+
+/// Most recent crash, its time and uptime, for `opm info`
+fn last_crash(item: &opm::process::Process) -> String {
+    match item.crash_history.last() {
+        Some(crash) => format!(
+            "{} at {} after {}ms ({} recorded)  ",
+            crash,
+            crash.time.to_rfc3339(),
+            crash.uptime_ms,
+            item.crash_history.len()
+        ),
+        None => string!("none  "),
+    }
+}
+
+/// cron_restart schedule with its next run in local time, for `opm info`
+fn cron_restart(item: &opm::process::Process) -> String {
+    let Some(expression) = &item.exec.cron_restart else {
+        return string!("none  ");
+    };
+    match schedule::next_after(expression, chrono::Utc::now()) {
+        Some(next) => format!("{}, next at {}  ", expression, next.with_timezone(&chrono::Local).to_rfc3339()),
+        None => format!("{}, no next run  ", expression),
+    }
+}
+
+/// List status of a job between runs, see Process::job_status
+fn job_status(status: &str) -> colored::ColoredString {
+    let padded = format!("{:<9}", status);
+    match status {
+        "completed" => padded.green().bold(),
+        "failed" => padded.red().bold(),
+        _ => padded.cyan().bold(),
+    }
+}
+
+/// Schedule and timeout of a job, for `opm info`
+fn job(item: &opm::process::Process) -> String {
+    match &item.exec.job {
+        Some(job) => format!("{}  ", job.describe()),
+        None => string!("none  "),
+    }
+}
+
+/// Most recent run of a job with its start time, for `opm info`
+fn last_run(item: &opm::process::Process) -> String {
+    match item.runs.last() {
+        Some(run) => format!("{}, started at {} ({} recorded)  ", run, run.started.to_rfc3339(), item.runs.len()),
+        None => string!("none  "),
+    }
+}
+
+/// Dependencies of a process, for `opm info`
+fn depends_on(item: &opm::process::Process) -> String {
+    match item.exec.depends_on.is_empty() {
+        true => string!("none  "),
+        false => format!("{}  ", item.exec.depends_on.iter().map(|dependency| dependency.to_string()).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+/// Sockets the daemon passes to a process, for `opm info`
+fn listen_sockets(item: &opm::process::Process) -> String {
+    match item.exec.listen.is_empty() {
+        true => string!("none  "),
+        false => format!("{}  ", item.exec.listen.iter().map(|socket| socket.to_string()).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+/// Worker position and port of a process started with --workers, for `opm info`
+fn instance(item: &opm::process::Process) -> String {
+    match &item.exec.instance {
+        Some(instance) => format!("{}  ", instance.describe()),
+        None => string!("none  "),
+    }
+}
+
+/// How the daemon captures the output of a process, for `opm info`
+fn log_capture(item: &opm::process::Process) -> String {
+    match &item.exec.log_capture {
+        Some(capture) => format!("{}  ", capture.describe()),
+        None => string!("off  "),
+    }
+}
+
+/// Whether `opm logs` reads the combined log instead of the out and error logs
+fn combined_log(item: &opm::process::Process) -> bool {
+    item.exec.log_capture.as_ref().is_some_and(|capture| capture.combined)
+}
+
+/// Hand the local process state to the daemon, so it monitors the processes started from it
+fn send_state(runner: &Runner) {
+    use global_placeholders::global;
+
+    let socket_path = global!("opm.socket");
+    match opm::socket::send_request(&socket_path, opm::socket::SocketRequest::SetState(runner.clone())) {
+        Ok(opm::socket::SocketResponse::Success) => {}
+        Ok(opm::socket::SocketResponse::Error(message)) => ::log::warn!("Failed to update daemon state via socket: {}", message),
+        Ok(_) => ::log::warn!("Unexpected response when updating daemon state"),
+        Err(e) => ::log::warn!("Failed to communicate with daemon to update state: {}", e),
+    }
+}
+
+/// Wait until the daemon reports a dependency as up, false when it is not within its ready timeout
+pub(crate) fn wait_for_dependency(dependency: &Dependency) -> bool {
+    let started = std::time::Instant::now();
+    loop {
+        let runner = Runner::new();
+        let Some(process) = runner.list.values().find(|process| process.name == dependency.name) else {
+            return true;
+        };
+        if process.satisfies(dependency.condition) {
+            return true;
+        }
+        if started.elapsed() > process.ready_timeout() {
+            return false;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(DEPENDENCY_POLL_INTERVAL_MS));
+    }
+}
+
+fn exec_mode(exec: &Exec, command: &str) -> String {
+    match exec.mode {
+        Some(mode) => format!("{mode}  "),
+        None => format!("{} (auto)  ", exec.resolve_mode(command)),
+    }
+}
+
+fn effective_rlimits(limits: Option<Limits>) -> String {
+    match limits {
+        Some(limits) => format!("{}  ", limits.describe()),
+        None => "n/a  ".to_string(),
+    }
+}
+
+fn cgroup_limits(resources: &Resources) -> String {
+    if resources.is_empty() {
+        return "none  ".to_string();
+    }
+
+    let mut limits = vec![];
+    if let Some(bytes) = resources.memory_max {
+        limits.push(format!("memory={}", helpers::format_memory(bytes)));
+    }
+    if let Some(cpu) = resources.format_cpu_max() {
+        limits.push(format!("cpu={cpu}"));
+    }
+    if let Some(pids) = resources.pids_max {
+        limits.push(format!("pids={pids}"));
+    }
+    format!("{}  ", limits.join(" "))
+}
+
 fn ensure_daemon_running() {
     use global_placeholders::global;
 
@@ -114,6 +291,8 @@ fn ensure_daemon_running() {
 
 // Constants for real-time statistics display timing
 pub(crate) const STATS_PRE_LIST_DELAY_MS: u64 = 100;
+// How often to ask the daemon whether a dependency is up while waiting for it
+const DEPENDENCY_POLL_INTERVAL_MS: u64 = 250;
 
 pub struct Internal<'i> {
     pub id: usize,
@@ -127,8 +306,9 @@ impl<'i> Internal<'i> {
         mut self,
         script: &String,
         name: &Option<String>,
-        watch: &Option<String>,
+        watch: &Option<Watch>,
         max_memory: &Option<String>,
+        exec: &Exec,
         silent: bool,
     ) -> Runner {
         let config = config::read();
@@ -146,6 +326,15 @@ impl<'i> Internal<'i> {
             None => 0,
         };
 
+        if let Err(err) = self.runner.check_dependencies(&name, &exec.depends_on) {
+            crashln!("{} {}", *helpers::FAIL, err);
+        }
+
+        let shared_port = exec.instance.as_ref().is_some_and(|instance| instance.shared_port);
+        if let Err(err) = listen::validate(&exec.listen, shared_port) {
+            crashln!("{} {}", *helpers::FAIL, err);
+        }
+
         if matches!(self.server_name, "internal" | "local") {
             ensure_daemon_running();
             // Check if script is a file path with an extension
@@ -185,7 +374,14 @@ impl<'i> Internal<'i> {
             };
 
             self.runner
-                .start(&name, &script_to_run, file::cwd(), watch, max_memory_bytes);
+                .start(
+                    &name,
+                    &script_to_run,
+                    file::cwd(),
+                    watch,
+                    max_memory_bytes,
+                    exec.clone(),
+                );
         } else {
             let Some(servers) = config::servers().servers else {
                 crashln!("{} Failed to read servers", *helpers::FAIL)
@@ -193,9 +389,14 @@ impl<'i> Internal<'i> {
 
             if let Some(server) = servers.get(self.server_name) {
                 match Runner::connect(self.server_name.into(), server.get(), false) {
-                    Some(mut remote) => {
-                        remote.start(&name, script, file::cwd(), watch, max_memory_bytes)
-                    }
+                    Some(mut remote) => remote.start(
+                        &name,
+                        script,
+                        file::cwd(),
+                        watch,
+                        max_memory_bytes,
+                        exec.clone(),
+                    ),
                     None => crashln!(
                         "{} Failed to connect (name={}, address={})",
                         *helpers::FAIL,
@@ -228,10 +429,72 @@ impl<'i> Internal<'i> {
         return self.runner;
     }
 
+    /// Create every instance of a worker group, each is told its position and port through the environment
+    pub fn create_group(
+        mut self,
+        script: &String,
+        name: &str,
+        group: &Group,
+        watch: &Option<Watch>,
+        max_memory: &Option<String>,
+        exec: &Exec,
+    ) -> Runner {
+        if let Err(err) = group.validate(name) {
+            crashln!("{} {}", *helpers::FAIL, err);
+        }
+        if self.runner.groups.contains_key(name) || !self.runner.group_instances(name).is_empty() {
+            crashln!(
+                "{} Group ({name}) already exists, use `opm scale {name} {}` to resize it",
+                *helpers::FAIL,
+                group.instances
+            );
+        }
+        // Remote servers only learn about the group from the instances themselves
+        if matches!(self.server_name, "internal" | "local") {
+            self.runner.groups.insert(name.to_string(), group.clone());
+        }
+
+        for index in 0..group.instances {
+            let instance = group.instance(name, index);
+            let port_info = match instance.port {
+                Some(port) if instance.shared_port => format!(" (PORT={} via SO_REUSEPORT)", port),
+                Some(port) => format!(" (PORT={})", port),
+                None => String::new(),
+            };
+            println!(
+                "  {} Starting worker {} of {}{}",
+                *helpers::SUCCESS,
+                index + 1,
+                group.instances,
+                port_info
+            );
+
+            self.runner = Internal {
+                id: 0, // 0 means create new process
+                server_name: self.server_name,
+                kind: self.kind.clone(),
+                runner: self.runner.clone(),
+            }
+            .create(
+                script,
+                &Some(Group::instance_name(name, index)),
+                watch,
+                max_memory,
+                &Exec {
+                    instance: Some(instance),
+                    ..exec.clone()
+                },
+                true,
+            );
+        }
+
+        self.runner
+    }
+
     pub fn restart(
         mut self,
         name: &Option<String>,
-        watch: &Option<String>,
+        watch: &Option<Watch>,
         reset_env: bool,
         silent: bool,
         increment_counter: bool,
@@ -251,7 +514,7 @@ impl<'i> Internal<'i> {
             let mut item = self.runner.get(self.id);
 
             match watch {
-                Some(path) => item.watch(path),
+                Some(watch) => item.watch(watch.clone()),
                 None => item.disable_watch(),
             }
 
@@ -572,11 +835,38 @@ impl<'i> Internal<'i> {
             memory_usage: String,
             #[tabled(rename = "memory limit")]
             memory_limit: String,
-            #[tabled(rename = "path hash")]
-            hash: String,
+            thresholds: String,
+            #[tabled(rename = "log rotation")]
+            log_rotation: String,
+            #[tabled(rename = "log capture")]
+            log_capture: String,
             #[tabled(rename = "watching")]
             watch: String,
             children: String,
+            #[tabled(rename = "cgroup limits")]
+            cgroup_limits: String,
+            #[tabled(rename = "rlimits")]
+            rlimits: String,
+            #[tabled(rename = "exec mode")]
+            exec_mode: String,
+            #[tabled(rename = "stop policy")]
+            stop_policy: String,
+            readiness: String,
+            liveness: String,
+            #[tabled(rename = "restart policy")]
+            restart_policy: String,
+            #[tabled(rename = "cron restart")]
+            cron_restart: String,
+            job: String,
+            #[tabled(rename = "depends on")]
+            depends_on: String,
+            listen: String,
+            instance: String,
+            umask: String,
+            #[tabled(rename = "exec group")]
+            group: String,
+            #[tabled(rename = "exec user")]
+            user: String,
             #[tabled(rename = "exec cwd")]
             path: String,
             #[tabled(rename = "script command ")]
@@ -588,6 +878,14 @@ impl<'i> Internal<'i> {
             failed_restart_attempts: u32,
             #[tabled(rename = "last restart attempt")]
             last_restart_attempt: String,
+            #[tabled(rename = "last crash")]
+            last_crash: String,
+            #[tabled(skip)]
+            crash_history: Vec<CrashRecord>,
+            #[tabled(rename = "last run")]
+            last_run: String,
+            #[tabled(skip)]
+            runs: Vec<JobRun>,
             uptime: String,
             pid: String,
             name: String,
@@ -601,10 +899,26 @@ impl<'i> Internal<'i> {
                      "pid": &self.pid.trim(),
                      "name": &self.name.trim(),
                      "path": &self.path.trim(),
+                     "user": &self.user.trim(),
+                     "group": &self.group.trim(),
+                     "umask": &self.umask.trim(),
+                     "cgroup_limits": &self.cgroup_limits.trim(),
+                     "rlimits": &self.rlimits.trim(),
+                     "exec_mode": &self.exec_mode.trim(),
+                     "stop_policy": &self.stop_policy.trim(),
+                     "readiness": &self.readiness.trim(),
+                     "liveness": &self.liveness.trim(),
+                     "restart_policy": &self.restart_policy.trim(),
+                     "cron_restart": &self.cron_restart.trim(),
+                     "job": &self.job.trim(),
+                     "depends_on": &self.depends_on.trim(),
+                     "listen": &self.listen.trim(),
+                     "instance": &self.instance.trim(),
                      "restarts": &self.restarts,
                      "failed_restart_attempts": &self.failed_restart_attempts,
                      "last_restart_attempt": &self.last_restart_attempt.trim(),
-                     "hash": &self.hash.trim(),
+                     "crash_history": &self.crash_history,
+                     "runs": &self.runs,
                      "watch": &self.watch.trim(),
                      "children": &self.children,
                      "uptime": &self.uptime.trim(),
@@ -614,6 +928,9 @@ impl<'i> Internal<'i> {
                      "command": &self.command.trim(),
                      "mem": &self.memory_usage.trim(),
                      "mem_limit": &self.memory_limit.trim(),
+                     "thresholds": &self.thresholds.trim(),
+                     "log_rotation": &self.log_rotation.trim(),
+                     "log_capture": &self.log_capture.trim(),
                      "log_error": &self.log_error.trim(),
                 });
 
@@ -718,7 +1035,7 @@ impl<'i> Internal<'i> {
                 let mut memory_usage: Option<MemoryInfo> = None;
                 let mut cpu_percent: Option<f64> = None;
 
-                let path = file::make_relative(&item.path, &home)
+                let cwd = file::make_relative(&item.exec.working_dir(&item.path), &home)
                     .to_string_lossy()
                     .into_owned();
                 let children = if item.children.is_empty() {
@@ -758,11 +1075,15 @@ impl<'i> Internal<'i> {
                     None => string!("0b"),
                 };
 
-                let status = if process_actually_running {
+                let status = if process_actually_running && item.launching.is_some() {
+                    "launching".cyan().bold()
+                } else if process_actually_running {
                     "online   ".green().bold()
                 } else if item.errored {
                     // Process reached restart limit - show as errored
                     "errored  ".red().bold()
+                } else if let Some(status) = item.job_status() {
+                    job_status(status)
                 } else if item.running && item.pid == 0 && crash_detection_enabled {
                     "starting  ".yellow().bold()
                 } else if crashed_while_running || crashed_by_flag {
@@ -804,16 +1125,46 @@ impl<'i> Internal<'i> {
                     cpu_percent,
                     memory_usage,
                     memory_limit,
+                    thresholds: format!("{}  ", item.exec.thresholds.describe(item.max_memory)),
+                    log_rotation: match (&item.exec.log_rotation, &full_config.daemon.log_rotation) {
+                        (Some(rotation), _) => format!("{}  ", rotation.describe()),
+                        (None, Some(rotation)) => format!("{} (global)  ", rotation.describe()),
+                        (None, None) => string!("none  "),
+                    },
+                    log_capture: log_capture(item),
                     id: string!(self.id),
                     // Always show restarts counter
                     // restarts is persisted and provides accurate restart count
                     restarts: item.restarts,
                     failed_restart_attempts: item.failed_restart_attempts,
                     last_restart_attempt: format_last_restart_attempt(item),
+                    last_crash: last_crash(item),
+                    crash_history: item.crash_history.clone(),
+                    last_run: last_run(item),
+                    runs: item.runs.clone(),
                     name: item.name.clone(),
                     log_out: item.logs().out,
-                    path: format!("{} ", path),
+                    path: format!("{} ", cwd),
                     log_error: item.logs().error,
+                    user: exec_identity(&item.exec.user),
+                    group: exec_identity(&item.exec.group),
+                    umask: item.exec.format_umask().unwrap_or_else(|| string!("inherited")),
+                    cgroup_limits: cgroup_limits(&item.exec.resources),
+                    exec_mode: exec_mode(&item.exec, &item.script),
+                    stop_policy: format!("{}  ", item.exec.stop.describe()),
+                    readiness: readiness(item),
+                    liveness: liveness(item),
+                    restart_policy: format!("{}  ", item.exec.restart.describe(config::read().daemon.restarts)),
+                    cron_restart: cron_restart(item),
+                    job: job(item),
+                    depends_on: depends_on(item),
+                    listen: listen_sockets(item),
+                    instance: instance(item),
+                    rlimits: effective_rlimits(ternary!(
+                        process_actually_running,
+                        opm::process::unix::rlimits::read(item.pid),
+                        None
+                    )),
                     status: ColoredString(status),
                     pid: ternary!(
                         process_actually_running,
@@ -826,16 +1177,7 @@ impl<'i> Internal<'i> {
                         config.args.join(" "),
                         item.script
                     ),
-                    hash: ternary!(
-                        item.watch.enabled,
-                        format!("{}  ", item.watch.hash),
-                        string!("none  ")
-                    ),
-                    watch: ternary!(
-                        item.watch.enabled,
-                        format!("{path}/{}  ", item.watch.path),
-                        string!("disabled  ")
-                    ),
+                    watch: format!("{}  ", item.watch.describe()),
                     uptime,
                 }];
 
@@ -870,9 +1212,17 @@ impl<'i> Internal<'i> {
             let (item, remote) = data;
             let remote = remote.remote.unwrap();
             let info = http::info(&remote, self.id);
-            let path = item.path.to_string_lossy().into_owned();
-
-            let status = if item.running {
+            let cwd = item
+                .exec
+                .working_dir(&item.path)
+                .to_string_lossy()
+                .into_owned();
+
+            let status = if item.running && item.launching.is_some() {
+                "launching".cyan().bold()
+            } else if let Some(status) = item.job_status() {
+                job_status(status)
+            } else if item.running {
                 "online   ".green().bold()
             } else if item.errored {
                 "errored  ".red().bold()
@@ -893,7 +1243,8 @@ impl<'i> Internal<'i> {
             };
 
             if let Ok(info) = info {
-                let stats = info.json::<ItemSingle>().unwrap().stats;
+                let single = info.json::<ItemSingle>().unwrap();
+                let (stats, limits) = (single.stats, single.info.limits);
                 let children = if item.children.is_empty() {
                     "none".to_string()
                 } else {
@@ -921,14 +1272,39 @@ impl<'i> Internal<'i> {
                     cpu_percent,
                     memory_usage,
                     memory_limit,
+                    thresholds: format!("{}  ", item.exec.thresholds.describe(item.max_memory)),
+                    log_rotation: match &item.exec.log_rotation {
+                        Some(rotation) => format!("{}  ", rotation.describe()),
+                        None => string!("server default  "),
+                    },
+                    log_capture: log_capture(&item),
                     id: string!(self.id),
-                    path: path.clone(),
+                    path: cwd,
+                    user: exec_identity(&item.exec.user),
+                    group: exec_identity(&item.exec.group),
+                    umask: item.exec.format_umask().unwrap_or_else(|| string!("inherited")),
+                    cgroup_limits: cgroup_limits(&item.exec.resources),
+                    exec_mode: exec_mode(&item.exec, &item.script),
+                    stop_policy: format!("{}  ", item.exec.stop.describe()),
+                    readiness: readiness(&item),
+                    liveness: liveness(&item),
+                    restart_policy: format!("{}  ", item.exec.restart.describe(config::read().daemon.restarts)),
+                    cron_restart: cron_restart(&item),
+                    job: job(&item),
+                    depends_on: depends_on(&item),
+                    listen: listen_sockets(&item),
+                    instance: instance(&item),
+                    rlimits: effective_rlimits(limits),
                     status: status.into(),
                     // Always show restarts counter
                     // restarts is persisted and provides accurate restart count
                     restarts: item.restarts,
                     failed_restart_attempts: item.failed_restart_attempts,
                     last_restart_attempt: format_last_restart_attempt(&item),
+                    last_crash: last_crash(&item),
+                    crash_history: item.crash_history.clone(),
+                    last_run: last_run(&item),
+                    runs: item.runs.clone(),
                     name: item.name.clone(),
                     pid: ternary!(
                         item.running && !item.crash.crashed,
@@ -937,22 +1313,13 @@ impl<'i> Internal<'i> {
                     ),
                     log_out: format!("{}/{}-out.log", remote.config.log_path, item.name),
                     log_error: format!("{}/{}-error.log", remote.config.log_path, item.name),
-                    hash: ternary!(
-                        item.watch.enabled,
-                        format!("{}  ", item.watch.hash),
-                        string!("none  ")
-                    ),
                     command: format!(
                         "{} {} '{}'",
                         remote.config.shell,
                         remote.config.args.join(" "),
                         item.script
                     ),
-                    watch: ternary!(
-                        item.watch.enabled,
-                        format!("{path}/{}  ", item.watch.path),
-                        string!("disabled  ")
-                    ),
+                    watch: format!("{}  ", item.watch.describe()),
                     uptime: uptime_value,
                 }];
 
@@ -965,9 +1332,9 @@ impl<'i> Internal<'i> {
         mut self,
         lines: &usize,
         follow: bool,
-        filter: Option<&str>,
         errors_only: bool,
         stats: bool,
+        filter: &Filter,
     ) {
         if !matches!(self.server_name, "internal" | "local") {
             let Some(servers) = config::servers().servers else {
@@ -1001,12 +1368,20 @@ impl<'i> Internal<'i> {
                 format!("Showing last {lines} lines for {}process [{}] (change the value with --lines option)", self.kind, self.id).yellow()
             );
 
-            for kind in vec!["error", "out"] {
+            // A combined log already holds both streams in the order they were written
+            let kinds = match combined_log(item) && !errors_only {
+                true => vec!["combined"],
+                false => vec!["error", "out"],
+            };
+            // Filters are applied to the whole log, rotated files included
+            let wanted = ternary!(filter.is_empty(), *lines, usize::MAX);
+
+            for kind in kinds {
                 if errors_only && kind == "out" {
                     continue;
                 }
 
-                let logs = http::logs(&self.runner.remote.as_ref().unwrap(), self.id, kind);
+                let logs = http::logs(&self.runner.remote.as_ref().unwrap(), self.id, kind, wanted);
 
                 if let Ok(log) = logs {
                     if log.lines.is_empty() {
@@ -1046,6 +1421,8 @@ impl<'i> Internal<'i> {
 
             if errors_only {
                 file::logs_with_options(item, *lines, "error", follow, filter, stats);
+            } else if combined_log(item) {
+                file::logs_with_options(item, *lines, "combined", follow, filter, stats);
             } else {
                 // When follow mode is enabled, we can't follow both logs simultaneously
                 // So we'll only display initial content for both, then follow stdout
@@ -1062,7 +1439,7 @@ impl<'i> Internal<'i> {
         }
     }
 
-    pub fn env(mut self) {
+    pub fn env(mut self, resolved: bool) {
         println!(
             "{}",
             format!("Showing env for {}process {}:\n", self.kind, self.id).bright_yellow()
@@ -1092,10 +1469,28 @@ impl<'i> Internal<'i> {
             };
         }
 
+        let secrets = Secrets::redactor();
         let item = self.runner.process(self.id);
-        item.env
-            .iter()
-            .for_each(|(key, value)| println!("{}: {}", key, value.green()));
+        if !resolved {
+            let mut env = item.env.clone();
+            secrets.redact(&mut env);
+            env.iter().for_each(|(key, value)| println!("{}: {}", key, value.green()));
+            return;
+        }
+
+        // Env files are read where the process runs, so only a local process can be resolved
+        if !matches!(self.server_name, "internal" | "local") {
+            crashln!("{} The resolved environment is only available for local processes", *helpers::FAIL);
+        }
+        match item.resolved_env() {
+            Ok(env) => {
+                let mut values: Env = env.iter().map(|(key, (value, _))| (key.clone(), value.clone())).collect();
+                secrets.redact(&mut values);
+                env.iter()
+                    .for_each(|(key, (_, source))| println!("{}: {} {}", key, values[key].green(), format!("({source})").white()))
+            }
+            Err(err) => crashln!("{} {err}", *helpers::FAIL),
+        }
     }
 
     pub fn get_command(mut self) {
@@ -1256,8 +1651,8 @@ impl<'i> Internal<'i> {
         let processes_to_check: Vec<(usize, String, Option<i64>)> = runner_temp
             .list()
             .filter_map(|(id, p)| {
-                // Only check processes that will be restored (running=true)
-                if p.running {
+                // Only check processes that will be restored (running=true or restart_policy=always)
+                if p.running || p.exec.restart.policy == RestartPolicy::Always {
                     Some((*id, p.script.clone(), p.session_id))
                 } else {
                     None
@@ -1445,6 +1840,7 @@ impl<'i> Internal<'i> {
                                                 e
                                             );
                                         }
+                                        rotation::remove(&path);
                                     }
                                 }
                             }
@@ -1499,7 +1895,8 @@ impl<'i> Internal<'i> {
         // Restore processes that were running before daemon stopped
         // Now we restore all processes that have running=true, regardless of crashed state
         // This preserves the original state and only resets counters
-        // Do NOT restore processes that were manually stopped (running=false)
+        // Do NOT restore processes that were manually stopped (running=false),
+        // unless their restart policy is always
         let processes_to_restore: Vec<(usize, String, bool, bool)> = runner
             .list()
             .filter_map(|(id, p)| {
@@ -1510,7 +1907,8 @@ impl<'i> Internal<'i> {
                 // We intentionally check ONLY p.running to include both cases.
                 // In both cases, restore will restart them. After successful restart,
                 // the daemon will clear the crashed flag if the process stays alive.
-                if p.running {
+                // restart_policy=always processes come back even when they were stopped.
+                if p.running || p.exec.restart.policy == RestartPolicy::Always {
                     Some((*id, p.name.clone(), p.running, p.crash.crashed))
                 } else {
                     None
@@ -1571,71 +1969,44 @@ impl<'i> Internal<'i> {
         use chrono::Utc;
         let batch_start_time = Utc::now();
 
-        // PARALLEL RESTORATION: Spawn all processes concurrently
-        // This dramatically reduces restore time for multiple processes
-        use std::sync::{Arc, Mutex};
-        use std::thread;
-
-        let runner_arc = Arc::new(Mutex::new(runner.clone()));
-        let mut handles = vec![];
-
-        for (id, name, _was_running, _was_crashed) in &processes_to_restore {
-            let id = *id;
-            let name = name.clone();
-            let runner_arc_clone = Arc::clone(&runner_arc);
-
-            let handle = thread::spawn(move || {
-                // Get the runner guard for this thread
-                let mut runner_guard = match runner_arc_clone.lock() {
-                    Ok(guard) => guard,
-                    Err(poisoned) => {
-                        ::log::error!(
-                            "Mutex poisoned during restore for process {}: {}",
-                            id,
-                            poisoned
-                        );
-                        // Recover from poison by taking ownership of the data
-                        poisoned.into_inner()
-                    }
-                };
-
-                // Always start fresh process during restore (no re-attachment)
-                // This prevents false positives from matching unrelated system processes
-                // Call restart directly on the shared runner without cloning to avoid lost updates
-                // Parameters: id, dead=false (user-initiated), increment_counter=false (counters reset later)
-                // Note: restart() already creates the action timestamp file internally
-                runner_guard.restart(id, false, false);
+        // Start the processes so each comes after the processes it depends on
+        let restore_ids: Vec<usize> = processes_to_restore.iter().map(|(id, ..)| *id).collect();
+        let order = match runner.start_order(&restore_ids) {
+            Ok(order) => order,
+            Err(err) => {
+                crate::daemon::clear_restore_in_progress();
+                crashln!("{} Cannot restore processes: {err}", *helpers::FAIL);
+            }
+        };
 
-                // Return (id, name)
-                (id, name)
-            });
+        let mut spawn_results: Vec<(usize, String)> = Vec::new();
+        for id in order {
+            let Some(process) = runner.info(id).cloned() else { continue };
 
-            handles.push(handle);
-        }
+            // A dependency that has to be ready is started already, the daemon reports when it is
+            for dependency in process.exec.depends_on.iter().filter(|dependency| dependency.condition == Condition::Ready) {
+                let Some(dependency_id) = restore_ids.iter().copied().find(|id| runner.info(*id).is_some_and(|p| p.name == dependency.name)) else {
+                    continue;
+                };
 
-        // Wait for all spawns to complete
-        let spawn_results: Vec<(usize, String)> =
-            handles.into_iter().filter_map(|h| h.join().ok()).collect();
-
-        // Get the updated runner state after all parallel spawns
-        runner = match Arc::try_unwrap(runner_arc) {
-            Ok(mutex) => match mutex.into_inner() {
-                Ok(inner) => inner,
-                Err(poisoned) => {
-                    ::log::warn!("Mutex poisoned during restore, recovering data");
-                    poisoned.into_inner()
+                send_state(&runner);
+                if !wait_for_dependency(dependency) {
+                    println!("{} Dependency ({dependency}) of ({}) is not ready, starting it anyway", *helpers::WARN, process.name);
                 }
-            },
-            Err(arc) => match arc.lock() {
-                Ok(guard) => guard.clone(),
-                Err(poisoned) => {
-                    ::log::warn!("Mutex poisoned during restore, recovering data");
-                    poisoned.into_inner().clone()
+                if let Some(latest) = Runner::new().info(dependency_id).cloned() {
+                    runner.list.insert(dependency_id, latest);
                 }
-            },
-        };
+            }
 
-        // Wait 2 seconds for all processes to stabilize after parallel spawning
+            // Always start fresh process during restore (no re-attachment)
+            // This prevents false positives from matching unrelated system processes
+            // Parameters: id, dead=false (user-initiated), increment_counter=false (counters reset later)
+            // Note: restart() already creates the action timestamp file internally
+            runner.restart(id, false, false);
+            spawn_results.push((id, process.name));
+        }
+
+        // Wait 2 seconds for all processes to stabilize after spawning
         // This gives the OS time to register all process trees before verification
         // Increased from 1 to 2 seconds to account for slower process startups
         std::thread::sleep(std::time::Duration::from_secs(2));
@@ -1893,10 +2264,14 @@ impl<'i> Internal<'i> {
                         }
                     }
 
-                    let status = if process_actually_running {
+                    let status = if process_actually_running && item.launching.is_some() {
+                        "launching".cyan().bold()
+                    } else if process_actually_running {
                         "online   ".green().bold()
                     } else if item.errored {
                         "errored  ".red().bold()
+                    } else if let Some(status) = item.job_status() {
+                        job_status(status)
                     } else if item.running {
                         if !crash_detection_enabled {
                             "stopped   ".red().bold()
@@ -1953,11 +2328,7 @@ impl<'i> Internal<'i> {
                             format!("{}  ", item.pid),
                             string!("n/a  ")
                         ),
-                        watch: ternary!(
-                            item.watch.enabled,
-                            format!("{}  ", item.watch.path),
-                            string!("disabled  ")
-                        ),
+                        watch: ternary!(item.watch.enabled, format!("{}  ", item.watch.paths.join(", ")), string!("disabled  ")),
                         uptime,
                     });
                 }
@@ -2153,10 +2524,14 @@ impl<'i> Internal<'i> {
 
                         let crash_detection_enabled = config::read().daemon.crash_detection;
 
-                        let status = if process_actually_running {
+                        let status = if process_actually_running && item.launching.is_some() {
+                            "launching".cyan().bold()
+                        } else if process_actually_running {
                             "online   ".green().bold()
                         } else if item.errored {
                             "errored  ".red().bold()
+                        } else if let Some(status) = item.job_status() {
+                            job_status(status)
                         } else if item.running {
                             if !crash_detection_enabled {
                                 "stopped   ".red().bold()
@@ -2207,11 +2582,7 @@ impl<'i> Internal<'i> {
                                 format!("{}  ", item.pid),
                                 string!("n/a  ")
                             ),
-                            watch: ternary!(
-                                item.watch.enabled,
-                                format!("{}  ", item.watch.path),
-                                string!("disabled  ")
-                            ),
+                            watch: ternary!(item.watch.enabled, format!("{}  ", item.watch.paths.join(", ")), string!("disabled  ")),
                             uptime,
                         });
                     }
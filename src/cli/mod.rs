@@ -4,10 +4,14 @@ pub use args::*;
 pub(crate) mod events;
 pub(crate) mod import;
 pub(crate) mod internal;
+pub(crate) mod secret;
 
 use internal::{Internal, STATS_PRE_LIST_DELAY_MS};
 use macros_rs::{crashln, string, ternary};
-use opm::{config, helpers, process::Runner};
+use opm::{
+    config, helpers, log,
+    process::{balancer::Balancer, entry::Filter, group::Group, Exec, Runner, Watch},
+};
 use std::env;
 use std::thread;
 use std::time::Duration;
@@ -61,16 +65,22 @@ pub fn get_version(short: bool) -> String {
 pub fn start(
     name: &Option<String>,
     args: &Args,
-    watch: &Option<String>,
+    watch: &Option<Watch>,
     max_memory: &Option<String>,
     reset_env: &bool,
     server_name: &String,
     workers: &Option<usize>,
     port_range: &Option<String>,
+    balancer: &Option<Balancer>,
+    exec: &Exec,
 ) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
 
+    if let Some(Err(err)) = watch.as_ref().map(Watch::validate) {
+        crashln!("{} {err}", *helpers::FAIL);
+    }
+
     let mut runner = Runner::new();
     let (kind, list_name) = format(server_name);
 
@@ -95,15 +105,17 @@ pub fn start(
             vec![]
         };
 
-        // Validate port range matches worker count if ports are specified
-        if !ports.is_empty() && ports.len() != *worker_count {
-            crashln!(
-                "{} Port range must provide exactly {} ports for {} workers",
-                *helpers::FAIL,
-                worker_count,
-                worker_count
-            );
-        }
+        // A single port is shared by all workers, a range gives each worker its own
+        let group = match Group::new(*worker_count, &ports) {
+            Ok(group) => Group { balancer: balancer.clone(), ..group },
+            Err(err) => crashln!("{} {err}", *helpers::FAIL),
+        };
+
+        // Workers are managed together by the group name, named like a single process would be
+        let group_name = match name {
+            Some(name) => string!(name),
+            None => string!(arg.split_whitespace().next().unwrap_or_default()),
+        };
 
         // Start multiple worker instances
         println!(
@@ -112,39 +124,13 @@ pub fn start(
             worker_count
         );
 
-        for i in 0..*worker_count {
-            let worker_name = if let Some(base_name) = name {
-                Some(format!("{}-worker-{}", base_name, i + 1))
-            } else {
-                Some(format!("worker-{}", i + 1))
-            };
-
-            // Determine port info for display
-            let port_info = if !ports.is_empty() {
-                format!(" (PORT={})", ports[i])
-            } else if let Some(port_str) = port_range {
-                format!(" (PORT={} via SO_REUSEPORT)", port_str)
-            } else {
-                String::new()
-            };
-
-            println!(
-                "  {} Starting worker {} of {}{}",
-                *helpers::SUCCESS,
-                i + 1,
-                worker_count,
-                port_info
-            );
-
-            // Create each worker as a new process
-            runner = Internal {
-                id: 0, // 0 means create new process
-                server_name,
-                kind: kind.clone(),
-                runner: runner.clone(),
-            }
-            .create(&arg.to_string(), &worker_name, watch, &None, true);
+        runner = Internal {
+            id: 0,
+            server_name,
+            kind: kind.clone(),
+            runner: runner.clone(),
         }
+        .create_group(&arg.to_string(), &group_name, &group, watch, &None, exec);
 
         runner.save();
 
@@ -153,6 +139,14 @@ pub fn start(
             *helpers::SUCCESS,
             worker_count
         );
+        if let Some(balancer) = &group.balancer {
+            println!(
+                "{} Balancing connections on {} over the ready workers ({})",
+                *helpers::SUCCESS,
+                balancer.address(),
+                balancer.strategy
+            );
+        }
 
         // Allow CPU stats to accumulate before displaying the list
         thread::sleep(Duration::from_millis(STATS_PRE_LIST_DELAY_MS));
@@ -167,6 +161,10 @@ pub fn start(
         );
 
         let process_ids: Vec<usize> = runner.items().keys().copied().collect();
+        let process_ids = match runner.start_order(&process_ids) {
+            Ok(order) => order,
+            Err(err) => crashln!("{} Cannot start all: {err}", *helpers::FAIL),
+        };
 
         if process_ids.is_empty() {
             println!("{} Cannot start all, no processes found", *helpers::FAIL);
@@ -199,25 +197,28 @@ pub fn start(
                 .restart(name, watch, *reset_env, false, false); // start by id - don't increment
                 runner.save();
             }
-            Args::Script(script) => match runner.find(&script, server_name) {
-                Some(id) => {
-                    runner = Internal {
-                        id,
-                        runner,
-                        server_name,
-                        kind,
+            Args::Script(script) => match runner.find_all(&script, server_name) {
+                ids if !ids.is_empty() => {
+                    // An existing process, or every instance of a group
+                    for id in ids {
+                        runner = Internal {
+                            id,
+                            runner,
+                            server_name,
+                            kind: kind.clone(),
+                        }
+                        .restart(name, watch, *reset_env, false, false); // start existing - don't increment
                     }
-                    .restart(name, watch, *reset_env, false, false); // start existing - don't increment
                     runner.save();
                 }
-                None => {
+                _ => {
                     runner = Internal {
                         id: 0,
                         runner,
                         server_name,
                         kind,
                     }
-                    .create(script, name, watch, max_memory, false);
+                    .create(script, name, watch, max_memory, exec, false);
                     runner.save();
                 }
             },
@@ -229,7 +230,16 @@ pub fn start(
     Internal::list_with_runner(&string!("default"), &list_name, Some(&runner));
 }
 
-fn parse_port_range(port_str: &str) -> Vec<u16> {
+/// Processes an action by name applies to, every instance when the name is a group
+fn find_all(runner: &Runner, name: &str, server_name: &String) -> Vec<usize> {
+    let ids = runner.find_all(name, server_name);
+    if ids.is_empty() {
+        crashln!("{} Process ({name}) not found", *helpers::FAIL);
+    }
+    ids
+}
+
+pub(crate) fn parse_port_range(port_str: &str) -> Vec<u16> {
     if port_str.contains('-') {
         // Parse range like "3000-3010"
         let parts: Vec<&str> = port_str.split('-').collect();
@@ -253,8 +263,11 @@ fn parse_port_range(port_str: &str) -> Vec<u16> {
 
         (start..=end).collect()
     } else {
-        // Single port - return empty vec to signal SO_REUSEPORT mode
-        vec![]
+        // Single port - every worker shares it through SO_REUSEPORT
+        let port: u16 = port_str
+            .parse()
+            .unwrap_or_else(|_| crashln!("{} Invalid port number", *helpers::FAIL));
+        vec![port]
     }
 }
 
@@ -268,7 +281,11 @@ pub fn stop(items: &Items, server_name: &String) {
     if items.is_all() {
         println!("{} Applying {kind}action stopAllProcess", *helpers::SUCCESS);
 
-        let process_ids: Vec<usize> = runner.items().keys().copied().collect();
+        // Stop in reverse start order, so nothing outlives the processes it depends on
+        let mut process_ids: Vec<usize> = runner.items().keys().copied().collect();
+        if let Ok(order) = runner.start_order(&process_ids) {
+            process_ids = order.into_iter().rev().collect();
+        }
 
         if process_ids.is_empty() {
             println!("{} Cannot stop all, no processes found", *helpers::FAIL);
@@ -296,8 +313,8 @@ pub fn stop(items: &Items, server_name: &String) {
                     }
                     .stop(false);
                 }
-                Item::Name(name) => match runner.find(&name, server_name) {
-                    Some(id) => {
+                Item::Name(name) => {
+                    for id in find_all(&runner, name, server_name) {
                         runner = Internal {
                             id,
                             server_name,
@@ -306,8 +323,7 @@ pub fn stop(items: &Items, server_name: &String) {
                         }
                         .stop(false);
                     }
-                    None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
-                },
+                }
             }
         }
         runner.save();
@@ -362,10 +378,7 @@ pub fn remove(items: &Items, server_name: &String) {
                         crashln!("{} Process (id={}) not found", *helpers::FAIL, id);
                     }
                 }
-                Item::Name(name) => match runner.find(&name, server_name) {
-                    Some(id) => ids_to_remove.push(id),
-                    None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
-                },
+                Item::Name(name) => ids_to_remove.extend(find_all(&runner, name, server_name)),
             }
         }
 
@@ -423,9 +436,9 @@ pub fn logs(
     lines: &usize,
     server_name: &String,
     follow: bool,
-    filter: Option<&str>,
     errors_only: bool,
     stats: bool,
+    filter: &Filter,
 ) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
@@ -440,7 +453,7 @@ pub fn logs(
             server_name,
             kind,
         }
-        .logs(lines, follow, filter, errors_only, stats),
+        .logs(lines, follow, errors_only, stats, filter),
         Item::Name(name) => match runner.find(&name, server_name) {
             Some(id) => Internal {
                 id,
@@ -448,14 +461,14 @@ pub fn logs(
                 server_name,
                 kind,
             }
-            .logs(lines, follow, filter, errors_only, stats),
+            .logs(lines, follow, errors_only, stats, filter),
             None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
         },
     }
 }
 
 // combine into a single function that handles multiple
-pub fn env(item: &Item, server_name: &String) {
+pub fn env(item: &Item, server_name: &String, resolved: bool) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
 
@@ -469,7 +482,7 @@ pub fn env(item: &Item, server_name: &String) {
             server_name,
             kind,
         }
-        .env(),
+        .env(resolved),
         Item::Name(name) => match runner.find(&name, server_name) {
             Some(id) => Internal {
                 id,
@@ -477,7 +490,7 @@ pub fn env(item: &Item, server_name: &String) {
                 server_name,
                 kind,
             }
-            .env(),
+            .env(resolved),
             None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
         },
     }
@@ -511,13 +524,24 @@ pub fn flush(item: &Item, server_name: &String) {
     }
 }
 
-pub fn restart(items: &Items, server_name: &String) {
+pub fn restart(items: &Items, server_name: &String, environment: &Option<String>) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
 
     let mut runner: Runner = Runner::new();
     let (kind, list_name) = format(server_name);
 
+    if environment.is_some() && !LOCAL_SERVER_NAMES.contains(&server_name.as_str()) {
+        crashln!("{} --env can only switch the environment of local processes", *helpers::FAIL);
+    }
+    let select_env = |runner: &mut Runner, id: usize| {
+        if let Some(name) = environment {
+            if let Err(err) = runner.select_env(id, Some(name.clone())) {
+                crashln!("{} Cannot restart ({id}) with --env: {err}", *helpers::FAIL);
+            }
+        }
+    };
+
     if items.is_all() {
         println!(
             "{} Applying {kind}action restartAllProcess",
@@ -530,6 +554,7 @@ pub fn restart(items: &Items, server_name: &String) {
             println!("{} Cannot restart all, no processes found", *helpers::FAIL);
         } else {
             for id in process_ids {
+                select_env(&mut runner, id);
                 runner = Internal {
                     id,
                     server_name,
@@ -544,6 +569,7 @@ pub fn restart(items: &Items, server_name: &String) {
         for item in &items.items {
             match item {
                 Item::Id(id) => {
+                    select_env(&mut runner, *id);
                     runner = Internal {
                         id: *id,
                         server_name,
@@ -552,8 +578,9 @@ pub fn restart(items: &Items, server_name: &String) {
                     }
                     .restart(&None, &None, false, false, true); // restart by id - increment counter
                 }
-                Item::Name(name) => match runner.find(&name, server_name) {
-                    Some(id) => {
+                Item::Name(name) => {
+                    for id in find_all(&runner, name, server_name) {
+                        select_env(&mut runner, id);
                         runner = Internal {
                             id,
                             server_name,
@@ -562,8 +589,7 @@ pub fn restart(items: &Items, server_name: &String) {
                         }
                         .restart(&None, &None, false, false, true); // restart by name - increment counter
                     }
-                    None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
-                },
+                }
             }
         }
         runner.save();
@@ -574,69 +600,121 @@ pub fn restart(items: &Items, server_name: &String) {
     Internal::list_with_runner(&string!("default"), &list_name, Some(&runner));
 }
 
-pub fn reload(items: &Items, server_name: &String) {
+pub fn reload(items: &Items, server_name: &String, max_unavailable: Option<usize>) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
 
     let mut runner: Runner = Runner::new();
     let (kind, list_name) = format(server_name);
 
-    if items.is_all() {
+    let process_ids: Vec<usize> = if items.is_all() {
         println!(
             "{} Applying {kind}action reloadAllProcess",
             *helpers::SUCCESS
         );
-
-        let process_ids: Vec<usize> = runner.items().keys().copied().collect();
-
-        if process_ids.is_empty() {
-            println!("{} Cannot reload all, no processes found", *helpers::FAIL);
-        } else {
-            for id in process_ids {
-                runner = Internal {
-                    id,
-                    server_name,
-                    kind: kind.clone(),
-                    runner: runner.clone(),
-                }
-                .reload(true);
-            }
-            runner.save();
-        }
+        runner.items().keys().copied().collect()
     } else {
+        let mut ids = vec![];
         for item in &items.items {
             match item {
-                Item::Id(id) => {
-                    runner = Internal {
-                        id: *id,
-                        server_name,
-                        kind: kind.clone(),
-                        runner: runner.clone(),
-                    }
-                    .reload(false);
-                }
-                Item::Name(name) => match runner.find(&name, server_name) {
-                    Some(id) => {
-                        runner = Internal {
-                            id,
-                            server_name,
-                            kind: kind.clone(),
-                            runner: runner.clone(),
-                        }
-                        .reload(false);
-                    }
-                    None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
-                },
+                Item::Id(id) => ids.push(*id),
+                Item::Name(name) => ids.extend(find_all(&runner, name, server_name)),
             }
         }
-        runner.save();
+        ids
+    };
+
+    if process_ids.is_empty() {
+        println!("{} Cannot reload all, no processes found", *helpers::FAIL);
+    } else if !LOCAL_SERVER_NAMES.contains(&server_name.as_str()) {
+        for id in process_ids {
+            runner = Internal {
+                id,
+                server_name,
+                kind: kind.clone(),
+                runner: runner.clone(),
+            }
+            .reload(items.is_all());
+        }
+    } else {
+        if let Some(id) = process_ids.iter().find(|id| !runner.exists(**id)) {
+            crashln!("{} Process (id={id}) not found", *helpers::FAIL);
+        }
+        let completed = rolling_reload(&mut runner, &process_ids, max_unavailable);
+
+        // Allow CPU stats to accumulate before displaying the list
+        thread::sleep(Duration::from_millis(STATS_PRE_LIST_DELAY_MS));
+        Internal::list_with_runner(&string!("default"), &list_name, Some(&runner));
+        if !completed {
+            crashln!("{} Rolling reload stopped, failed processes kept their old instance", *helpers::FAIL);
+        }
+        return;
     }
+    runner.save();
 
     // Allow CPU stats to accumulate before displaying the list
     thread::sleep(Duration::from_millis(STATS_PRE_LIST_DELAY_MS));
     Internal::list_with_runner(&string!("default"), &list_name, Some(&runner));
 }
 
+/// Reload local processes without downtime, see Runner::rollout. False when one of them rolled back
+fn rolling_reload(runner: &mut Runner, ids: &[usize], max_unavailable: Option<usize>) -> bool {
+    let max_unavailable = max_unavailable.unwrap_or_else(|| runner.max_unavailable(ids));
+    let list = ids.iter().map(usize::to_string).collect::<Vec<_>>().join(",");
+    println!(
+        "{} Applying action rollingReload on ({list}), {max_unavailable} at a time",
+        *helpers::SUCCESS
+    );
+
+    let rollout = runner.rollout(ids, Some(max_unavailable));
+    for id in &rollout.reloaded {
+        println!("{} Reloaded ({id}) ✓", *helpers::SUCCESS);
+        log!("process reloaded (id={})", id);
+        if let Some(process) = runner.info(*id) {
+            events::emit_event(
+                opm::events::EventType::ProcessRestart,
+                *id,
+                &process.name,
+                &format!("Process '{}' reloaded via CLI", process.name),
+            );
+        }
+    }
+    for rolled_back in &rollout.rolled_back {
+        println!(
+            "{} Rolled back ({}), the new instance failed: {}",
+            *helpers::FAIL,
+            rolled_back.id,
+            rolled_back.reason
+        );
+    }
+    if !rollout.skipped.is_empty() {
+        let skipped = rollout.skipped.iter().map(usize::to_string).collect::<Vec<_>>().join(",");
+        println!("{} Skipped ({skipped}) after the rollback", *helpers::FAIL);
+    }
+
+    rollout.failed().is_empty()
+}
+
+pub fn scale(name: &String, instances: usize) {
+    let mut runner: Runner = Runner::new();
+    println!("{} Applying action scaleGroup", *helpers::SUCCESS);
+
+    match runner.scale(name, instances) {
+        Ok(previous) => {
+            runner.save();
+            println!(
+                "{} Scaled group ({name}) from {previous} to {instances} instances",
+                *helpers::SUCCESS
+            );
+        }
+        Err(err) => crashln!("{} {err}", *helpers::FAIL),
+    }
+
+    // Allow CPU stats to accumulate before displaying the list
+    thread::sleep(Duration::from_millis(STATS_PRE_LIST_DELAY_MS));
+    Internal::list_with_runner(&string!("default"), &string!("local"), Some(&runner));
+}
+
 pub fn get_command(item: &Item, server_name: &String) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
@@ -0,0 +1,83 @@
+use colored::Colorize;
+use macros_rs::crashln;
+use std::io::{IsTerminal, Read};
+
+use opm::{
+    helpers,
+    secret::{self, Secrets},
+};
+
+fn load() -> Secrets {
+    Secrets::load().unwrap_or_else(|err| crashln!("{} {err}", *helpers::FAIL))
+}
+
+fn save(secrets: &Secrets) {
+    if let Err(err) = secrets.save() {
+        crashln!("{} {err}", *helpers::FAIL);
+    }
+}
+
+/// Store a secret, read from stdin or a hidden prompt when the value is not given, so it stays out of the shell history
+pub fn set(name: &str, value: &Option<String>) {
+    if let Err(err) = secret::validate(name) {
+        crashln!("{} {err}", *helpers::FAIL);
+    }
+
+    let value = match value {
+        Some(value) => value.clone(),
+        None if std::io::stdin().is_terminal() => {
+            match inquire::Password::new(&format!("Value of secret {name}:")).without_confirmation().prompt() {
+                Ok(value) => value,
+                Err(err) => crashln!("{} Failed to read the secret: {err}", *helpers::FAIL),
+            }
+        }
+        None => {
+            let mut value = String::new();
+            if let Err(err) = std::io::stdin().read_to_string(&mut value) {
+                crashln!("{} Failed to read the secret from stdin: {err}", *helpers::FAIL);
+            }
+            let trimmed = value.strip_suffix('\n').unwrap_or(&value);
+            trimmed.strip_suffix('\r').unwrap_or(trimmed).to_string()
+        }
+    };
+
+    let mut secrets = load();
+    let replaced = secrets.get(name).is_some();
+    if let Err(err) = secrets.set(name, value) {
+        crashln!("{} {err}", *helpers::FAIL);
+    }
+    save(&secrets);
+
+    println!(
+        "{} {} secret ({name}), use it as {} in the environment of a process",
+        *helpers::SUCCESS,
+        if replaced { "Updated" } else { "Stored" },
+        secret::reference(name).cyan()
+    );
+}
+
+pub fn get(name: &str) {
+    match load().get(name) {
+        Some(value) => println!("{value}"),
+        None => crashln!("{} Secret ({name}) not found", *helpers::FAIL),
+    }
+}
+
+pub fn remove(name: &str) {
+    let mut secrets = load();
+    if !secrets.remove(name) {
+        crashln!("{} Secret ({name}) not found", *helpers::FAIL);
+    }
+    save(&secrets);
+    println!("{} Removed secret ({name})", *helpers::SUCCESS);
+}
+
+pub fn list() {
+    let secrets = load();
+    let names: Vec<&String> = secrets.names().collect();
+    if names.is_empty() {
+        println!("{} No secrets stored, add one with `opm secret set <name>`", *helpers::SUCCESS);
+        return;
+    }
+    names.iter().for_each(|name| println!("{} {}", name, secret::reference(name).white()));
+}
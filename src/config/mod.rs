@@ -85,6 +85,9 @@ pub fn read() -> Config {
                         }),
                         crash_detection: true,
                         crash_grace_period: 2,
+                        cgroup: None,
+                        log_rotation: None,
+                        log_sinks: Vec::new(),
                     },
                     role: structs::Role::Standalone,
                 };
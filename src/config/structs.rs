@@ -57,6 +57,25 @@ pub struct Daemon {
     /// This prevents false crash detection for processes that take time to initialize
     #[serde(default = "default_crash_grace_period")]
     pub crash_grace_period: u64,
+    /// Optional cgroup v2 backend for per-process resource limits and tracking
+    #[serde(default)]
+    pub cgroup: Option<Cgroup>,
+    /// Log rotation of processes that do not set their own
+    #[serde(default)]
+    pub log_rotation: Option<crate::process::rotation::Rotation>,
+    /// Destinations process logs are forwarded to, set as [[daemon.log_sinks]]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub log_sinks: Vec<crate::process::sink::Sink>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Cgroup {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Parent of the per-process cgroups, must be writable by the daemon
+    /// (e.g. a delegated user slice when not running as root)
+    #[serde(default = "default_cgroup_parent")]
+    pub parent: String,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
@@ -118,6 +137,10 @@ fn default_crash_grace_period() -> u64 {
     2
 }
 
+fn default_cgroup_parent() -> String {
+    "/sys/fs/cgroup/opm".to_string()
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Servers {
     pub servers: Option<BTreeMap<String, Server>>,
@@ -162,4 +185,6 @@ pub struct NotificationEvents {
     pub process_restart: bool,
     #[serde(default)]
     pub process_delete: bool,
+    #[serde(default)]
+    pub process_threshold_exceeded: bool,
 }
@@ -12,9 +12,10 @@ use lazy_static::lazy_static;
 use once_cell::sync::OnceCell;
 use opm::config;
 use prometheus::{
-    opts, register_counter, register_gauge, register_histogram, register_histogram_vec,
+    opts, register_counter, register_counter_vec, register_gauge, register_gauge_vec,
+    register_histogram, register_histogram_vec,
 };
-use prometheus::{Counter, Gauge, Histogram, HistogramVec};
+use prometheus::{Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramVec};
 use serde_json::{json, Value};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -69,6 +70,42 @@ lazy_static! {
         &["route"]
     )
     .unwrap();
+    pub static ref PROCESS_LIVENESS_HEALTHY: GaugeVec = register_gauge_vec!(
+        "process_liveness_healthy",
+        "1 when the last liveness probe of a process passed, 0 when it failed.",
+        &["id", "name"]
+    )
+    .unwrap();
+    pub static ref PROCESS_LIVENESS_FAILURES: GaugeVec = register_gauge_vec!(
+        "process_liveness_consecutive_failures",
+        "Failed liveness probes of a process since the last successful one.",
+        &["id", "name"]
+    )
+    .unwrap();
+    pub static ref PROCESS_LIVENESS_CHECKS: CounterVec = register_counter_vec!(
+        "process_liveness_checks_total",
+        "Liveness probes run per process, by result.",
+        &["id", "name", "result"]
+    )
+    .unwrap();
+    pub static ref BALANCER_CONNECTIONS: GaugeVec = register_gauge_vec!(
+        "balancer_backend_connections",
+        "Open connections the group balancer proxies to an instance.",
+        &["group", "id", "name"]
+    )
+    .unwrap();
+    pub static ref BALANCER_CONNECTIONS_TOTAL: CounterVec = register_counter_vec!(
+        "balancer_backend_connections_total",
+        "Connections the group balancer sent to an instance.",
+        &["group", "id", "name"]
+    )
+    .unwrap();
+    pub static ref BALANCER_CONNECT_FAILURES: CounterVec = register_counter_vec!(
+        "balancer_backend_connect_failures_total",
+        "Connections to an instance that failed, the balancer retried them on the next instance.",
+        &["group", "id", "name"]
+    )
+    .unwrap();
 }
 
 // struct ApiDoc;
@@ -215,6 +252,26 @@ pub async fn start(webui: bool) {
     // Safe to ignore error: API start is called once per daemon lifecycle
     let _ = GLOBAL_EVENT_MANAGER.set(event_manager.clone());
 
+    // Events raised inside the library (e.g. SIGKILL escalation) go straight to the manager
+    // They may come from the socket handler threads, which have no tokio runtime of their own
+    let emitter_manager = event_manager.clone();
+    opm::events::set_emitter(move |event| {
+        let event_manager = emitter_manager.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move { event_manager.add_event(event).await });
+            }
+            Err(_) => {
+                std::thread::spawn(move || {
+                    match tokio::runtime::Builder::new_current_thread().build() {
+                        Ok(runtime) => runtime.block_on(event_manager.add_event(event)),
+                        Err(err) => log::warn!("Failed to record event: {}", err),
+                    }
+                });
+            }
+        }
+    });
+
     log::info!("API start: Initializing notification manager");
     // Initialize notification manager with current config
     let notification_config = config::read().daemon.notifications;
@@ -248,6 +305,8 @@ pub async fn start(webui: bool) {
         routes::settings_page,
         routes::action_handler,
         routes::env_handler,
+        routes::runs_handler,
+        routes::watch_handler,
         routes::info_handler,
         routes::dump_handler,
         routes::save_handler,
@@ -32,8 +32,10 @@ use opm::{
     config, helpers,
     process::{
         dump, get_process_cpu_usage_with_children_from_process, get_process_memory_with_children,
-        http::client, ItemSingle, ProcessItem, Runner,
+        capture::Capture, deps::Dependency, entry::Entry, environment::Environment, health::{Liveness, Readiness}, http::client, instance::Instance, job::{Job, JobRun}, listen::{self, Listen}, restart::{Backoff, Restart, RestartPolicy}, rollout::Rollout, rotation::{self, Rotation}, schedule, threshold::Thresholds, unix::rlimits::Limits, watch::{self, Watch}, Exec, ExecMode, ItemSingle, KillMode, ProcessItem,
+        Resources, Runner, StopPolicy,
     },
+    secret::Secrets,
 };
 
 use crate::daemon::{
@@ -50,7 +52,7 @@ use std::{
     env,
     fs::{self, File},
     io::{self, BufRead, BufReader},
-    path::PathBuf,
+    path::{Path, PathBuf},
     thread::sleep,
     time::Duration,
 };
@@ -102,8 +104,68 @@ pub(crate) struct CreateBody {
     script: String,
     #[schema(value_type = String, example = "/projects/app")]
     path: PathBuf,
-    #[schema(example = "src")]
-    watch: Option<String>,
+    /// A path, a list of paths or the watch settings
+    #[schema(value_type = Option<Watch>)]
+    #[serde(default, deserialize_with = "watch::deserialize_optional")]
+    watch: Option<Watch>,
+    #[schema(example = "500M")]
+    max_memory: Option<String>,
+    #[serde(default)]
+    thresholds: Thresholds,
+    #[serde(default)]
+    log_rotation: Option<Rotation>,
+    #[serde(default)]
+    log_capture: Option<Capture>,
+    #[schema(value_type = Option<String>, example = "/srv/app")]
+    cwd: Option<PathBuf>,
+    #[schema(example = "www-data")]
+    user: Option<String>,
+    #[schema(example = "www-data")]
+    group: Option<String>,
+    #[schema(example = "022")]
+    umask: Option<String>,
+    #[schema(example = "512M")]
+    memory_max: Option<String>,
+    #[schema(example = "150%")]
+    cpu_max: Option<String>,
+    #[schema(example = 256)]
+    pids_max: Option<u64>,
+    #[serde(default)]
+    limits: Limits,
+    #[schema(example = "direct")]
+    exec_mode: Option<ExecMode>,
+    #[schema(example = "SIGINT")]
+    stop_signal: Option<String>,
+    #[schema(example = 30000)]
+    kill_timeout_ms: Option<u64>,
+    #[schema(example = "mixed")]
+    kill_mode: Option<KillMode>,
+    ready: Option<Readiness>,
+    liveness: Option<Liveness>,
+    #[schema(example = "on-failure")]
+    restart_policy: Option<RestartPolicy>,
+    #[serde(default)]
+    #[schema(example = json!([0, 143]))]
+    success_exit_codes: Vec<i32>,
+    #[schema(example = 10)]
+    max_restarts: Option<u64>,
+    #[schema(example = 10000)]
+    min_uptime_ms: Option<u64>,
+    #[serde(default)]
+    backoff: Backoff,
+    #[schema(example = "0 3 * * *")]
+    cron_restart: Option<String>,
+    job: Option<Job>,
+    #[schema(value_type = Vec<String>, example = json!(["redis", "migrate:ready"]))]
+    #[serde(default)]
+    depends_on: Vec<Dependency>,
+    #[schema(value_type = Vec<String>, example = json!(["tcp://0.0.0.0:8080"]))]
+    #[serde(default)]
+    listen: Vec<Listen>,
+    #[serde(default)]
+    environment: Environment,
+    #[serde(default)]
+    instance: Option<Instance>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -117,6 +179,26 @@ pub(crate) struct ActionResponse {
 #[derive(Serialize, Deserialize, ToSchema)]
 pub(crate) struct LogResponse {
     logs: Vec<String>,
+    /// Time, level, message and JSON fields parsed from each of the logs
+    #[serde(default)]
+    entries: Vec<Entry>,
+}
+
+impl LogResponse {
+    fn new(logs: Vec<String>) -> Self {
+        let entries = logs.iter().map(|line| Entry::parse(line)).collect();
+        LogResponse { logs, entries }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub(crate) struct WatchResponse {
+    settings: Watch,
+    /// The daemon runs a watcher, only while the process is running with watch enabled
+    #[schema(example = true)]
+    active: bool,
+    #[serde(flatten)]
+    status: watch::Status,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -832,6 +914,7 @@ pub async fn restore_handler(_t: Token) -> Json<ActionResponse> {
                                             e
                                         );
                                     }
+                                    rotation::remove(&path);
                                 }
                             }
                         }
@@ -996,6 +1079,8 @@ pub struct NotificationEvents {
     process_restart: bool,
     #[serde(default)]
     process_delete: bool,
+    #[serde(default)]
+    process_threshold_exceeded: bool,
 }
 
 impl Default for NotificationEvents {
@@ -1008,6 +1093,7 @@ impl Default for NotificationEvents {
             process_crash: false,
             process_restart: false,
             process_delete: false,
+            process_threshold_exceeded: false,
         }
     }
 }
@@ -1070,6 +1156,11 @@ pub async fn get_notifications_handler(_t: Token) -> Json<NotificationConfig> {
                     .as_ref()
                     .map(|e| e.process_delete)
                     .unwrap_or(false),
+                process_threshold_exceeded: notif
+                    .events
+                    .as_ref()
+                    .map(|e| e.process_threshold_exceeded)
+                    .unwrap_or(false),
             },
             channels: notif.channels.unwrap_or_default(),
         },
@@ -1118,6 +1209,7 @@ pub async fn save_notifications_handler(
             process_crash: body.events.process_crash,
             process_restart: body.events.process_restart,
             process_delete: body.events.process_delete,
+            process_threshold_exceeded: body.events.process_threshold_exceeded,
         }),
         channels: Some(body.channels.clone()),
     });
@@ -1706,6 +1798,7 @@ pub async fn logs_handler(
             let log_file = match kind.as_str() {
                 "out" | "stdout" => item.logs().out,
                 "error" | "stderr" => item.logs().error,
+                "combined" => item.logs().combined,
                 _ => item.logs().out,
             };
 
@@ -1715,9 +1808,9 @@ pub async fn logs_handler(
                     let logs: Vec<String> = reader.lines().collect::<io::Result<_>>().unwrap();
 
                     timer.observe_duration();
-                    Ok(Json(LogResponse { logs }))
+                    Ok(Json(LogResponse::new(logs)))
                 }
-                Err(_) => Ok(Json(LogResponse { logs: vec![] })),
+                Err(_) => Ok(Json(LogResponse::new(vec![]))),
             }
         }
         None => {
@@ -1727,12 +1820,13 @@ pub async fn logs_handler(
     }
 }
 
-#[get("/process/<id>/logs/<kind>/raw")]
+#[get("/process/<id>/logs/<kind>/raw?<lines>")]
 #[utoipa::path(get, tag = "Process", path = "/process/{id}/logs/{kind}/raw", 
     security((), ("api_key" = [])),
     params(
         ("id" = usize, Path, description = "Process id to get logs for", example = 0),
-        ("kind" = String, Path, description = "Log output type", example = "out")
+        ("kind" = String, Path, description = "Log output type", example = "out"),
+        ("lines" = Option<usize>, Query, description = "Only the last lines, read across rotated files", example = 100)
     ),
     responses(
         (
@@ -1746,7 +1840,7 @@ pub async fn logs_handler(
         )
     )
 )]
-pub async fn logs_raw_handler(id: usize, kind: String, _t: Token) -> Result<String, NotFound> {
+pub async fn logs_raw_handler(id: usize, kind: String, lines: Option<usize>, _t: Token) -> Result<String, NotFound> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["log"]).start_timer();
 
     HTTP_COUNTER.inc();
@@ -1755,12 +1849,20 @@ pub async fn logs_raw_handler(id: usize, kind: String, _t: Token) -> Result<Stri
             let log_file = match kind.as_str() {
                 "out" | "stdout" => item.logs().out,
                 "error" | "stderr" => item.logs().error,
+                "combined" => item.logs().combined,
                 _ => item.logs().out,
             };
 
-            let data = match fs::read_to_string(&log_file) {
-                Ok(data) => format!("# PATH {log_file}\n{data}"),
-                Err(err) => err.to_string(),
+            let data = match lines {
+                Some(lines) => {
+                    let logs = rotation::lines(Path::new(&log_file), lines);
+                    let logs = &logs[logs.len().saturating_sub(lines)..];
+                    format!("# PATH {log_file}\n{}", logs.join("\n"))
+                }
+                None => match fs::read_to_string(&log_file) {
+                    Ok(data) => format!("# PATH {log_file}\n{data}"),
+                    Err(err) => err.to_string(),
+                },
             };
 
             timer.observe_duration();
@@ -1831,10 +1933,11 @@ pub async fn info_handler(id: usize, _t: Token) -> Result<Json<ItemSingle>, NotF
     let runner = Runner::new();
 
     if runner.exists(id) {
-        let item = runner.get(id);
+        let mut item = runner.get(id).fetch();
+        item.info.exec.environment.redact(&Secrets::redactor());
         HTTP_COUNTER.inc();
         timer.observe_duration();
-        Ok(Json(item.fetch()))
+        Ok(Json(item))
     } else {
         Err(not_found("Process was not found"))
     }
@@ -1848,6 +1951,7 @@ pub async fn info_handler(id: usize, _t: Token) -> Result<Json<ItemSingle>, NotF
             description = "Create process successful", body = ActionResponse,
             example = json!({"action": "create", "done": true }), status = 200,
         ),
+        (status = BAD_REQUEST, description = "Invalid execution options", body = ErrorMessage),
         (status = INTERNAL_SERVER_ERROR, description = "Failed to create process", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
@@ -1859,7 +1963,7 @@ pub async fn create_handler(
     body: Json<CreateBody>,
     event_manager: &State<std::sync::Arc<opm::events::EventManager>>,
     _t: Token,
-) -> Result<Json<ActionResponse>, ()> {
+) -> Result<Json<ActionResponse>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM
         .with_label_values(&["create"])
         .start_timer();
@@ -1872,7 +1976,138 @@ pub async fn create_handler(
         None => string!(body.script.split_whitespace().next().unwrap_or_default()),
     };
 
-    runner.start(&name, &body.script, body.path.clone(), &body.watch, 0);
+    let umask = match body.umask.as_deref().map(helpers::parse_umask).transpose() {
+        Ok(umask) => umask,
+        Err(err) => {
+            timer.observe_duration();
+            return Err(generic_error(Status::BadRequest, err));
+        }
+    };
+
+    let resources = match Resources::parse(
+        body.memory_max.as_deref(),
+        body.cpu_max.as_deref(),
+        body.pids_max,
+    ) {
+        Ok(resources) => resources,
+        Err(err) => {
+            timer.observe_duration();
+            return Err(generic_error(Status::BadRequest, err));
+        }
+    };
+
+    let stop = match StopPolicy::parse(
+        body.stop_signal.as_deref(),
+        body.kill_timeout_ms,
+        body.kill_mode,
+    ) {
+        Ok(stop) => stop,
+        Err(err) => {
+            timer.observe_duration();
+            return Err(generic_error(Status::BadRequest, err));
+        }
+    };
+
+    if let Some(Err(err)) = body.ready.as_ref().map(Readiness::validate) {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    if let Some(Err(err)) = body.liveness.as_ref().map(Liveness::validate) {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    let restart = Restart {
+        policy: body.restart_policy.unwrap_or_default(),
+        success_exit_codes: body.success_exit_codes.clone(),
+        max_restarts: body.max_restarts,
+        min_uptime_ms: body.min_uptime_ms,
+        backoff: body.backoff.clone(),
+    };
+    if let Err(err) = restart.validate() {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    if let Some(Err(err)) = body.cron_restart.as_deref().map(schedule::validate) {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    if let Some(Err(err)) = body.job.as_ref().map(Job::validate) {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    if let Err(err) = runner.check_dependencies(&name, &body.depends_on) {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    if let Some(Err(err)) = body.instance.as_ref().map(Instance::validate) {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    let shared_port = body.instance.as_ref().is_some_and(|instance| instance.shared_port);
+    if let Err(err) = listen::validate(&body.listen, shared_port) {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    if let Err(err) = body.environment.validate() {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    if let Err(err) = body.thresholds.validate() {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    if let Some(Err(err)) = body.log_rotation.as_ref().map(Rotation::validate) {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    if let Some(Err(err)) = body.watch.as_ref().map(Watch::validate) {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, err));
+    }
+
+    let max_memory = match body.max_memory.as_deref().map(helpers::parse_memory).transpose() {
+        Ok(max_memory) => max_memory.unwrap_or(0),
+        Err(err) => {
+            timer.observe_duration();
+            return Err(generic_error(Status::BadRequest, err));
+        }
+    };
+
+    let exec = Exec {
+        cwd: body.cwd.clone(),
+        user: body.user.clone(),
+        group: body.group.clone(),
+        umask,
+        resources,
+        limits: body.limits.clone(),
+        mode: body.exec_mode,
+        stop,
+        ready: body.ready.clone(),
+        liveness: body.liveness.clone(),
+        restart,
+        cron_restart: body.cron_restart.clone(),
+        job: body.job.clone(),
+        depends_on: body.depends_on.clone(),
+        listen: body.listen.clone(),
+        environment: body.environment.clone(),
+        thresholds: body.thresholds.clone(),
+        log_rotation: body.log_rotation.clone(),
+        log_capture: body.log_capture.clone(),
+        instance: body.instance.clone(),
+    };
+
+    runner.start(&name, &body.script, body.path.clone(), &body.watch, max_memory, exec);
 
     // Find the just-created process by name to get its ID
     // Since we just created it and this is a fresh Runner instance, it should be the only one with this name
@@ -1974,8 +2209,71 @@ pub async fn env_handler(id: usize, _t: Token) -> Result<EnvList, NotFound> {
     HTTP_COUNTER.inc();
     match Runner::new().info(id) {
         Some(item) => {
+            let mut env = item.env.clone();
+            Secrets::redactor().redact(&mut env);
+            timer.observe_duration();
+            Ok(Json(env))
+        }
+        None => {
+            timer.observe_duration();
+            Err(not_found("Process was not found"))
+        }
+    }
+}
+
+#[get("/process/<id>/runs")]
+#[utoipa::path(get, tag = "Process", path = "/process/{id}/runs", security((), ("api_key" = [])),
+    params(("id" = usize, Path, description = "Job process id to get the run history for", example = 0)),
+    responses(
+        (status = 200, description = "Run history of the job, oldest first", body = [JobRun]),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn runs_handler(id: usize, _t: Token) -> Result<Json<Vec<JobRun>>, NotFound> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["runs"]).start_timer();
+
+    HTTP_COUNTER.inc();
+    match Runner::new().info(id) {
+        Some(item) => {
+            timer.observe_duration();
+            Ok(Json(item.runs.clone()))
+        }
+        None => {
+            timer.observe_duration();
+            Err(not_found("Process was not found"))
+        }
+    }
+}
+
+#[get("/process/<id>/watch")]
+#[utoipa::path(get, tag = "Process", path = "/process/{id}/watch", security((), ("api_key" = [])),
+    params(("id" = usize, Path, description = "Process id to get the watch state for", example = 0)),
+    responses(
+        (status = 200, description = "Watch settings, watched directories and the last changed file", body = WatchResponse),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn watch_handler(id: usize, _t: Token) -> Result<Json<WatchResponse>, NotFound> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["watch"]).start_timer();
+
+    HTTP_COUNTER.inc();
+    match Runner::new().info(id) {
+        Some(item) => {
+            let status = watch::status(id);
             timer.observe_duration();
-            Ok(Json(item.clone().env))
+            Ok(Json(WatchResponse {
+                settings: item.watch.clone(),
+                active: status.is_some(),
+                status: status.unwrap_or_default(),
+            }))
         }
         None => {
             timer.observe_duration();
@@ -1991,6 +2289,7 @@ pub async fn env_handler(id: usize, _t: Token) -> Result<EnvList, NotFound> {
     responses(
         (status = 200, description = "Run action on process successful", body = ActionResponse),
         (status = NOT_FOUND, description = "Process/action was not found", body = ErrorMessage),
+        (status = INTERNAL_SERVER_ERROR, description = "Reload rolled back, the old instance keeps running", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
             example = json!({"code": 401, "message": "Unauthorized"})
@@ -2002,7 +2301,7 @@ pub async fn action_handler(
     body: Json<ActionBody>,
     event_manager: &State<std::sync::Arc<opm::events::EventManager>>,
     _t: Token,
-) -> Result<Json<ActionResponse>, NotFound> {
+) -> Result<Json<ActionResponse>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM
         .with_label_values(&["action"])
         .start_timer();
@@ -2056,9 +2355,13 @@ pub async fn action_handler(
                 Ok(Json(attempt(true, method)))
             }
             "reload" => {
-                let mut item = runner.get(id);
-                item.reload(true); // reload should increment
-                item.get_runner().save();
+                // Start the new instance, wait until it is ready, then stop the old one
+                let rollout = runner.rollout(&[id], None);
+                if let Some(rolled_back) = rollout.rolled_back.first() {
+                    timer.observe_duration();
+                    let message = format!("Reload of process '{}' rolled back, the new instance failed: {}", process_name, rolled_back.reason);
+                    return Err(generic_error(Status::InternalServerError, message));
+                }
 
                 // Emit process restart event (reload is essentially a restart)
                 let event = opm::events::Event::new(
@@ -2124,11 +2427,11 @@ pub async fn action_handler(
             }
             _ => {
                 timer.observe_duration();
-                Err(not_found("Invalid action attempt"))
+                Err(generic_error(Status::NotFound, "Invalid action attempt".to_string()))
             }
         }
     } else {
-        Err(not_found("Process was not found"))
+        Err(generic_error(Status::NotFound, "Process was not found".to_string()))
     }
 }
 
@@ -2138,6 +2441,10 @@ pub struct BulkActionBody {
     ids: Vec<usize>,
     #[schema(example = "restart")]
     method: String,
+    /// Processes a reload replaces at once, defaults to their group's max_unavailable or 1
+    #[schema(example = 1)]
+    #[serde(default)]
+    max_unavailable: Option<usize>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -2145,6 +2452,9 @@ pub struct BulkActionResponse {
     success: Vec<usize>,
     failed: Vec<usize>,
     action: String,
+    /// Outcome of a reload, with the reason each rolled back process failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rollout: Option<Rollout>,
 }
 
 #[post("/process/bulk-action", format = "json", data = "<body>")]
@@ -2171,6 +2481,22 @@ pub async fn bulk_action_handler(
 
     HTTP_COUNTER.inc();
 
+    // A reload rolls through all processes together, so max_unavailable of them are replaced at once
+    if method == "reload" {
+        let mut runner = Runner::new();
+        let (ids, mut failed): (Vec<usize>, Vec<usize>) = body.ids.iter().partition(|id| runner.exists(**id));
+        let rollout = runner.rollout(&ids, body.max_unavailable.filter(|count| *count > 0));
+        failed.extend(rollout.failed());
+
+        timer.observe_duration();
+        return Json(BulkActionResponse {
+            success: rollout.reloaded.clone(),
+            failed,
+            action: method.to_string(),
+            rollout: Some(rollout),
+        });
+    }
+
     for id in &body.ids {
         // Create a new runner for each iteration to avoid borrow checker issues
         let mut runner = Runner::new();
@@ -2189,12 +2515,6 @@ pub async fn bulk_action_handler(
                     item.get_runner().save();
                     success.push(*id);
                 }
-                "reload" => {
-                    let mut item = runner.get(*id);
-                    item.reload(true);
-                    item.get_runner().save();
-                    success.push(*id);
-                }
                 "stop" | "kill" => {
                     let mut item = runner.get(*id);
                     item.stop();
@@ -2219,6 +2539,7 @@ pub async fn bulk_action_handler(
         success,
         failed,
         action: method.to_string(),
+        rollout: None,
     })
 }
 
@@ -2741,9 +3062,7 @@ pub async fn agent_process_logs_handler(
         match tokio::time::timeout(tokio::time::Duration::from_secs(5), receiver).await {
             Ok(Ok(response)) => {
                 if response.success {
-                    Ok(Json(LogResponse {
-                        logs: response.logs,
-                    }))
+                    Ok(Json(LogResponse::new(response.logs)))
                 } else {
                     Err(generic_error(
                         Status::NotFound,
@@ -2791,6 +3110,7 @@ pub async fn agent_process_logs_handler(
                 let log_file = match kind.as_str() {
                     "out" | "stdout" => process.logs().out,
                     "error" | "stderr" => process.logs().error,
+                    "combined" => process.logs().combined,
                     _ => process.logs().out,
                 };
 
@@ -2799,11 +3119,11 @@ pub async fn agent_process_logs_handler(
                         let reader = BufReader::new(data);
                         let logs: Vec<String> = reader.lines().collect::<io::Result<_>>().unwrap();
                         timer.observe_duration();
-                        return Ok(Json(LogResponse { logs }));
+                        return Ok(Json(LogResponse::new(logs)));
                     }
                     Err(_) => {
                         timer.observe_duration();
-                        return Ok(Json(LogResponse { logs: vec![] }));
+                        return Ok(Json(LogResponse::new(vec![])));
                     }
                 }
             } else {
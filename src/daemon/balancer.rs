@@ -0,0 +1,436 @@
+use super::api::{BALANCER_CONNECTIONS, BALANCER_CONNECTIONS_TOTAL, BALANCER_CONNECT_FAILURES};
+use opm::process::{
+    balancer::{Balancer, CONNECT_TIMEOUT_MS, REFRESH_INTERVAL_MS, REFUSED_BACKOFF_MS},
+    Runner,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::copy_bidirectional,
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+    time,
+};
+
+/// Instance of a group the balancer can send connections to
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Backend {
+    id: usize,
+    name: String,
+    port: u16,
+}
+
+impl Backend {
+    fn labels<'a>(&'a self, group: &'a str, id: &'a str) -> [&'a str; 3] {
+        [group, id, &self.name]
+    }
+}
+
+/// Ready instances of a balanced group and the connections open to each of them
+struct Pool {
+    group: String,
+    balancer: Balancer,
+    /// Shared with the pool that replaces this one when the group moves to another port
+    backends: Arc<Mutex<Backends>>,
+    turn: AtomicUsize,
+}
+
+#[derive(Default)]
+struct Backends {
+    ready: Vec<Backend>,
+    /// Open connections by process id, kept for instances that left the pool until their connections close
+    active: HashMap<usize, usize>,
+    /// Instances that refused a connection, passed over until the time by process id
+    refused: HashMap<usize, Instant>,
+    /// Instances with metric series, removed once they left the pool and their connections closed
+    exported: HashSet<Backend>,
+}
+
+impl Backends {
+    /// Remove the metrics of instances that are gone, returns true once none are left
+    fn forget(&mut self, group: &str) -> bool {
+        let Backends { ready, active, exported, .. } = self;
+        exported.retain(|backend| {
+            let keep = ready.contains(backend) || active.contains_key(&backend.id);
+            if !keep {
+                let id = backend.id.to_string();
+                let labels = backend.labels(group, &id);
+                let _ = BALANCER_CONNECTIONS.remove_label_values(&labels);
+                let _ = BALANCER_CONNECTIONS_TOTAL.remove_label_values(&labels);
+                let _ = BALANCER_CONNECT_FAILURES.remove_label_values(&labels);
+            }
+            keep
+        });
+        exported.is_empty()
+    }
+}
+
+/// A connection counted against a backend until it is dropped
+struct Connection {
+    pool: Arc<Pool>,
+    backend: Backend,
+}
+
+impl Pool {
+    /// Pick the backend for the next connection, skipping the ones that already refused it
+    /// Instances that recently refused another connection are only picked when no other one is left
+    fn take(self: &Arc<Self>, tried: &HashSet<usize>) -> Option<Connection> {
+        let mut backends = self.backends.lock().unwrap();
+        let now = Instant::now();
+        backends.refused.retain(|_, until| *until > now);
+
+        let untried: Vec<&Backend> = backends.ready.iter().filter(|b| !tried.contains(&b.id)).collect();
+        let candidates: Vec<Backend> = match untried.iter().any(|b| !backends.refused.contains_key(&b.id)) {
+            true => untried.into_iter().filter(|b| !backends.refused.contains_key(&b.id)).cloned().collect(),
+            false => untried.into_iter().cloned().collect(),
+        };
+        let active: Vec<usize> = candidates.iter().map(|b| backends.active.get(&b.id).copied().unwrap_or(0)).collect();
+
+        let index = self.balancer.strategy.pick(&active, self.turn.fetch_add(1, Ordering::Relaxed))?;
+        let backend = candidates[index].clone();
+        *backends.active.entry(backend.id).or_default() += 1;
+        backends.exported.insert(backend.clone());
+
+        let id = backend.id.to_string();
+        BALANCER_CONNECTIONS.with_label_values(&backend.labels(&self.group, &id)).inc();
+        BALANCER_CONNECTIONS_TOTAL.with_label_values(&backend.labels(&self.group, &id)).inc();
+
+        Some(Connection { pool: Arc::clone(self), backend })
+    }
+
+    /// Swap in the instances that are ready now, returns true once no instance has metrics left
+    fn update(&self, ready: Vec<Backend>) -> bool {
+        let mut backends = self.backends.lock().unwrap();
+        if backends.ready != ready {
+            log!("[balancer] backends changed", "group" => self.group, "ready" => ready.len());
+        }
+        backends.ready = ready;
+        backends.forget(&self.group)
+    }
+
+    /// Pass over `backend` for the next connections
+    fn refused(&self, backend: &Backend) {
+        let until = Instant::now() + Duration::from_millis(REFUSED_BACKOFF_MS);
+        self.backends.lock().unwrap().refused.insert(backend.id, until);
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let mut backends = self.pool.backends.lock().unwrap();
+        if let Some(count) = backends.active.get_mut(&self.backend.id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                backends.active.remove(&self.backend.id);
+            }
+        }
+
+        let id = self.backend.id.to_string();
+        BALANCER_CONNECTIONS.with_label_values(&self.backend.labels(&self.pool.group, &id)).dec();
+    }
+}
+
+/// Balanced groups and their ready instances, read from the current state
+fn snapshot() -> Vec<(String, Balancer, Vec<Backend>)> {
+    let runner = Runner::new_direct();
+    runner
+        .groups
+        .iter()
+        .filter_map(|(name, group)| {
+            let backends = runner
+                .balancer_backends(name)
+                .into_iter()
+                .filter_map(|p| {
+                    let port = p.exec.instance.as_ref()?.port?;
+                    Some(Backend { id: p.id, name: p.name.clone(), port })
+                })
+                .collect();
+            Some((name.clone(), group.balancer.clone()?, backends))
+        })
+        .collect()
+}
+
+/// Keep a listener on the public port of every balanced group, following the state of their instances
+pub async fn run() {
+    let mut pools: HashMap<String, (Arc<Pool>, JoinHandle<()>)> = HashMap::new();
+    // Pools of removed or moved groups, kept until the connections they proxied closed
+    let mut stopped: HashMap<String, Arc<Pool>> = HashMap::new();
+    let mut bind_errors: HashSet<String> = HashSet::new();
+    let mut interval = time::interval(Duration::from_millis(REFRESH_INTERVAL_MS));
+
+    loop {
+        interval.tick().await;
+
+        let groups = match tokio::task::spawn_blocking(snapshot).await {
+            Ok(groups) => groups,
+            Err(err) => {
+                log!("[balancer] failed to read state", "error" => err);
+                continue;
+            }
+        };
+
+        // Close listeners of groups that were removed or moved to another port
+        pools.retain(|name, (pool, handle)| {
+            let keep = groups.iter().any(|(group, balancer, _)| group == name && *balancer == pool.balancer);
+            if !keep {
+                handle.abort();
+                log!("[balancer] stopped", "group" => name, "address" => pool.balancer.address());
+                stopped.insert(name.clone(), Arc::clone(pool));
+            }
+            keep
+        });
+
+        for (name, balancer, ready) in groups {
+            if let Some((pool, _)) = pools.get(&name) {
+                pool.update(ready);
+                continue;
+            }
+
+            let listener = match TcpListener::bind(balancer.address()).await {
+                Ok(listener) => listener,
+                Err(err) => {
+                    if bind_errors.insert(name.clone()) {
+                        log!("[balancer] failed to listen", "group" => name, "address" => balancer.address(), "error" => err);
+                    }
+                    continue;
+                }
+            };
+            bind_errors.remove(&name);
+            log!("[balancer] listening", "group" => name, "address" => balancer.address(), "strategy" => balancer.strategy);
+
+            // A group back on another port keeps counting the connections still open from before
+            let pool = Arc::new(Pool {
+                group: name.clone(),
+                balancer,
+                backends: stopped.remove(&name).map(|pool| Arc::clone(&pool.backends)).unwrap_or_default(),
+                turn: AtomicUsize::new(0),
+            });
+            pool.update(ready);
+
+            let handle = tokio::spawn(accept(listener, Arc::clone(&pool)));
+            pools.insert(name, (pool, handle));
+        }
+
+        stopped.retain(|_, pool| !pool.update(Vec::new()));
+    }
+}
+
+async fn accept(listener: TcpListener, pool: Arc<Pool>) {
+    loop {
+        match listener.accept().await {
+            Ok((client, _)) => {
+                tokio::spawn(proxy(client, Arc::clone(&pool)));
+            }
+            Err(err) => {
+                log!("[balancer] failed to accept", "group" => pool.group, "error" => err);
+                time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+/// Forward a client to the first backend that accepts it
+async fn proxy(mut client: TcpStream, pool: Arc<Pool>) {
+    let _ = client.set_nodelay(true);
+    let mut tried = HashSet::new();
+
+    while let Some(connection) = pool.take(&tried) {
+        let backend = &connection.backend;
+        let connect = TcpStream::connect(("127.0.0.1", backend.port));
+
+        match time::timeout(Duration::from_millis(CONNECT_TIMEOUT_MS), connect).await {
+            Ok(Ok(mut upstream)) => {
+                let _ = upstream.set_nodelay(true);
+                let _ = copy_bidirectional(&mut client, &mut upstream).await;
+                return;
+            }
+            result => {
+                let error = match result {
+                    Ok(Err(err)) => err.to_string(),
+                    _ => format!("no connection within {}ms", CONNECT_TIMEOUT_MS),
+                };
+                let id = backend.id.to_string();
+                BALANCER_CONNECT_FAILURES.with_label_values(&backend.labels(&pool.group, &id)).inc();
+                log!("[balancer] backend refused connection", "group" => pool.group, "name" => backend.name, "port" => backend.port, "error" => error);
+                pool.refused(backend);
+                tried.insert(backend.id);
+            }
+        }
+    }
+
+    log!("[balancer] no ready instance for connection", "group" => pool.group);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opm::process::balancer::Strategy;
+    use prometheus::core::Collector;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// An instance that answers every connection with its id and keeps it open until the client closes
+    async fn instance(id: u8) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let _ = stream.write_all(&[id]).await;
+                    let _ = stream.read(&mut [0u8; 1]).await;
+                });
+            }
+        });
+        port
+    }
+
+    fn backend(id: usize, port: u16) -> Backend {
+        Backend { id, name: format!("web-{}", id), port }
+    }
+
+    /// A pool of `backends` accepting connections on its own port
+    async fn balanced(group: &str, strategy: Strategy, backends: &[Backend]) -> (Arc<Pool>, u16) {
+        static LOG: std::sync::Once = std::sync::Once::new();
+        LOG.call_once(|| global_placeholders::init!("opm.daemon.log", std::env::temp_dir().join("opm-balancer-test.log").display().to_string()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let pool = Arc::new(Pool {
+            group: group.to_string(),
+            balancer: Balancer::new(port, strategy),
+            backends: Arc::default(),
+            turn: AtomicUsize::new(0),
+        });
+        pool.update(backends.to_vec());
+        tokio::spawn(accept(listener, Arc::clone(&pool)));
+        (pool, port)
+    }
+
+    /// Connect through the balancer, returns the connection and the id of the instance that answered
+    async fn connect(port: u16) -> (TcpStream, u8) {
+        let mut client = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let mut id = [0u8; 1];
+        client.read_exact(&mut id).await.unwrap();
+        (client, id[0])
+    }
+
+    /// Wait until `pool` has `open` connections left
+    async fn settle(pool: &Pool, open: usize) {
+        for _ in 0..200 {
+            if pool.backends.lock().unwrap().active.values().sum::<usize>() == open {
+                return;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{} connections did not close", pool.group);
+    }
+
+    fn gauge(pool: &Pool, backend: &Backend) -> f64 {
+        BALANCER_CONNECTIONS.with_label_values(&backend.labels(&pool.group, &backend.id.to_string())).get()
+    }
+
+    /// Names of the instances of `pool` with a connection counter on /metrics
+    fn series(pool: &Pool) -> Vec<String> {
+        let mut names: Vec<String> = BALANCER_CONNECTIONS_TOTAL
+            .collect()
+            .iter()
+            .flat_map(|family| family.get_metric())
+            .filter_map(|metric| {
+                let label = |name| metric.get_label().iter().find(|label| label.get_name() == name).map(|label| label.get_value().to_string());
+                (label("group")? == pool.group).then(|| label("name")).flatten()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_round_robin_proxy() {
+        let backends = [backend(0, instance(0).await), backend(1, instance(1).await)];
+        let (pool, port) = balanced("test-round-robin", Strategy::RoundRobin, &backends).await;
+
+        let mut served = Vec::new();
+        for _ in 0..4 {
+            let (client, id) = connect(port).await;
+            served.push(id);
+            drop(client);
+        }
+        assert_eq!(served, vec![0, 1, 0, 1]);
+
+        settle(&pool, 0).await;
+        assert!(backends.iter().all(|backend| gauge(&pool, backend) == 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_least_conn_proxy() {
+        let backends = [backend(0, instance(0).await), backend(1, instance(1).await)];
+        let (pool, port) = balanced("test-least-conn", Strategy::LeastConn, &backends).await;
+
+        // The other instance takes every connection while one stays open
+        let (held, busy) = connect(port).await;
+        for _ in 0..3 {
+            let (client, id) = connect(port).await;
+            assert_ne!(id, busy);
+            assert_eq!(gauge(&pool, &backends[id as usize]), 1.0);
+            drop(client);
+            settle(&pool, 1).await;
+        }
+        assert_eq!(gauge(&pool, &backends[busy as usize]), 1.0);
+
+        drop(held);
+        settle(&pool, 0).await;
+        assert!(backends.iter().all(|backend| gauge(&pool, backend) == 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_failover_proxy() {
+        // Nothing listens on the port of the first instance any more
+        let refused = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let backends = [backend(0, refused), backend(1, instance(1).await)];
+        let (pool, port) = balanced("test-failover", Strategy::RoundRobin, &backends).await;
+
+        for _ in 0..3 {
+            let (client, id) = connect(port).await;
+            assert_eq!(id, 1);
+            drop(client);
+        }
+        // Once refused, the instance is passed over instead of delaying every connection
+        let failures = BALANCER_CONNECT_FAILURES.with_label_values(&backends[0].labels(&pool.group, "0")).get();
+        assert_eq!(failures, 1.0);
+
+        settle(&pool, 0).await;
+        assert!(backends.iter().all(|backend| gauge(&pool, backend) == 0.0));
+    }
+
+    #[tokio::test]
+    async fn test_removed_backend_metrics() {
+        let backends = [backend(0, instance(0).await), backend(1, instance(1).await)];
+        let (pool, port) = balanced("test-removed", Strategy::RoundRobin, &backends).await;
+
+        let (held, first) = connect(port).await;
+        let (client, _) = connect(port).await;
+        drop(client);
+        settle(&pool, 1).await;
+        assert_eq!(series(&pool), vec!["web-0", "web-1"]);
+
+        // An instance that left the pool keeps its metrics while a connection to it is open
+        let (left, stays) = (&backends[first as usize], &backends[1 - first as usize]);
+        assert!(!pool.update(vec![stays.clone()]));
+        assert_eq!(series(&pool), vec!["web-0", "web-1"]);
+
+        drop(held);
+        settle(&pool, 0).await;
+        assert!(!pool.update(vec![stays.clone()]));
+        assert_eq!(series(&pool), vec![stays.name.clone()]);
+        assert!(!series(&pool).contains(&left.name));
+
+        // Every series of a removed group goes
+        assert!(pool.update(Vec::new()));
+        assert!(series(&pool).is_empty());
+    }
+}
@@ -1,11 +1,13 @@
 #[macro_use]
 mod log;
 mod api;
+mod balancer;
 mod fork;
 
 use api::{
     DAEMON_CPU_PERCENTAGE, DAEMON_MEM_USAGE, DAEMON_START_TIME, GLOBAL_EVENT_MANAGER,
-    GLOBAL_NOTIFICATION_MANAGER,
+    GLOBAL_NOTIFICATION_MANAGER, PROCESS_LIVENESS_CHECKS, PROCESS_LIVENESS_FAILURES,
+    PROCESS_LIVENESS_HEALTHY,
 };
 use chrono::{DateTime, Utc};
 use colored::Colorize;
@@ -20,15 +22,22 @@ use serde_json::json;
 use std::panic;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::{process, thread::sleep, time::Duration};
+use std::{process, thread::sleep, time::{Duration, Instant}};
 
 use opm::{
     config,
     helpers::{self, ColoredString},
     process::{
-        dump, extract_search_pattern_from_command, get_process_cpu_usage_with_children_from_process, hash, Runner,
-        COOLDOWN_LOG_INTERVAL_SECS, PROCESS_CLEANUP_WAIT_MS,
-        RESTART_COOLDOWN_SECS,
+        dump, extract_search_pattern_from_command, get_process_cpu_usage_with_children_from_process,
+        health::{self, Liveness},
+        job::Job,
+        listen,
+        restart::{self, CrashRecord, Exit, RestartPolicy},
+        rotation,
+        schedule, sink,
+        threshold::{ThresholdAction, Usage},
+        watch::{self, WatchAction},
+        Runner, COOLDOWN_LOG_INTERVAL_SECS, PROCESS_CLEANUP_WAIT_MS,
     },
 };
 
@@ -51,6 +60,22 @@ static ENABLE_WEBUI: AtomicBool = AtomicBool::new(false);
 // This prevents race condition where daemon restarts processes that restore is already handling
 static RESTORE_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
 const RESTORE_IN_PROGRESS_FILE: &str = "restore_in_progress.flag";
+// Processes whose crash was handled by crash detection and still wait for their restart
+// (e.g. during the backoff delay), so the restart is counted once it is attempted
+static PENDING_RESTARTS: once_cell::sync::Lazy<dashmap::DashSet<usize>> =
+    once_cell::sync::Lazy::new(dashmap::DashSet::new);
+// Next cron_restart run per process, with the expression it was planned from
+type PlannedRun = (String, Option<DateTime<Utc>>);
+static CRON_RESTARTS: once_cell::sync::Lazy<dashmap::DashMap<usize, PlannedRun>> =
+    once_cell::sync::Lazy::new(dashmap::DashMap::new);
+// Next run per cron job, planned the same way as CRON_RESTARTS
+static JOB_RUNS: once_cell::sync::Lazy<dashmap::DashMap<usize, PlannedRun>> =
+    once_cell::sync::Lazy::new(dashmap::DashMap::new);
+// Memory and CPU usage per process, compared against its thresholds
+static USAGE: once_cell::sync::Lazy<dashmap::DashMap<usize, Usage>> =
+    once_cell::sync::Lazy::new(dashmap::DashMap::new);
+// Last dependency cycle reported, so a cycle is logged once instead of every monitoring cycle
+static REPORTED_CYCLE: Mutex<Option<String>> = Mutex::new(None);
 
 fn max_failed_restart_attempts(limit: u64) -> u32 {
     limit.min(u32::MAX as u64) as u32
@@ -79,7 +104,23 @@ extern "C" fn handle_sigpipe(_: libc::c_int) {
 }
 
 // Helper function to emit crash event and notification
-async fn emit_crash_event_and_notification(id: usize, name: String) {
+// An OOM kill reported by the process cgroup gets its own wording
+async fn emit_crash_event_and_notification(id: usize, name: String, crash: CrashRecord) {
+    let uptime = helpers::format_duration(crash.time - chrono::Duration::milliseconds(crash.uptime_ms as i64));
+    let (title, event_message, message) = if crash.oom_killed {
+        let message = format!(
+            "Process '{}' was killed by the OOM killer (cgroup memory.max reached) after {}",
+            name, uptime
+        );
+        ("Process Out of Memory", message.clone(), message)
+    } else {
+        (
+            "Process Crashed",
+            format!("Process '{}' crashed ({}) after {}", name, crash, uptime),
+            format!("Process '{}' has crashed ({}) after {}", name, crash, uptime),
+        )
+    };
+
     // Emit event if EventManager is available
     if let Some(event_manager) = GLOBAL_EVENT_MANAGER.get() {
         let event = opm::events::Event::new(
@@ -88,7 +129,7 @@ async fn emit_crash_event_and_notification(id: usize, name: String) {
             "Local".to_string(),
             Some(id.to_string()),
             Some(name.clone()),
-            format!("Process '{}' crashed", name),
+            event_message,
         );
         event_manager.add_event(event).await;
     }
@@ -98,13 +139,318 @@ async fn emit_crash_event_and_notification(id: usize, name: String) {
         notification_manager
             .send(
                 opm::notifications::NotificationEvent::ProcessCrash,
-                "Process Crashed",
-                &format!("Process '{}' has crashed", name),
+                title,
+                &message,
             )
             .await;
     }
 }
 
+/// Whether the schedule of a process came due, planning its next run when it did
+/// A schedule is planned from now when first seen or changed, so missed runs are not made up
+fn cron_due(plans: &dashmap::DashMap<usize, PlannedRun>, id: usize, expression: &str) -> bool {
+    let now = Utc::now();
+    let mut planned = plans
+        .entry(id)
+        .or_insert_with(|| (expression.to_string(), schedule::next_after(expression, now)));
+    if planned.0 != expression {
+        *planned = (expression.to_string(), schedule::next_after(expression, now));
+    }
+
+    match planned.1 {
+        Some(next) if next <= now => {
+            planned.1 = schedule::next_after(expression, now);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Restart a process once its cron_restart schedule comes due, without touching the crash counters
+/// The first check of a schedule only plans its next run, so a missed run is not made up on daemon start
+fn check_cron_restart(runner: &mut Runner, id: usize, item: &opm::process::Process, expression: &str) -> bool {
+    if !cron_due(&CRON_RESTARTS, id, expression) {
+        return false;
+    }
+
+    let message = format!("Process '{}' restarted by its cron_restart schedule ({})", item.name, expression);
+    log!("[daemon] scheduled restart", "name" => &item.name, "id" => id, "cron" => expression);
+    opm::events::emit(opm::events::Event::new(
+        opm::events::EventType::ProcessScheduledRestart,
+        "local".to_string(),
+        "Local".to_string(),
+        Some(id.to_string()),
+        Some(item.name.clone()),
+        message,
+    ));
+    runner.restart(id, false, false);
+    runner.save_direct();
+    true
+}
+
+/// Start the runs of a job when due, kill a run past its timeout and skip runs that would overlap
+/// Returns true when the rest of the monitoring cycle should be skipped for this process
+fn check_job(runner: &mut Runner, id: usize, item: &opm::process::Process, job: &Job, restore_in_progress: bool) -> bool {
+    if item.pid > 0 {
+        let elapsed = (Utc::now() - item.started).to_std().unwrap_or_default();
+        if let Some(timeout) = job.timeout().filter(|timeout| elapsed > *timeout) {
+            if opm::process::is_pid_alive(item.pid) {
+                log!("[daemon] job run timed out", "name" => &item.name, "id" => id, "timeout_ms" => timeout.as_millis());
+                runner.timeout_run(id);
+                runner.save_direct();
+                return true;
+            }
+        }
+
+        if let Some(expression) = job.cron.as_deref() {
+            if cron_due(&JOB_RUNS, id, expression) {
+                log!("[daemon] job run skipped, previous run still in progress", "name" => &item.name, "id" => id, "cron" => expression);
+            }
+        }
+        return false;
+    }
+
+    if !item.is_waiting_for_run() {
+        return false;
+    }
+
+    // A job without a schedule runs once after it is started
+    let due = match job.cron.as_deref() {
+        Some(expression) => cron_due(&JOB_RUNS, id, expression),
+        None => item.runs.last().is_none_or(|run| run.started < item.started),
+    };
+    if !due || restore_in_progress || runner.is_frozen(id) {
+        return true;
+    }
+
+    // A scheduled run is skipped while a dependency is down, a one-shot job keeps waiting for it
+    if let Some(dependency) = runner.pending_dependency(&item.exec.depends_on) {
+        if job.cron.is_some() {
+            log!("[daemon] job run skipped, dependency is not up", "name" => &item.name, "id" => id, "dependency" => dependency);
+        }
+        return true;
+    }
+
+    log!("[daemon] starting job run", "name" => &item.name, "id" => id, "job" => job.describe());
+    let process = runner.process(id);
+    process.restarts = 0;
+    process.failed_restart_attempts = 0;
+    runner.restart(id, true, false);
+
+    // A run that could not be spawned ends right away as failed, it is not retried until the next one
+    let process = runner.process(id);
+    if process.pid == 0 {
+        log!("[daemon] job run failed to start", "name" => &item.name, "id" => id);
+        process.started = Utc::now();
+        process.begin_run();
+        process.finish_run(None, false);
+        process.record_crash(CrashRecord::new(process.started, None, false, Some(string!("run failed to start"))));
+        process.running = job.cron.is_some();
+        process.crash.crashed = false;
+    }
+    runner.save_direct();
+    true
+}
+
+/// Start a process that was held back until its dependencies are up
+/// Returns true when the rest of the monitoring cycle should be skipped for this process
+fn start_after_dependencies(runner: &mut Runner, id: usize, item: &opm::process::Process, restore_in_progress: bool) -> bool {
+    if item.pid > 0 || item.crash.crashed || item.exec.job.is_some() || item.exec.depends_on.is_empty() {
+        return false;
+    }
+    if restore_in_progress || runner.is_frozen(id) || runner.pending_dependency(&item.exec.depends_on).is_some() {
+        return true;
+    }
+
+    log!("[daemon] dependencies are up, starting process", "name" => &item.name, "id" => id);
+    runner.restart(id, true, false);
+    runner.save_direct();
+    true
+}
+
+/// Order the processes so dependencies are handled first, a process started in a monitoring cycle
+/// then lets the processes depending on it start in the same cycle
+fn monitoring_order(runner: &Runner, ids: Vec<usize>) -> Vec<usize> {
+    match runner.start_order(&ids) {
+        Ok(order) => {
+            *REPORTED_CYCLE.lock().unwrap_or_else(|err| err.into_inner()) = None;
+            order
+        }
+        Err(err) => {
+            let mut reported = REPORTED_CYCLE.lock().unwrap_or_else(|err| err.into_inner());
+            if reported.as_deref() != Some(err.as_str()) {
+                log!("[daemon] processes on a dependency cycle are not started", "error" => &err);
+                *reported = Some(err);
+            }
+            ids
+        }
+    }
+}
+
+/// Emit the event and notification of a process that exceeded one of its thresholds
+fn emit_threshold_exceeded(id: usize, name: &str, message: String) {
+    opm::events::emit(opm::events::Event::new(
+        opm::events::EventType::ProcessThresholdExceeded,
+        "local".to_string(),
+        "Local".to_string(),
+        Some(id.to_string()),
+        Some(name.to_string()),
+        message.clone(),
+    ));
+
+    let notification_manager = GLOBAL_NOTIFICATION_MANAGER.get();
+    if let (Ok(handle), Some(notification_manager)) = (tokio::runtime::Handle::try_current(), notification_manager) {
+        handle.spawn(async move {
+            notification_manager
+                .send(
+                    opm::notifications::NotificationEvent::ProcessThresholdExceeded,
+                    "Process Threshold Exceeded",
+                    &message,
+                )
+                .await;
+        });
+    }
+}
+
+/// Sample the memory and CPU usage of a process and take its threshold action once one stays exceeded
+/// Returns true when the process was restarted or stopped
+fn check_thresholds(runner: &mut Runner, id: usize, item: &opm::process::Process) -> bool {
+    let thresholds = &item.exec.thresholds;
+    let pid = item.shell_pid.unwrap_or(item.pid);
+    let now = Instant::now();
+
+    let mut usage = USAGE.entry(id).or_insert_with(|| Usage::new(pid, now));
+    if !usage.is_for(pid) {
+        *usage = Usage::new(pid, now);
+    }
+
+    let mut breach = None;
+    if item.max_memory > 0 {
+        if let Some(memory) = opm::process::get_process_memory_with_children(pid) {
+            let sustained = thresholds.memory_for();
+            if usage.memory(now, memory.rss, item.max_memory, sustained, &thresholds.action) {
+                let (rss, max) = (helpers::format_memory(memory.rss), helpers::format_memory(item.max_memory));
+                breach = Some(match sustained.is_zero() {
+                    true => format!("memory {rss} is above max_memory {max}"),
+                    false => format!("memory {rss} stayed above max_memory {max} for {}ms", sustained.as_millis()),
+                });
+            }
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    if let Some(max_cpu) = thresholds.max_cpu.filter(|_| breach.is_none()) {
+        if usage.cpu_due(now, thresholds.cpu_sample_interval()) {
+            if let Ok(process) = Process::new(pid as u32) {
+                let percent = get_process_cpu_usage_with_children_from_process(&process, pid);
+                let window = thresholds.cpu_window();
+                if let Some(average) = usage.cpu(now, percent, max_cpu, window, &thresholds.action) {
+                    breach = Some(format!("CPU averaged {:.1}% over {}ms, above max_cpu {}%", average, window.as_millis(), max_cpu));
+                }
+            }
+        }
+    }
+    drop(usage);
+
+    let Some(breach) = breach else {
+        return false;
+    };
+
+    let outcome = match &thresholds.action {
+        ThresholdAction::Restart => "restarting".to_string(),
+        ThresholdAction::Stop => "stopping".to_string(),
+        ThresholdAction::Signal(signal) => format!("sending {}", signal),
+        ThresholdAction::NotifyOnly => "notify only".to_string(),
+    };
+    log!("[daemon] threshold exceeded", "name" => &item.name, "id" => id, "breach" => &breach, "action" => thresholds.action.to_string());
+    emit_threshold_exceeded(id, &item.name, format!("Process '{}' exceeded a threshold, {} ({})", item.name, breach, outcome));
+
+    match &thresholds.action {
+        ThresholdAction::Restart => {
+            runner.process(id).record_crash(CrashRecord::new(item.started, None, false, Some(breach)));
+            runner.restart(id, false, true);
+        }
+        ThresholdAction::Stop => {
+            runner.process(id).record_crash(CrashRecord::new(item.started, None, false, Some(breach)));
+            runner.stop(id);
+        }
+        ThresholdAction::Signal(signal) => {
+            let sent = signal.parse::<nix::sys::signal::Signal>().and_then(|signal| nix::sys::signal::kill(nix::unistd::Pid::from_raw(item.pid as i32), signal));
+            if let Err(err) = sent {
+                log!("[daemon] failed to send threshold signal", "name" => &item.name, "id" => id, "signal" => signal, "error" => err.to_string());
+            }
+            return false;
+        }
+        ThresholdAction::NotifyOnly => return false,
+    }
+    runner.save_direct();
+    true
+}
+
+/// Collect the finished liveness probe of a process and start the next one once the interval has passed
+/// Returns true when the process was restarted for reaching its failure threshold
+fn check_liveness(runner: &mut Runner, id: usize, item: &opm::process::Process, liveness: &Liveness) -> bool {
+    let id_label = id.to_string();
+
+    if let Some(result) = health::finished_probe(id, item.pid) {
+        let passed = result.is_ok();
+        let process = runner.process(id);
+        let unhealthy = process.health.record(result, liveness.failure_threshold());
+        let failures = process.health.consecutive_failures;
+        let last_error = process.health.last_error.clone().unwrap_or_default();
+
+        PROCESS_LIVENESS_CHECKS
+            .with_label_values(&[&id_label, &item.name, ternary!(passed, "success", "failure")])
+            .inc();
+        PROCESS_LIVENESS_HEALTHY
+            .with_label_values(&[&id_label, &item.name])
+            .set(ternary!(passed, 1.0, 0.0));
+        PROCESS_LIVENESS_FAILURES
+            .with_label_values(&[&id_label, &item.name])
+            .set(failures as f64);
+
+        if unhealthy {
+            let message = format!(
+                "Process '{}' failed {} liveness probes in a row ({}), restarting",
+                item.name, failures, last_error
+            );
+            log!("[daemon] liveness threshold reached, restarting", "name" => &item.name, "id" => id, "failures" => failures, "error" => &last_error);
+            let reason = format!("liveness probe failed {} times ({})", failures, last_error);
+            runner.process(id).record_crash(CrashRecord::new(item.started, None, false, Some(reason)));
+            opm::events::emit(opm::events::Event::new(
+                opm::events::EventType::ProcessUnhealthy,
+                "local".to_string(),
+                "Local".to_string(),
+                Some(id_label),
+                Some(item.name.clone()),
+                message,
+            ));
+            runner.restart(id, false, true);
+            runner.save_direct();
+            return true;
+        }
+
+        if !passed {
+            log!("[daemon] liveness probe failed", "name" => &item.name, "id" => id, "failures" => failures, "error" => &last_error);
+        }
+        runner.save_direct();
+    }
+
+    let last_check = runner.process(id).health.last_check.unwrap_or(item.started);
+    let due = (Utc::now() - last_check).to_std().unwrap_or_default() >= liveness.interval();
+    if due {
+        liveness.spawn(
+            id,
+            item.pid,
+            config::read().runner.shell,
+            item.exec.working_dir(&item.path),
+            item.env.clone(),
+        );
+    }
+
+    false
+}
+
 /// Reap zombie child processes by calling try_wait() on all process handles
 /// This function should be called periodically (e.g., at the start of each monitoring cycle)
 /// to prevent zombie process accumulation
@@ -122,8 +468,11 @@ fn reap_zombie_processes() {
                 Ok(Some(status)) => {
                     // Child has exited - remove from handles before dropping the lock
                     // This eliminates any race condition where another thread could access the handle
+                    // The exit status is kept for the crash detection below, which decides on a restart with it
+                    let exit = Exit::from(status);
+                    restart::record_exit(pid, exit);
                     opm::process::PROCESS_HANDLES.remove(&pid);
-                    log!("[daemon] reaped zombie/exited process", "pid" => pid, "success" => status.success());
+                    log!("[daemon] reaped zombie/exited process", "pid" => pid, "exit" => exit);
                 }
                 Ok(None) => {
                     // Child is still running, nothing to do
@@ -157,6 +506,11 @@ fn should_fail_process_validation(
 }
 
 fn is_process_still_alive(process: &opm::process::Process) -> bool {
+    let cgroup = process.cgroup.as_ref();
+    if let Some(populated) = cgroup.and_then(|cgroup| cgroup.is_populated()) {
+        return populated;
+    }
+
     let shell_alive = process
         .shell_pid
         .map_or(false, |pid| opm::process::is_pid_alive(pid));
@@ -168,7 +522,7 @@ fn is_process_still_alive(process: &opm::process::Process) -> bool {
         && (opm::process::is_pid_alive(process.pid) || shell_alive || session_alive);
 
     let tree_alive = if process.pid > 0 {
-        opm::process::is_process_or_children_alive_sysinfo(process.pid, &process.children)
+        opm::process::is_process_or_children_alive_sysinfo(process.pid, &process.children, cgroup)
     } else {
         process
             .children
@@ -211,7 +565,7 @@ fn restart_process() {
     let mut runner = Runner::new_direct();
     // Collect IDs first to avoid borrowing issues during iteration
     // Use process_ids() instead of items().keys() to avoid cloning all processes
-    let process_ids: Vec<usize> = runner.process_ids().collect();
+    let process_ids = monitoring_order(&runner, runner.process_ids().collect());
 
     for id in process_ids {
         let restore_in_progress = is_restore_in_progress();
@@ -221,8 +575,25 @@ fn restart_process() {
             None => continue, // Process was removed, skip it
         };
 
+        // Logs grow while the process runs, and stopped ones may still be due on their schedule
+        if let Some(rotation) = item.exec.log_rotation.as_ref().or(daemon_config.log_rotation.as_ref()) {
+            let logs = item.logs();
+            rotation::check(&[&logs.out, &logs.error, &logs.combined], rotation);
+        }
+
+        if let Some(job) = item.exec.job.as_ref().filter(|_| item.running) {
+            if check_job(&mut runner, id, &item, job, restore_in_progress) {
+                continue;
+            }
+        }
+
+        if item.running && start_after_dependencies(&mut runner, id, &item, restore_in_progress) {
+            continue;
+        }
+
         // Check if PID info is missing/incomplete - log error and skip crash detection
-        if opm::process::is_pid_info_missing(item.pid, &item.children) {
+        // A crashed process without an instance (e.g. one that failed its readiness check) still needs its retry
+        if opm::process::is_pid_info_missing(item.pid, &item.children) && !item.crash.crashed {
             ::log::error!("[daemon] process {} ({}) has missing/incomplete PID info (pid={}, children={:?}) - cannot determine crash status",
                 item.name, id, item.pid, item.children);
             // DO NOT mark as crashed when PID info is missing
@@ -238,11 +609,17 @@ fn restart_process() {
             .shell_pid
             .map_or(false, |pid| opm::process::is_pid_alive(pid));
 
+        // cgroup membership, when available, replaces the session heuristic below
+        let cgroup_alive = item.cgroup.as_ref().and_then(|cgroup| cgroup.is_populated());
+
         // Check if session is alive (more robust than individual PID checks)
         // This handles process forking where the main PID exits but children continue running
-        let session_alive = item
-            .session_id
-            .map_or(false, |sid| opm::process::is_session_alive(sid));
+        let session_alive = match cgroup_alive {
+            Some(populated) => populated,
+            None => item
+                .session_id
+                .map_or(false, |sid| opm::process::is_session_alive(sid)),
+        };
 
         // PM2-STYLE VALIDATION: Check for PID reuse and command mismatch
         // This is the "single source of truth" validation that prevents ghost processes
@@ -303,8 +680,11 @@ fn restart_process() {
             false
         } else {
             has_valid_pid
-                && (opm::process::is_process_or_children_alive_sysinfo(item.pid, &item.children)
-                    || shell_alive
+                && (opm::process::is_process_or_children_alive_sysinfo(
+                    item.pid,
+                    &item.children,
+                    item.cgroup.as_ref(),
+                ) || shell_alive
                     || session_alive)
         };
 
@@ -354,29 +734,65 @@ fn restart_process() {
                 runner.save_direct();
             }
 
-            // Perform other checks for living processes (memory, watch).
-            if item.running && item.max_memory > 0 {
-                let pid_for_monitoring = item.shell_pid.unwrap_or(item.pid);
-                if let Some(memory_info) =
-                    opm::process::get_process_memory_with_children(pid_for_monitoring)
-                {
-                    if memory_info.rss > item.max_memory {
-                        log!("[daemon] memory limit exceeded", "name" => &item.name, "id" => id, "memory" => memory_info.rss, "limit" => item.max_memory);
-                        runner.stop(id);
+            // Hold the process in "launching" until its readiness check passes
+            if item.running && item.launching.is_some() && runner.exists(id) {
+                let process = runner.process(id);
+                if process.is_ready() {
+                    process.launching = None;
+                    process.failed_restart_attempts = 0;
+                    runner.save_direct();
+                    log!("[daemon] process passed readiness check", "name" => &item.name, "id" => id);
+                } else {
+                    let timeout = item.exec.ready.as_ref().map(|ready| ready.timeout()).unwrap_or_default();
+                    let launching_for = (Utc::now() - item.started).to_std().unwrap_or_default();
+                    if launching_for > timeout {
+                        log!("[daemon] readiness timeout, treating as failed start", "name" => &item.name, "id" => id, "timeout_ms" => timeout.as_millis());
+                        runner.fail_launch(id);
+                        runner.save_direct();
                         continue;
                     }
                 }
             }
 
+            if let Some(expression) = item.exec.cron_restart.as_deref().filter(|_| item.running) {
+                if check_cron_restart(&mut runner, id, &item, expression) {
+                    continue;
+                }
+            }
+
+            if let Some(liveness) = item.exec.liveness.as_ref().filter(|_| item.running && item.launching.is_none()) {
+                if check_liveness(&mut runner, id, &item, liveness) {
+                    continue;
+                }
+            }
+
+            // Perform other checks for living processes (memory and CPU thresholds, watch).
+            let has_thresholds = item.max_memory > 0 || item.exec.thresholds.max_cpu.is_some();
+            if item.running && has_thresholds && check_thresholds(&mut runner, id, &item) {
+                continue;
+            }
+
             if item.running && item.watch.enabled {
-                let path = item.path.join(item.watch.path.clone());
-                if hash::create(path) != item.watch.hash {
-                    log!("[daemon] watch triggered reload", "name" => &item.name, "id" => id);
-                    runner.restart(id, false, true);
+                watch::sync(id, &item.path, &item.watch);
+                if let Some(changed) = watch::changed(id, item.watch.debounce()) {
+                    log!("[daemon] watch triggered restart", "name" => &item.name, "id" => id, "changed" => &changed, "action" => item.watch.action);
+                    match item.watch.action {
+                        WatchAction::Restart => runner.restart(id, false, true),
+                        WatchAction::Reload => runner.reload(id, false, true),
+                    };
                     continue;
                 }
             }
 
+            // An instance that stayed up for min_uptime was a successful start, stop backing off
+            if item.running && item.failed_restart_attempts > 0 && item.launching.is_none() {
+                let uptime = (Utc::now() - item.started).to_std().unwrap_or_default();
+                if uptime >= item.exec.restart.min_uptime() && runner.exists(id) {
+                    runner.process(id).failed_restart_attempts = 0;
+                    runner.save_direct();
+                }
+            }
+
             // If process is stable, clear the crashed flag.
             if item.running && item.crash.crashed {
                 let uptime_secs = (Utc::now() - item.started).num_seconds();
@@ -428,14 +844,23 @@ fn restart_process() {
             // 2. Prevented stopped processes from staying stopped (would restart them instead)
             // When a process dies, we no longer try to adopt its children
 
+            // The exit status is only known for processes this daemon spawned,
+            // anything else (e.g. started by the CLI) counts as a failure.
+            // A known exit is final, so it is handled even within the action delay and grace period
+            let exit = match item.pid > 0 {
+                true => restart::take_exit(item.shell_pid.unwrap_or(item.pid)),
+                false => None,
+            };
+
             if daemon_config.crash_detection {
                 // --- CRASH DETECTION LOGIC ---
                 // Only run crash detection if not within action delay
                 // This prevents false crash detection for processes that are still starting up
-                if !within_action_delay {
+                if !within_action_delay || exit.is_some() {
                     // Check if restore is in progress - if so, skip crash detection to prevent conflicts
                     // This prevents the daemon from restarting processes that are being restored
-                    if restore_in_progress {
+                    // A job run ending is still recorded, restore may be waiting for it as a dependency
+                    if restore_in_progress && item.exec.job.is_none() {
                         continue; // Skip monitoring during restore operations
                     }
 
@@ -444,11 +869,24 @@ fn restart_process() {
                     let just_started = (Utc::now() - item.started).num_seconds() < grace_period;
                     let is_new_crash = item.pid > 0;
 
-                    if is_new_crash && !just_started {
-                        // Check if this is a manual stop (user-initiated via 'opm stop')
-                        // Re-read the latest process state to check the manual_stop flag
-                        // (item is a snapshot from the start of the loop, might be stale)
-                        let is_manual_stop = runner.exists(id) && runner.process(id).manual_stop;
+                    if is_new_crash && (!just_started || exit.is_some()) {
+                        // item and runner are snapshots from the start of the cycle, the CLI may have stopped,
+                        // restarted or removed the process since. Continue from the latest state so saving
+                        // below does not bring back what was changed in the meantime
+                        runner = Runner::new_direct();
+                        let Some(latest) = runner.info(id).filter(|p| p.name == item.name).cloned() else {
+                            log!("[daemon] process was removed (not a crash)", "name" => &item.name, "id" => id);
+                            continue;
+                        };
+
+                        // Replaced by a restart elsewhere (e.g. 'opm restart' or 'opm scale'), the exit is the old instance
+                        if latest.pid != item.pid {
+                            log!("[daemon] process was replaced (not a crash)", "name" => &item.name, "id" => id, "pid" => latest.pid);
+                            continue;
+                        }
+
+                        // Check if this is a manual stop (user-initiated via 'opm stop' or 'opm restart')
+                        let is_manual_stop = latest.manual_stop;
 
                         if is_manual_stop {
                             if runner.exists(id) {
@@ -465,23 +903,14 @@ fn restart_process() {
                             continue;
                         }
 
-                        let process_handle_pid = item.shell_pid.unwrap_or(item.pid);
-                        let mut exited_successfully = false;
-                        let mut handle_found = false;
-
-                        if let Some((_, handle_ref)) =
-                            opm::process::PROCESS_HANDLES.remove(&process_handle_pid)
-                        {
-                            handle_found = true;
-                            if let Ok(mut child) = handle_ref.lock() {
-                                if let Ok(Some(status)) = child.try_wait() {
-                                    exited_successfully = status.success();
-                                    log!("[daemon] reaped exited process", "name" => &item.name, "id" => id, "success" => exited_successfully);
-                                }
-                            }
+                        let restart_config = &item.exec.restart;
+                        let exited_successfully =
+                            exit.as_ref().is_some_and(|exit| restart_config.is_success(exit));
+                        if let Some(exit) = &exit {
+                            log!("[daemon] reaped exited process", "name" => &item.name, "id" => id, "exit" => exit, "success" => exited_successfully);
                         }
 
-                        if handle_found && exited_successfully {
+                        if exited_successfully && item.exec.job.is_none() {
                             // Safe PID adoption for shell wrapper scenarios (Stirling-PDF fix)
                             // If the shell wrapper exits cleanly but has exactly ONE child process,
                             // adopt that child as the new primary PID instead of marking as stopped
@@ -560,39 +989,112 @@ fn restart_process() {
                                 }
                             }
 
-                            // No suitable child to adopt - process exited cleanly, mark as stopped
+                        }
+
+                        // Anything but a clean exit goes into the crash history
+                        let crash = (!exited_successfully).then(|| {
+                            let oom_killed = item.cgroup.as_ref().is_some_and(|cgroup| cgroup.oom_killed());
+                            CrashRecord::new(item.started, exit, oom_killed, None)
+                        });
+                        if let Some(crash) = crash.as_ref().filter(|_| runner.exists(id)) {
+                            runner.process(id).record_crash(crash.clone());
+                        }
+
+                        // A finished job run waits for the next one, unless it failed and the restart policy retries it
+                        if let Some(job) = item.exec.job.as_ref().filter(|_| runner.exists(id)) {
+                            let retry = !exited_successfully
+                                && restart_config.should_restart(exit.as_ref())
+                                && item.restarts < restart_config.max_restarts(daemon_config.restarts);
+                            let process = runner.process(id);
+                            process.finish_run(exit, false);
+
+                            if !retry {
+                                process.pid = 0;
+                                process.shell_pid = None;
+                                process.children = vec![];
+                                process.crash.crashed = false;
+                                process.running = item.running && job.cron.is_some();
+                                runner.save_direct();
+                                log!("[daemon] job run finished", "name" => &item.name, "id" => id, "exit" => exit.map(|exit| exit.to_string()).unwrap_or_default(), "success" => exited_successfully);
+                                if let Some(crash) = crash {
+                                    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                                        handle.spawn(emit_crash_event_and_notification(id, item.name.clone(), crash));
+                                    }
+                                }
+                                continue;
+                            }
+                        }
+
+                        // An instance that did not stay up for min_uptime counts as a failed start,
+                        // which grows the restart delay and counts toward the failed restart limit
+                        let uptime = (Utc::now() - item.started).to_std().unwrap_or_default();
+                        if runner.exists(id) {
+                            let process = runner.process(id);
+                            if uptime < restart_config.min_uptime() {
+                                process.failed_restart_attempts += 1;
+                            } else {
+                                process.failed_restart_attempts = 0;
+                            }
+                        }
+
+                        if !restart_config.should_restart(exit.as_ref()) {
+                            // The restart policy leaves this exit alone, mark as stopped
                             if runner.exists(id) {
                                 let process = runner.process(id);
                                 process.running = false;
                                 process.pid = 0;
                                 process.shell_pid = None;
-                                process.crash.crashed = false;
+                                process.crash.crashed = !exited_successfully;
                                 runner.save_direct();
-                                log!("[daemon] process stopped cleanly", "name" => &item.name, "id" => id);
+                                if exited_successfully {
+                                    log!("[daemon] process stopped cleanly", "name" => &item.name, "id" => id);
+                                } else if let Some(crash) = crash {
+                                    log!("[daemon] process exited, not restarted by its restart policy", "name" => &item.name, "id" => id, "policy" => restart_config.policy, "cause" => &crash);
+                                    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                                        handle.spawn(emit_crash_event_and_notification(id, item.name.clone(), crash));
+                                    }
+                                }
                             }
                             continue;
                         }
 
-                        // If handle not found, or it exited with an error, it's a crash.
+                        // A failure, or a clean exit under always/unless-stopped: mark for the restart below
                         if runner.exists(id) {
                             let process = runner.process(id);
                             process.pid = 0;
                             process.shell_pid = None;
                             process.crash.crashed = true;
+                            PENDING_RESTARTS.insert(id);
+                            let max_restarts = restart_config.max_restarts(daemon_config.restarts);
 
-                            if item.running {
+                            if item.running && exited_successfully {
+                                if item.restarts >= max_restarts {
+                                    process.running = false;
+                                    process.errored = true;
+                                    log!("[daemon] process reached max restart limit, setting errored state", "name" => &item.name, "id" => id, "restarts" => item.restarts, "limit" => max_restarts);
+                                } else {
+                                    log!("[daemon] process exited cleanly, restarting per restart policy", "name" => &item.name, "id" => id, "policy" => restart_config.policy);
+                                }
+                            } else if let Some(crash) = crash.filter(|_| item.running) {
                                 let process_name = item.name.clone();
+                                if crash.oom_killed {
+                                    log!("[daemon] process killed by OOM killer", "name" => &item.name, "id" => id);
+                                }
+                                log!("[daemon] process crash cause", "name" => &item.name, "id" => id, "cause" => &crash, "uptime_ms" => crash.uptime_ms);
                                 if let Some(handle) = tokio::runtime::Handle::try_current().ok() {
-                                    handle
-                                        .spawn(emit_crash_event_and_notification(id, process_name));
+                                    handle.spawn(emit_crash_event_and_notification(
+                                        id,
+                                        process_name,
+                                        crash,
+                                    ));
                                 }
 
                                 // Check restart limit using restarts counter
                                 // This is the single source of truth displayed in `opm info`
-                                if item.restarts >= daemon_config.restarts {
+                                if item.restarts >= max_restarts {
                                     process.running = false;
                                     process.errored = true;
-                                    log!("[daemon] process reached max restart limit, setting errored state", "name" => &item.name, "id" => id, "restarts" => item.restarts, "limit" => daemon_config.restarts);
+                                    log!("[daemon] process reached max restart limit, setting errored state", "name" => &item.name, "id" => id, "restarts" => item.restarts, "limit" => max_restarts);
                                 } else {
                                     log!("[daemon] process crashed", "name" => &item.name, "id" => id, "restarts" => item.restarts);
                                 }
@@ -627,31 +1129,41 @@ fn restart_process() {
                                 continue;
                             }
 
+                            // Died within the grace period with an unknown exit, leave it to crash detection
+                            if proc.pid > 0 && proc.exec.restart.policy == RestartPolicy::Never {
+                                continue;
+                            }
+
+                            // A job between runs is started by its schedule, not restarted
+                            if proc.is_waiting_for_run() {
+                                continue;
+                            }
+
                             // Check restart limit BEFORE attempting restart
-                            if proc.restarts >= daemon_config.restarts {
+                            let max_restarts = proc.exec.restart.max_restarts(daemon_config.restarts);
+                            if proc.restarts >= max_restarts {
                                 // Limit reached - stop the process permanently and set errored state
+                                PENDING_RESTARTS.remove(&id);
                                 if let Some(process) = runner.list.get_mut(&id) {
                                     process.running = false;
                                     process.errored = true;
                                 }
                                 runner.save_direct();
                                 log!("[daemon] process reached max restart limit, stopping permanently with errored state", 
-                                    "name" => &proc.name, "id" => id, "restarts" => proc.restarts, "limit" => daemon_config.restarts);
+                                    "name" => &proc.name, "id" => id, "restarts" => proc.restarts, "limit" => max_restarts);
                             } else {
-                                // Simplified restart cooldown mechanism with fixed 2s delay
-                                // As per requirement #4, no complex exponential backoff
-                                let seconds_since_last_attempt = proc
+                                // Restart delay from the process backoff, growing with each failed start in a row
+                                let since_last_attempt = proc
                                     .last_restart_attempt
-                                    .map(|t| (Utc::now() - t).num_seconds())
-                                    .unwrap_or(i64::MAX); // Never attempted - allow restart immediately
+                                    .map(|t| (Utc::now() - t).to_std().unwrap_or_default())
+                                    .unwrap_or(Duration::MAX); // Never attempted - allow restart immediately
+                                let seconds_since_last_attempt = since_last_attempt.as_secs() as i64;
+                                let restart_delay = proc.restart_delay();
 
-                                // Fixed 2-second delay for all restart attempts
-                                let base_delay = RESTART_COOLDOWN_SECS;
-
-                                let max_failed_attempts_limit =
-                                    max_failed_restart_attempts(daemon_config.restarts);
+                                let max_failed_attempts_limit = max_failed_restart_attempts(max_restarts);
                                 if proc.failed_restart_attempts >= max_failed_attempts_limit {
                                     // Set to FATAL_ERROR state - stop auto-restart until manual intervention
+                                    PENDING_RESTARTS.remove(&id);
                                     if let Some(process) = runner.list.get_mut(&id) {
                                         process.running = false;
                                         process.errored = true;
@@ -665,8 +1177,7 @@ fn restart_process() {
                                     continue; // Skip restart - requires manual intervention
                                 }
 
-                                let within_cooldown =
-                                    seconds_since_last_attempt < base_delay as i64;
+                                let within_cooldown = since_last_attempt < restart_delay;
 
                                 if within_cooldown {
                                     // Process is in cooldown period - skip restart
@@ -678,7 +1189,7 @@ fn restart_process() {
                                         log!("[daemon] process in restart cooldown", 
                                             "name" => &proc.name, 
                                             "id" => id, 
-                                            "wait_secs" => base_delay - seconds_since_last_attempt as u64);
+                                            "wait_ms" => restart_delay.saturating_sub(since_last_attempt).as_millis());
                                     }
                                 } else if runner.is_frozen(id) {
                                     log!("[daemon] process is frozen, skipping restart", "name" => &proc.name, "id" => id);
@@ -688,25 +1199,36 @@ fn restart_process() {
                                     // This prevents double-counting when restart fails and we retry on the next cycle
                                     // - pid > 0: Process was running and crashed, this is a new restart attempt
                                     // - pid = 0: Process failed to start/restart, this is a retry of the same attempt
-                                    let is_new_crash = proc.pid > 0;
+                                    // Crash detection clears the PID of crashes it handled, those are tracked in PENDING_RESTARTS
+                                    let is_new_crash = proc.pid > 0 || PENDING_RESTARTS.remove(&id).is_some();
+                                    // Crash detection already counted a start that did not reach min_uptime
+                                    let unclassified_crash = proc.pid > 0;
 
                                     if is_new_crash {
                                         let new_restart_count = proc.restarts + 1;
                                         if let Some(process) = runner.list.get_mut(&id) {
                                             process.restarts = new_restart_count;
                                         }
+                                        if unclassified_crash {
+                                            let oom_killed = proc.cgroup.as_ref().is_some_and(|cgroup| cgroup.oom_killed());
+                                            let crash = CrashRecord::new(proc.started, exit, oom_killed, None);
+                                            runner.process(id).record_crash(crash.clone());
+                                            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                                                handle.spawn(emit_crash_event_and_notification(id, proc.name.clone(), crash));
+                                            }
+                                        }
                                         log!("[daemon] restarting crashed process", 
                                             "name" => &proc.name, 
                                             "id" => id, 
                                             "restarts" => new_restart_count, 
-                                            "cooldown_secs" => base_delay);
+                                            "delay_ms" => restart_delay.as_millis());
                                     } else {
                                         log!("[daemon] retrying failed restart", 
                                             "name" => &proc.name, 
                                             "id" => id, 
                                             "restarts" => proc.restarts, 
                                             "failed_attempts" => proc.failed_restart_attempts,
-                                            "cooldown_secs" => base_delay);
+                                            "delay_ms" => restart_delay.as_millis());
                                     }
 
                                     // Record restart attempt timestamp BEFORE attempting restart
@@ -726,35 +1248,26 @@ fn restart_process() {
 
                                      // Update failed restart counter based on result
                                      // Verify process is actually running by checking PID > 0 AND process is alive
-                                     // Also check if this was a quick crash (within 10 seconds) to detect flapping
+                                     // The counter is reset once the new instance stays up for min_uptime
                                      if let Some(process) = runner.list.get_mut(&id) {
                                          if process.pid > 0
                                              && opm::process::is_pid_alive(process.pid)
                                          {
                                             // Restart succeeded - process has valid PID and is alive
-                                            // Check if previous process crashed quickly (within 10s of start)
+                                            // A previous instance that died unnoticed before min_uptime was a failed start
                                             // This indicates a flapping process that needs exponential backoff
-                                            if is_new_crash {
-                                                // Use the process's started time which was updated by restart()
+                                            if unclassified_crash {
                                                 // Calculate how long the PREVIOUS instance ran before crashing
-                                                let previous_started = item.started;
-                                                let crash_time =
-                                                    (Utc::now() - previous_started).num_seconds();
-                                                if crash_time < 10 {
+                                                let crash_time = (Utc::now() - item.started).to_std().unwrap_or_default();
+                                                if crash_time < proc.exec.restart.min_uptime() {
                                                     // Quick crash detected - this counts as a failed restart
                                                     process.failed_restart_attempts += 1;
-                                                    log!("[daemon] quick crash detected ({}s), incrementing failure counter",
+                                                    log!("[daemon] quick crash detected, incrementing failure counter",
                                                         "name" => &proc.name,
                                                         "id" => id,
-                                                        "crash_time_secs" => crash_time,
+                                                        "crash_time_ms" => crash_time.as_millis(),
                                                         "failed_attempts" => process.failed_restart_attempts);
-                                                } else {
-                                                    // Process ran long enough - reset failure counter
-                                                    process.failed_restart_attempts = 0;
                                                 }
-                                            } else {
-                                                // Retry of failed restart succeeded - reset counter
-                                                process.failed_restart_attempts = 0;
                                             }
                                         } else {
                                             // Restart failed - no valid PID or process is not alive
@@ -1146,6 +1659,20 @@ pub fn start(verbose: bool) {
         // Permanent dump should be loaded into memory only during `opm restore`.
         opm::process::dump::clear_memory();
 
+        // Processes started from here get copies of sockets this daemon keeps bound, the CLI asks for them over the socket
+        listen::own();
+        // Process logs are forwarded from here, sinks changed in config.toml apply on the next daemon start
+        sink::start(&config::read().daemon.log_sinks);
+
+        // Receive READY=1 notifications for processes using the notify readiness check
+        let notify_path = global!("opm.notify");
+        if let Err(e) = opm::process::health::listen(std::path::Path::new(&notify_path)) {
+            log!("[daemon] Failed to bind notify socket", "path" => &notify_path, "error" => format!("{}", e));
+        }
+
+        // Proxy the public port of groups started with --balance to their ready instances
+        tokio::spawn(balancer::run());
+
         // Start Unix socket server for CLI-daemon communication
         // Socket server must be started AFTER init_on_startup() to ensure memory cache is ready
         // Use a channel to synchronize socket server readiness
@@ -1214,7 +1741,13 @@ pub fn start(verbose: bool) {
             // 3. Daemon stability is critical - it manages all processes and must not crash
             // If a process monitoring operation fails, we log it and continue
             // This ensures the daemon remains stable even when individual processes fail
-            if !Runner::new().is_empty() {
+            let runner = Runner::new();
+            // Close listen sockets of removed processes, the others stay bound while their process restarts
+            listen::retain(runner.list.values().flat_map(|item| &item.exec.listen));
+            // Watchers of stopped, removed or no longer watching processes stop with them
+            watch::retain(runner.list.iter().filter(|(_, item)| item.running && item.watch.enabled).map(|(id, _)| *id));
+            sink::track(runner.list.iter().map(|(id, item)| sink::Source::new(*id, item)));
+            if !runner.is_empty() {
                 let result = panic::catch_unwind(|| {
                     restart_process();
                 });
@@ -1764,11 +2297,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -1782,6 +2311,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Default::default(),
+            cgroup: None,
+            launching: None,
+            health: Default::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         }
     }
 
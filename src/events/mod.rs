@@ -5,6 +5,29 @@ use std::fs;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+type Emitter = Arc<dyn Fn(Event) + Send + Sync>;
+
+/// Where events raised inside the library go, installed by the daemon or the CLI
+static EMITTER: once_cell::sync::Lazy<std::sync::RwLock<Option<Emitter>>> =
+    once_cell::sync::Lazy::new(|| std::sync::RwLock::new(None));
+
+/// Route events raised by library code (e.g. while stopping a process) to `emitter`
+/// Replaces any previous emitter, the daemon overrides the one inherited from the CLI
+pub fn set_emitter(emitter: impl Fn(Event) + Send + Sync + 'static) {
+    if let Ok(mut current) = EMITTER.write() {
+        *current = Some(Arc::new(emitter));
+    }
+}
+
+/// Hand an event to the installed emitter, dropped when none is installed
+pub fn emit(event: Event) {
+    let emitter = EMITTER.read().ok().and_then(|emitter| emitter.clone());
+    match emitter {
+        Some(emitter) => emitter(event),
+        None => log::debug!("No event emitter installed, dropping {:?}", event.event_type),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum EventType {
@@ -15,6 +38,10 @@ pub enum EventType {
     ProcessCrash,
     ProcessRestart,
     ProcessDelete,
+    ProcessKilled,
+    ProcessUnhealthy,
+    ProcessScheduledRestart,
+    ProcessThresholdExceeded,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,4 +1,4 @@
-use crate::{helpers, log, process::Process};
+use crate::{helpers, log, process::{entry::{Entry, Filter, Level}, rotation, Process}};
 use colored::Colorize;
 use macros_rs::{crashln, string, ternary};
 
@@ -12,7 +12,7 @@ use std::{
 };
 
 pub fn logs(item: &Process, lines_to_tail: usize, kind: &str) {
-    logs_with_options(item, lines_to_tail, kind, false, None, false);
+    logs_with_options(item, lines_to_tail, kind, false, &Filter::default(), false);
 }
 
 pub fn logs_with_options(
@@ -20,22 +20,21 @@ pub fn logs_with_options(
     lines_to_tail: usize,
     kind: &str,
     follow: bool,
-    filter: Option<&str>,
+    filter: &Filter,
     stats: bool,
 ) {
     let log_file = match kind {
         "out" => item.logs().out,
         "error" => item.logs().error,
+        "combined" => item.logs().combined,
         _ => item.logs().out,
     };
 
-    if !Exists::check(&log_file).empty() {
-        let file = File::open(&log_file).unwrap();
-        let reader = BufReader::new(file);
-        let lines: Vec<String> = reader
-            .lines()
-            .map(|line| line.unwrap_or_else(|err| format!("error reading line: {err}")))
-            .collect();
+    // A log that was just rotated is empty, its lines are in the rotated files
+    if !Exists::check(&log_file).empty() || !rotation::rotated(Path::new(&log_file)).is_empty() {
+        // Lines the current log is short of are read from its rotated files, all of them when filtering
+        let wanted = ternary!(filter.is_empty(), lines_to_tail, usize::MAX);
+        let lines = rotation::lines(Path::new(&log_file), wanted);
 
         logs_internal_with_options(
             lines,
@@ -67,14 +66,13 @@ pub fn logs_with_options(
 
                     for line in reader.lines() {
                         if let Ok(line) = line {
-                            if let Some(pattern) = filter {
-                                if !line.to_lowercase().contains(&pattern.to_lowercase()) {
-                                    continue;
-                                }
+                            let entry = Entry::parse(&line);
+                            if !filter.matches(&line, &entry, kind) {
+                                continue;
                             }
 
-                            let (level_indicator, line_color) = detect_log_level(&line, kind);
-                            let color = ternary!(kind == "out", "green", "red");
+                            let (level_indicator, line_color) = indicator(entry.level_in(kind));
+                            let color = ternary!(kind == "error", "red", "green");
                             println!(
                                 "{} {} {}",
                                 format!("{}|{}", item.id, item.name).color(color),
@@ -119,11 +117,12 @@ pub fn logs_internal(
         id,
         log_type,
         item_name,
-        None,
+        &Filter::default(),
         false,
     );
 }
 
+/// Show the last `lines_to_tail` lines passing `filter`, JSON lines are shown with the level they carry
 pub fn logs_internal_with_options(
     lines: Vec<String>,
     lines_to_tail: usize,
@@ -131,7 +130,7 @@ pub fn logs_internal_with_options(
     id: usize,
     log_type: &str,
     item_name: &str,
-    filter: Option<&str>,
+    filter: &Filter,
     stats: bool,
 ) {
     println!(
@@ -139,43 +138,24 @@ pub fn logs_internal_with_options(
         format!("\n{log_file} last {lines_to_tail} lines:").bright_black()
     );
 
-    let color = ternary!(log_type == "out", "green", "red");
-    let start_index = if lines.len() > lines_to_tail {
-        lines.len() - lines_to_tail
-    } else {
-        0
-    };
+    let color = ternary!(log_type == "error", "red", "green");
+    let mut filtered_lines = filter.select(lines, log_type);
+    filtered_lines.drain(..filtered_lines.len().saturating_sub(lines_to_tail));
 
     // Statistics counters
     let mut error_count = 0;
     let mut warn_count = 0;
     let mut info_count = 0;
     let mut debug_count = 0;
-    let mut filtered_lines = Vec::new();
-
-    for (_, line) in lines.iter().skip(start_index).enumerate() {
-        // Apply filter if provided
-        if let Some(pattern) = filter {
-            if !line.to_lowercase().contains(&pattern.to_lowercase()) {
-                continue;
-            }
-        }
 
-        // Detect log level in the line content for better identification
-        let (level_indicator, line_color) = detect_log_level(&line, log_type);
-
-        // Count log levels for statistics
-        if level_indicator.contains("ERR") {
-            error_count += 1;
-        } else if level_indicator.contains("WARN") {
-            warn_count += 1;
-        } else if level_indicator.contains("INFO") {
-            info_count += 1;
-        } else if level_indicator.contains("DBG") {
-            debug_count += 1;
+    for (_, entry) in &filtered_lines {
+        match entry.level_in(log_type) {
+            Some(Level::Error | Level::Fatal) => error_count += 1,
+            Some(Level::Warn) => warn_count += 1,
+            Some(Level::Info) => info_count += 1,
+            Some(Level::Debug | Level::Trace) => debug_count += 1,
+            None => {}
         }
-
-        filtered_lines.push((level_indicator, line_color, line));
     }
 
     // Display statistics if requested
@@ -211,7 +191,8 @@ pub fn logs_internal_with_options(
     }
 
     // Display the filtered logs
-    for (level_indicator, line_color, line) in filtered_lines {
+    for (line, entry) in filtered_lines {
+        let (level_indicator, line_color) = indicator(entry.level_in(log_type));
         println!(
             "{} {} {}",
             format!("{}|{}", id, item_name).color(color),
@@ -221,37 +202,12 @@ pub fn logs_internal_with_options(
     }
 }
 
-/// Detect log level from line content and return appropriate indicator and color
-fn detect_log_level(line: &str, log_type: &str) -> (String, &'static str) {
-    let line_lower = line.to_lowercase();
-
-    // Check for common error patterns
-    if line_lower.contains("error") || line_lower.contains("fatal") || line_lower.contains("fail") {
-        return ("[ ERR]".to_string(), "red");
-    }
-
-    // Check for common warning patterns
-    if line_lower.contains("warn") || line_lower.contains("warning") {
-        return ("[WARN]".to_string(), "yellow");
-    }
-
-    // Check for debug patterns
-    if line_lower.contains("debug") || line_lower.contains("trace") {
-        return ("[ DBG]".to_string(), "cyan");
-    }
-
-    // Check for info patterns
-    if line_lower.contains("info") {
-        return ("[INFO]".to_string(), "blue");
-    }
-
-    // Default based on log type
-    if log_type == "error" {
-        return ("[ ERR]".to_string(), "red");
+/// Indicator and color of a line, a line without a level is plain output
+fn indicator(level: Option<Level>) -> (&'static str, &'static str) {
+    match level {
+        Some(level) => level.indicator(),
+        None => ("[OUT ]", "white"),
     }
-
-    // Default to stdout
-    ("[OUT ]".to_string(), "white")
 }
 
 pub fn cwd() -> PathBuf {
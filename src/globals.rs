@@ -57,18 +57,24 @@ pub(crate) fn init() {
             init!("opm.log", format!("{path}/.opm/opm.log"));
             init!("opm.pid", format!("{path}/.opm/daemon.pid"));
             init!("opm.socket", format!("{path}/.opm/opm.sock"));
+            init!("opm.notify", format!("{path}/.opm/notify.sock"));
             init!("opm.dump", format!("{path}/.opm/process.dump"));
             // Note: opm.dump.temp kept for backward compatibility (migration from old versions)
             init!("opm.dump.temp", format!("{path}/.opm/process.temp.dump"));
 
+            init!("opm.secrets", format!("{path}/.opm/secrets"));
+            init!("opm.secret.key", format!("{path}/.opm/secret.key"));
+
             init!("opm.daemon.kind", config.daemon.kind);
             init!("opm.daemon.log", format!("{path}/.opm/daemon.log"));
 
             let out = format!("{}/{{}}-out.log", config.runner.log_path);
             let error = format!("{}/{{}}-error.log", config.runner.log_path);
+            let combined = format!("{}/{{}}-combined.log", config.runner.log_path);
 
             init!("opm.logs.out", out);
             init!("opm.logs.error", error);
+            init!("opm.logs.combined", combined);
         }
         None => crashln!("{} Impossible to get your home directory", *helpers::FAIL),
     }
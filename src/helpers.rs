@@ -119,6 +119,57 @@ pub fn parse_memory(mem_str: &str) -> Result<u64, String> {
     }
 }
 
+/// Parse an octal umask string like "022" or "0o027"
+pub fn parse_umask(umask: &str) -> Result<u32, String> {
+    let digits = umask.trim().trim_start_matches("0o");
+
+    match u32::from_str_radix(digits, 8) {
+        Ok(mask) if mask <= 0o777 => Ok(mask),
+        _ => Err(format!(
+            "Invalid umask: {}. Use an octal value like '022' or '0027'",
+            umask
+        )),
+    }
+}
+
+/// Parse a CPU limit like "150%" or "1.5" (CPUs) into a percentage of one CPU
+pub fn parse_cpu_max(cpu: &str) -> Result<u32, String> {
+    let cpu = cpu.trim();
+    let percent = match cpu.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().ok(),
+        None => cpu.parse::<f64>().ok().map(|cpus| cpus * 100.0),
+    };
+
+    match percent {
+        Some(percent) if percent >= 1.0 && percent <= u32::MAX as f64 => Ok(percent.round() as u32),
+        _ => Err(format!(
+            "Invalid cpu limit: {}. Use a percentage like '150%' or a CPU count like '1.5'",
+            cpu
+        )),
+    }
+}
+
+/// Parse a signal like "SIGINT", "int" or "2" into its canonical name
+pub fn parse_signal(signal: &str) -> Result<String, String> {
+    let trimmed = signal.trim();
+    let parsed = match trimmed.parse::<i32>() {
+        Ok(number) => nix::sys::signal::Signal::try_from(number).ok(),
+        Err(_) => {
+            let name = trimmed.to_uppercase();
+            let name = if name.starts_with("SIG") { name } else { format!("SIG{name}") };
+            name.parse::<nix::sys::signal::Signal>().ok()
+        }
+    };
+
+    match parsed {
+        Some(signal) => Ok(signal.as_str().to_string()),
+        None => Err(format!(
+            "Invalid signal: {}. Use a name like 'SIGINT' or a number like '2'",
+            signal
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -244,4 +295,31 @@ mod tests {
         assert!(parse_memory("100X").is_err());
         assert!(parse_memory("").is_err());
     }
+
+    #[test]
+    fn test_parse_umask() {
+        assert_eq!(parse_umask("022").unwrap(), 0o022);
+        assert_eq!(parse_umask("0027").unwrap(), 0o027);
+        assert_eq!(parse_umask("0o077").unwrap(), 0o077);
+        assert!(parse_umask("999").is_err());
+        assert!(parse_umask("1000").is_err());
+    }
+
+    #[test]
+    fn test_parse_cpu_max() {
+        assert_eq!(parse_cpu_max("150%"), Ok(150));
+        assert_eq!(parse_cpu_max("1.5"), Ok(150));
+        assert_eq!(parse_cpu_max("0.25"), Ok(25));
+        assert!(parse_cpu_max("0").is_err());
+        assert!(parse_cpu_max("fast").is_err());
+    }
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("SIGINT"), Ok("SIGINT".to_string()));
+        assert_eq!(parse_signal("quit"), Ok("SIGQUIT".to_string()));
+        assert_eq!(parse_signal("15"), Ok("SIGTERM".to_string()));
+        assert!(parse_signal("SIGNOPE").is_err());
+        assert!(parse_signal("0").is_err());
+    }
 }
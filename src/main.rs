@@ -7,7 +7,22 @@ use clap::{Parser, Subcommand};
 use clap_verbosity_flag::{LogLevel, Verbosity};
 use global_placeholders::global;
 use macros_rs::{crashln, str, string};
-use std::fs;
+use opm::process::{
+    balancer::{Balancer, Strategy},
+    capture::{self, Capture, Format as LogFormat, Range},
+    deps::Dependency,
+    entry::{self, Filter, Level},
+    health::{Liveness, Readiness},
+    job::Job,
+    listen::Listen,
+    restart::{Backoff, Restart, RestartPolicy},
+    rotation::{Rotation, Schedule},
+    schedule,
+    threshold::{self, ThresholdAction, Thresholds},
+    watch::{Watch, WatchAction},
+    Exec, ExecMode, KillMode, Resources, StopPolicy,
+};
+use std::{fs, path::PathBuf};
 use update_informer::{registry, Check};
 
 use crate::{
@@ -60,9 +75,14 @@ enum Daemon {
     /// Setup systemd service to start OPM daemon automatically
     #[command(visible_alias = "install")]
     Setup,
+    /// Relay the captured output of an instance to its logs, started by opm with the pipes passed as descriptors
+    #[command(hide = true)]
+    Capture { target: String },
 }
 
 // add opm restore command
+// Parsed once per invocation, so the size of the Start variant does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Import process from environment file
@@ -70,6 +90,9 @@ enum Commands {
     Import {
         /// Path of file to import
         path: String,
+        /// Named environment to apply, from the env_<name> blocks of each process
+        #[arg(long = "env")]
+        environment: Option<String>,
     },
     /// Export environment file from process
     #[command(visible_alias = "get")]
@@ -86,12 +109,54 @@ enum Commands {
         name: Option<String>,
         #[clap(value_parser = cli::validate::<Args>)]
         args: Args,
-        /// Watch to reload path
-        #[arg(long)]
-        watch: Option<String>,
+        /// Paths to watch, relative to the process path, restarting it on changes (repeat or separate with commas)
+        #[arg(long, value_delimiter = ',')]
+        watch: Vec<String>,
+        /// Globs of changes to ignore (default node_modules, .git, logs and *.log)
+        #[arg(long, requires = "watch", value_delimiter = ',')]
+        watch_ignore: Option<Vec<String>>,
+        /// Milliseconds without changes before the process is restarted (default 500)
+        #[arg(long, requires = "watch")]
+        watch_debounce_ms: Option<u64>,
+        /// How a change is applied, reload starts the new instance before stopping the old one (default restart)
+        #[arg(long, requires = "watch", value_name = "restart|reload")]
+        watch_action: Option<WatchAction>,
         /// Maximum memory limit (e.g., 100M, 1G)
         #[arg(long)]
         max_memory: Option<String>,
+        /// Milliseconds memory must stay above --max-memory before the threshold action
+        #[arg(long, requires = "max_memory")]
+        max_memory_for_ms: Option<u64>,
+        /// CPU usage in percent, averaged over --max-cpu-window-ms, that triggers the threshold action
+        #[arg(long, value_parser = threshold::parse_percent)]
+        max_cpu: Option<f64>,
+        /// Milliseconds the CPU usage is averaged over for --max-cpu (default 60000)
+        #[arg(long, requires = "max_cpu", value_parser = clap::value_parser!(u64).range(1..))]
+        max_cpu_window_ms: Option<u64>,
+        /// What happens once a threshold is exceeded (default stop)
+        #[arg(long, value_name = "restart|stop|signal:<SIGNAL>|notify-only")]
+        threshold_action: Option<ThresholdAction>,
+        /// Rotate the logs once one is larger (e.g., 10M), overrides [daemon.log_rotation]
+        #[arg(long, group = "log_rotation", value_parser = opm::helpers::parse_memory)]
+        log_max_size: Option<u64>,
+        /// Rotate the logs on a schedule, regardless of their size
+        #[arg(long, group = "log_rotation", value_name = "hourly|daily")]
+        log_rotate: Option<Schedule>,
+        /// Rotated files kept per log (default 5)
+        #[arg(long, requires = "log_rotation", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        log_keep: Option<usize>,
+        /// Gzip rotated log files
+        #[arg(long, requires = "log_rotation")]
+        log_compress: bool,
+        /// Read the output through the daemon, which prefixes each line with an RFC3339 timestamp
+        #[arg(long)]
+        log_capture: bool,
+        /// How captured lines are written, implies --log-capture (default text)
+        #[arg(long, value_name = "text|json")]
+        log_format: Option<LogFormat>,
+        /// Also merge stdout and stderr into a combined log, implies --log-capture
+        #[arg(long)]
+        log_combined: bool,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
@@ -104,6 +169,102 @@ enum Commands {
         /// Port range for workers (e.g., "3000-3010" or just "3000" for SO_REUSEPORT)
         #[arg(short = 'p', long)]
         port_range: Option<String>,
+        /// Public port the daemon balances over the workers' ports (e.g., "8080" or "127.0.0.1:8080")
+        #[arg(long, requires = "workers", value_parser = Balancer::parse)]
+        balance: Option<Balancer>,
+        /// How the balancer spreads connections over the ready workers
+        #[arg(long, requires = "balance", value_name = "round-robin|least-conn")]
+        balance_strategy: Option<Strategy>,
+        /// Working directory for the process (defaults to the current directory)
+        #[arg(long)]
+        cwd: Option<PathBuf>,
+        /// Run the process as this user (name or uid, daemon must run as root)
+        #[arg(long)]
+        user: Option<String>,
+        /// Run the process as this group (name or gid, defaults to the user's group)
+        #[arg(long)]
+        group: Option<String>,
+        /// File mode creation mask in octal (e.g., 022)
+        #[arg(long, value_parser = opm::helpers::parse_umask)]
+        umask: Option<u32>,
+        /// cgroup memory.max (e.g., 512M), needs daemon.cgroup enabled
+        #[arg(long, value_parser = opm::helpers::parse_memory)]
+        memory_max: Option<u64>,
+        /// cgroup cpu.max as a percentage or CPU count (e.g., 150% or 1.5)
+        #[arg(long, value_parser = opm::helpers::parse_cpu_max)]
+        cpu_max: Option<u32>,
+        /// cgroup pids.max
+        #[arg(long)]
+        pids_max: Option<u64>,
+        /// Spawn the command directly or through the shell (detected by default)
+        #[arg(long, value_name = "direct|shell")]
+        exec_mode: Option<ExecMode>,
+        /// Signal sent to stop the process (e.g., SIGINT), SIGTERM by default
+        #[arg(long, value_parser = opm::helpers::parse_signal)]
+        stop_signal: Option<String>,
+        /// Milliseconds to wait after the stop signal before sending SIGKILL
+        #[arg(long)]
+        kill_timeout_ms: Option<u64>,
+        /// Processes that receive the stop signal and SIGKILL
+        #[arg(long, value_name = "group|main|mixed")]
+        kill_mode: Option<KillMode>,
+        /// Check that must pass before the process is online: tcp:<port>, http:<url>, log:<regex> or notify
+        #[arg(long, value_parser = |spec: &str| Readiness::parse(spec, None))]
+        ready: Option<Readiness>,
+        /// Milliseconds the readiness check may take before the start counts as failed
+        #[arg(long, requires = "ready")]
+        ready_timeout_ms: Option<u64>,
+        /// Probe that restarts the process after repeated failures: http:<url>, tcp:<port> or exec:<command>
+        #[arg(long, value_parser = Liveness::parse)]
+        liveness: Option<Liveness>,
+        /// Milliseconds between liveness probes
+        #[arg(long, requires = "liveness", value_parser = clap::value_parser!(u64).range(1..))]
+        liveness_interval_ms: Option<u64>,
+        /// Milliseconds a liveness probe may take before it counts as failed
+        #[arg(long, requires = "liveness")]
+        liveness_timeout_ms: Option<u64>,
+        /// Consecutive failed liveness probes that restart the process
+        #[arg(long, requires = "liveness", value_parser = clap::value_parser!(u32).range(1..))]
+        liveness_threshold: Option<u32>,
+        /// Which exits are restarted
+        #[arg(long, value_name = "always|on-failure|never|unless-stopped")]
+        restart_policy: Option<RestartPolicy>,
+        /// Exit codes that count as a clean exit (default 0)
+        #[arg(long, value_delimiter = ',', allow_negative_numbers = true)]
+        success_exit_codes: Vec<i32>,
+        /// Restarts before the process is errored, overrides daemon.restarts
+        #[arg(long)]
+        max_restarts: Option<u64>,
+        /// Milliseconds an instance must stay up for its start to count as successful
+        #[arg(long)]
+        min_uptime_ms: Option<u64>,
+        /// Delay before a restart in milliseconds, doubled for every failed start in a row
+        #[arg(long)]
+        restart_delay_ms: Option<u64>,
+        /// Longest delay between restart attempts in milliseconds
+        #[arg(long)]
+        max_restart_delay_ms: Option<u64>,
+        /// Factor the restart delay grows by for every failed start in a row
+        #[arg(long)]
+        backoff_multiplier: Option<f64>,
+        /// Restart on a cron schedule in local time, e.g. "0 3 * * *"
+        #[arg(long, value_parser = |expression: &str| schedule::validate(expression).map(|_| expression.to_string()))]
+        cron_restart: Option<String>,
+        /// Run as a job on a cron schedule in local time instead of keeping it running
+        #[arg(long, conflicts_with_all = ["once", "cron_restart"], value_parser = |expression: &str| schedule::validate(expression).map(|_| expression.to_string()))]
+        cron: Option<String>,
+        /// Run as a job once, it is not restarted after a successful exit
+        #[arg(long, conflicts_with = "cron_restart")]
+        once: bool,
+        /// Milliseconds a job run may take before it is killed
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        job_timeout_ms: Option<u64>,
+        /// Start after these processes, by name; append ":ready" to wait until one is ready
+        #[arg(long, value_delimiter = ',')]
+        depends_on: Vec<Dependency>,
+        /// Socket the daemon binds once and passes to the process on fd 3 and up: tcp://<ip>:<port> or unix:///<path>
+        #[arg(long, value_delimiter = ',')]
+        listen: Vec<Listen>,
     },
     /// Stop/Kill a process
     #[command(visible_alias = "kill")]
@@ -128,6 +289,9 @@ enum Commands {
     Env {
         #[clap(value_parser = cli::validate::<Item>)]
         item: Item,
+        /// Show the environment the next start receives, with the source of each variable
+        #[arg(long)]
+        resolved: bool,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
@@ -193,6 +357,18 @@ enum Commands {
         /// Show log statistics
         #[arg(long)]
         stats: bool,
+        /// Only lines written since a time (RFC3339) or a duration ago (e.g., 10m, 2h, 1d), needs captured logs
+        #[arg(long)]
+        since: Option<String>,
+        /// Only lines written until a time (RFC3339) or a duration ago
+        #[arg(long, conflicts_with = "follow")]
+        until: Option<String>,
+        /// Only lines at this level or above (trace, debug, info, warn, error, fatal), JSON lines use their level field
+        #[arg(long)]
+        level: Option<Level>,
+        /// Only JSON lines with a field of this value, e.g. req.method=GET (repeat to require several)
+        #[arg(long, value_parser = entry::parse_field)]
+        field: Vec<(String, String)>,
     },
     /// Flush a process log
     #[command(visible_alias = "clean", visible_alias = "log_rotate")]
@@ -214,20 +390,35 @@ enum Commands {
     Restart {
         #[clap(value_parser = cli::validate_items)]
         items: Items,
+        /// Switch to a named environment defined with env_<name> before restarting
+        #[arg(long = "env")]
+        environment: Option<String>,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
     },
 
-    /// Reload a process (same as restart - stops and starts the process)
+    /// Reload a process without downtime: start the new instance, wait until it is ready, then stop the old one
     Reload {
         #[clap(value_parser = cli::validate_items)]
         items: Items,
+        /// Instances replaced at once, defaults to the group's max_unavailable or 1
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        max_unavailable: Option<u64>,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
     },
 
+    /// Add or remove instances of a worker group
+    Scale {
+        /// Group name, as given to `start --name` with `--workers`
+        name: String,
+        /// Number of instances the group should have
+        #[arg(value_parser = clap::value_parser!(u64).range(1..))]
+        instances: u64,
+    },
+
     /// Get startup command for a process
     #[command(visible_alias = "cstart", visible_alias = "startup")]
     GetCommand {
@@ -267,6 +458,30 @@ enum Commands {
         #[command(subcommand)]
         command: AgentCommand,
     },
+
+    /// Encrypted secrets, used in a process environment as secret://<name>
+    #[command(visible_alias = "secrets")]
+    Secret {
+        #[command(subcommand)]
+        command: SecretCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretCommand {
+    /// Store a secret, the value is read from stdin or prompted for when not given
+    Set {
+        name: String,
+        value: Option<String>,
+    },
+    /// Print the value of a secret
+    Get { name: String },
+    /// Remove a secret
+    #[command(visible_alias = "remove")]
+    Rm { name: String },
+    /// List stored secrets
+    #[command(visible_alias = "list")]
+    Ls,
 }
 
 #[derive(Subcommand)]
@@ -937,6 +1152,15 @@ fn start_agent_daemon() {
 
 fn main() {
     let cli = Cli::parse();
+
+    // A capture relay only reads its pipes, it skips the update check and setup below
+    if let Commands::Daemon { command: Daemon::Capture { target } } = &cli.command {
+        if let Err(err) = capture::relay(target) {
+            crashln!("{} {err}", *opm::helpers::FAIL);
+        }
+        return;
+    }
+
     let mut env = env_logger::Builder::new();
     let level = cli.verbose.log_level_filter();
     let informer = update_informer::new(registry::Crates, "opm", env!("CARGO_PKG_VERSION"));
@@ -950,6 +1174,17 @@ fn main() {
 
     globals::init();
 
+    // Events raised while stopping processes from the CLI are forwarded to the daemon
+    opm::events::set_emitter(|event| {
+        let id = event.process_id.as_deref().and_then(|id| id.parse().ok()).unwrap_or(0);
+        cli::events::emit_event(
+            event.event_type,
+            id,
+            event.process_name.as_deref().unwrap_or_default(),
+            &event.message,
+        );
+    });
+
     // Configure custom certificates for TLS
     use rustls::crypto::ring::default_provider;
     use rustls::ClientConfig;
@@ -970,26 +1205,143 @@ fn main() {
     env.filter_level(level).init();
 
     match &cli.command {
-        Commands::Import { path } => cli::import::read_hcl(path),
+        Commands::Import { path, environment } => cli::import::read_hcl(path, environment),
         Commands::Export { items, path } => cli::import::export_hcl(items, path),
         Commands::Start {
             name,
             args,
             watch,
+            watch_ignore,
+            watch_debounce_ms,
+            watch_action,
             max_memory,
+            max_memory_for_ms,
+            max_cpu,
+            max_cpu_window_ms,
+            threshold_action,
+            log_max_size,
+            log_rotate,
+            log_keep,
+            log_compress,
+            log_capture,
+            log_format,
+            log_combined,
             server,
             reset_env,
             workers,
             port_range,
+            balance,
+            balance_strategy,
+            cwd,
+            user,
+            group,
+            umask,
+            memory_max,
+            cpu_max,
+            pids_max,
+            exec_mode,
+            stop_signal,
+            kill_timeout_ms,
+            kill_mode,
+            ready,
+            ready_timeout_ms,
+            liveness,
+            liveness_interval_ms,
+            liveness_timeout_ms,
+            liveness_threshold,
+            restart_policy,
+            success_exit_codes,
+            max_restarts,
+            min_uptime_ms,
+            restart_delay_ms,
+            max_restart_delay_ms,
+            backoff_multiplier,
+            cron_restart,
+            cron,
+            once,
+            job_timeout_ms,
+            depends_on,
+            listen,
         } => cli::start(
             name,
             args,
-            watch,
+            &(!watch.is_empty()).then(|| Watch {
+                ignore: watch_ignore.clone(),
+                debounce_ms: *watch_debounce_ms,
+                action: watch_action.unwrap_or_default(),
+                ..Watch::new(watch.clone())
+            }),
             max_memory,
             reset_env,
             &defaults(server),
             workers,
             port_range,
+            &balance.clone().map(|balancer| Balancer {
+                strategy: balance_strategy.unwrap_or_default(),
+                ..balancer
+            }),
+            &Exec {
+                cwd: cwd.clone(),
+                user: user.clone(),
+                group: group.clone(),
+                umask: *umask,
+                resources: Resources {
+                    memory_max: *memory_max,
+                    cpu_max: *cpu_max,
+                    pids_max: *pids_max,
+                },
+                mode: *exec_mode,
+                stop: StopPolicy {
+                    stop_signal: stop_signal.clone(),
+                    kill_timeout_ms: *kill_timeout_ms,
+                    kill_mode: kill_mode.unwrap_or_default(),
+                },
+                ready: ready.clone().map(|ready| Readiness {
+                    timeout_ms: *ready_timeout_ms,
+                    ..ready
+                }),
+                liveness: liveness.clone().map(|liveness| Liveness {
+                    interval_ms: *liveness_interval_ms,
+                    timeout_ms: *liveness_timeout_ms,
+                    failure_threshold: *liveness_threshold,
+                    ..liveness
+                }),
+                restart: Restart {
+                    policy: restart_policy.unwrap_or_default(),
+                    success_exit_codes: success_exit_codes.clone(),
+                    max_restarts: *max_restarts,
+                    min_uptime_ms: *min_uptime_ms,
+                    backoff: Backoff {
+                        initial_ms: *restart_delay_ms,
+                        max_ms: *max_restart_delay_ms,
+                        multiplier: *backoff_multiplier,
+                    },
+                },
+                cron_restart: cron_restart.clone(),
+                job: (cron.is_some() || *once).then(|| Job {
+                    cron: cron.clone(),
+                    timeout_ms: *job_timeout_ms,
+                }),
+                depends_on: depends_on.clone(),
+                listen: listen.clone(),
+                thresholds: Thresholds {
+                    action: threshold_action.clone().unwrap_or_default(),
+                    memory_for_ms: *max_memory_for_ms,
+                    max_cpu: *max_cpu,
+                    cpu_window_ms: *max_cpu_window_ms,
+                },
+                log_rotation: (log_max_size.is_some() || log_rotate.is_some()).then_some(Rotation {
+                    max_size: *log_max_size,
+                    schedule: *log_rotate,
+                    keep: *log_keep,
+                    compress: *log_compress,
+                }),
+                log_capture: (*log_capture || log_format.is_some() || *log_combined).then(|| Capture {
+                    format: log_format.unwrap_or_default(),
+                    combined: *log_combined,
+                }),
+                ..Exec::default()
+            },
         ),
         Commands::Stop { items, server } => cli::stop(items, &defaults(server)),
         Commands::Remove { items, server } => cli::remove(items, &defaults(server)),
@@ -1006,7 +1358,7 @@ fn main() {
             Internal::restore(&defaults(server))
         }
         Commands::Save { server } => Internal::save(&defaults(server)),
-        Commands::Env { item, server } => cli::env(item, &defaults(server)),
+        Commands::Env { item, resolved, server } => cli::env(item, &defaults(server), *resolved),
         Commands::Details {
             item,
             format,
@@ -1021,15 +1373,27 @@ fn main() {
             filter,
             errors_only,
             stats,
-        } => cli::logs(
-            item,
-            lines,
-            &defaults(server),
-            *follow,
-            filter.as_deref(),
-            *errors_only,
-            *stats,
-        ),
+            since,
+            until,
+            level,
+            field,
+        } => match Range::new(since.as_deref(), until.as_deref()) {
+            Ok(range) => cli::logs(
+                item,
+                lines,
+                &defaults(server),
+                *follow,
+                *errors_only,
+                *stats,
+                &Filter {
+                    pattern: filter.clone(),
+                    level: *level,
+                    fields: field.clone(),
+                    range,
+                },
+            ),
+            Err(err) => crashln!("{} {err}", *opm::helpers::FAIL),
+        },
         Commands::Flush { item, server } => cli::flush(item, &defaults(server)),
 
         Commands::Daemon { command } => match command {
@@ -1038,10 +1402,14 @@ fn main() {
             Daemon::Health { format } => daemon::health(format),
             Daemon::Restore { api, webui } => daemon::restart(api, webui, level.as_str() != "OFF"),
             Daemon::Setup => daemon::setup(),
+            Daemon::Capture { .. } => {}
         },
 
-        Commands::Restart { items, server } => cli::restart(items, &defaults(server)),
-        Commands::Reload { items, server } => cli::reload(items, &defaults(server)),
+        Commands::Restart { items, environment, server } => cli::restart(items, &defaults(server), environment),
+        Commands::Reload { items, max_unavailable, server } => {
+            cli::reload(items, &defaults(server), max_unavailable.map(|count| count as usize))
+        }
+        Commands::Scale { name, instances } => cli::scale(name, *instances as usize),
         Commands::GetCommand { item, server } => cli::get_command(item, &defaults(server)),
         Commands::Adjust {
             item,
@@ -1115,6 +1483,13 @@ fn main() {
             AgentCommand::Disconnect => agent_disconnect(),
             AgentCommand::Status => agent_status(),
         },
+
+        Commands::Secret { command } => match command {
+            SecretCommand::Set { name, value } => cli::secret::set(name, value),
+            SecretCommand::Get { name } => cli::secret::get(name),
+            SecretCommand::Rm { name } => cli::secret::remove(name),
+            SecretCommand::Ls => cli::secret::list(),
+        },
     };
 
     if !matches!(&cli.command, Commands::Daemon { .. })
@@ -1124,6 +1499,7 @@ fn main() {
         && !matches!(&cli.command, Commands::GetCommand { .. })
         && !matches!(&cli.command, Commands::Adjust { .. })
         && !matches!(&cli.command, Commands::Agent { .. })
+        && !matches!(&cli.command, Commands::Secret { .. })
     {
         // When auto-starting daemon, read API/WebUI settings from config
         if !daemon::pid::exists() {
@@ -283,6 +283,7 @@ pub enum NotificationEvent {
     ProcessCrash,
     ProcessRestart,
     ProcessDelete,
+    ProcessThresholdExceeded,
 }
 
 impl NotificationEvent {
@@ -296,6 +297,7 @@ impl NotificationEvent {
             NotificationEvent::ProcessCrash => events.process_crash,
             NotificationEvent::ProcessRestart => events.process_restart,
             NotificationEvent::ProcessDelete => events.process_delete,
+            NotificationEvent::ProcessThresholdExceeded => events.process_threshold_exceeded,
         }
     }
 
@@ -303,7 +305,7 @@ impl NotificationEvent {
     pub fn urgency(&self) -> Urgency {
         match self {
             NotificationEvent::ProcessCrash => Urgency::Critical,
-            NotificationEvent::AgentDisconnect => Urgency::Normal,
+            NotificationEvent::AgentDisconnect | NotificationEvent::ProcessThresholdExceeded => Urgency::Normal,
             _ => Urgency::Low,
         }
     }
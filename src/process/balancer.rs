@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Milliseconds between refreshes of the balanced groups and their ready instances
+pub const REFRESH_INTERVAL_MS: u64 = 1000;
+
+/// Milliseconds the daemon waits for an instance to accept a proxied connection
+pub const CONNECT_TIMEOUT_MS: u64 = 2000;
+
+/// Milliseconds an instance that refused a connection is passed over by the next ones
+pub const REFUSED_BACKOFF_MS: u64 = 2000;
+
+/// TCP proxy the daemon runs in front of a group, set with `--balance <port>` or `balance = <port>`
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Balancer {
+    /// Public port the daemon listens on
+    #[schema(example = 8080)]
+    pub port: u16,
+    /// Address the daemon listens on
+    #[schema(example = "0.0.0.0")]
+    #[serde(default = "default_host")]
+    pub host: String,
+    /// How connections are spread over the instances
+    #[serde(default)]
+    pub strategy: Strategy,
+}
+
+fn default_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+impl Balancer {
+    pub fn new(port: u16, strategy: Strategy) -> Self {
+        Balancer {
+            port,
+            host: default_host(),
+            strategy,
+        }
+    }
+
+    /// Parse `--balance` as a port or host:port
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (host, port) = match spec.trim().rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() => (host.to_string(), port),
+            _ => (default_host(), spec.trim()),
+        };
+        match port.parse::<u16>() {
+            Ok(port) if port > 0 => Ok(Balancer { port, host, ..Default::default() }),
+            _ => Err(format!("Invalid balance port: {}. Use a port or host:port", spec)),
+        }
+    }
+
+    /// Address the daemon binds the public port on
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// Which ready instance the next connection is sent to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Each instance in turn
+    #[default]
+    RoundRobin,
+    /// The instance with the fewest open connections, in turn among equals
+    LeastConn,
+}
+
+impl Strategy {
+    /// Index of the backend for connection number `turn`, given the open connections of each backend
+    pub fn pick(&self, active: &[usize], turn: usize) -> Option<usize> {
+        if active.is_empty() {
+            return None;
+        }
+        match self {
+            Strategy::RoundRobin => Some(turn % active.len()),
+            Strategy::LeastConn => (0..active.len())
+                .map(|offset| (turn + offset) % active.len())
+                .min_by_key(|index| active[*index]),
+        }
+    }
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(strategy: &str) -> Result<Self, Self::Err> {
+        match strategy.trim().to_lowercase().as_str() {
+            "round-robin" => Ok(Strategy::RoundRobin),
+            "least-conn" => Ok(Strategy::LeastConn),
+            _ => Err(format!("Invalid balance strategy: {}. Use 'round-robin' or 'least-conn'", strategy)),
+        }
+    }
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Strategy::RoundRobin => write!(f, "round-robin"),
+            Strategy::LeastConn => write!(f, "least-conn"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_balance_strategy() {
+        assert_eq!(Strategy::RoundRobin.pick(&[], 0), None);
+        assert_eq!(Strategy::RoundRobin.pick(&[5, 0, 0], 0), Some(0));
+        assert_eq!(Strategy::RoundRobin.pick(&[5, 0, 0], 4), Some(1));
+
+        assert_eq!(Strategy::LeastConn.pick(&[5, 2, 3], 0), Some(1));
+        assert_eq!(Strategy::LeastConn.pick(&[1, 0, 0], 0), Some(1));
+        assert_eq!(Strategy::LeastConn.pick(&[1, 0, 0], 2), Some(2));
+
+        let balancer = Balancer::parse("8080").unwrap();
+        assert_eq!(balancer.address(), "0.0.0.0:8080");
+        assert_eq!(balancer.strategy, Strategy::RoundRobin);
+        assert_eq!(Balancer::parse("127.0.0.1:8080").unwrap().address(), "127.0.0.1:8080");
+        assert!(Balancer::parse("0").is_err());
+        assert!(Balancer::parse("localhost:http").is_err());
+
+        assert_eq!("least-conn".parse::<Strategy>(), Ok(Strategy::LeastConn));
+        assert!("random".parse::<Strategy>().is_err());
+        assert_eq!(Strategy::LeastConn.to_string(), "least-conn");
+    }
+}
@@ -0,0 +1,353 @@
+use super::listen;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::{fs::MetadataExt, process::CommandExt},
+    },
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use utoipa::ToSchema;
+
+/// Longest line written as one record, the rest of a longer line follows in further records
+const MAX_LINE: usize = 16 * 1024;
+
+/// How often a log is checked for having been replaced
+const REOPEN_CHECK: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How each captured line is written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// The line prefixed with an RFC3339 timestamp
+    #[default]
+    Text,
+    /// One JSON object per line with the time, stream, process and message
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.trim().to_lowercase().as_str() {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Invalid log format: {}. Use 'text' or 'json'", format)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Output read through pipes and timestamped instead of written by the child straight to its logs
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Capture {
+    #[serde(default)]
+    pub format: Format,
+    /// Also write stdout and stderr, interleaved as they arrive, to `<name>-combined.log`
+    #[serde(default)]
+    pub combined: bool,
+}
+
+impl Capture {
+    /// e.g. "json, combined"
+    pub fn describe(&self) -> String {
+        format!("{}{}", self.format, if self.combined { ", combined" } else { "" })
+    }
+}
+
+/// Stream a captured line was read from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stream {
+    Out,
+    Error,
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stream::Out => write!(f, "out"),
+            Stream::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Where the captured output of one instance is written
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Target {
+    pub name: String,
+    /// Worker position, tagged on JSON lines
+    pub instance: Option<usize>,
+    pub out: PathBuf,
+    pub error: PathBuf,
+    pub combined: Option<PathBuf>,
+    pub format: Format,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    time: &'a str,
+    stream: String,
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<usize>,
+    msg: &'a str,
+}
+
+impl Target {
+    /// Line written for `text`, text lines in the combined log are tagged with their stream
+    fn line(&self, time: &str, stream: Stream, text: &str, combined: bool) -> String {
+        match self.format {
+            Format::Text if combined => format!("{} [{}] {}\n", time, stream, text),
+            Format::Text => format!("{} {}\n", time, text),
+            Format::Json => {
+                let entry = Entry { time, stream: stream.to_string(), name: &self.name, instance: self.instance, msg: text };
+                format!("{}\n", serde_json::to_string(&entry).unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// A log reopened once its file is replaced, e.g. removed by a restore cleanup
+struct Sink {
+    path: PathBuf,
+    file: Option<File>,
+    /// Last time the file was compared with the path, unset after a failed write
+    checked: Option<Instant>,
+}
+
+impl Sink {
+    fn new(path: &Path) -> Self {
+        Sink { path: path.to_path_buf(), file: None, checked: None }
+    }
+
+    fn write(&mut self, line: &str) {
+        // Rotation truncates in place, so a replaced file is only looked for now and then
+        if self.checked.is_none_or(|checked| checked.elapsed() >= REOPEN_CHECK) {
+            let replaced = match (&self.file, fs::metadata(&self.path)) {
+                (Some(file), Ok(current)) => file.metadata().map_or(true, |open| open.ino() != current.ino() || open.dev() != current.dev()),
+                _ => true,
+            };
+            if replaced {
+                self.file = OpenOptions::new().create(true).append(true).open(&self.path).ok();
+            }
+            self.checked = self.file.as_ref().map(|_| Instant::now());
+        }
+        if let Some(file) = &mut self.file {
+            if let Err(err) = file.write_all(line.as_bytes()) {
+                log::warn!("Failed to write captured output to {}: {}", self.path.display(), err);
+                self.checked = None;
+            }
+        }
+    }
+}
+
+/// Write ends of the stdout and stderr pipes for a new instance
+/// The read ends go to a relay process of the instance, so its output is captured whether or not the daemon runs
+pub fn pipes(target: &Target) -> Result<(OwnedFd, OwnedFd), String> {
+    let (out_reader, out) = std::io::pipe().map_err(|err| format!("Failed to create the out pipe: {}", err))?;
+    let (error_reader, error) = std::io::pipe().map_err(|err| format!("Failed to create the error pipe: {}", err))?;
+    let target = serde_json::to_string(target).map_err(|err| err.to_string())?;
+    let binary = std::env::current_exe().map_err(|err| format!("Failed to find the opm binary: {}", err))?;
+    let readers = [out_reader.as_raw_fd(), error_reader.as_raw_fd()];
+
+    let mut command = Command::new(binary);
+    command.args(["daemon", "capture", &target]).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    unsafe {
+        command.pre_exec(move || {
+            // A session of its own, so signals meant for the daemon or the instance do not reach the relay
+            libc::setsid();
+            listen::inherit(&readers)
+        });
+    }
+
+    // The relay forks and returns at once, the copy reading the pipes is left to init
+    match command.status() {
+        Ok(status) if status.success() => Ok((out.into(), error.into())),
+        Ok(status) => Err(format!("Capture relay {}", status)),
+        Err(err) => Err(format!("Failed to start the capture relay: {}", err)),
+    }
+}
+
+/// Run by `opm daemon capture`, reads the pipes passed from LISTEN_FDS_START into the logs of `target`
+/// Returns once every copy of the write ends is closed, i.e. the instance and its children exited
+pub fn relay(target: &str) -> Result<(), String> {
+    let target: Target = serde_json::from_str(target).map_err(|err| format!("Invalid capture target: {}", err))?;
+    // The parent exits right away, so whoever started the relay neither waits for it nor keeps a zombie
+    match unsafe { libc::fork() } {
+        -1 => return Err(format!("Failed to fork the capture relay: {}", std::io::Error::last_os_error())),
+        0 => {}
+        _ => std::process::exit(0),
+    }
+
+    let (out, error) = unsafe { (OwnedFd::from_raw_fd(listen::LISTEN_FDS_START), OwnedFd::from_raw_fd(listen::LISTEN_FDS_START + 1)) };
+    let target = Arc::new(target);
+    let combined = target.combined.as_deref().map(|path| Arc::new(Mutex::new(Sink::new(path))));
+
+    let reader = {
+        let (target, combined, sink) = (Arc::clone(&target), combined.clone(), Sink::new(&target.out));
+        std::thread::Builder::new()
+            .name("capture-out".to_string())
+            .spawn(move || read(BufReader::new(File::from(out)), Stream::Out, &target, sink, combined))
+            .map_err(|err| format!("Failed to start reading out: {}", err))?
+    };
+    read(BufReader::new(File::from(error)), Stream::Error, &target, Sink::new(&target.error), combined);
+    reader.join().map_err(|_| "Reading out failed".to_string())
+}
+
+/// Write every line read to the logs, a line longer than MAX_LINE is written in parts
+fn read(mut reader: impl BufRead, stream: Stream, target: &Target, mut sink: Sink, combined: Option<Arc<Mutex<Sink>>>) {
+    let mut buffer = Vec::new();
+
+    loop {
+        buffer.clear();
+        match (&mut reader).take(MAX_LINE as u64).read_until(b'\n', &mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        while buffer.last().is_some_and(|byte| matches!(byte, b'\n' | b'\r')) {
+            buffer.pop();
+        }
+
+        let text = String::from_utf8_lossy(&buffer);
+        let time = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        sink.write(&target.line(&time, stream, &text, false));
+        if let Some(combined) = &combined {
+            combined.lock().unwrap().write(&target.line(&time, stream, &text, true));
+        }
+    }
+}
+
+/// Time window of `opm logs --since/--until`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Range {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Range {
+    pub fn new(since: Option<&str>, until: Option<&str>) -> Result<Self, String> {
+        let now = Utc::now();
+        let range = Range {
+            since: since.map(|since| instant(since, now)).transpose()?,
+            until: until.map(|until| instant(until, now)).transpose()?,
+        };
+        match (range.since, range.until) {
+            (Some(since), Some(until)) if since > until => Err("--since is after --until".to_string()),
+            _ => Ok(range),
+        }
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| time >= since) && self.until.is_none_or(|until| time <= until)
+    }
+}
+
+/// An RFC3339 time, or a duration before `now` like "90s", "10m", "2h" or "1d"
+fn instant(spec: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let spec = spec.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(spec) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let invalid = || format!("Invalid time '{}', use RFC3339 (2024-01-01T12:00:00Z) or a duration like 10m, 2h or 1d", spec);
+    let split = spec.find(|char: char| !char.is_ascii_digit()).ok_or_else(invalid)?;
+    let amount: i64 = spec[..split].parse().map_err(|_| invalid())?;
+    let duration = match &spec[split..] {
+        "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => None,
+    };
+    duration.and_then(|duration| now.checked_sub_signed(duration)).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_captured_lines() {
+        let mut target = Target {
+            name: "api".to_string(),
+            instance: Some(1),
+            out: PathBuf::from("api-out.log"),
+            error: PathBuf::from("api-error.log"),
+            combined: None,
+            format: Format::Text,
+        };
+        let time = "2024-01-01T12:00:00.000Z";
+        assert_eq!(target.line(time, Stream::Out, "listening", false), "2024-01-01T12:00:00.000Z listening\n");
+        assert_eq!(target.line(time, Stream::Error, "oops", true), "2024-01-01T12:00:00.000Z [error] oops\n");
+
+        target.format = Format::Json;
+        let line = target.line(time, Stream::Error, "say \"hi\"", true);
+        assert_eq!(line, "{\"time\":\"2024-01-01T12:00:00.000Z\",\"stream\":\"error\",\"name\":\"api\",\"instance\":1,\"msg\":\"say \\\"hi\\\"\"}\n");
+    }
+
+    #[test]
+    fn test_range() {
+        let now = DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(instant("10m", now).unwrap().to_rfc3339(), "2024-01-01T11:50:00+00:00");
+        assert_eq!(instant("1d", now).unwrap().to_rfc3339(), "2023-12-31T12:00:00+00:00");
+        assert_eq!(instant("2024-01-01T13:00:00+01:00", now).unwrap(), now);
+        assert!(instant("10", now).is_err());
+        assert!(instant("ten minutes", now).is_err());
+        assert!(Range::new(Some("1h"), Some("2h")).is_err());
+        assert!(Range::new(Some("1h"), Some("30m")).is_ok());
+    }
+
+    #[test]
+    fn test_long_lines() {
+        let dir = std::env::temp_dir().join(format!("opm-capture-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = Target { name: "api".to_string(), instance: None, out: dir.join("out.log"), error: dir.join("error.log"), combined: None, format: Format::Text };
+
+        let input = format!("{}\nshort\n", "a".repeat(MAX_LINE * 2 + 100));
+        read(std::io::Cursor::new(input), Stream::Out, &target, Sink::new(&target.out), None);
+        let written = fs::read_to_string(&target.out).unwrap();
+        let parts: Vec<usize> = written.lines().map(|line| line.split_once(' ').unwrap().1.len()).collect();
+        assert_eq!(parts, vec![MAX_LINE, MAX_LINE, 100, 5]);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_replaced_log() {
+        let dir = std::env::temp_dir().join(format!("opm-capture-replaced-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.log");
+        let mut sink = Sink::new(&path);
+
+        sink.write("first\n");
+        fs::remove_file(&path).unwrap();
+        sink.write("second\n");
+        assert!(!path.exists());
+
+        sink.checked = Instant::now().checked_sub(REOPEN_CHECK);
+        sink.write("third\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "third\n");
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::{fmt, str::FromStr, time::Duration};
+
+/// How long to wait for a dependency to become ready when it has no readiness check or job timeout
+pub const READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What a process waits for before it is started after a dependency
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Condition {
+    /// The dependency was started
+    #[default]
+    Started,
+    /// The dependency passed its readiness check, or a job dependency completed a run successfully
+    Ready,
+}
+
+/// A process this one is started after, written as `name` or `name:ready`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Dependency {
+    pub name: String,
+    pub condition: Condition,
+}
+
+impl FromStr for Dependency {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (name, condition) = match spec.trim().rsplit_once(':') {
+            Some((name, "ready")) => (name, Condition::Ready),
+            Some((name, "started")) => (name, Condition::Started),
+            Some((_, condition)) => {
+                return Err(format!(
+                    "Unknown dependency condition '{}' in '{}', expected started or ready",
+                    condition, spec
+                ))
+            }
+            None => (spec.trim(), Condition::Started),
+        };
+        if name.is_empty() {
+            return Err(format!("Dependency '{}' has no process name", spec));
+        }
+        Ok(Dependency {
+            name: name.to_string(),
+            condition,
+        })
+    }
+}
+
+impl TryFrom<String> for Dependency {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        spec.parse()
+    }
+}
+
+impl From<Dependency> for String {
+    fn from(dependency: Dependency) -> Self {
+        dependency.to_string()
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.condition {
+            Condition::Started => write!(f, "{}", self.name),
+            Condition::Ready => write!(f, "{}:ready", self.name),
+        }
+    }
+}
+
+/// Check the dependencies of a process before it is created
+pub fn validate(name: &str, dependencies: &[Dependency]) -> Result<(), String> {
+    match dependencies
+        .iter()
+        .find(|dependency| dependency.name == name)
+    {
+        Some(_) => Err(format!("Process '{}' cannot depend on itself", name)),
+        None => Ok(()),
+    }
+}
+
+/// Order processes so every one comes after the processes it depends on, keeping the given order otherwise
+/// Dependencies on processes outside of `nodes` are ignored. A cycle is an error naming the processes on it
+pub fn start_order(nodes: &[(usize, &str, &[Dependency])]) -> Result<Vec<usize>, String> {
+    let by_name: HashMap<&str, usize> = nodes
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(index, (_, name, _))| (*name, index))
+        .collect();
+    let edges: Vec<Vec<usize>> = nodes
+        .iter()
+        .map(|(_, _, dependencies)| {
+            dependencies
+                .iter()
+                .filter_map(|dependency| by_name.get(dependency.name.as_str()).copied())
+                .collect()
+        })
+        .collect();
+
+    // 0 = not visited, 1 = on the current path, 2 = ordered
+    let mut state = vec![0u8; nodes.len()];
+    let mut order = Vec::with_capacity(nodes.len());
+
+    fn visit(
+        index: usize,
+        edges: &[Vec<usize>],
+        state: &mut [u8],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), Vec<usize>> {
+        match state[index] {
+            2 => return Ok(()),
+            1 => {
+                let start = path
+                    .iter()
+                    .position(|&on_path| on_path == index)
+                    .unwrap_or(0);
+                let mut cycle = path[start..].to_vec();
+                cycle.push(index);
+                return Err(cycle);
+            }
+            _ => {}
+        }
+
+        state[index] = 1;
+        path.push(index);
+        for &dependency in &edges[index] {
+            visit(dependency, edges, state, path, order)?;
+        }
+        path.pop();
+        state[index] = 2;
+        order.push(index);
+        Ok(())
+    }
+
+    for index in 0..nodes.len() {
+        if let Err(cycle) = visit(index, &edges, &mut state, &mut vec![], &mut order) {
+            let names: Vec<&str> = cycle.iter().map(|&index| nodes[index].1).collect();
+            return Err(format!(
+                "Dependency cycle between processes: {}",
+                names.join(" -> ")
+            ));
+        }
+    }
+
+    Ok(order.into_iter().map(|index| nodes[index].0).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deps(specs: &[&str]) -> Vec<Dependency> {
+        specs.iter().map(|spec| spec.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_dependency_parsing() {
+        assert_eq!(
+            "redis".parse::<Dependency>().unwrap(),
+            Dependency {
+                name: "redis".into(),
+                condition: Condition::Started
+            }
+        );
+        assert_eq!(
+            "migrate:ready".parse::<Dependency>().unwrap().condition,
+            Condition::Ready
+        );
+        assert_eq!(
+            "migrate:ready".parse::<Dependency>().unwrap().to_string(),
+            "migrate:ready"
+        );
+        assert!("redis:healthy".parse::<Dependency>().is_err());
+        assert!(":ready".parse::<Dependency>().is_err());
+        assert!(validate("api", &deps(&["api"])).is_err());
+    }
+
+    #[test]
+    fn test_start_order_and_cycles() {
+        let (api, redis, migrate, worker) = (
+            deps(&["redis", "migrate:ready"]),
+            deps(&[]),
+            deps(&["redis"]),
+            deps(&["api", "missing"]),
+        );
+        let nodes: Vec<(usize, &str, &[Dependency])> = vec![
+            (0, "api", &api),
+            (1, "redis", &redis),
+            (2, "migrate", &migrate),
+            (3, "worker", &worker),
+        ];
+        assert_eq!(start_order(&nodes).unwrap(), vec![1, 2, 0, 3]);
+
+        let (a, b, c) = (deps(&["b"]), deps(&["c"]), deps(&["a:ready"]));
+        let nodes: Vec<(usize, &str, &[Dependency])> =
+            vec![(0, "a", &a), (1, "b", &b), (2, "c", &c)];
+        assert_eq!(
+            start_order(&nodes).unwrap_err(),
+            "Dependency cycle between processes: a -> b -> c -> a"
+        );
+    }
+}
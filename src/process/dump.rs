@@ -55,6 +55,8 @@ static MEMORY_CACHE: Lazy<Mutex<Option<Runner>>> = Lazy::new(|| Mutex::new(None)
 fn permanent_snapshot(source: &Runner) -> Runner {
     let mut snapshot = source.clone();
 
+    // Variables are saved as written, secret:// references included, redacting by value would rewrite
+    // unrelated variables that happen to equal a secret
     for process in snapshot.list.values_mut() {
         process.pid = 0;
         process.shell_pid = None;
@@ -73,6 +75,7 @@ fn empty_runner() -> Runner {
         id: Id::new(0),
         list: BTreeMap::new(),
         remote: None,
+        groups: BTreeMap::new(),
     }
 }
 
@@ -153,6 +156,7 @@ fn merge_runners(mut permanent: Runner, memory: Option<Runner>) -> Runner {
         // - So memory cache always contains the complete merged state
         // - GetState simply returns this authoritative state
         permanent.list = memory.list;
+        permanent.groups = memory.groups;
         // When memory has state, also update the counter to match memory's counter
         // This ensures deletions properly decrease the counter
         let mem_counter = memory.id.counter.load(Ordering::SeqCst);
@@ -262,6 +266,7 @@ pub fn read() -> Runner {
             id: Id::new(0),
             list: BTreeMap::new(),
             remote: None,
+            groups: BTreeMap::new(),
         };
 
         write(&runner);
@@ -301,6 +306,7 @@ pub fn read() -> Runner {
                 id: Id::new(0),
                 list: BTreeMap::new(),
                 remote: None,
+                groups: BTreeMap::new(),
             };
 
             write(&runner);
@@ -0,0 +1,353 @@
+use super::capture::Range;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{fmt, str::FromStr};
+use utoipa::ToSchema;
+
+/// Severity of a log line, ordered from least to most severe
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl Level {
+    /// Level of a name used by common loggers, e.g. "WARNING", "err" or "critical"
+    fn named(name: &str) -> Option<Level> {
+        match name.to_lowercase().as_str() {
+            "trace" | "trc" => Some(Level::Trace),
+            "debug" | "dbg" => Some(Level::Debug),
+            "info" | "information" | "notice" => Some(Level::Info),
+            "warn" | "warning" => Some(Level::Warn),
+            "error" | "err" | "dpanic" => Some(Level::Error),
+            "fatal" | "critical" | "crit" | "panic" | "alert" | "emerg" | "emergency" => Some(Level::Fatal),
+            _ => None,
+        }
+    }
+
+    /// Numeric levels of pino and bunyan
+    fn numbered(level: f64) -> Level {
+        match level {
+            level if level <= 10.0 => Level::Trace,
+            level if level <= 20.0 => Level::Debug,
+            level if level <= 30.0 => Level::Info,
+            level if level <= 40.0 => Level::Warn,
+            level if level <= 50.0 => Level::Error,
+            _ => Level::Fatal,
+        }
+    }
+
+    /// Level of a text line, the first word naming one, leaving out words as common in prose as in logs
+    /// Words like "failed" or "errors" do not count, so "0 errors" is not an error
+    fn words(text: &str) -> Option<Level> {
+        text.split(|char: char| !char.is_ascii_alphabetic())
+            .filter(|word| !matches!(word.to_lowercase().as_str(), "" | "alert" | "notice" | "information" | "emergency"))
+            .find_map(Level::named)
+    }
+
+    /// Indicator and color `opm logs` shows the level with
+    pub fn indicator(&self) -> (&'static str, &'static str) {
+        match self {
+            Level::Trace => ("[TRCE]", "cyan"),
+            Level::Debug => ("[ DBG]", "cyan"),
+            Level::Info => ("[INFO]", "blue"),
+            Level::Warn => ("[WARN]", "yellow"),
+            Level::Error => ("[ ERR]", "red"),
+            Level::Fatal => ("[FATL]", "red"),
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        Level::named(level.trim()).ok_or_else(|| format!("Invalid log level: {}. Use trace, debug, info, warn, error or fatal", level))
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Level::Trace => write!(f, "trace"),
+            Level::Debug => write!(f, "debug"),
+            Level::Info => write!(f, "info"),
+            Level::Warn => write!(f, "warn"),
+            Level::Error => write!(f, "error"),
+            Level::Fatal => write!(f, "fatal"),
+        }
+    }
+}
+
+/// What a log line says about itself, read from JSON fields when the line is JSON (pino, zap, bunyan,
+/// tracing-subscriber) and from its words otherwise
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Entry {
+    #[schema(value_type = Option<String>, example = "2024-01-01T12:00:00.000Z")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<Level>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msg: Option<String>,
+    /// Stream of a captured line, "out" or "error"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<String>,
+    /// All fields of a JSON line, for a line captured as JSON those the process wrote
+    #[schema(value_type = Option<Object>)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Map<String, Value>>,
+}
+
+impl Entry {
+    pub fn parse(line: &str) -> Entry {
+        Entry::written(line).0
+    }
+
+    /// Entry of a line with the text the process wrote, without what a log capture added to it
+    pub fn written(line: &str) -> (Entry, String) {
+        let mut entry = Entry::default();
+        let mut text = line.trim().to_string();
+
+        // Text capture: "<RFC3339> [stream] line", the stream tag only in combined logs
+        if let Some((time, rest)) = text.split_once(' ').and_then(|(first, rest)| Some((rfc3339(first)?, rest))) {
+            entry.time = Some(time);
+            let rest = match ["out", "error"].iter().find_map(|stream| Some((*stream, rest.strip_prefix(&format!("[{}] ", stream))?))) {
+                Some((stream, rest)) => {
+                    entry.stream = Some(stream.to_string());
+                    rest
+                }
+                None => rest,
+            };
+            text = rest.to_string();
+        }
+
+        let mut fields = object(&text);
+        // JSON capture wraps what the process wrote in msg
+        if let Some(wrapper) = fields.as_ref().filter(|fields| captured(fields)) {
+            entry.time = time(wrapper);
+            entry.stream = wrapper.get("stream").and_then(Value::as_str).map(str::to_string);
+            text = wrapper.get("msg").and_then(Value::as_str).unwrap_or_default().to_string();
+            fields = object(&text);
+        }
+
+        match fields {
+            Some(fields) => {
+                entry.time = time(&fields).or(entry.time);
+                entry.level = level(&fields);
+                entry.msg = message(&fields);
+                entry.fields = Some(fields);
+            }
+            None => {
+                entry.level = Level::words(&text);
+                entry.msg = Some(text.clone());
+            }
+        }
+        (entry, text)
+    }
+
+    /// Level the line is shown and filtered with, a line without one counts as an error on stderr
+    pub fn level_in(&self, kind: &str) -> Option<Level> {
+        let stream = self.stream.as_deref().unwrap_or(kind);
+        self.level.or((stream == "error").then_some(Level::Error))
+    }
+
+    /// Value of a field as text, `key` may be a dotted path into nested objects
+    pub fn field(&self, key: &str) -> Option<String> {
+        let fields = self.fields.as_ref()?;
+        let value = fields.get(key).or_else(|| {
+            let mut parts = key.split('.');
+            let first = fields.get(parts.next()?)?;
+            parts.try_fold(first, |value, part| value.get(part))
+        })?;
+        Some(match value {
+            Value::String(text) => text.clone(),
+            value => value.to_string(),
+        })
+    }
+}
+
+/// Lines `opm logs` shows, selected by text, level, fields and time
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// Text the line contains, case-insensitive
+    pub pattern: Option<String>,
+    /// Lowest level shown
+    pub level: Option<Level>,
+    /// Fields a JSON line must have, as key and value
+    pub fields: Vec<(String, String)>,
+    pub range: Range,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_none() && self.level.is_none() && self.fields.is_empty() && self.range.is_all()
+    }
+
+    /// Whether a line passes the text, level and field filters, the time window is applied by `select`
+    pub fn matches(&self, line: &str, entry: &Entry, kind: &str) -> bool {
+        self.pattern.as_ref().is_none_or(|pattern| line.to_lowercase().contains(&pattern.to_lowercase()))
+            && self.level.is_none_or(|level| entry.level_in(kind).is_some_and(|line_level| line_level >= level))
+            && self.fields.iter().all(|(key, value)| entry.field(key).as_deref() == Some(value.as_str()))
+    }
+
+    /// Lines passing the filter with their entries
+    /// A line without a timestamp (e.g. a stack trace) is inside the time window when the line before it is
+    pub fn select(&self, lines: Vec<String>, kind: &str) -> Vec<(String, Entry)> {
+        let mut inside = self.range.is_all();
+        lines
+            .into_iter()
+            .filter_map(|line| {
+                let entry = Entry::parse(&line);
+                if let Some(time) = entry.time.filter(|_| !self.range.is_all()) {
+                    inside = self.range.contains(time);
+                }
+                (inside && self.matches(&line, &entry, kind)).then_some((line, entry))
+            })
+            .collect()
+    }
+}
+
+/// A `key=value` given to `--field`
+pub fn parse_field(field: &str) -> Result<(String, String), String> {
+    match field.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+        _ => Err(format!("Invalid field '{}', use key=value", field)),
+    }
+}
+
+fn rfc3339(time: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time).ok().map(|time| time.with_timezone(&Utc))
+}
+
+fn object(text: &str) -> Option<Map<String, Value>> {
+    match text.starts_with('{') {
+        true => serde_json::from_str(text).ok(),
+        false => None,
+    }
+}
+
+/// A line written by a JSON log capture, see capture::Target
+fn captured(fields: &Map<String, Value>) -> bool {
+    fields.get("msg").is_some_and(Value::is_string)
+        && fields.get("name").is_some_and(Value::is_string)
+        && fields.get("stream").and_then(Value::as_str).is_some_and(|stream| matches!(stream, "out" | "error"))
+}
+
+/// `time`, `timestamp` or `ts` as RFC3339, or as a number of milliseconds (pino, bunyan) or seconds (zap)
+fn time(fields: &Map<String, Value>) -> Option<DateTime<Utc>> {
+    ["time", "timestamp", "ts", "@timestamp"].iter().find_map(|key| match fields.get(*key)? {
+        Value::String(time) => rfc3339(time),
+        Value::Number(number) => {
+            let number = number.as_f64()?;
+            match number > 1e11 {
+                true => DateTime::from_timestamp_millis(number as i64),
+                false => DateTime::from_timestamp_millis((number * 1000.0) as i64),
+            }
+        }
+        _ => None,
+    })
+}
+
+/// `level`, `severity` or `lvl`, named or numbered like pino
+fn level(fields: &Map<String, Value>) -> Option<Level> {
+    ["level", "severity", "lvl", "log.level"].iter().find_map(|key| match fields.get(*key)? {
+        Value::String(name) => Level::named(name),
+        Value::Number(number) => number.as_f64().map(Level::numbered),
+        _ => None,
+    })
+}
+
+/// `msg` or `message`, tracing-subscriber keeps it in `fields.message`
+fn message(fields: &Map<String, Value>) -> Option<String> {
+    ["msg", "message"]
+        .iter()
+        .find_map(|key| fields.get(*key))
+        .or_else(|| fields.get("fields")?.get("message"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> Option<DateTime<Utc>> {
+        rfc3339(time)
+    }
+
+    #[test]
+    fn test_json_entries() {
+        let pino = Entry::parse(r#"{"level":40,"time":1704110400000,"pid":1,"msg":"slow query","req":{"method":"GET"}}"#);
+        assert_eq!(pino.level, Some(Level::Warn));
+        assert_eq!(pino.time, at("2024-01-01T12:00:00Z"));
+        assert_eq!(pino.msg.as_deref(), Some("slow query"));
+        assert_eq!(pino.field("req.method").as_deref(), Some("GET"));
+        assert_eq!(pino.field("pid").as_deref(), Some("1"));
+
+        let zap = Entry::parse(r#"{"level":"error","ts":1704110400.5,"caller":"main.go:10","msg":"failed"}"#);
+        assert_eq!((zap.level, zap.time), (Some(Level::Error), at("2024-01-01T12:00:00.500Z")));
+
+        let tracing = Entry::parse(r#"{"timestamp":"2024-01-01T12:00:00Z","level":"INFO","fields":{"message":"listening"},"target":"app"}"#);
+        assert_eq!((tracing.level, tracing.msg.as_deref()), (Some(Level::Info), Some("listening")));
+
+        let gcp = Entry::parse(r#"{"severity":"CRITICAL","message":"down"}"#);
+        assert_eq!(gcp.level, Some(Level::Fatal));
+
+        // JSON written by the process, captured as JSON by the daemon
+        let (captured, written) = Entry::written(r#"{"time":"2024-01-01T12:00:00.000Z","stream":"out","name":"api","msg":"{\"level\":30,\"msg\":\"ok\"}"}"#);
+        assert_eq!(written, r#"{"level":30,"msg":"ok"}"#);
+        assert_eq!((captured.level, captured.msg.as_deref()), (Some(Level::Info), Some("ok")));
+        assert_eq!((captured.time, captured.stream.as_deref()), (at("2024-01-01T12:00:00Z"), Some("out")));
+
+        let text = Entry::parse(r#"{"time":"2024-01-01T12:00:00.000Z","stream":"error","name":"api","msg":"Traceback"}"#);
+        assert_eq!((text.level, text.level_in("out")), (None, Some(Level::Error)));
+    }
+
+    #[test]
+    fn test_text_entries() {
+        assert_eq!(Entry::parse("Failed to connect, 0 errors so far").level, None);
+        assert_eq!(Entry::parse("[WARN] disk almost full").level, Some(Level::Warn));
+        assert_eq!(Entry::parse("time=12:00 level=debug msg=tick").level, Some(Level::Debug));
+        assert_eq!(Entry::parse("Error: ECONNREFUSED").level, Some(Level::Error));
+
+        let captured = Entry::parse("2024-01-01T12:00:00.000Z [error] INFO retrying after error");
+        assert_eq!((captured.level, captured.stream.as_deref()), (Some(Level::Info), Some("error")));
+        assert_eq!(captured.time, at("2024-01-01T12:00:00Z"));
+        assert_eq!(Entry::parse("2024-01-01T12:00:00.000Z {\"level\":\"warn\"}").level, Some(Level::Warn));
+        assert_eq!(Entry::parse("listening").level_in("error"), Some(Level::Error));
+        assert_eq!(Entry::parse("listening").level_in("out"), None);
+    }
+
+    #[test]
+    fn test_filter() {
+        let lines = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+        let shown = |selected: Vec<(String, Entry)>| selected.into_iter().map(|(line, _)| line).collect::<Vec<_>>();
+
+        let json = lines(&[r#"{"level":30,"msg":"a","user":"bob"}"#, r#"{"level":50,"msg":"b","user":"bob"}"#, r#"{"level":60,"msg":"c","user":"eve"}"#, "plain WARN"]);
+        let filter = Filter { level: Some(Level::Warn), ..Filter::default() };
+        assert_eq!(shown(filter.select(json.clone(), "out")), vec![json[1].clone(), json[2].clone(), json[3].clone()]);
+        let filter = Filter { fields: vec![parse_field("user=bob").unwrap()], ..Filter::default() };
+        assert_eq!(shown(filter.select(json.clone(), "out")), vec![json[0].clone(), json[1].clone()]);
+        assert!(parse_field("=bob").is_err());
+
+        let filter = Filter {
+            range: Range { since: at("2024-01-01T11:00:00Z"), until: at("2024-01-01T11:30:00Z") },
+            ..Filter::default()
+        };
+        let text = lines(&[
+            "before the first timestamp",
+            "2024-01-01T10:59:59.000Z too early",
+            "2024-01-01T11:00:00.000Z Error: failed",
+            "    at main (app.js:1)",
+            "2024-01-01T11:30:00.001Z too late",
+        ]);
+        assert_eq!(shown(filter.select(text, "out")), vec!["2024-01-01T11:00:00.000Z Error: failed", "    at main (app.js:1)"]);
+    }
+}
@@ -0,0 +1,414 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+use utoipa::ToSchema;
+
+use super::Env;
+use crate::secret::Secrets;
+
+/// Variables a process declares on top of the environment it inherits
+/// Values may use `${VAR}` and `${VAR:-default}`, expanded each time the process starts
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Environment {
+    /// Variables from HCL `env`
+    #[schema(example = json!({"DATABASE_URL": "postgres://${DB_HOST:-localhost}/app"}))]
+    #[serde(default)]
+    pub vars: Env,
+    /// Files loaded in order after the .env in the process path, relative to the working directory
+    #[schema(value_type = Vec<String>, example = json!(["config/common.env"]))]
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    /// Variables of each named environment, from HCL `env_<name>`
+    #[schema(example = json!({"production": {"NODE_ENV": "production"}}))]
+    #[serde(default)]
+    pub overlays: BTreeMap<String, Env>,
+    /// Named environment applied over `vars`, chosen with `--env <name>`
+    #[schema(example = "production")]
+    #[serde(default)]
+    pub selected: Option<String>,
+}
+
+/// Where the final value of a variable came from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    /// Environment of the process that starts the instance
+    System,
+    /// Environment stored with the process when it was created
+    Stored,
+    /// A .env or env_file
+    File(PathBuf),
+    /// HCL `env`
+    Declared,
+    /// HCL `env_<name>` of the selected environment
+    Overlay(String),
+    /// Worker position of a group instance
+    Instance,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::System => write!(f, "system"),
+            Source::Stored => write!(f, "stored"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Declared => write!(f, "env"),
+            Source::Overlay(name) => write!(f, "env_{}", name),
+            Source::Instance => write!(f, "instance"),
+        }
+    }
+}
+
+/// Final environment of a process by variable name, with the source of each value
+pub type Resolved = BTreeMap<String, (String, Source)>;
+
+impl Environment {
+    /// Check the selected environment exists before it is applied
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.selected {
+            Some(name) if !self.overlays.contains_key(name) => {
+                let known: Vec<&str> = self.overlays.keys().map(String::as_str).collect();
+                Err(format!(
+                    "Environment '{}' is not defined, known: {}",
+                    name,
+                    match known.is_empty() {
+                        true => "none".to_string(),
+                        false => known.join(", "),
+                    }
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Show secret values among the declared variables as their references
+    pub fn redact(&mut self, secrets: &Secrets) {
+        secrets.redact(&mut self.vars);
+        self.overlays.values_mut().for_each(|vars| secrets.redact(vars));
+    }
+
+    /// Layer every source, later ones win: system, stored, .env in `path`, env files, `env`, the selected environment, `instance`
+    /// `cwd` resolves relative env files, values from files and declarations are expanded against what is below them
+    pub fn resolve(&self, system: &Env, stored: &Env, path: &Path, cwd: &Path, instance: &Env) -> Result<Resolved, String> {
+        let mut resolved = Resolved::new();
+        let apply = |layer: Env, source: Source, resolved: &mut Resolved| {
+            for (key, value) in layer {
+                resolved.insert(key, (value, source.clone()));
+            }
+        };
+
+        apply(system.clone(), Source::System, &mut resolved);
+        // Stored variables were mostly captured from the environment, only differing values are attributed to the store
+        for (key, value) in stored {
+            if system.get(key) != Some(value) {
+                resolved.insert(key.clone(), (value.clone(), Source::Stored));
+            }
+        }
+
+        let dotenv = path.join(".env");
+        let mut files: Vec<(PathBuf, bool)> = vec![(dotenv, false)];
+        files.extend(self.files.iter().map(|file| (cwd.join(file), true)));
+        for (file, required) in files {
+            if !required && !file.is_file() {
+                continue;
+            }
+            let contents = std::fs::read_to_string(&file)
+                .map_err(|err| format!("Failed to read env file {}: {}", file.display(), err))?;
+            let entries = parse(&contents).map_err(|err| format!("Invalid env file {}: {}", file.display(), err))?;
+            let layer = expand(&entries, &values(&resolved))?;
+            apply(layer, Source::File(file), &mut resolved);
+        }
+
+        let layer = expand(&self.vars, &values(&resolved))?;
+        apply(layer, Source::Declared, &mut resolved);
+
+        self.validate()?;
+        if let Some(name) = &self.selected {
+            let layer = expand(&self.overlays[name], &values(&resolved))?;
+            apply(layer, Source::Overlay(name.clone()), &mut resolved);
+        }
+
+        apply(instance.clone(), Source::Instance, &mut resolved);
+        Ok(resolved)
+    }
+}
+
+fn values(resolved: &Resolved) -> Env {
+    resolved.iter().map(|(key, (value, _))| (key.clone(), value.clone())).collect()
+}
+
+/// `KEY=value` pairs for process_run
+pub fn pairs(resolved: &Resolved) -> Vec<String> {
+    resolved.iter().map(|(key, (value, _))| format!("{}={}", key, value)).collect()
+}
+
+/// Expand the templates of a layer, a variable refers to others of the same layer or, for its own name, to the one below
+pub fn expand(layer: &Env, below: &Env) -> Result<Env, String> {
+    fn resolve(key: &str, layer: &Env, below: &Env, done: &mut Env, stack: &mut Vec<String>) -> Result<String, String> {
+        if let Some(value) = done.get(key) {
+            return Ok(value.clone());
+        }
+        if stack.iter().any(|pending| pending == key) {
+            stack.push(key.to_string());
+            return Err(format!("Variables refer to each other: {}", stack.join(" -> ")));
+        }
+
+        stack.push(key.to_string());
+        let value = interpolate(&layer[key], &mut |name| match name != key && layer.contains_key(name) {
+            true => resolve(name, layer, below, done, stack).map(Some),
+            false => Ok(below.get(name).cloned()),
+        })?;
+        stack.pop();
+
+        done.insert(key.to_string(), value.clone());
+        Ok(value)
+    }
+
+    let mut done = Env::new();
+    for key in layer.keys() {
+        resolve(key, layer, below, &mut done, &mut Vec::new())?;
+    }
+    Ok(done)
+}
+
+/// Replace `${VAR}` and `${VAR:-default}` in `value`, `$${` is a literal `${`
+/// The default applies when the variable is unset or empty and may itself refer to variables
+pub fn interpolate(value: &str, lookup: &mut dyn FnMut(&str) -> Result<Option<String>, String>) -> Result<String, String> {
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        if let Some(escaped) = after.strip_prefix("${") {
+            output.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let Some(template) = after.strip_prefix('{') else {
+            output.push('$');
+            rest = after;
+            continue;
+        };
+        let end = closing_brace(template).ok_or_else(|| format!("Unclosed ${{ in '{}'", value))?;
+
+        let (name, default) = match template[..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&template[..end], None),
+        };
+        if !is_name(name) {
+            return Err(format!("Invalid variable name '{}' in '{}'", name, value));
+        }
+
+        match (lookup(name)?, default) {
+            (Some(found), None) => output.push_str(&found),
+            (Some(found), Some(_)) if !found.is_empty() => output.push_str(&found),
+            (_, Some(default)) => output.push_str(&interpolate(default, lookup)?),
+            (None, None) => {}
+        }
+        rest = &template[end + 1..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Position of the brace closing a template, nested `${...}` in a default included
+fn closing_brace(template: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (index, character) in template.char_indices() {
+        match character {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(index),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse a dotenv file without expanding it: `KEY=value`, optional `export `, `#` comments,
+/// single quotes kept literally, double quotes with `\n`, `\t`, `\"` and `\\` escapes
+pub fn parse(contents: &str) -> Result<Env, String> {
+    let mut env = Env::new();
+    let mut lines = contents.lines().enumerate();
+
+    while let Some((number, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected KEY=value", number + 1));
+        };
+        let key = key.trim();
+        if !is_name(key) {
+            return Err(format!("line {}: invalid variable name '{}'", number + 1, key));
+        }
+
+        let value = value.trim_start();
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                // Quoted values may continue on the next lines
+                let mut quoted = value[1..].to_string();
+                while closing_quote(&quoted, quote).is_none() {
+                    let Some((_, next)) = lines.next() else {
+                        return Err(format!("line {}: unclosed {} quote", number + 1, quote));
+                    };
+                    quoted.push('\n');
+                    quoted.push_str(next);
+                }
+                let end = closing_quote(&quoted, quote).unwrap();
+                match quote {
+                    '"' => unescape(&quoted[..end]),
+                    _ => quoted[..end].to_string(),
+                }
+            }
+            _ => match value.find(" #") {
+                Some(comment) => value[..comment].trim_end().to_string(),
+                None => value.trim_end().to_string(),
+            },
+        };
+        env.insert(key.to_string(), value);
+    }
+
+    Ok(env)
+}
+
+fn closing_quote(value: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (index, character) in value.char_indices() {
+        match character {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => return Some(index),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut characters = value.chars();
+    while let Some(character) = characters.next() {
+        match (character, character == '\\') {
+            (_, true) => match characters.next() {
+                Some('n') => output.push('\n'),
+                Some('t') => output.push('\t'),
+                Some(other @ ('"' | '\\')) => output.push(other),
+                Some(other) => {
+                    output.push('\\');
+                    output.push(other);
+                }
+                None => output.push('\\'),
+            },
+            (character, false) => output.push(character),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(pairs: &[(&str, &str)]) -> Env {
+        pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let below = env(&[("HOME", "/home/app"), ("EMPTY", ""), ("PATH", "/usr/bin")]);
+        let layer = env(&[
+            ("DATA", "${HOME}/data"),
+            ("HOST", "${DB_HOST:-localhost}"),
+            ("URL", "postgres://${HOST}:${PORT:-5432}/${EMPTY:-app}"),
+            ("PATH", "${PATH}:/opt/bin"),
+            ("LITERAL", "$${HOME} costs $5"),
+            ("NESTED", "${MISSING:-${HOME}/fallback}"),
+        ]);
+
+        let expanded = expand(&layer, &below).unwrap();
+        assert_eq!(expanded["DATA"], "/home/app/data");
+        assert_eq!(expanded["HOST"], "localhost");
+        assert_eq!(expanded["URL"], "postgres://localhost:5432/app");
+        assert_eq!(expanded["PATH"], "/usr/bin:/opt/bin");
+        assert_eq!(expanded["LITERAL"], "${HOME} costs $5");
+        assert_eq!(expanded["NESTED"], "/home/app/fallback");
+
+        assert!(expand(&env(&[("A", "${B}"), ("B", "${A}")]), &below).is_err());
+        assert!(expand(&env(&[("A", "${B")]), &below).is_err());
+        assert!(expand(&env(&[("A", "${1B}")]), &below).is_err());
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let parsed = parse(concat!(
+            "# comment\n",
+            "export NODE_ENV=production\n",
+            "PLAIN = value # trailing comment\n",
+            "SINGLE='${HOME} stays'\n",
+            "DOUBLE=\"line\\nbreak \\\"quoted\\\"\"\n",
+            "MULTI=\"first\n",
+            "second\"\n",
+            "EMPTY=\n",
+        ))
+        .unwrap();
+
+        assert_eq!(parsed["NODE_ENV"], "production");
+        assert_eq!(parsed["PLAIN"], "value");
+        assert_eq!(parsed["SINGLE"], "${HOME} stays");
+        assert_eq!(parsed["DOUBLE"], "line\nbreak \"quoted\"");
+        assert_eq!(parsed["MULTI"], "first\nsecond");
+        assert_eq!(parsed["EMPTY"], "");
+
+        assert!(parse("NOVALUE\n").is_err());
+        assert!(parse("BAD-NAME=1\n").is_err());
+        assert!(parse("OPEN=\"never closed\n").is_err());
+    }
+
+    #[test]
+    fn test_resolve_layers() {
+        let dir = std::env::temp_dir().join(format!("opm-env-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("config")).unwrap();
+        std::fs::write(dir.join(".env"), "FROM_DOTENV=1\nLEVEL=dotenv\n").unwrap();
+        std::fs::write(dir.join("config/app.env"), "LEVEL=file\nDATA=${HOME}/data\n").unwrap();
+
+        let environment = Environment {
+            vars: env(&[("LEVEL", "env"), ("NODE_ENV", "development")]),
+            files: vec![PathBuf::from("config/app.env")],
+            overlays: BTreeMap::from([("production".to_string(), env(&[("NODE_ENV", "production")]))]),
+            selected: Some("production".to_string()),
+        };
+        let system = env(&[("HOME", "/home/app"), ("SHELL", "/bin/sh")]);
+        let stored = env(&[("SHELL", "/bin/sh"), ("TOKEN", "abc")]);
+        let instance = env(&[("PORT", "3000")]);
+
+        let resolved = environment.resolve(&system, &stored, &dir, &dir, &instance).unwrap();
+        assert_eq!(resolved["SHELL"], ("/bin/sh".to_string(), Source::System));
+        assert_eq!(resolved["TOKEN"], ("abc".to_string(), Source::Stored));
+        assert_eq!(resolved["FROM_DOTENV"], ("1".to_string(), Source::File(dir.join(".env"))));
+        assert_eq!(resolved["DATA"], ("/home/app/data".to_string(), Source::File(dir.join("config/app.env"))));
+        assert_eq!(resolved["LEVEL"], ("env".to_string(), Source::Declared));
+        assert_eq!(resolved["NODE_ENV"], ("production".to_string(), Source::Overlay("production".to_string())));
+        assert_eq!(resolved["PORT"], ("3000".to_string(), Source::Instance));
+        assert!(pairs(&resolved).contains(&"NODE_ENV=production".to_string()));
+
+        let missing = Environment { files: vec![PathBuf::from("missing.env")], ..Default::default() };
+        assert!(missing.resolve(&system, &stored, &dir, &dir, &instance).is_err());
+        let unknown = Environment { selected: Some("staging".to_string()), ..environment };
+        assert!(unknown.resolve(&system, &stored, &dir, &dir, &instance).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+use super::balancer::Balancer;
+use super::instance::Instance;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Workers started together with `--workers` or `instances = N`, managed by the group name
+/// Each instance is a regular process whose Exec.instance names the group
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Group {
+    /// Number of instances the group is scaled to
+    #[schema(example = 4)]
+    pub instances: usize,
+    /// Port of the first instance, the next instances count up from it unless the port is shared
+    #[schema(example = 3000)]
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// All instances serve on `port` through SO_REUSEPORT
+    #[serde(default)]
+    pub shared_port: bool,
+    /// Instances a rolling reload replaces at once
+    #[schema(example = 1)]
+    #[serde(default)]
+    pub max_unavailable: Option<usize>,
+    /// TCP proxy the daemon runs in front of the instances
+    #[serde(default)]
+    pub balancer: Option<Balancer>,
+}
+
+impl Group {
+    /// Build a group from `--port-range` style ports, one per instance or a single port all instances share
+    pub fn new(instances: usize, ports: &[u16]) -> Result<Self, String> {
+        if ports.len() > 1 && ports.len() != instances {
+            return Err(format!(
+                "Port range must provide exactly {} ports for {} workers",
+                instances, instances
+            ));
+        }
+        Ok(Group {
+            instances,
+            port: ports.first().copied(),
+            shared_port: ports.len() == 1,
+            max_unavailable: None,
+            balancer: None,
+        })
+    }
+
+    /// Check the group can hand out a port to each of its instances
+    pub fn validate(&self, name: &str) -> Result<(), String> {
+        if self.instances == 0 {
+            return Err(format!("Group '{}' needs at least one instance", name));
+        }
+        if self.max_unavailable == Some(0) {
+            return Err(format!("Group '{}' needs max_unavailable of at least 1", name));
+        }
+        if let (Some(port), false) = (self.port, self.shared_port) {
+            if port as usize + self.instances - 1 > u16::MAX as usize {
+                return Err(format!("Group '{}' runs out of ports above {} for {} instances", name, port, self.instances));
+            }
+        }
+        if let Some(balancer) = &self.balancer {
+            let Some(port) = self.port.filter(|_| !self.shared_port) else {
+                return Err(format!("Group '{}' needs a port range to balance over, one port per instance", name));
+            };
+            if (port as usize..port as usize + self.instances).contains(&(balancer.port as usize)) {
+                return Err(format!("Group '{}' balances on port {} which an instance already uses", name, balancer.port));
+            }
+        }
+        Ok(())
+    }
+
+    /// Port of the instance at `index`
+    pub fn port(&self, index: usize) -> Option<u16> {
+        match self.shared_port {
+            true => self.port,
+            false => self.port.map(|port| port + index as u16),
+        }
+    }
+
+    /// Instances a rolling reload replaces at once
+    pub fn max_unavailable(&self) -> usize {
+        self.max_unavailable.unwrap_or(super::rollout::DEFAULT_MAX_UNAVAILABLE)
+    }
+
+    /// Position and port of the instance at `index`
+    pub fn instance(&self, name: &str, index: usize) -> Instance {
+        Instance {
+            group: Some(name.to_string()),
+            id: index,
+            count: self.instances,
+            port: self.port(index),
+            shared_port: self.shared_port && self.port.is_some(),
+        }
+    }
+
+    /// Process name of the instance at `index`, e.g. "api-worker-1"
+    pub fn instance_name(name: &str, index: usize) -> String {
+        format!("{}-worker-{}", name, index + 1)
+    }
+
+    /// Ports as given to `--port-range`, for HCL export
+    pub fn port_range(&self) -> Option<String> {
+        let port = self.port?;
+        match self.shared_port || self.instances < 2 {
+            true => Some(port.to_string()),
+            false => Some(format!("{}-{}", port, port as usize + self.instances - 1)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_ports() {
+        let range = Group::new(3, &[3000, 3001, 3002]).unwrap();
+        assert_eq!(range.instance("api", 2).port, Some(3002));
+        assert_eq!(range.instance("api", 2).count, 3);
+        assert!(!range.instance("api", 2).shared_port);
+        assert_eq!(range.port_range().as_deref(), Some("3000-3002"));
+        assert_eq!(Group::instance_name("api", 0), "api-worker-1");
+
+        let shared = Group::new(4, &[3000]).unwrap();
+        assert_eq!(shared.instance("api", 3).port, Some(3000));
+        assert!(shared.instance("api", 3).shared_port);
+        assert_eq!(shared.port_range().as_deref(), Some("3000"));
+
+        assert!(Group::default().validate("api").is_err());
+        assert!(Group::new(2, &[3000, 3001, 3002]).is_err());
+        assert!(Group::new(2, &[65535]).unwrap().validate("api").is_ok());
+        assert!(Group::new(2, &[65535, 0]).unwrap().validate("api").is_err());
+        assert!(Group::new(2, &[]).unwrap().instance("api", 0).port.is_none());
+        assert_eq!(range.max_unavailable(), 1);
+        assert!(Group { max_unavailable: Some(0), ..range.clone() }.validate("api").is_err());
+
+        let balanced = |group: &Group, port| Group { balancer: Some(Balancer::parse(port).unwrap()), ..group.clone() };
+        assert!(balanced(&range, "8080").validate("api").is_ok());
+        assert!(balanced(&range, "3002").validate("api").is_err());
+        assert!(balanced(&shared, "8080").validate("api").is_err());
+    }
+}
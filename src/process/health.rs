@@ -0,0 +1,826 @@
+use super::Env;
+use chrono::{DateTime, Utc};
+use global_placeholders::global;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    net::{TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use utoipa::ToSchema;
+
+/// Time a process gets to become ready when timeout_ms is not set
+pub const DEFAULT_READY_TIMEOUT_MS: u64 = 30_000;
+
+/// Time a single probe may take when timeout_ms is not set
+pub const DEFAULT_PROBE_TIMEOUT_MS: u64 = 1000;
+
+/// Time between liveness probes when interval_ms is not set
+pub const DEFAULT_LIVENESS_INTERVAL_MS: u64 = 10_000;
+
+/// Consecutive failed liveness probes before the process is restarted
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// READY=1 notifications nobody asked for are forgotten after this long
+const NOTIFICATION_TTL_SECS: u64 = 600;
+
+/// PIDs that sent READY=1 to the notify socket, filled by the daemon's listener thread
+static NOTIFIED: Lazy<Mutex<HashMap<i64, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Set once this process listens on the notify socket, only the daemon does
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// Liveness probes running in the background, keyed by process id
+static PROBES: Lazy<Probes<Result<(), String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Readiness checks running in the background, keyed by process id
+static READINESS: Lazy<Probes<bool>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+type Probes<T> = Mutex<HashMap<usize, RunningProbe<T>>>;
+
+struct RunningProbe<T> {
+    /// PID of the instance being probed, results for an earlier instance are dropped
+    pid: i64,
+    handle: JoinHandle<T>,
+}
+
+/// Take the result of a finished probe of process `id` in `probes`, None while it runs or when none was started
+fn take_finished<T>(probes: &Probes<T>, id: usize, pid: i64) -> Option<std::thread::Result<T>> {
+    let mut probes = probes.lock().ok()?;
+    if !probes.get(&id)?.handle.is_finished() {
+        return None;
+    }
+
+    let probe = probes.remove(&id)?;
+    let result = probe.handle.join();
+    (probe.pid == pid).then_some(result)
+}
+
+/// What a started process has to do before it counts as online
+/// Exactly one of tcp, http, log or notify is set
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Readiness {
+    /// Address that must accept TCP connections, a bare port means 127.0.0.1
+    #[schema(example = "3000")]
+    #[serde(default)]
+    pub tcp: Option<String>,
+    /// URL that must answer a GET with a 2xx status
+    #[schema(example = "http://127.0.0.1:3000/health")]
+    #[serde(default)]
+    pub http: Option<String>,
+    /// Regex matched against the stdout lines written by this instance
+    #[schema(example = "listening on")]
+    #[serde(default)]
+    pub log: Option<String>,
+    /// Wait for READY=1 (or OPM_READY=1) on the socket in $NOTIFY_SOCKET, like sd_notify
+    #[serde(default)]
+    pub notify: bool,
+    /// Milliseconds before an instance that is not ready counts as a failed start
+    #[schema(example = 30000)]
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+impl Readiness {
+    /// Parse a check written as "tcp:3000", "http:<url>", "log:<regex>" or "notify"
+    pub fn parse(spec: &str, timeout_ms: Option<u64>) -> Result<Self, String> {
+        let (kind, value) = spec.split_once(':').unwrap_or((spec, ""));
+        let value = (!value.is_empty()).then(|| value.to_string());
+
+        let readiness = match kind.trim() {
+            "tcp" => Readiness { tcp: value, ..Default::default() },
+            "http" => Readiness { http: value, ..Default::default() },
+            "log" => Readiness { log: value, ..Default::default() },
+            "notify" if value.is_none() => Readiness { notify: true, ..Default::default() },
+            _ => {
+                return Err(format!(
+                    "Invalid readiness check: {}. Use 'tcp:<port>', 'http:<url>', 'log:<regex>' or 'notify'",
+                    spec
+                ))
+            }
+        };
+
+        let readiness = Readiness { timeout_ms, ..readiness };
+        readiness.validate()?;
+        Ok(readiness)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let checks = [self.tcp.is_some(), self.http.is_some(), self.log.is_some(), self.notify];
+        match checks.iter().filter(|set| **set).count() {
+            1 => {}
+            0 => return Err("Readiness check needs one of tcp, http, log or notify".to_string()),
+            _ => return Err("Readiness check takes only one of tcp, http, log or notify".to_string()),
+        }
+
+        if let Some(address) = &self.tcp {
+            if tcp_address(address).is_none() {
+                return Err(format!("Invalid readiness tcp address: {}", address));
+            }
+        }
+        if let Some(url) = &self.http {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("Invalid readiness url: {}. Use an http:// or https:// URL", url));
+            }
+        }
+        if let Some(pattern) = &self.log {
+            Regex::new(pattern).map_err(|err| format!("Invalid readiness log regex: {}", err))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.unwrap_or(DEFAULT_READY_TIMEOUT_MS))
+    }
+
+    /// One-line summary such as "tcp 3000 (30000ms)"
+    pub fn describe(&self) -> String {
+        let check = match (&self.tcp, &self.http, &self.log) {
+            (Some(address), _, _) => format!("tcp {}", address),
+            (_, Some(url), _) => format!("http {}", url),
+            (_, _, Some(pattern)) => format!("log /{}/", pattern),
+            _ => "notify".to_string(),
+        };
+        format!("{} ({}ms)", check, self.timeout().as_millis())
+    }
+
+    /// Run the check once; `log_path`/`log_offset` locate this instance's output,
+    /// `pids` are the processes whose notifications count
+    pub fn probe(&self, log_path: &str, log_offset: u64, pids: &[i64]) -> bool {
+        let timeout = Duration::from_millis(DEFAULT_PROBE_TIMEOUT_MS);
+        if let Some(address) = &self.tcp {
+            return tcp_probe(address, timeout).is_ok();
+        }
+        if let Some(url) = &self.http {
+            return http_probe(url, timeout).is_ok();
+        }
+        if let Some(pattern) = &self.log {
+            return Regex::new(pattern).is_ok_and(|regex| log_ready(log_path, log_offset, &regex));
+        }
+        self.notify && take_notification(pids)
+    }
+
+    /// Start a check of instance `pid` on a background thread unless one is still running for `id`,
+    /// so a slow TCP or HTTP check does not hold up the caller
+    pub fn spawn(&self, id: usize, pid: i64, log_path: String, log_offset: u64, pids: Vec<i64>) {
+        let Ok(mut checks) = READINESS.lock() else {
+            return;
+        };
+        if checks.contains_key(&id) {
+            return;
+        }
+
+        let readiness = self.clone();
+        let spawned = std::thread::Builder::new()
+            .name(format!("readiness-{}", id))
+            .spawn(move || readiness.probe(&log_path, log_offset, &pids));
+
+        match spawned {
+            Ok(handle) => {
+                checks.insert(id, RunningProbe { pid, handle });
+            }
+            Err(err) => log::error!("[health] failed to start readiness check for {}: {}", id, err),
+        }
+    }
+}
+
+/// Result of a finished readiness check of process `id`, None while it runs or when none was started
+/// A result for an instance other than `pid` is discarded
+pub fn finished_readiness(id: usize, pid: i64) -> Option<bool> {
+    take_finished(&READINESS, id, pid).map(|result| result.unwrap_or(false))
+}
+
+/// Readiness state of an instance that has not passed its check yet
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Launch {
+    /// Size of the stdout log when the instance was spawned, the log check only reads past it
+    pub log_offset: u64,
+}
+
+impl Launch {
+    /// Record where the instance's output will start, taken before spawning
+    pub fn begin(stdout_path: &str) -> Self {
+        Launch {
+            log_offset: std::fs::metadata(stdout_path).map(|meta| meta.len()).unwrap_or(0),
+        }
+    }
+}
+
+/// Periodic check of a running process, restarting it after repeated failures
+/// Exactly one of http, tcp or exec is set
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Liveness {
+    /// URL that must answer a GET with a 2xx status
+    #[schema(example = "http://127.0.0.1:3000/health")]
+    #[serde(default)]
+    pub http: Option<String>,
+    /// Address that must accept TCP connections, a bare port means 127.0.0.1
+    #[schema(example = "3000")]
+    #[serde(default)]
+    pub tcp: Option<String>,
+    /// Shell command run in the process working directory that must exit with 0
+    #[schema(example = "./healthcheck.sh")]
+    #[serde(default)]
+    pub exec: Option<String>,
+    /// Milliseconds between probes
+    #[schema(example = 10000)]
+    #[serde(default)]
+    pub interval_ms: Option<u64>,
+    /// Milliseconds a single probe may take before it counts as failed
+    #[schema(example = 1000)]
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Consecutive failures that restart the process
+    #[schema(example = 3)]
+    #[serde(default)]
+    pub failure_threshold: Option<u32>,
+}
+
+impl Liveness {
+    /// Parse a probe written as "http:<url>", "tcp:<port>" or "exec:<command>"
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (kind, value) = spec.split_once(':').unwrap_or((spec, ""));
+        let value = (!value.is_empty()).then(|| value.to_string());
+
+        let liveness = match kind.trim() {
+            "http" => Liveness { http: value, ..Default::default() },
+            "tcp" => Liveness { tcp: value, ..Default::default() },
+            "exec" => Liveness { exec: value, ..Default::default() },
+            _ => {
+                return Err(format!(
+                    "Invalid liveness probe: {}. Use 'http:<url>', 'tcp:<port>' or 'exec:<command>'",
+                    spec
+                ))
+            }
+        };
+
+        liveness.validate()?;
+        Ok(liveness)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let probes = [self.http.is_some(), self.tcp.is_some(), self.exec.is_some()];
+        match probes.iter().filter(|set| **set).count() {
+            1 => {}
+            0 => return Err("Liveness probe needs one of http, tcp or exec".to_string()),
+            _ => return Err("Liveness probe takes only one of http, tcp or exec".to_string()),
+        }
+
+        if let Some(address) = &self.tcp {
+            if tcp_address(address).is_none() {
+                return Err(format!("Invalid liveness tcp address: {}", address));
+            }
+        }
+        if let Some(url) = &self.http {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("Invalid liveness url: {}. Use an http:// or https:// URL", url));
+            }
+        }
+        if self.interval_ms == Some(0) {
+            return Err("Liveness interval_ms must be greater than 0".to_string());
+        }
+        if self.failure_threshold == Some(0) {
+            return Err("Liveness failure_threshold must be greater than 0".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.unwrap_or(DEFAULT_LIVENESS_INTERVAL_MS))
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms.unwrap_or(DEFAULT_PROBE_TIMEOUT_MS))
+    }
+
+    pub fn failure_threshold(&self) -> u32 {
+        self.failure_threshold.unwrap_or(DEFAULT_FAILURE_THRESHOLD)
+    }
+
+    /// One-line summary such as "http http://127.0.0.1/health (every 10000ms, 1000ms timeout, 3 failures)"
+    pub fn describe(&self) -> String {
+        let probe = match (&self.http, &self.tcp, &self.exec) {
+            (Some(url), _, _) => format!("http {}", url),
+            (_, Some(address), _) => format!("tcp {}", address),
+            (_, _, Some(command)) => format!("exec `{}`", command),
+            _ => "none".to_string(),
+        };
+        format!(
+            "{} (every {}ms, {}ms timeout, {} failures)",
+            probe,
+            self.interval().as_millis(),
+            self.timeout().as_millis(),
+            self.failure_threshold()
+        )
+    }
+
+    /// Run the probe once, an exec probe runs `shell -c <command>` in `cwd` with `env`
+    pub fn check(&self, shell: &str, cwd: &Path, env: &Env) -> Result<(), String> {
+        let timeout = self.timeout();
+        match (&self.http, &self.tcp, &self.exec) {
+            (Some(url), _, _) => http_probe(url, timeout),
+            (_, Some(address), _) => tcp_probe(address, timeout),
+            (_, _, Some(command)) => exec_probe(command, shell, cwd, env, timeout),
+            _ => Ok(()),
+        }
+    }
+
+    /// Start a probe of instance `pid` on a background thread unless one is still running for `id`
+    pub fn spawn(&self, id: usize, pid: i64, shell: String, cwd: PathBuf, env: Env) {
+        let Ok(mut probes) = PROBES.lock() else {
+            return;
+        };
+        if probes.contains_key(&id) {
+            return;
+        }
+
+        let liveness = self.clone();
+        let spawned = std::thread::Builder::new()
+            .name(format!("liveness-{}", id))
+            .spawn(move || liveness.check(&shell, &cwd, &env));
+
+        match spawned {
+            Ok(handle) => {
+                probes.insert(id, RunningProbe { pid, handle });
+            }
+            Err(err) => log::error!("[health] failed to start liveness probe for {}: {}", id, err),
+        }
+    }
+}
+
+/// Take the result of a finished probe of process `id`, None while it runs or when none was started
+/// A result for an instance other than `pid` is discarded
+pub fn finished_probe(id: usize, pid: i64) -> Option<Result<(), String>> {
+    take_finished(&PROBES, id, pid).map(|result| result.unwrap_or_else(|_| Err("liveness probe panicked".to_string())))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// No probe configured, or none finished yet for this instance
+    #[default]
+    Unknown,
+    Healthy,
+    Unhealthy,
+}
+
+impl std::fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HealthStatus::Unknown => write!(f, "unknown"),
+            HealthStatus::Healthy => write!(f, "healthy"),
+            HealthStatus::Unhealthy => write!(f, "unhealthy"),
+        }
+    }
+}
+
+/// Liveness probe results of the current instance
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Health {
+    pub status: HealthStatus,
+    /// Failed probes since the last successful one
+    pub consecutive_failures: u32,
+    /// When the last probe finished
+    #[schema(value_type = Option<String>, example = "2024-01-01T00:00:00Z")]
+    pub last_check: Option<DateTime<Utc>>,
+    /// Why the last probe failed, cleared by a successful probe
+    pub last_error: Option<String>,
+}
+
+impl Health {
+    /// Record a probe result, true when it makes the failures reach `threshold`
+    pub fn record(&mut self, result: Result<(), String>, threshold: u32) -> bool {
+        self.last_check = Some(Utc::now());
+        match result {
+            Ok(()) => {
+                self.status = HealthStatus::Healthy;
+                self.consecutive_failures = 0;
+                self.last_error = None;
+                false
+            }
+            Err(err) => {
+                self.consecutive_failures += 1;
+                self.last_error = Some(err);
+                if self.consecutive_failures >= threshold {
+                    self.status = HealthStatus::Unhealthy;
+                }
+                self.consecutive_failures == threshold
+            }
+        }
+    }
+}
+
+fn tcp_address(address: &str) -> Option<String> {
+    let address = match address.parse::<u16>() {
+        Ok(port) => format!("127.0.0.1:{}", port),
+        Err(_) => address.to_string(),
+    };
+    address.to_socket_addrs().ok()?.next().map(|_| address)
+}
+
+fn tcp_probe(address: &str, timeout: Duration) -> Result<(), String> {
+    let address = tcp_address(address).ok_or_else(|| format!("cannot resolve {}", address))?;
+    let mut last_error = format!("no address for {}", address);
+    for addr in address.to_socket_addrs().map_err(|err| err.to_string())? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(_) => return Ok(()),
+            Err(err) => last_error = format!("connect to {} failed: {}", addr, err),
+        }
+    }
+    Err(last_error)
+}
+
+fn http_probe(url: &str, timeout: Duration) -> Result<(), String> {
+    let url = url.to_string();
+    // The blocking client must not run on a thread that has entered the daemon's tokio runtime
+    std::thread::spawn(move || {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .build()
+            .map_err(|err| err.to_string())?;
+        let response = client.get(&url).send().map_err(|err| format!("GET {} failed: {}", url, err))?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(format!("GET {} returned {}", url, status)),
+        }
+    })
+    .join()
+    .unwrap_or_else(|_| Err("http probe panicked".to_string()))
+}
+
+fn exec_probe(command: &str, shell: &str, cwd: &Path, env: &Env, timeout: Duration) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+
+    let mut child = Command::new(shell)
+        .arg("-c")
+        .arg(command)
+        .current_dir(cwd)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()
+        .map_err(|err| format!("failed to run `{}`: {}", command, err))?;
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok(()),
+            Ok(Some(status)) => return Err(format!("`{}` exited with {}", command, status)),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+            Ok(None) => {
+                // The command may have started children of its own, take down its whole group
+                unsafe { libc::kill(-(child.id() as i32), libc::SIGKILL) };
+                let _ = child.wait();
+                return Err(format!("`{}` timed out after {}ms", command, timeout.as_millis()));
+            }
+            Err(err) => return Err(format!("failed to wait for `{}`: {}", command, err)),
+        }
+    }
+}
+
+fn log_ready(path: &str, offset: u64, regex: &Regex) -> bool {
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return false;
+    };
+    // A truncated log starts over, anything in it was written by this instance
+    let len = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+    let offset = if len < offset { 0 } else { offset };
+
+    let mut contents = String::new();
+    if file.seek(SeekFrom::Start(offset)).is_err() || file.read_to_string(&mut contents).is_err() {
+        return false;
+    }
+    contents.lines().any(|line| regex.is_match(line))
+}
+
+/// Consume a READY=1 notification sent by any of `pids`
+/// Outside of the daemon (e.g. a rolling reload run by the CLI) the daemon is asked for it
+pub fn take_notification(pids: &[i64]) -> bool {
+    if !LISTENING.load(Ordering::Relaxed) {
+        let request = crate::socket::SocketRequest::TakeNotification(pids.to_vec());
+        return matches!(
+            crate::socket::send_request(&global!("opm.socket"), request),
+            Ok(crate::socket::SocketResponse::Notified(true))
+        );
+    }
+
+    let Ok(mut notified) = NOTIFIED.lock() else {
+        return false;
+    };
+    let found = pids.iter().any(|pid| notified.contains_key(pid));
+    if found {
+        for pid in pids {
+            notified.remove(pid);
+        }
+    }
+    found
+}
+
+fn record_notification(message: &str, sender: Option<i64>) {
+    let ready = message
+        .lines()
+        .any(|line| matches!(line.trim(), "READY=1" | "OPM_READY=1" | "OPM_READY"));
+    if !ready {
+        return;
+    }
+
+    let main_pid = message
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("MAINPID="))
+        .and_then(|pid| pid.parse::<i64>().ok());
+
+    // A helper such as systemd-notify exits right away, so its ancestors are recorded while they can
+    // still be looked up; the managed process is one of them
+    let senders: Vec<i64> = sender.into_iter().chain(main_pid).flat_map(ancestors).collect();
+
+    if let Ok(mut notified) = NOTIFIED.lock() {
+        notified.retain(|_, at| at.elapsed() < Duration::from_secs(NOTIFICATION_TTL_SECS));
+        for pid in senders {
+            log::debug!("[health] ready notification from pid {}", pid);
+            notified.insert(pid, Instant::now());
+        }
+    }
+}
+
+/// `pid` followed by its parents, stopping below init and the daemon itself
+fn ancestors(pid: i64) -> Vec<i64> {
+    let daemon = std::process::id() as i64;
+    let mut chain = vec![pid];
+    let mut current = pid;
+    while let Ok(Some(parent)) = super::unix::process_info::get_parent_pid(current as i32) {
+        let parent = parent as i64;
+        if parent <= 1 || parent == daemon || chain.contains(&parent) {
+            break;
+        }
+        chain.push(parent);
+        current = parent;
+    }
+    chain
+}
+
+/// Receive one datagram and the PID of its sender (from SCM_CREDENTIALS)
+#[cfg(target_os = "linux")]
+fn receive(fd: std::os::unix::io::RawFd) -> std::io::Result<(String, Option<i64>)> {
+    let mut data = [0u8; 4096];
+    let mut control = [0u8; 64];
+    let mut iov = libc::iovec {
+        iov_base: data.as_mut_ptr() as *mut libc::c_void,
+        iov_len: data.len(),
+    };
+
+    let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = control.len() as _;
+
+    let received = unsafe { libc::recvmsg(fd, &mut message, 0) };
+    if received < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut sender = None;
+    unsafe {
+        let mut header = libc::CMSG_FIRSTHDR(&message);
+        while !header.is_null() {
+            if (*header).cmsg_level == libc::SOL_SOCKET && (*header).cmsg_type == libc::SCM_CREDENTIALS {
+                let credentials = std::ptr::read_unaligned(libc::CMSG_DATA(header) as *const libc::ucred);
+                sender = Some(credentials.pid as i64);
+            }
+            header = libc::CMSG_NXTHDR(&message, header);
+        }
+    }
+
+    Ok((String::from_utf8_lossy(&data[..received as usize]).into_owned(), sender))
+}
+
+/// Without SCM_CREDENTIALS the sender has to identify itself with MAINPID=<pid>
+#[cfg(not(target_os = "linux"))]
+fn receive(fd: std::os::unix::io::RawFd) -> std::io::Result<(String, Option<i64>)> {
+    let mut data = [0u8; 4096];
+    let received = unsafe { libc::recv(fd, data.as_mut_ptr() as *mut libc::c_void, data.len(), 0) };
+    if received < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok((String::from_utf8_lossy(&data[..received as usize]).into_owned(), None))
+}
+
+/// Bind the notify socket and collect READY=1 notifications on a background thread
+pub fn listen(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::{fs::PermissionsExt, io::AsRawFd, net::UnixDatagram};
+
+    let _ = std::fs::remove_file(path);
+    let socket = UnixDatagram::bind(path)?;
+    // Processes may run as another user and still have to reach the socket
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o666))?;
+
+    #[cfg(target_os = "linux")]
+    unsafe {
+        let enable: libc::c_int = 1;
+        if libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PASSCRED,
+            &enable as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        ) != 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    std::thread::Builder::new()
+        .name("notify-socket".to_string())
+        .spawn(move || loop {
+            match receive(socket.as_raw_fd()) {
+                Ok((message, sender)) => record_notification(&message, sender),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    log::error!("[health] notify socket failed: {}", err);
+                    break;
+                }
+            }
+        })?;
+    LISTENING.store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_readiness() {
+        let readiness = Readiness::parse("tcp:3000", None).unwrap();
+        assert_eq!(readiness.tcp.as_deref(), Some("3000"));
+        assert_eq!(readiness.describe(), "tcp 3000 (30000ms)");
+
+        let readiness = Readiness::parse("http:http://127.0.0.1:8080/health", Some(5000)).unwrap();
+        assert_eq!(readiness.http.as_deref(), Some("http://127.0.0.1:8080/health"));
+        assert_eq!(readiness.timeout(), Duration::from_millis(5000));
+
+        assert!(Readiness::parse("notify", None).unwrap().notify);
+        assert_eq!(Readiness::parse("log:listening on :\\d+", None).unwrap().log.as_deref(), Some("listening on :\\d+"));
+
+        assert!(Readiness::parse("tcp", None).is_err());
+        assert!(Readiness::parse("log:(", None).is_err());
+        assert!(Readiness::parse("http:localhost", None).is_err());
+        assert!(Readiness::parse("udp:53", None).is_err());
+        assert!(Readiness { tcp: Some("1".into()), notify: true, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_tcp_readiness() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let readiness = Readiness::parse(&format!("tcp:{}", port), None).unwrap();
+        assert!(readiness.probe("", 0, &[]));
+
+        drop(listener);
+        assert!(!readiness.probe("", 0, &[]));
+    }
+
+    #[test]
+    fn test_background_readiness() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let readiness = Readiness::parse(&format!("tcp:{}", listener.local_addr().unwrap().port()), None).unwrap();
+        let finished = |pid| {
+            (0..100).find_map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                finished_readiness(usize::MAX, pid)
+            })
+        };
+
+        assert_eq!(finished_readiness(usize::MAX, 1), None);
+        readiness.spawn(usize::MAX, 1, String::new(), 0, vec![]);
+        assert_eq!(finished(1), Some(true));
+
+        // The check of a replaced instance does not count for the new one
+        readiness.spawn(usize::MAX, 1, String::new(), 0, vec![]);
+        assert_eq!(finished(2), None);
+    }
+
+    #[test]
+    fn test_log_readiness_reads_past_offset() {
+        let path = std::env::temp_dir().join(format!("opm-ready-log-{}", std::process::id()));
+        std::fs::write(&path, "listening on 3000\n").unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        let launch = Launch::begin(&path);
+        let readiness = Readiness::parse("log:listening on \\d+", None).unwrap();
+        assert!(!readiness.probe(&path, launch.log_offset, &[]), "lines from the previous run must not count");
+
+        std::fs::write(&path, "listening on 3000\nstarting\nlistening on 3001\n").unwrap();
+        assert!(readiness.probe(&path, launch.log_offset, &[]));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_liveness() {
+        let liveness = Liveness::parse("http:http://127.0.0.1:3000/health").unwrap();
+        assert_eq!(liveness.http.as_deref(), Some("http://127.0.0.1:3000/health"));
+        assert_eq!(liveness.interval(), Duration::from_millis(DEFAULT_LIVENESS_INTERVAL_MS));
+        assert_eq!(liveness.failure_threshold(), DEFAULT_FAILURE_THRESHOLD);
+
+        let liveness = Liveness::parse("exec:test -f /tmp/ok").unwrap();
+        assert_eq!(liveness.describe(), "exec `test -f /tmp/ok` (every 10000ms, 1000ms timeout, 3 failures)");
+        assert_eq!(Liveness::parse("tcp:8080").unwrap().tcp.as_deref(), Some("8080"));
+
+        assert!(Liveness::parse("exec").is_err());
+        assert!(Liveness::parse("log:ready").is_err());
+        assert!(Liveness { exec: Some("true".into()), interval_ms: Some(0), ..Default::default() }.validate().is_err());
+        assert!(Liveness { exec: Some("true".into()), failure_threshold: Some(0), ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_health_record_threshold() {
+        let mut health = Health::default();
+        assert!(!health.record(Err("refused".into()), 2));
+        assert_eq!(health.status, HealthStatus::Unknown);
+        assert!(health.record(Err("refused".into()), 2), "second failure reaches the threshold");
+        assert_eq!(health.status, HealthStatus::Unhealthy);
+        assert!(!health.record(Err("refused".into()), 2), "the threshold is only reported once");
+
+        assert!(!health.record(Ok(()), 2));
+        assert_eq!(health.status, HealthStatus::Healthy);
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.last_error, None);
+    }
+
+    #[test]
+    fn test_exec_liveness() {
+        let env = Env::from([("HEALTH_FILE".to_string(), "present".to_string())]);
+        let cwd = std::env::temp_dir();
+        let check = |command: &str, timeout_ms| {
+            Liveness { exec: Some(command.into()), timeout_ms: Some(timeout_ms), ..Default::default() }.check("sh", &cwd, &env)
+        };
+
+        assert!(check("test \"$HEALTH_FILE\" = present", 1000).is_ok());
+        assert!(check("exit 3", 1000).unwrap_err().contains("exited with"));
+
+        let started = Instant::now();
+        assert!(check("sleep 5", 100).unwrap_err().contains("timed out after 100ms"));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_liveness_probe_in_background() {
+        let liveness = Liveness { exec: Some("true".into()), ..Default::default() };
+        let id = usize::MAX - std::process::id() as usize;
+
+        liveness.spawn(id, 100, "sh".into(), std::env::temp_dir(), Env::new());
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let result = loop {
+            if let Some(result) = finished_probe(id, 100) {
+                break result;
+            }
+            assert!(Instant::now() < deadline, "probe did not finish");
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert!(result.is_ok());
+
+        // A result for a previous instance is dropped
+        liveness.spawn(id, 100, "sh".into(), std::env::temp_dir(), Env::new());
+        std::thread::sleep(Duration::from_millis(300));
+        assert_eq!(finished_probe(id, 200), None);
+        assert_eq!(finished_probe(id, 100), None, "the stale result was consumed");
+    }
+
+    #[test]
+    fn test_notify_readiness() {
+        let path = std::env::temp_dir().join(format!("opm-notify-{}.sock", std::process::id()));
+        listen(&path).unwrap();
+
+        let pid = std::process::id() as i64;
+        let readiness = Readiness::parse("notify", None).unwrap();
+        assert!(!readiness.probe("", 0, &[pid]));
+
+        let client = std::os::unix::net::UnixDatagram::unbound().unwrap();
+        client.send_to(b"STATUS=warming up\n", &path).unwrap();
+        client.send_to(b"READY=1\n", &path).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !readiness.probe("", 0, &[pid]) {
+            assert!(Instant::now() < deadline, "READY=1 from this process was not recorded");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        // A notification is consumed by the check that saw it
+        assert!(!readiness.probe("", 0, &[pid]));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::process::Remote;
+use crate::process::{capture::Capture, deps::Dependency, environment::Environment, health::{Liveness, Readiness}, instance::Instance, listen::Listen, job::Job, restart::{Backoff, RestartPolicy}, rotation::Rotation, threshold::Thresholds, unix::rlimits::Limits, watch::Watch, Exec, ExecMode, KillMode, Remote};
 use macros_rs::{fmtstr, string};
 use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_ENCODING};
 use reqwest::Client;
@@ -20,7 +20,36 @@ struct CreateBody<'c> {
     pub name: &'c String,
     pub script: &'c String,
     pub path: PathBuf,
-    pub watch: &'c Option<String>,
+    pub watch: &'c Option<Watch>,
+    pub max_memory: Option<String>,
+    pub thresholds: &'c Thresholds,
+    pub log_rotation: &'c Option<Rotation>,
+    pub log_capture: &'c Option<Capture>,
+    pub cwd: &'c Option<PathBuf>,
+    pub user: &'c Option<String>,
+    pub group: &'c Option<String>,
+    pub umask: Option<String>,
+    pub memory_max: Option<String>,
+    pub cpu_max: Option<String>,
+    pub pids_max: Option<u64>,
+    pub limits: &'c Limits,
+    pub exec_mode: Option<ExecMode>,
+    pub stop_signal: &'c Option<String>,
+    pub kill_timeout_ms: Option<u64>,
+    pub kill_mode: KillMode,
+    pub ready: &'c Option<Readiness>,
+    pub liveness: &'c Option<Liveness>,
+    pub restart_policy: RestartPolicy,
+    pub success_exit_codes: &'c Vec<i32>,
+    pub max_restarts: Option<u64>,
+    pub min_uptime_ms: Option<u64>,
+    pub backoff: &'c Backoff,
+    pub cron_restart: &'c Option<String>,
+    pub job: &'c Option<Job>,
+    pub depends_on: &'c Vec<Dependency>,
+    pub listen: &'c Vec<Listen>,
+    pub environment: &'c Environment,
+    pub instance: &'c Option<Instance>,
 }
 
 pub mod sync {
@@ -78,10 +107,11 @@ pub fn logs(
     Remote { address, token, .. }: &Remote,
     id: usize,
     kind: &str,
+    lines: usize,
 ) -> Result<LogResponse, anyhow::Error> {
     let (client, headers) = sync::client(token);
     let response = client
-        .get(fmtstr!("{address}/process/{id}/logs/{kind}/raw"))
+        .get(fmtstr!("{address}/process/{id}/logs/{kind}/raw?lines={lines}"))
         .headers(headers)
         .send()?;
     let log = response.text()?;
@@ -108,7 +138,9 @@ pub fn create(
     name: &String,
     script: &String,
     path: PathBuf,
-    watch: &Option<String>,
+    watch: &Option<Watch>,
+    max_memory: u64,
+    exec: &Exec,
 ) -> Result<sync::Response, anyhow::Error> {
     let (client, headers) = sync::client(token);
     let content = CreateBody {
@@ -116,6 +148,35 @@ pub fn create(
         script,
         path,
         watch,
+        max_memory: (max_memory > 0).then(|| max_memory.to_string()),
+        thresholds: &exec.thresholds,
+        log_rotation: &exec.log_rotation,
+        log_capture: &exec.log_capture,
+        cwd: &exec.cwd,
+        user: &exec.user,
+        group: &exec.group,
+        umask: exec.format_umask(),
+        memory_max: exec.resources.memory_max.map(|bytes| bytes.to_string()),
+        cpu_max: exec.resources.format_cpu_max(),
+        pids_max: exec.resources.pids_max,
+        limits: &exec.limits,
+        exec_mode: exec.mode,
+        stop_signal: &exec.stop.stop_signal,
+        kill_timeout_ms: exec.stop.kill_timeout_ms,
+        kill_mode: exec.stop.kill_mode,
+        ready: &exec.ready,
+        liveness: &exec.liveness,
+        restart_policy: exec.restart.policy,
+        success_exit_codes: &exec.restart.success_exit_codes,
+        max_restarts: exec.restart.max_restarts,
+        min_uptime_ms: exec.restart.min_uptime_ms,
+        backoff: &exec.restart.backoff,
+        cron_restart: &exec.cron_restart,
+        job: &exec.job,
+        depends_on: &exec.depends_on,
+        listen: &exec.listen,
+        environment: &exec.environment,
+        instance: &exec.instance,
     };
 
     Ok(client
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+use utoipa::ToSchema;
+
+use super::Env;
+
+/// Descriptor a worker finds the shared listening socket on
+pub const LISTEN_FD: RawFd = 3;
+
+/// Pending connections the kernel queues on a shared socket before the worker accepts them
+const LISTEN_BACKLOG: libc::c_int = 511;
+
+/// Position of a worker started with `--workers`, exported to it as environment variables
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Instance {
+    /// Group the worker belongs to, see Runner::groups
+    #[schema(example = "api")]
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Zero-based index of the worker, exported as OPM_INSTANCE_ID and NODE_APP_INSTANCE
+    #[schema(example = 0)]
+    pub id: usize,
+    /// Number of workers started together, exported as OPM_INSTANCES
+    #[schema(example = 4)]
+    pub count: usize,
+    /// Port the worker serves on, exported as PORT
+    #[schema(example = 3000)]
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// All workers serve on `port`, each receives a socket bound with SO_REUSEPORT on fd 3
+    #[serde(default)]
+    pub shared_port: bool,
+}
+
+impl Instance {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id >= self.count {
+            return Err(format!("Instance id {} is out of range for {} instances", self.id, self.count));
+        }
+        if self.shared_port && self.port.is_none() {
+            return Err("A shared instance port needs a port number".to_string());
+        }
+        Ok(())
+    }
+
+    /// Variables the worker is started with, these take priority over the stored environment
+    pub fn env(&self) -> Env {
+        let mut env = Env::from([
+            ("OPM_INSTANCE_ID".to_string(), self.id.to_string()),
+            ("NODE_APP_INSTANCE".to_string(), self.id.to_string()),
+            ("OPM_INSTANCES".to_string(), self.count.to_string()),
+        ]);
+        if let Some(port) = self.port {
+            env.insert("PORT".to_string(), port.to_string());
+        }
+        if self.shared_port {
+            env.insert("OPM_LISTEN_FD".to_string(), LISTEN_FD.to_string());
+        }
+        env
+    }
+
+    /// One-line summary such as "2 of 4 in group api, PORT=3001"
+    pub fn describe(&self) -> String {
+        let position = match &self.group {
+            Some(group) => format!("{} of {} in group {}", self.id + 1, self.count, group),
+            None => format!("{} of {}", self.id + 1, self.count),
+        };
+        match (self.port, self.shared_port) {
+            (Some(port), true) => format!("{}, PORT={} shared via SO_REUSEPORT on fd {}", position, port, LISTEN_FD),
+            (Some(port), false) => format!("{}, PORT={}", position, port),
+            (None, _) => position,
+        }
+    }
+}
+
+/// Bind a listening TCP socket on all interfaces with SO_REUSEPORT set
+/// Every worker gets its own socket on the same port and the kernel spreads new connections across them
+pub fn bind_shared(port: u16) -> Result<OwnedFd, String> {
+    let failed = |action: &str| format!("Failed to {} shared port {}: {}", action, port, std::io::Error::last_os_error());
+
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(failed("create a socket for"));
+    }
+    // Closed on every early return below
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let enable: libc::c_int = 1;
+    for option in [libc::SO_REUSEADDR, libc::SO_REUSEPORT] {
+        let set = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                option,
+                &enable as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if set != 0 {
+            return Err(failed("set socket options on"));
+        }
+    }
+
+    let mut address: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    address.sin_family = libc::AF_INET as libc::sa_family_t;
+    address.sin_port = port.to_be();
+    address.sin_addr.s_addr = libc::INADDR_ANY.to_be();
+
+    let bound = unsafe {
+        libc::bind(
+            fd,
+            &address as *const libc::sockaddr_in as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+        )
+    };
+    if bound != 0 {
+        return Err(failed("bind"));
+    }
+    if unsafe { libc::listen(fd, LISTEN_BACKLOG) } != 0 {
+        return Err(failed("listen on"));
+    }
+
+    Ok(socket)
+}
+
+/// Place the shared socket on LISTEN_FD in the child, called between fork and exec
+pub fn inherit(fd: RawFd) -> std::io::Result<()> {
+    let result = match fd == LISTEN_FD {
+        // dup2 onto itself keeps close-on-exec, so clear the flag directly
+        true => unsafe { libc::fcntl(fd, libc::F_SETFD, 0) },
+        false => unsafe { libc::dup2(fd, LISTEN_FD) },
+    };
+    match result < 0 {
+        true => Err(std::io::Error::last_os_error()),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instance_env_and_shared_port() {
+        let instance = Instance { id: 1, count: 4, port: Some(3001), ..Default::default() };
+        let env = instance.env();
+        assert_eq!(env["PORT"], "3001");
+        assert_eq!(env["OPM_INSTANCE_ID"], "1");
+        assert_eq!(env["NODE_APP_INSTANCE"], "1");
+        assert_eq!(env["OPM_INSTANCES"], "4");
+        assert!(!env.contains_key("OPM_LISTEN_FD"));
+        assert_eq!(instance.describe(), "2 of 4, PORT=3001");
+        assert!(Instance { id: 4, count: 4, ..Default::default() }.validate().is_err());
+        assert!(Instance { count: 2, shared_port: true, ..Default::default() }.validate().is_err());
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let first = bind_shared(port).unwrap();
+        let second = bind_shared(port).unwrap();
+        drop((first, second));
+
+        let taken = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+        assert!(bind_shared(taken.local_addr().unwrap().port()).is_err());
+    }
+}
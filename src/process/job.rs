@@ -0,0 +1,141 @@
+use super::{
+    restart::{Exit, Restart},
+    schedule,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Runs kept in the history of a job, the oldest are dropped first
+pub const RUN_HISTORY_LIMIT: usize = 20;
+
+/// Makes a process a job that runs once or on a cron schedule, instead of being kept running
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Job {
+    /// Cron expression in local time, the job runs once when unset
+    #[schema(example = "*/15 * * * *")]
+    #[serde(default)]
+    pub cron: Option<String>,
+    /// Milliseconds a run may take before it is killed
+    #[schema(example = 600000)]
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+impl Job {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(cron) = &self.cron {
+            schedule::validate(cron)?;
+        }
+        if self.timeout_ms == Some(0) {
+            return Err("Job timeout_ms must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_ms.map(Duration::from_millis)
+    }
+
+    /// One-line summary such as "cron `0 3 * * *`, 600000ms timeout"
+    pub fn describe(&self) -> String {
+        let schedule = match &self.cron {
+            Some(cron) => format!("cron `{}`", cron),
+            None => "once".to_string(),
+        };
+        match self.timeout_ms {
+            Some(timeout) => format!("{}, {}ms timeout", schedule, timeout),
+            None => format!("{}, no timeout", schedule),
+        }
+    }
+}
+
+/// One run of a job
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct JobRun {
+    #[schema(value_type = String, example = "2026-01-01T03:00:00Z")]
+    pub started: DateTime<Utc>,
+    /// Unset while the run is in progress
+    #[schema(value_type = Option<String>, example = "2026-01-01T03:00:05Z")]
+    #[serde(default)]
+    pub finished: Option<DateTime<Utc>>,
+    #[schema(example = 5000)]
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// None while running, or when the exit status could not be collected
+    #[serde(default)]
+    pub exit: Option<Exit>,
+    /// The run was killed for exceeding timeout_ms
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+impl JobRun {
+    pub fn begin() -> Self {
+        JobRun {
+            started: Utc::now(),
+            finished: None,
+            duration_ms: None,
+            exit: None,
+            timed_out: false,
+        }
+    }
+
+    pub fn finish(&mut self, exit: Option<Exit>, timed_out: bool) {
+        let finished = Utc::now();
+        self.finished = Some(finished);
+        self.duration_ms = Some((finished - self.started).num_milliseconds().max(0) as u64);
+        self.exit = exit;
+        self.timed_out = timed_out;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.finished.is_none()
+    }
+
+    /// Finished in time with an exit the restart configuration counts as a success
+    pub fn succeeded(&self, restart: &Restart) -> bool {
+        !self.is_running() && !self.timed_out && self.exit.as_ref().is_some_and(|exit| restart.is_success(exit))
+    }
+}
+
+impl std::fmt::Display for JobRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let Some(duration) = self.duration_ms else {
+            return write!(f, "running since {}", self.started.to_rfc3339());
+        };
+        match (&self.exit, self.timed_out) {
+            (_, true) => write!(f, "timed out after {}ms", duration),
+            (Some(exit), false) => write!(f, "{} after {}ms", exit, duration),
+            (None, false) => write!(f, "ended after {}ms", duration),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_validation_and_runs() {
+        assert!(Job::default().validate().is_ok());
+        assert!(Job { cron: Some("0 3 * * *".to_string()), timeout_ms: Some(1000) }.validate().is_ok());
+        assert!(Job { cron: Some("0 25 * * *".to_string()), ..Default::default() }.validate().is_err());
+        assert!(Job { timeout_ms: Some(0), ..Default::default() }.validate().is_err());
+        assert_eq!(Job::default().describe(), "once, no timeout");
+
+        let mut run = JobRun::begin();
+        assert!(run.is_running());
+        assert!(run.to_string().starts_with("running since"));
+
+        run.started -= chrono::Duration::milliseconds(1500);
+        run.finish(Some(Exit { code: Some(0), signal: None, core_dumped: false }), false);
+        assert!(!run.is_running());
+        assert!(run.duration_ms.unwrap() >= 1500);
+        assert!(run.to_string().starts_with("exit code 0 after"));
+
+        run.finish(None, true);
+        assert!(run.to_string().starts_with("timed out after"));
+    }
+}
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::SocketAddr,
+    os::fd::{AsRawFd, OwnedFd, RawFd},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+
+/// First descriptor of the passed sockets, SD_LISTEN_FDS_START in systemd
+pub const LISTEN_FDS_START: RawFd = 3;
+
+/// How long the daemon keeps a socket no process lists anymore, covers a process created while its state is in flight
+const RELEASE_AFTER: Duration = Duration::from_secs(30);
+
+/// Set in the daemon, the only process that binds the sockets and keeps them across restarts
+static OWNER: AtomicBool = AtomicBool::new(false);
+
+/// Sockets bound by the daemon, by their spec
+static SOCKETS: Lazy<Mutex<HashMap<Listen, Bound>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct Bound {
+    socket: OwnedFd,
+    /// Last time a process used or listed the socket
+    used: Instant,
+}
+
+/// A socket the daemon listens on for a process, written as `tcp://host:port` or `unix:///path`
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Listen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl Listen {
+    /// Name passed in LISTEN_FDNAMES, e.g. "tcp-8080" or "app.sock"
+    pub fn name(&self) -> String {
+        match self {
+            Listen::Tcp(address) => format!("tcp-{}", address.port()),
+            Listen::Unix(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().replace(':', "_"))
+                .unwrap_or_else(|| "unix".to_string()),
+        }
+    }
+
+    /// Bind and listen, a stale unix socket file from an earlier daemon is replaced
+    fn bind(&self) -> Result<OwnedFd, String> {
+        let failed = |err: std::io::Error| format!("Failed to listen on {}: {}", self, err);
+        match self {
+            Listen::Tcp(address) => std::net::TcpListener::bind(address).map(OwnedFd::from).map_err(failed),
+            Listen::Unix(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(failed)?;
+                }
+                let _ = std::fs::remove_file(path);
+                std::os::unix::net::UnixListener::bind(path).map(OwnedFd::from).map_err(failed)
+            }
+        }
+    }
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec = spec.trim();
+        if let Some(address) = spec.strip_prefix("tcp://") {
+            return match address.parse::<SocketAddr>() {
+                Ok(address) if address.port() > 0 => Ok(Listen::Tcp(address)),
+                _ => Err(format!("Invalid listen address '{}', expected tcp://<ip>:<port>", spec)),
+            };
+        }
+        if let Some(path) = spec.strip_prefix("unix://") {
+            return match path.starts_with('/') {
+                true => Ok(Listen::Unix(PathBuf::from(path))),
+                false => Err(format!("Invalid listen path '{}', expected unix:///absolute/path", spec)),
+            };
+        }
+        Err(format!("Invalid listen socket '{}', use tcp://<ip>:<port> or unix:///<path>", spec))
+    }
+}
+
+impl TryFrom<String> for Listen {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        spec.parse()
+    }
+}
+
+impl From<Listen> for String {
+    fn from(listen: Listen) -> Self {
+        listen.to_string()
+    }
+}
+
+impl fmt::Display for Listen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Listen::Tcp(address) => write!(f, "tcp://{}", address),
+            Listen::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+/// Check the sockets of a process before it is created
+pub fn validate(listen: &[Listen], shared_port: bool) -> Result<(), String> {
+    if shared_port && !listen.is_empty() {
+        return Err("listen sockets cannot be combined with a shared worker port, both are passed on fd 3".to_string());
+    }
+    match listen.iter().enumerate().find(|(index, socket)| listen[..*index].contains(socket)) {
+        Some((_, socket)) => Err(format!("Listen socket {} is given twice", socket)),
+        None => Ok(()),
+    }
+}
+
+/// Bind listen sockets in this process and keep them, called once by the daemon
+pub fn own() {
+    OWNER.store(true, Ordering::Release);
+}
+
+/// Sockets for a new instance, in the order of `listen`
+/// The daemon binds each socket once and hands out copies, anywhere else the copies come from the daemon
+pub fn sockets(listen: &[Listen]) -> Result<Vec<OwnedFd>, String> {
+    if listen.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !OWNER.load(Ordering::Acquire) {
+        return match crate::socket::request_sockets(&global_placeholders::global!("opm.socket"), listen) {
+            Ok(sockets) => Ok(sockets),
+            Err(err) => {
+                log::warn!("Daemon did not pass the listen sockets ({}), binding them for this instance only", err);
+                listen.iter().map(Listen::bind).collect()
+            }
+        };
+    }
+
+    let mut sockets = SOCKETS.lock().unwrap();
+    listen
+        .iter()
+        .map(|spec| {
+            if !sockets.contains_key(spec) {
+                sockets.insert(spec.clone(), Bound { socket: spec.bind()?, used: Instant::now() });
+            }
+            let bound = sockets.get_mut(spec).unwrap();
+            bound.used = Instant::now();
+            bound.socket.try_clone().map_err(|err| format!("Failed to pass {}: {}", spec, err))
+        })
+        .collect()
+}
+
+/// Close the sockets no process has listed for a while, with the listed ones marked as used
+pub fn retain<'a>(listed: impl IntoIterator<Item = &'a Listen>) {
+    let mut sockets = SOCKETS.lock().unwrap();
+    for spec in listed {
+        if let Some(bound) = sockets.get_mut(spec) {
+            bound.used = Instant::now();
+        }
+    }
+    sockets.retain(|spec, bound| {
+        let keep = bound.used.elapsed() < RELEASE_AFTER;
+        if let (false, Listen::Unix(path)) = (keep, spec) {
+            let _ = std::fs::remove_file(path);
+        }
+        keep
+    });
+}
+
+/// LISTEN_FDS and LISTEN_FDNAMES for the sockets of `listen`, LISTEN_PID is only known in the child
+pub fn env(listen: &[Listen]) -> Vec<(String, String)> {
+    vec![
+        ("LISTEN_FDS".to_string(), listen.len().to_string()),
+        ("LISTEN_FDNAMES".to_string(), listen.iter().map(Listen::name).collect::<Vec<_>>().join(":")),
+    ]
+}
+
+/// Shell script that exports LISTEN_PID as its own pid and execs the real command in its place
+pub const EXEC_WITH_PID: &str = "LISTEN_PID=$$; export LISTEN_PID; exec \"$@\"";
+
+/// Place the sockets on consecutive descriptors from LISTEN_FDS_START, called between fork and exec
+pub fn inherit(sockets: &[RawFd]) -> std::io::Result<()> {
+    // Move every socket above the target range first, so placing one cannot close another
+    let above = LISTEN_FDS_START + sockets.len() as RawFd;
+    let mut moved = [0 as RawFd; 64];
+    if sockets.len() > moved.len() {
+        return Err(std::io::Error::from_raw_os_error(libc::EMFILE));
+    }
+    for (index, fd) in sockets.iter().enumerate() {
+        let copy = unsafe { libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, above) };
+        if copy < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        moved[index] = copy;
+    }
+    // dup2 clears close-on-exec on the target
+    for (index, fd) in moved[..sockets.len()].iter().enumerate() {
+        if unsafe { libc::dup2(*fd, LISTEN_FDS_START + index as RawFd) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Raw descriptors of the sockets, for use between fork and exec
+pub fn raw(sockets: &[OwnedFd]) -> Vec<RawFd> {
+    sockets.iter().map(AsRawFd::as_raw_fd).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_specs() {
+        let tcp: Listen = "tcp://0.0.0.0:8080".parse().unwrap();
+        assert_eq!(tcp, Listen::Tcp("0.0.0.0:8080".parse().unwrap()));
+        assert_eq!(tcp.to_string(), "tcp://0.0.0.0:8080");
+        assert_eq!(tcp.name(), "tcp-8080");
+        assert_eq!("tcp://[::1]:9000".parse::<Listen>().unwrap().name(), "tcp-9000");
+
+        let unix: Listen = "unix:///run/app.sock".parse().unwrap();
+        assert_eq!(unix, Listen::Unix(PathBuf::from("/run/app.sock")));
+        assert_eq!(unix.name(), "app.sock");
+
+        assert!("tcp://localhost:8080".parse::<Listen>().is_err());
+        assert!("tcp://0.0.0.0:0".parse::<Listen>().is_err());
+        assert!("unix://run/app.sock".parse::<Listen>().is_err());
+        assert!("udp://0.0.0.0:53".parse::<Listen>().is_err());
+
+        let env = env(&[tcp.clone(), unix.clone()]);
+        assert_eq!(env[0], ("LISTEN_FDS".to_string(), "2".to_string()));
+        assert_eq!(env[1], ("LISTEN_FDNAMES".to_string(), "tcp-8080:app.sock".to_string()));
+
+        assert!(validate(&[tcp.clone(), unix.clone()], false).is_ok());
+        assert!(validate(&[tcp.clone(), tcp.clone()], false).is_err());
+        assert!(validate(&[tcp], true).is_err());
+        assert!(validate(&[], true).is_ok());
+    }
+
+    #[test]
+    fn test_daemon_keeps_sockets() {
+        own();
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let spec: Listen = format!("tcp://127.0.0.1:{}", port).parse().unwrap();
+
+        let first = sockets(std::slice::from_ref(&spec)).unwrap();
+        drop(first);
+        // The daemon still holds the socket, so a client can connect while no instance runs
+        assert!(std::net::TcpStream::connect(("127.0.0.1", port)).is_ok());
+        assert_eq!(sockets(std::slice::from_ref(&spec)).unwrap().len(), 1);
+    }
+}
@@ -1,16 +1,46 @@
+pub mod balancer;
+pub mod capture;
+pub mod deps;
+pub mod entry;
 pub mod dump;
-pub mod hash;
+pub mod environment;
+pub mod group;
+pub mod health;
 pub mod http;
 pub mod id;
+pub mod instance;
+pub mod job;
+pub mod listen;
+pub mod restart;
+pub mod rollout;
+pub mod rotation;
+pub mod schedule;
+pub mod sink;
+pub mod threshold;
 pub mod unix;
-
-use crate::{config, config::structs::Server, file, helpers};
+pub mod watch;
+
+use crate::{config, config::structs::Server, helpers, secret::Secrets};
+use unix::{cgroup::Cgroup, rlimits::Limits};
+use health::{Health, HealthStatus, Launch, Liveness, Readiness};
+use capture::Capture;
+use deps::Dependency;
+use environment::{Environment, Resolved};
+use group::Group;
+use instance::Instance;
+use job::{Job, JobRun};
+use listen::Listen;
+use restart::{CrashRecord, Restart};
+use rollout::{RolledBack, Rollout};
+use rotation::Rotation;
+use threshold::Thresholds;
+pub use watch::Watch;
 
 use std::{
     collections::{BTreeMap, HashSet},
     env,
     fs::File,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
     thread,
     time::Duration,
@@ -46,19 +76,17 @@ pub static PROCESS_HANDLES: Lazy<DashMap<i64, Arc<Mutex<std::process::Child>>>>
 static PID_REGISTRY: Lazy<Mutex<HashSet<i64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
 
 // Constants for process termination waiting
-const MAX_TERMINATION_WAIT_ATTEMPTS: u32 = 50;
 const TERMINATION_CHECK_INTERVAL_MS: u64 = 100;
+// Time a process gets to exit after its stop signal before SIGKILL, unless kill_timeout_ms is set
+pub const DEFAULT_KILL_TIMEOUT_MS: u64 = 5000;
+// Time allowed for the kernel to tear down a process after SIGKILL
+const SIGKILL_WAIT_MS: u64 = 1000;
 
 // Grace period for process status determination
 // Processes within this period after start show as "starting" instead of "crashed"
 // This prevents false crash reports during slow process initialization and restart cycles
 const STATUS_GRACE_PERIOD_SECS: i64 = 15;
 
-// Anti-spam restart cooldown constants
-// Simplified fixed delay for restart attempts as per requirement #4
-pub const RESTART_COOLDOWN_SECS: u64 = 2;
-// No exponential backoff - use same 2s delay for all restarts
-pub const FAILED_RESTART_COOLDOWN_SECS: u64 = 2;
 // Interval for periodic cooldown logging to reduce log noise
 pub const COOLDOWN_LOG_INTERVAL_SECS: i64 = 2;
 // Wait time after killing processes to allow OS resource cleanup
@@ -69,8 +97,9 @@ pub const PROCESS_CLEANUP_WAIT_MS: u64 = 500;
 /// as pid::running() but implemented here to avoid circular dependencies.
 /// This is more reliable than trying to create a process handle that could fail
 /// for other reasons (permissions, etc.)
+/// Zombies count as terminated, they only wait for their parent to reap them
 /// Returns true if process terminated, false if timeout reached
-fn wait_for_process_termination(pid: i64) -> bool {
+fn wait_for_process_termination(pid: i64, timeout: Duration) -> bool {
     // Don't wait for invalid PIDs - they're already "terminated"
     // PID 0 signals all processes in current process group (not a specific process)
     // Negative PIDs signal process groups (not individual processes)
@@ -80,16 +109,19 @@ fn wait_for_process_termination(pid: i64) -> bool {
         return true;
     }
 
-    for _ in 0..MAX_TERMINATION_WAIT_ATTEMPTS {
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
         // Check if process is still running using libc::kill with signal 0
         // This returns 0 if the process exists, -1 if it doesn't (or permission denied)
         let process_exists = unsafe { libc::kill(pid as i32, 0) == 0 };
-        if !process_exists {
+        if !process_exists || unix::is_process_zombie(pid as i32) {
             return true; // Process has terminated (or we don't have permission to check)
         }
+        if std::time::Instant::now() >= deadline {
+            return false; // Timeout reached, process is still running
+        }
         thread::sleep(Duration::from_millis(TERMINATION_CHECK_INTERVAL_MS));
     }
-    false // Timeout reached, process is still running
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -112,6 +144,17 @@ pub struct Info {
     pub uptime: String,
     pub command: String,
     pub children: Vec<i64>,
+    #[serde(default)]
+    pub exec: Exec,
+    /// rlimits in effect for the running process, from /proc/<pid>/limits
+    #[serde(default)]
+    pub limits: Option<Limits>,
+    /// Liveness probe results of the current instance
+    #[serde(default)]
+    pub health: Health,
+    /// Last crashes, oldest first
+    #[serde(default)]
+    pub crash_history: Vec<CrashRecord>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -154,6 +197,8 @@ pub struct Raw {
 pub struct LogInfo {
     pub out: String,
     pub error: String,
+    /// Both streams interleaved, only written with a combined log capture
+    pub combined: String,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -176,6 +221,9 @@ pub struct ProcessItem {
     pub agent_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub agent_api_endpoint: Option<String>,
+    /// Worker group the process is an instance of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
 }
 
 #[derive(Clone)]
@@ -251,23 +299,117 @@ pub struct Process {
     /// Used to display tree indicator in UI
     #[serde(default)]
     pub is_process_tree: bool,
+    /// Working directory, user, group and umask applied before exec
+    #[serde(default)]
+    pub exec: Exec,
+    /// cgroup the current instance runs in, set when the cgroup backend is enabled
+    #[serde(default)]
+    pub cgroup: Option<Cgroup>,
+    /// Set while the current instance has not passed its readiness check
+    #[serde(default)]
+    pub launching: Option<Launch>,
+    /// Liveness probe results of the current instance
+    #[serde(default)]
+    pub health: Health,
+    /// Last crashes, oldest first
+    #[serde(default)]
+    pub crash_history: Vec<CrashRecord>,
+    /// Last runs of a job, oldest first
+    #[serde(default)]
+    pub runs: Vec<JobRun>,
 }
 
 impl Process {
-    pub fn restart_cooldown_delay_secs(&self) -> u64 {
-        if self.failed_restart_attempts > 0 {
-            FAILED_RESTART_COOLDOWN_SECS
-        } else {
-            RESTART_COOLDOWN_SECS
+    /// Environment the next start of this process receives, with the source of each variable
+    pub fn resolved_env(&self) -> Result<Resolved, String> {
+        resolve_env(&self.exec, &self.env, &self.path)
+    }
+
+    /// Show secret values in the environment of this process as their references
+    pub fn redact(&mut self, secrets: &Secrets) {
+        secrets.redact(&mut self.env);
+        self.exec.environment.redact(secrets);
+    }
+
+    /// Open a new entry in the run history of a job, closing one left unfinished
+    pub fn begin_run(&mut self) {
+        if self.exec.job.is_none() {
+            return;
+        }
+        self.finish_run(None, false);
+        self.runs.push(JobRun::begin());
+        let excess = self.runs.len().saturating_sub(job::RUN_HISTORY_LIMIT);
+        self.runs.drain(..excess);
+    }
+
+    /// Close the run in progress, if any
+    pub fn finish_run(&mut self, exit: Option<restart::Exit>, timed_out: bool) {
+        if let Some(run) = self.runs.last_mut().filter(|run| run.is_running()) {
+            run.finish(exit, timed_out);
+        }
+    }
+
+    /// A job between runs: enabled, without an instance and without a pending retry
+    pub fn is_waiting_for_run(&self) -> bool {
+        self.exec.job.is_some() && self.running && self.pid == 0 && !self.crash.crashed
+    }
+
+    /// Status of a job between runs: "scheduled" for a cron job, "completed" or "failed" once a one-shot job ran
+    pub fn job_status(&self) -> Option<&'static str> {
+        let job = self.exec.job.as_ref()?;
+        if self.is_waiting_for_run() {
+            return job.cron.as_ref().map(|_| "scheduled");
         }
+        if self.running || self.crash.crashed || self.manual_stop {
+            return None;
+        }
+
+        let run = self.runs.last().filter(|run| !run.is_running())?;
+        Some(ternary!(run.succeeded(&self.exec.restart), "completed", "failed"))
+    }
+
+    /// Whether processes depending on this one with the given condition can be started
+    pub fn satisfies(&self, condition: deps::Condition) -> bool {
+        match (condition, &self.exec.job) {
+            (deps::Condition::Started, _) => self.pid > 0 || self.job_status().is_some(),
+            (deps::Condition::Ready, Some(_)) => self
+                .runs
+                .iter()
+                .rev()
+                .find(|run| !run.is_running())
+                .is_some_and(|run| run.succeeded(&self.exec.restart)),
+            (deps::Condition::Ready, None) => {
+                self.running && self.pid > 0 && self.launching.is_none() && is_pid_alive(self.pid)
+            }
+        }
+    }
+
+    /// How long a process depending on this one waits for it to become ready
+    pub fn ready_timeout(&self) -> Duration {
+        match (&self.exec.ready, &self.exec.job) {
+            (Some(ready), _) => ready.timeout(),
+            (None, Some(job)) => job.timeout().unwrap_or(deps::READY_TIMEOUT),
+            (None, None) => deps::READY_TIMEOUT,
+        }
+    }
+
+    /// Add a crash to the history, dropping the oldest beyond restart::CRASH_HISTORY_LIMIT
+    pub fn record_crash(&mut self, record: CrashRecord) {
+        self.crash_history.push(record);
+        let excess = self.crash_history.len().saturating_sub(restart::CRASH_HISTORY_LIMIT);
+        self.crash_history.drain(..excess);
+    }
+
+    /// Delay before the next restart attempt, growing with each failed start in a row
+    pub fn restart_delay(&self) -> Duration {
+        self.exec.restart.backoff.delay(self.failed_restart_attempts)
     }
 
     pub fn restart_cooldown_remaining_secs(&self) -> u64 {
         self.last_restart_attempt
             .map(|t| {
-                let elapsed_secs = (Utc::now() - t).num_seconds().max(0) as u64;
-                self.restart_cooldown_delay_secs()
-                    .saturating_sub(elapsed_secs)
+                let elapsed = (Utc::now() - t).to_std().unwrap_or_default();
+                self.restart_delay().saturating_sub(elapsed).as_secs_f64().ceil() as u64
             })
             .unwrap_or(0)
     }
@@ -284,12 +426,258 @@ pub struct Crash {
     pub crashed: bool,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
-pub struct Watch {
-    pub enabled: bool,
-    #[schema(example = "/path")]
-    pub path: String,
-    pub hash: String,
+/// Execution context applied to the child before exec
+/// Every field is optional; unset fields inherit from the daemon
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Exec {
+    /// Working directory (relative paths resolve against the process path)
+    #[schema(value_type = Option<String>, example = "/srv/app")]
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    /// User name or uid to run as
+    #[schema(example = "www-data")]
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Group name or gid to run as (defaults to the user's primary group)
+    #[schema(example = "www-data")]
+    #[serde(default)]
+    pub group: Option<String>,
+    /// File mode creation mask
+    #[schema(example = 18)]
+    #[serde(default)]
+    pub umask: Option<u32>,
+    /// cgroup v2 limits, only applied when the daemon has the cgroup backend enabled
+    #[serde(default)]
+    pub resources: Resources,
+    /// rlimits set with setrlimit in the child
+    #[serde(default)]
+    pub limits: Limits,
+    /// Force direct spawn or a shell instead of detecting it from the command
+    #[serde(default)]
+    pub mode: Option<ExecMode>,
+    /// Stop signal, kill timeout and which processes receive them
+    #[serde(default)]
+    pub stop: StopPolicy,
+    /// Check that must pass before a new instance counts as online
+    #[serde(default)]
+    pub ready: Option<Readiness>,
+    /// Periodic probe that restarts the process after repeated failures
+    #[serde(default)]
+    pub liveness: Option<Liveness>,
+    /// Which exits are restarted, restart limits and the delay between attempts
+    #[serde(default)]
+    pub restart: Restart,
+    /// Cron expression for scheduled restarts, evaluated in local time
+    #[schema(example = "0 3 * * *")]
+    #[serde(default)]
+    pub cron_restart: Option<String>,
+    /// Runs the process once or on a schedule instead of keeping it running
+    #[serde(default)]
+    pub job: Option<Job>,
+    /// Processes, by name, that are started before this one
+    #[schema(value_type = Vec<String>, example = json!(["redis", "migrate:ready"]))]
+    #[serde(default)]
+    pub depends_on: Vec<Dependency>,
+    /// Sockets the daemon binds once and passes to every instance, so connections queue while it restarts
+    #[schema(value_type = Vec<String>, example = json!(["tcp://0.0.0.0:8080", "unix:///run/app.sock"]))]
+    #[serde(default)]
+    pub listen: Vec<Listen>,
+    /// Env files, interpolated variables and named environments layered over the inherited environment
+    #[serde(default)]
+    pub environment: Environment,
+    /// What happens once max_memory or max_cpu stays exceeded
+    #[serde(default)]
+    pub thresholds: Thresholds,
+    /// Rotation of the out and error logs, `[daemon.log_rotation]` applies when unset
+    #[serde(default)]
+    pub log_rotation: Option<Rotation>,
+    /// Output read by the daemon through pipes, timestamped and optionally merged into a combined log
+    #[serde(default)]
+    pub log_capture: Option<Capture>,
+    /// Position of the process among workers started together, with the port it serves on
+    #[serde(default)]
+    pub instance: Option<Instance>,
+}
+
+/// Which processes receive the stop signal and the SIGKILL escalation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum KillMode {
+    /// Stop signal and SIGKILL go to the main process and all of its children
+    #[default]
+    Group,
+    /// Only the main process is signalled, children are left alone
+    Main,
+    /// Stop signal to the main process, SIGKILL to whatever remains of the tree
+    Mixed,
+}
+
+impl std::str::FromStr for KillMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.trim().to_lowercase().as_str() {
+            "group" => Ok(KillMode::Group),
+            "main" => Ok(KillMode::Main),
+            "mixed" => Ok(KillMode::Mixed),
+            _ => Err(format!("Invalid kill mode: {}. Use 'group', 'main' or 'mixed'", mode)),
+        }
+    }
+}
+
+impl std::fmt::Display for KillMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KillMode::Group => write!(f, "group"),
+            KillMode::Main => write!(f, "main"),
+            KillMode::Mixed => write!(f, "mixed"),
+        }
+    }
+}
+
+/// How a process is asked to stop before it is killed
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct StopPolicy {
+    /// Signal sent first, SIGTERM when unset
+    #[schema(example = "SIGINT")]
+    #[serde(default)]
+    pub stop_signal: Option<String>,
+    /// Milliseconds to wait for the process to exit before SIGKILL
+    #[schema(example = 30000)]
+    #[serde(default)]
+    pub kill_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub kill_mode: KillMode,
+}
+
+impl StopPolicy {
+    pub fn parse(
+        stop_signal: Option<&str>,
+        kill_timeout_ms: Option<u64>,
+        kill_mode: Option<KillMode>,
+    ) -> Result<Self, String> {
+        Ok(StopPolicy {
+            stop_signal: stop_signal.map(helpers::parse_signal).transpose()?,
+            kill_timeout_ms,
+            kill_mode: kill_mode.unwrap_or_default(),
+        })
+    }
+
+    pub fn signal(&self) -> Signal {
+        self.stop_signal
+            .as_deref()
+            .and_then(|name| name.parse().ok())
+            .unwrap_or(Signal::SIGTERM)
+    }
+
+    pub fn kill_timeout(&self) -> Duration {
+        Duration::from_millis(self.kill_timeout_ms.unwrap_or(DEFAULT_KILL_TIMEOUT_MS))
+    }
+
+    /// One-line summary such as "SIGINT, 30000ms, mixed"
+    pub fn describe(&self) -> String {
+        format!(
+            "{}, {}ms, {}",
+            self.signal().as_str(),
+            self.kill_timeout().as_millis(),
+            self.kill_mode
+        )
+    }
+}
+
+/// How the command is turned into a process
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecMode {
+    /// Split into words and exec the program without a shell
+    Direct,
+    /// Pass the command to the configured shell
+    Shell,
+}
+
+impl std::str::FromStr for ExecMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.trim().to_lowercase().as_str() {
+            "direct" => Ok(ExecMode::Direct),
+            "shell" => Ok(ExecMode::Shell),
+            _ => Err(format!("Invalid exec mode: {}. Use 'direct' or 'shell'", mode)),
+        }
+    }
+}
+
+impl std::fmt::Display for ExecMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecMode::Direct => write!(f, "direct"),
+            ExecMode::Shell => write!(f, "shell"),
+        }
+    }
+}
+
+/// Limits written to the process cgroup (None = unlimited)
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Resources {
+    /// memory.max in bytes
+    #[schema(example = 536870912)]
+    #[serde(default)]
+    pub memory_max: Option<u64>,
+    /// cpu.max as a percentage of one CPU (150 = 1.5 CPUs)
+    #[schema(example = 150)]
+    #[serde(default)]
+    pub cpu_max: Option<u32>,
+    /// pids.max
+    #[schema(example = 256)]
+    #[serde(default)]
+    pub pids_max: Option<u64>,
+}
+
+impl Resources {
+    /// Build limits from user input such as "512M" and "150%"
+    pub fn parse(
+        memory_max: Option<&str>,
+        cpu_max: Option<&str>,
+        pids_max: Option<u64>,
+    ) -> Result<Self, String> {
+        Ok(Resources {
+            memory_max: memory_max.map(helpers::parse_memory).transpose()?,
+            cpu_max: cpu_max.map(helpers::parse_cpu_max).transpose()?,
+            pids_max,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.memory_max.is_none() && self.cpu_max.is_none() && self.pids_max.is_none()
+    }
+
+    pub fn format_cpu_max(&self) -> Option<String> {
+        self.cpu_max.map(|percent| format!("{}%", percent))
+    }
+}
+
+impl Exec {
+    /// Resolve the directory the child should start in
+    pub fn working_dir(&self, path: &Path) -> PathBuf {
+        match &self.cwd {
+            Some(cwd) if cwd.is_absolute() => cwd.clone(),
+            Some(cwd) => path.join(cwd),
+            None => path.to_path_buf(),
+        }
+    }
+
+    pub fn format_umask(&self) -> Option<String> {
+        self.umask.map(|mask| format!("{:04o}", mask))
+    }
+
+    /// The configured exec mode, or the one detected from the command
+    pub fn resolve_mode(&self, command: &str) -> ExecMode {
+        match self.mode {
+            Some(mode) => mode,
+            None if command_needs_shell(command) => ExecMode::Shell,
+            None => ExecMode::Direct,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -298,6 +686,9 @@ pub struct Runner {
     #[serde(skip, default)]
     pub remote: Option<Remote>,
     pub list: BTreeMap<usize, Process>,
+    /// Worker groups by name, their instances are the processes in `list` whose Exec.instance names the group
+    #[serde(default)]
+    pub groups: BTreeMap<String, Group>,
 }
 
 #[derive(Clone, Debug)]
@@ -342,6 +733,10 @@ pub struct ProcessMetadata {
     pub args: Vec<String>,
     /// Environment variables
     pub env: Vec<String>,
+    /// Working directory, user, group and umask
+    pub exec: Exec,
+    /// cgroup directory to place the child in (None = cgroup backend disabled)
+    pub cgroup: Option<PathBuf>,
 }
 
 macro_rules! lock {
@@ -353,52 +748,118 @@ macro_rules! lock {
     }};
 }
 
-fn kill_children(children: Vec<i64>) {
-    for pid in children {
-        match kill(Pid::from_raw(pid as i32), Signal::SIGTERM) {
-            Ok(_) => {}
-            Err(nix::errno::Errno::ESRCH) => {
-                // Process already terminated
-            }
-            Err(err) => {
-                log::error!("Failed to stop pid {}: {err:?}", pid);
-            }
+fn signal_pid(pid: i64, signal: Signal) {
+    if pid <= 0 {
+        return;
+    }
+
+    match kill(Pid::from_raw(pid as i32), signal) {
+        Ok(_) => {}
+        Err(nix::errno::Errno::ESRCH) => {
+            // Process already terminated
+        }
+        Err(err) => {
+            log::error!("Failed to send {} to pid {}: {err:?}", signal.as_str(), pid);
         }
     }
 }
 
-/// Load environment variables from .env file in the specified directory
-fn load_dotenv(path: &PathBuf) -> BTreeMap<String, String> {
-    let env_file = path.join(".env");
-    let mut env_vars = BTreeMap::new();
-
-    if env_file.exists() && env_file.is_file() {
-        match dotenvy::from_path_iter(&env_file) {
-            Ok(iter) => {
-                for item in iter {
-                    match item {
-                        Ok((key, value)) => {
-                            env_vars.insert(key, value);
-                        }
-                        Err(err) => {
-                            log::warn!("Failed to parse .env entry: {}", err);
-                        }
-                    }
-                }
-                if !env_vars.is_empty() {
-                    log::info!(
-                        "Loaded {} environment variables from .env file",
-                        env_vars.len()
-                    );
+/// Stop a process tree according to its stop policy
+/// The stop signal goes out first; whatever the kill mode covers and is still alive after
+/// kill_timeout_ms receives SIGKILL. Returns true when the process had to be escalated to SIGKILL
+pub fn terminate(pid: i64, children: Vec<i64>, policy: &StopPolicy) -> bool {
+    let signal = policy.signal();
+    let mut tree = children;
+    for child in process_find_children(pid) {
+        if !tree.contains(&child) {
+            tree.push(child);
+        }
+    }
+
+    if policy.kill_mode == KillMode::Group {
+        // Stop child processes first
+        for &child in &tree {
+            signal_pid(child, signal);
+        }
+    }
+    signal_pid(pid, signal);
+
+    let mut escalated = false;
+    if !wait_for_process_termination(pid, policy.kill_timeout()) {
+        signal_pid(pid, Signal::SIGKILL);
+        escalated = true;
+        if !wait_for_process_termination(pid, Duration::from_millis(SIGKILL_WAIT_MS)) {
+            log::warn!("Process {} is still alive after SIGKILL", pid);
+        }
+    }
+
+    match policy.kill_mode {
+        KillMode::Main => {}
+        KillMode::Group | KillMode::Mixed => {
+            for child in tree {
+                if is_pid_alive(child) {
+                    signal_pid(child, Signal::SIGKILL);
+                    // In mixed mode killing the leftovers is the expected outcome, not an escalation
+                    escalated |= policy.kill_mode == KillMode::Group;
                 }
             }
-            Err(err) => {
-                log::warn!("Failed to read .env file at {:?}: {}", env_file, err);
-            }
         }
     }
 
-    env_vars
+    escalated
+}
+
+/// Stop a process and record an escalation to SIGKILL in the daemon log and the event history
+pub(crate) fn terminate_and_record(id: usize, name: &str, pid: i64, children: Vec<i64>, policy: &StopPolicy, action: &str) {
+    if !terminate(pid, children, policy) {
+        return;
+    }
+
+    let message = format!(
+        "Process '{}' did not exit within {}ms of {} during {}, sent SIGKILL",
+        name,
+        policy.kill_timeout().as_millis(),
+        policy.signal().as_str(),
+        action
+    );
+    log::warn!("{} (id={}, pid={})", message, id, pid);
+    crate::events::emit(crate::events::Event::new(
+        crate::events::EventType::ProcessKilled,
+        "local".to_string(),
+        "Local".to_string(),
+        Some(id.to_string()),
+        Some(name.to_string()),
+        message,
+    ));
+}
+
+/// cgroup directory for the next instance of a process, None when the backend is disabled
+/// Restarts keep the current directory as long as the configured parent is unchanged
+fn cgroup_path(
+    daemon: &config::structs::Daemon,
+    name: &str,
+    id: usize,
+    current: Option<&Cgroup>,
+) -> Option<PathBuf> {
+    let parent = Path::new(&daemon.cgroup.as_ref().filter(|cgroup| cgroup.enabled)?.parent);
+
+    match current {
+        Some(current) if current.path.parent() == Some(parent) => Some(current.path.clone()),
+        _ => Some(parent.join(unix::cgroup::dir_name(name, id))),
+    }
+}
+
+/// Environment handed to a new instance of a process, the layers are described on Environment::resolve
+fn resolve_env(exec: &Exec, stored: &Env, path: &Path) -> Result<Resolved, String> {
+    let system: Env = unix::env()
+        .into_iter()
+        .filter_map(|pair| pair.split_once('=').map(|(key, value)| (key.to_string(), value.to_string())))
+        .collect();
+    let instance = exec.instance.as_ref().map(Instance::env).unwrap_or_default();
+
+    exec.environment
+        .resolve(&system, stored, path, &exec.working_dir(path), &instance)
+        .map_err(|err| format!("Failed to resolve environment: {err}"))
 }
 
 /// Check if a process with the given PID is alive
@@ -586,11 +1047,12 @@ impl Runner {
         name: &String,
         command: &String,
         path: PathBuf,
-        watch: &Option<String>,
+        watch: &Option<Watch>,
         max_memory: u64,
+        exec: Exec,
     ) -> &mut Self {
         if let Some(remote) = &self.remote {
-            if let Err(err) = http::create(remote, name, command, path, watch) {
+            if let Err(err) = http::create(remote, name, command, path, watch, max_memory, &exec) {
                 crashln!(
                     "{} Failed to start create {name}\nError: {:#?}",
                     *helpers::FAIL,
@@ -599,43 +1061,39 @@ impl Runner {
             };
         } else {
             let id = self.id.next();
-            let config = config::read().runner;
+            let full_config = config::read();
+            let config = full_config.runner;
             let crash = Crash { crashed: false };
 
-            let watch = match watch {
-                Some(watch) => Watch {
-                    enabled: true,
-                    path: string!(watch),
-                    hash: hash::create(file::cwd().join(watch)),
-                },
-                None => Watch {
-                    enabled: false,
-                    path: string!(""),
-                    hash: string!(""),
-                },
-            };
-
-            // Load environment variables from .env file
-            let dotenv_vars = load_dotenv(&path);
-            let system_env = unix::env();
+            let watch = watch.clone().unwrap_or_default();
 
-            // Prepare process environment with dotenv variables having priority
-            let mut process_env = Vec::with_capacity(dotenv_vars.len() + system_env.len());
-            // Add dotenv variables first (higher priority)
-            for (key, value) in &dotenv_vars {
-                process_env.push(format!("{}={}", key, value));
+            // Stored with the process so later starts see the environment it was created from
+            let mut stored_env: Env = env::vars().collect();
+            // Kept with the process so restarts and restores hand the worker the same port
+            if let Some(instance) = &exec.instance {
+                stored_env.extend(instance.env());
             }
-            // Then add system environment
-            process_env.extend(system_env);
 
-            let result = match process_run(ProcessMetadata {
-                args: config.args,
-                name: name.clone(),
-                shell: config.shell,
-                command: command.clone(),
-                log_path: config.log_path,
-                env: process_env,
-            }) {
+            // Jobs are only registered here, the daemon launches each run so it can collect the exit status
+            // A process whose dependencies are not up yet is likewise left for the daemon to start
+            let spawned = match exec.job.is_some() || self.pending_dependency(&exec.depends_on).is_some() {
+                true => resolve_env(&exec, &stored_env, &path).map(|_| ProcessRunResult::pending()),
+                false => resolve_env(&exec, &stored_env, &path).and_then(|resolved| process_run(ProcessMetadata {
+                    args: config.args,
+                    name: name.clone(),
+                    shell: config.shell,
+                    command: command.clone(),
+                    log_path: config.log_path,
+                    env: environment::pairs(&resolved),
+                    exec: Exec {
+                        cwd: Some(exec.working_dir(&path)),
+                        ..exec.clone()
+                    },
+                    cgroup: cgroup_path(&full_config.daemon, name, id, None),
+                })),
+            };
+
+            let result = match spawned {
                 Ok(result) => result,
                 Err(err) => {
                     log::error!("Failed to start process '{}': {}", name, err);
@@ -649,20 +1107,13 @@ impl Runner {
                 }
             };
 
-            // Merge .env variables into the stored environment (dotenv takes priority)
-            let mut stored_env: Env = env::vars().collect();
-            // Extend with dotenv variables (this overwrites any existing keys)
-            stored_env.extend(dotenv_vars);
-
             // Check for duplicate PIDs before inserting new process
             // This prevents tracking the same process multiple times
             // (unless it's a PM2-like multi-worker setup with legitimate parent-child relationships)
-            let (has_duplicate, error_msg) = check_duplicate_pid(
-                &self.list,
-                id,
-                result.pid,
-                result.shell_pid,
-            );
+            let (has_duplicate, error_msg) = match result.pid > 0 {
+                true => check_duplicate_pid(&self.list, id, result.pid, result.shell_pid),
+                false => (false, String::new()),
+            };
             
             if has_duplicate {
                 log::warn!(
@@ -681,7 +1132,7 @@ impl Runner {
             }
 
             // Register the PID in the global registry to prevent race conditions during parallel operations
-            {
+            if result.pid > 0 {
                 let mut pid_registry = PID_REGISTRY.lock().unwrap();
                 if pid_registry.contains(&result.pid) {
                     log::warn!(
@@ -758,6 +1209,12 @@ impl Runner {
                     session_id: result.session_id, // Store session ID for tracking
                     process_start_time: result.start_time, // Store for PID reuse detection
                     is_process_tree: result.shell_pid.is_some(), // Mark as tree if has shell wrapper
+                    exec,
+                    cgroup: result.cgroup,
+                    launching: result.launch,
+                    health: Health::default(),
+                    crash_history: Vec::new(),
+                    runs: Vec::new(),
                 },
             );
 
@@ -789,6 +1246,8 @@ impl Runner {
                 pid: previous_pid,
                 shell_pid: previous_shell_pid,
                 running: was_running,
+                exec,
+                cgroup,
                 ..
             } = self
                 .list
@@ -828,23 +1287,22 @@ impl Runner {
                 process.errored = false;
             }
 
-            kill_children(process.children.clone());
-            if let Err(err) = process_stop(process.pid) {
-                log::warn!(
-                    "Failed to stop process {} during restart: {}",
-                    process.pid,
-                    err
-                );
-                // Continue with restart even if stop fails - process may already be dead
+            // Same as stop: mark the exit as intended BEFORE killing the process, otherwise
+            // the daemon may reap it first and respawn the old instance next to the new one
+            if !dead && process.pid > 0 {
+                process.manual_stop = true;
+                self.save();
             }
+            let process = self.process(id);
 
             // Wait for the process to actually terminate before starting a new one
             // This prevents conflicts when restarting processes that hold resources (e.g., network connections)
-            if !wait_for_process_termination(process.pid) {
-                log::warn!(
-                    "Process {} did not terminate within timeout during restart",
-                    process.pid
-                );
+            terminate_and_record(id, &name, process.pid, process.children.clone(), &exec.stop, "restart");
+
+            // The new instance joins the same cgroup, so nothing from the old one may linger there
+            // kill_mode = "main" leaves the other members running on purpose
+            if let Some(cgroup) = cgroup.as_ref().filter(|_| exec.stop.kill_mode != KillMode::Main) {
+                unix::cgroup::kill(&cgroup.path);
             }
 
             if let Err(err) = std::env::set_current_dir(&path) {
@@ -888,34 +1346,20 @@ impl Runner {
                 return self;
             }
 
-            // Load environment variables from .env file
-            let dotenv_vars = load_dotenv(&path);
-            let system_env = unix::env();
-
-            // Prepare process environment with dotenv variables having priority
-            let stored_env_vec: Vec<String> = process
-                .env
-                .iter()
-                .map(|(key, value)| format!("{}={}", key, value))
-                .collect();
-            let mut temp_env =
-                Vec::with_capacity(dotenv_vars.len() + stored_env_vec.len() + system_env.len());
-            // Add dotenv variables first (highest priority)
-            for (key, value) in &dotenv_vars {
-                temp_env.push(format!("{}={}", key, value));
-            }
-            // Then add stored environment
-            temp_env.extend(stored_env_vec);
-            // Finally add system environment
-            temp_env.extend(system_env);
-
-            let result = match process_run(ProcessMetadata {
-                args: config.args,
-                name: name.clone(),
-                shell: config.shell,
-                log_path: config.log_path,
-                command: script.to_string(),
-                env: temp_env,
+            let result = match resolve_env(&exec, &process.env, &path).and_then(|resolved| {
+                process_run(ProcessMetadata {
+                    args: config.args,
+                    name: name.clone(),
+                    shell: config.shell,
+                    log_path: config.log_path,
+                    command: script.to_string(),
+                    env: environment::pairs(&resolved),
+                    exec: Exec {
+                        cwd: Some(exec.working_dir(&path)),
+                        ..exec.clone()
+                    },
+                    cgroup: cgroup_path(&full_config.daemon, &name, id, cgroup.as_ref()),
+                })
             }) {
                 Ok(result) => result,
                 Err(err) => {
@@ -1057,8 +1501,12 @@ impl Runner {
             process.session_id = result.session_id;
             process.process_start_time = result.start_time;
             process.is_process_tree = result.shell_pid.is_some();
+            process.cgroup = result.cgroup;
+            process.launching = result.launch;
+            process.health = Health::default();
             process.running = true;
             process.started = Utc::now();
+            process.begin_run();
             // Clear crashed flag after successful restart
             // This allows the daemon to properly detect if the process crashes again
             process.crash.crashed = false;
@@ -1101,10 +1549,11 @@ impl Runner {
                 );
             }
 
-            // Merge .env variables into the stored environment (dotenv takes priority)
-            let mut updated_env: Env = env::vars().collect();
-            updated_env.extend(dotenv_vars);
-            process.env.extend(updated_env);
+            // Files and declared variables are read again on every start, only the inherited environment is stored
+            process.env.extend(env::vars());
+            if let Some(instance) = &process.exec.instance {
+                process.env.extend(instance.env());
+            }
 
             // Don't reset crash counter - keep it to preserve crash history
             // The daemon will reset it automatically after the process runs successfully
@@ -1141,108 +1590,22 @@ impl Runner {
                 );
             };
         } else {
-            let process = self.process(id);
-            let full_config = config::read();
-            let config = full_config.runner;
-            let max_restarts = full_config.daemon.restarts;
-            let Process {
-                path,
-                script,
-                name,
-                env,
-                watch: _,
-                max_memory: _,
-                ..
-            } = process.clone();
-
-            // Save the current working directory so we can restore it after reload
-            let original_dir = std::env::current_dir().ok();
+            let max_restarts = config::read().daemon.restarts;
+            let name = self.process(id).name.clone();
 
             // Increment restart counter based on parameters:
             // - dead=true (daemon auto-restart): don't increment (daemon already incremented)
             // - dead=false with increment_counter=true (manual reload): increment
             // - dead=false with increment_counter=false (not currently used): don't increment
             if !dead && increment_counter {
-                process.restarts += 1;
-            }
-
-            if let Err(err) = std::env::set_current_dir(&path) {
-                // Restore working directory before returning
-                if let Some(ref dir) = original_dir {
-                    let _ = std::env::set_current_dir(dir);
-                }
-
-                // When dead=true (crash reload), keep running=true so daemon will retry on next cycle
-                // When dead=false (manual reload), set running=false to stop retrying until user manually reloads
-                if !dead {
-                    process.running = false;
-                }
-                process.children = vec![];
-                // Reset PID to 0 to indicate process never successfully started
-                // This prevents handle_restart_failure from marking it as crashed
-                process.pid = 0;
-                process.shell_pid = None;
-
-                // Increment crash counter for reload failures to count against restart limit
-                // When dead=true (daemon reload): don't increment (daemon already incremented)
-                // When dead=false (manual reload): increment (first time counting this failure)
-                self.handle_restart_failure(id, &name, max_restarts, !dead);
-                // Note: handle_restart_failure sets crashed=true, so we don't need to set it here
-
-                // Save state to persist counter increments and state changes
-                self.save_after_restart_failure(dead);
-
-                log::error!(
-                    "Failed to set working directory {:?} for process {} during reload: {}",
-                    path,
-                    name,
-                    err
-                );
-                println!(
-                    "{} Failed to set working directory {:?}\nError: {:#?}",
-                    *helpers::FAIL,
-                    path,
-                    err
-                );
-                return self;
+                self.process(id).restarts += 1;
             }
 
-            // Load environment variables from .env file
-            let dotenv_vars = load_dotenv(&path);
-            let system_env = unix::env();
-
-            // Prepare process environment with dotenv variables having priority
-            let stored_env_vec: Vec<String> = env
-                .iter()
-                .map(|(key, value)| format!("{}={}", key, value))
-                .collect();
-            let mut temp_env =
-                Vec::with_capacity(dotenv_vars.len() + stored_env_vec.len() + system_env.len());
-            // Add dotenv variables first (highest priority)
-            for (key, value) in &dotenv_vars {
-                temp_env.push(format!("{}={}", key, value));
-            }
-            // Then add stored environment
-            temp_env.extend(stored_env_vec);
-            // Finally add system environment
-            temp_env.extend(system_env);
-
             // Start new process first
-            let result = match process_run(ProcessMetadata {
-                args: config.args,
-                name: name.clone(),
-                shell: config.shell,
-                log_path: config.log_path,
-                command: script.to_string(),
-                env: temp_env,
-            }) {
-                Ok(result) => result,
+            let old = match self.launch_replacement(id) {
+                Ok(old) => old,
                 Err(err) => {
-                    // Restore working directory before returning
-                    if let Some(ref dir) = original_dir {
-                        let _ = std::env::set_current_dir(dir);
-                    }
-
+                    let process = self.process(id);
                     // When dead=true (crash reload), keep running=true so daemon will retry on next cycle
                     // When dead=false (manual reload), set running=false to stop retrying until user manually reloads
                     if !dead {
@@ -1275,81 +1638,216 @@ impl Runner {
                 }
             };
 
-            // Store old PID before updating
-            let old_pid = process.pid;
-            let old_children = process.children.clone();
+            // Save state after successful reload to persist changes, before the old process exits
+            // so the daemon finds the new instance in its place instead of a crash
+            // Use save_direct() when called from daemon (dead=true) to avoid serialization
+            // that would lose fields marked with #[serde(skip)] like the restart counter
+            if dead {
+                self.save_direct();
+            } else {
+                self.save();
+            }
+
+            // Now stop the old process after the new one is running
+            // and wait for it to fully terminate to release any held resources
+            terminate_and_record(id, &name, old.pid, old.children, &old.exec.stop, "reload");
+        }
 
-            // First, remove the old PID from the registry if it existed
-            // This prevents stale PIDs from accumulating in the registry
-            {
-                let mut pid_registry = PID_REGISTRY.lock().unwrap();
-                if process.pid > 0 {
-                    pid_registry.remove(&process.pid);
-                }
-                if let Some(old_shell_pid) = process.shell_pid {
-                    pid_registry.remove(&old_shell_pid);
-                }
-                drop(pid_registry);
+        return self;
+    }
+
+    /// Start a new instance of process `id` next to the running one and track it in its place
+    /// Returns the process as it was, the caller stops the old instance or rolls back to it
+    fn launch_replacement(&mut self, id: usize) -> Result<Process, String> {
+        let full_config = config::read();
+        let config = full_config.runner;
+        let old = self.process(id).clone();
+        let Process {
+            path,
+            script,
+            name,
+            env,
+            exec,
+            cgroup,
+            ..
+        } = old.clone();
+
+        // Save the current working directory so we can restore it after reload
+        let original_dir = std::env::current_dir().ok();
+        if let Err(err) = std::env::set_current_dir(&path) {
+            return Err(format!("Failed to set working directory {:?}: {}", path, err));
+        }
+
+        let result = resolve_env(&exec, &env, &path).and_then(|resolved| {
+            process_run(ProcessMetadata {
+                args: config.args,
+                name: name.clone(),
+                shell: config.shell,
+                log_path: config.log_path,
+                command: script.to_string(),
+                env: environment::pairs(&resolved),
+                exec: Exec {
+                    cwd: Some(exec.working_dir(&path)),
+                    ..exec.clone()
+                },
+                cgroup: cgroup_path(&full_config.daemon, &name, id, cgroup.as_ref()),
+            })
+        });
+
+        // Restore the original working directory
+        if let Some(dir) = original_dir {
+            if let Err(err) = std::env::set_current_dir(&dir) {
+                log::warn!("Failed to restore working directory after reload: {}", err);
             }
-            
-            // Update process with new PID
+        }
+        let result = result?;
+
+        // First, remove the old PID from the registry if it existed
+        // This prevents stale PIDs from accumulating in the registry
+        {
+            let mut pid_registry = PID_REGISTRY.lock().unwrap();
+            if old.pid > 0 {
+                pid_registry.remove(&old.pid);
+            }
+            if let Some(old_shell_pid) = old.shell_pid {
+                pid_registry.remove(&old_shell_pid);
+            }
+            drop(pid_registry);
+        }
+
+        // Update process with new PID
+        let process = self.process(id);
+        process.pid = result.pid;
+        process.shell_pid = result.shell_pid;
+        process.session_id = result.session_id;
+        process.process_start_time = result.start_time;
+        process.is_process_tree = result.shell_pid.is_some();
+        process.children = vec![];
+        process.cgroup = result.cgroup;
+        process.launching = result.launch;
+        process.health = Health::default();
+        process.running = true;
+        process.started = Utc::now();
+        process.begin_run();
+        // Clear crashed flag after successful restart
+        // This allows the daemon to properly detect if the process crashes again
+        process.crash.crashed = false;
+        // Clear manual_stop flag when process is started/restarted
+        process.manual_stop = false;
+        process.last_action_at = Utc::now();
+
+        // Files and declared variables are read again on every start, only the inherited environment is stored
+        process.env.extend(env::vars());
+        if let Some(instance) = &process.exec.instance {
+            process.env.extend(instance.env());
+        }
+
+        // Don't reset crash counter - keep it to preserve crash history
+        // The daemon will reset it automatically after the process runs successfully
+        // for the grace period (1 second), which provides better visibility into
+        // process stability over time.
+
+        Ok(old)
+    }
+
+    /// Kill the new instance of process `id` and track the old one again
+    fn roll_back(&mut self, id: usize, old: Process) {
+        let new = std::mem::replace(self.process(id), old);
+        terminate_and_record(id, &new.name, new.pid, new.children, &new.exec.stop, "rollback");
+    }
+
+    /// Wait for the new instance of process `id` to pass its readiness check
+    /// Without a check it only has to stay up for rollout::SETTLE_MS
+    fn await_ready(&mut self, id: usize) -> Result<(), String> {
+        loop {
             let process = self.process(id);
-            process.pid = result.pid;
-            process.shell_pid = result.shell_pid;
-            process.session_id = result.session_id;
-            process.process_start_time = result.start_time;
-            process.is_process_tree = result.shell_pid.is_some();
-            process.running = true;
-            process.started = Utc::now();
-            // Clear crashed flag after successful restart
-            // This allows the daemon to properly detect if the process crashes again
-            process.crash.crashed = false;
-            // Clear manual_stop flag when process is started/restarted
-            process.manual_stop = false;
-            process.last_action_at = Utc::now();
+            let elapsed = (Utc::now() - process.started).to_std().unwrap_or_default();
+            if !is_pid_alive(process.shell_pid.unwrap_or(process.pid)) {
+                return Err(format!("exited after {}ms", elapsed.as_millis()));
+            }
 
-            // Merge .env variables into the stored environment (dotenv takes priority)
-            let mut updated_env: Env = env::vars().collect();
-            updated_env.extend(dotenv_vars);
-            process.env.extend(updated_env);
+            match process.exec.ready.as_ref().map(|ready| ready.timeout()) {
+                Some(_) if process.is_ready() => {
+                    process.launching = None;
+                    return Ok(());
+                }
+                Some(timeout) if elapsed > timeout => {
+                    return Err(format!("not ready within {}ms", timeout.as_millis()));
+                }
+                None if elapsed >= Duration::from_millis(rollout::SETTLE_MS) => return Ok(()),
+                _ => std::thread::sleep(Duration::from_millis(rollout::POLL_INTERVAL_MS)),
+            }
+        }
+    }
 
-            // Don't reset crash counter - keep it to preserve crash history
-            // The daemon will reset it automatically after the process runs successfully
-            // for the grace period (1 second), which provides better visibility into
-            // process stability over time.
+    /// Replace the instances of `ids` without downtime, `max_unavailable` at a time
+    /// Each batch starts its new instances, waits for them to become ready and only then stops the old ones.
+    /// A new instance that fails is killed and the old one kept, the batches after it are skipped
+    pub fn rollout(&mut self, ids: &[usize], max_unavailable: Option<usize>) -> Rollout {
+        let mut outcome = Rollout::default();
+        if self.remote.is_some() {
+            for id in ids {
+                self.reload(*id, false, true);
+                outcome.reloaded.push(*id);
+            }
+            return outcome;
+        }
 
-            // Now stop the old process after the new one is running
-            kill_children(old_children);
-            if let Err(err) = process_stop(old_pid) {
-                log::warn!("Failed to stop old process during reload: {err}");
+        let max_unavailable = max_unavailable.unwrap_or_else(|| self.max_unavailable(ids));
+        for batch in rollout::batches(ids, max_unavailable) {
+            if !outcome.rolled_back.is_empty() {
+                outcome.skipped.extend(batch);
+                continue;
             }
 
-            // Wait for old process to fully terminate to release any held resources
-            if !wait_for_process_termination(old_pid) {
-                log::warn!(
-                    "Old process {} did not terminate within timeout during reload",
-                    old_pid
-                );
+            let mut launched = Vec::with_capacity(batch.len());
+            for id in batch {
+                match self.launch_replacement(*id) {
+                    Ok(old) => launched.push((*id, old)),
+                    Err(reason) => outcome.rolled_back.push(RolledBack { id: *id, reason }),
+                }
             }
 
-            // Restore the original working directory
-            if let Some(dir) = original_dir {
-                if let Err(err) = std::env::set_current_dir(&dir) {
-                    log::warn!("Failed to restore working directory after reload: {}", err);
+            let mut ready = Vec::with_capacity(launched.len());
+            for (id, old) in launched {
+                match self.await_ready(id) {
+                    Ok(()) => ready.push((id, old)),
+                    Err(reason) => {
+                        log::warn!("Rolling back reload of '{}' (id={}): {}", old.name, id, reason);
+                        self.roll_back(id, old);
+                        outcome.rolled_back.push(RolledBack { id, reason });
+                    }
                 }
             }
 
-            // Save state after successful reload to persist changes
-            // Use save_direct() when called from daemon (dead=true) to avoid serialization
-            // that would lose fields marked with #[serde(skip)] like the restart counter
-            if dead {
-                self.save_direct();
-            } else {
-                self.save();
+            // The daemon has to see the new instances before the old ones exit
+            self.save();
+            for (id, old) in ready {
+                self.process(id).restarts += 1;
+                terminate_and_record(id, &old.name, old.pid, old.children, &old.exec.stop, "reload");
+                outcome.reloaded.push(id);
             }
+            self.save();
         }
 
-        return self;
+        outcome
+    }
+
+    /// Instances a rollout of `ids` replaces at once, the setting of their group when they all belong to one
+    pub fn max_unavailable(&self, ids: &[usize]) -> usize {
+        let groups: HashSet<&str> = ids
+            .iter()
+            .filter_map(|id| self.info(*id))
+            .filter_map(|process| process.exec.instance.as_ref()?.group.as_deref())
+            .collect();
+        match (groups.len(), ids.iter().all(|id| self.info(*id).is_some_and(|p| p.exec.instance.is_some()))) {
+            (1, true) => groups
+                .into_iter()
+                .next()
+                .and_then(|name| self.groups.get(name))
+                .map_or(rollout::DEFAULT_MAX_UNAVAILABLE, Group::max_unavailable),
+            _ => rollout::DEFAULT_MAX_UNAVAILABLE,
+        }
     }
 
     /// Direct process removal without daemon delegation
@@ -1363,6 +1861,9 @@ impl Runner {
             .info(id)
             .map(|p| p.children.clone())
             .unwrap_or_default();
+        let cgroup = self.info(id).and_then(|p| p.cgroup.clone());
+        let name = self.info(id).map(|p| p.name.clone()).unwrap_or_default();
+        let stop = self.info(id).map(|p| p.exec.stop.clone()).unwrap_or_default();
 
         // Mark as stopped first to prevent auto-restart during removal
         // This is important if daemon is running and monitoring processes
@@ -1381,16 +1882,7 @@ impl Runner {
 
         // Now kill the actual process using the saved PID info
         if pid > 0 {
-            kill_children(children);
-            let _ = process_stop(pid);
-
-            // Wait for process termination
-            if !wait_for_process_termination(pid) {
-                log::warn!(
-                    "Process {} did not terminate within timeout during remove",
-                    pid
-                );
-            }
+            terminate_and_record(id, &name, pid, children, &stop, "remove");
 
             // Remove child handle from global state if it exists
             let handle_pid = shell_pid.unwrap_or(pid);
@@ -1408,6 +1900,11 @@ impl Runner {
             }
             drop(pid_registry);
         }
+
+        if let Some(cgroup) = cgroup {
+            unix::cgroup::kill(&cgroup.path);
+            unix::cgroup::remove(&cgroup.path);
+        }
     }
 
     pub fn remove(&mut self, id: usize) {
@@ -1457,6 +1954,8 @@ impl Runner {
             return; // Don't compact remote processes
         }
 
+        self.prune_groups();
+
         // If list is empty, reset ID counter to 0
         if self.list.is_empty() {
             self.id = id::Id::new(0);
@@ -1661,6 +2160,15 @@ impl Runner {
         return self;
     }
 
+    /// Apply the named environment of a process from its next start, `None` goes back to the plain `env`
+    pub fn select_env(&mut self, id: usize, name: Option<String>) -> Result<&mut Self, String> {
+        let environment = &mut self.process(id).exec.environment;
+        let selected = Environment { selected: name, ..environment.clone() };
+        selected.validate()?;
+        *environment = selected;
+        Ok(self)
+    }
+
     pub fn clear_env(&mut self, id: usize) -> &mut Self {
         if let Some(remote) = &self.remote {
             if let Err(err) = http::clear_env(remote, id) {
@@ -1756,6 +2264,9 @@ impl Runner {
             let pid_to_check = process_to_stop.pid;
             let shell_pid = process_to_stop.shell_pid;
             let children = process_to_stop.children.clone();
+            let cgroup = process_to_stop.cgroup.clone();
+            let name = process_to_stop.name.clone();
+            let stop = process_to_stop.exec.stop.clone();
 
             // CRITICAL: Set manual_stop flag BEFORE killing the process
             // This prevents a race condition where the daemon detects the process death
@@ -1771,31 +2282,31 @@ impl Runner {
             self.save();
 
             // Now kill the process - daemon will see manual_stop=true if it checks
-            kill_children(children);
-            let _ = process_stop(pid_to_check); // Continue even if stopping fails
+            // waiting until Process is terminated, escalating to SIGKILL after kill_timeout_ms
+            terminate_and_record(id, &name, pid_to_check, children, &stop, "stop");
 
-            // waiting until Process is terminated
-            if !wait_for_process_termination(pid_to_check) {
-                log::warn!(
-                    "Process {} did not terminate within timeout during stop",
-                    pid_to_check
-                );
+            // Take down anything that escaped the process tree (double forks, setsid)
+            if let Some(cgroup) = cgroup.as_ref().filter(|_| stop.kill_mode != KillMode::Main) {
+                unix::cgroup::kill(&cgroup.path);
             }
 
             // Remove child handle from global state if it exists
             // Use shell_pid if available, otherwise try regular pid
             let handle_pid = shell_pid.unwrap_or(pid_to_check);
+            let mut exit = None;
             if let Some((_, handle)) = PROCESS_HANDLES.remove(&handle_pid) {
                 // Wait for the child process to complete and reap it
                 if let Ok(mut child) = handle.lock() {
-                    if let Err(e) = child.wait() {
-                        log::warn!("Failed to wait for child process {}: {}", handle_pid, e);
+                    match child.wait() {
+                        Ok(status) => exit = Some(status.into()),
+                        Err(e) => log::warn!("Failed to wait for child process {}: {}", handle_pid, e),
                     }
                 }
             }
 
             // Clear PIDs after process is fully stopped
             let process = self.process(id);
+            process.finish_run(exit, false);
             // Keep restarts counter to preserve restart history - only reset via reset_counters()
             process.children = vec![];
             // Set PID to 0 to indicate no valid PID and prevent monitor from treating this as a crash
@@ -1810,6 +2321,90 @@ impl Runner {
         return self;
     }
 
+    /// Kill an instance that did not pass its readiness check in time
+    /// The process stays marked as running and crashed, so the daemon retries it as a failed start
+    pub fn fail_launch(&mut self, id: usize) -> &mut Self {
+        let process = self.process(id);
+        let (pid, name) = (process.pid, process.name.clone());
+        let timeout = process.exec.ready.as_ref().map(|ready| ready.timeout()).unwrap_or_default();
+        let exit = self.end_instance(id, "failed start");
+
+        let message = format!("Process '{}' did not become ready within {}ms", name, timeout.as_millis());
+        let reason = format!("not ready within {}ms", timeout.as_millis());
+        let process = self.process(id);
+        process.record_crash(CrashRecord::new(process.started, exit, false, Some(reason)));
+        process.crash.crashed = true;
+        process.failed_restart_attempts += 1;
+        process.last_restart_attempt = Some(Utc::now());
+
+        log::warn!("{} (id={}, pid={})", message, id, pid);
+        crate::events::emit(crate::events::Event::new(
+            crate::events::EventType::ProcessCrash,
+            "local".to_string(),
+            "Local".to_string(),
+            Some(id.to_string()),
+            Some(name),
+            message,
+        ));
+
+        self
+    }
+
+    /// Kill a job run that exceeded its timeout_ms, a cron job then waits for its next run
+    pub fn timeout_run(&mut self, id: usize) -> &mut Self {
+        let process = self.process(id);
+        let name = process.name.clone();
+        let timeout = process.exec.job.as_ref().and_then(Job::timeout).unwrap_or_default();
+        let exit = self.end_instance(id, "job timeout");
+
+        let message = format!("Job '{}' timed out after {}ms", name, timeout.as_millis());
+        let reason = format!("run timed out after {}ms", timeout.as_millis());
+        let process = self.process(id);
+        process.finish_run(exit, true);
+        process.record_crash(CrashRecord::new(process.started, exit, false, Some(reason)));
+        process.crash.crashed = false;
+        process.running = process.exec.job.as_ref().is_some_and(|job| job.cron.is_some());
+
+        log::warn!("{} (id={})", message, id);
+        crate::events::emit(crate::events::Event::new(
+            crate::events::EventType::ProcessCrash,
+            "local".to_string(),
+            "Local".to_string(),
+            Some(id.to_string()),
+            Some(name),
+            message,
+        ));
+
+        self
+    }
+
+    /// Kill the current instance of a process with its stop policy and collect its exit status
+    fn end_instance(&mut self, id: usize, action: &str) -> Option<restart::Exit> {
+        let process = self.process(id);
+        let (pid, shell_pid, children, cgroup) =
+            (process.pid, process.shell_pid, process.children.clone(), process.cgroup.clone());
+        let (name, stop) = (process.name.clone(), process.exec.stop.clone());
+
+        terminate_and_record(id, &name, pid, children, &stop, action);
+        if let Some(cgroup) = cgroup.as_ref().filter(|_| stop.kill_mode != KillMode::Main) {
+            unix::cgroup::kill(&cgroup.path);
+        }
+
+        let mut exit = None;
+        if let Some((_, handle)) = PROCESS_HANDLES.remove(&shell_pid.unwrap_or(pid)) {
+            if let Ok(mut child) = handle.lock() {
+                exit = child.wait().ok().map(restart::Exit::from);
+            }
+        }
+
+        let process = self.process(id);
+        process.pid = 0;
+        process.shell_pid = None;
+        process.children = vec![];
+        process.launching = None;
+        exit
+    }
+
     pub fn flush(&mut self, id: usize) -> &mut Self {
         if let Some(remote) = &self.remote {
             if let Err(err) = http::flush(remote, id) {
@@ -1842,13 +2437,8 @@ impl Runner {
         return self;
     }
 
-    pub fn watch(&mut self, id: usize, path: &str, enabled: bool) -> &mut Self {
-        let process = self.process(id);
-        process.watch = Watch {
-            enabled,
-            path: string!(path),
-            hash: ternary!(enabled, hash::create(process.path.join(path)), string!("")),
-        };
+    pub fn watch(&mut self, id: usize, watch: Watch) -> &mut Self {
+        self.process(id).watch = watch;
 
         return self;
     }
@@ -1861,7 +2451,25 @@ impl Runner {
         return self;
     }
 
-    pub fn find(&self, name: &str, server_name: &String) -> Option<usize> {
+    pub fn find(&self, name: &str, server_name: &String) -> Option<usize> {
+        self.on_server(server_name)
+            .list
+            .iter()
+            .find(|(_, p)| p.name == name)
+            .map(|(id, _)| *id)
+    }
+
+    /// Processes named `name`, or the instances of the group with that name
+    pub fn find_all(&self, name: &str, server_name: &String) -> Vec<usize> {
+        let runner = self.on_server(server_name);
+        match runner.list.iter().find(|(_, p)| p.name == name) {
+            Some((id, _)) => vec![*id],
+            None => runner.group_instances(name),
+        }
+    }
+
+    /// This runner for local servers, otherwise the state fetched from the named remote server
+    fn on_server(&self, server_name: &String) -> Runner {
         let mut runner = self.clone();
 
         if !matches!(&**server_name, "internal" | "local") {
@@ -1886,10 +2494,139 @@ impl Runner {
         }
 
         runner
+    }
+
+    /// Process ids of the instances of a group, ordered by their position in it
+    pub fn group_instances(&self, name: &str) -> Vec<usize> {
+        let mut instances: Vec<(usize, usize)> = self
             .list
             .iter()
-            .find(|(_, p)| p.name == name)
-            .map(|(id, _)| *id)
+            .filter_map(|(id, p)| {
+                let instance = p.exec.instance.as_ref()?;
+                (instance.group.as_deref() == Some(name)).then_some((instance.id, *id))
+            })
+            .collect();
+        instances.sort_unstable();
+        instances.into_iter().map(|(_, id)| id).collect()
+    }
+
+    /// Instances of a group the balancer may send connections to: running, ready and passing their liveness probe
+    pub fn balancer_backends(&self, name: &str) -> Vec<&Process> {
+        self.group_instances(name)
+            .into_iter()
+            .filter_map(|id| self.list.get(&id))
+            .filter(|p| p.running && p.pid > 0 && !p.crash.crashed && p.launching.is_none())
+            .filter(|p| p.health.status != HealthStatus::Unhealthy && is_pid_alive(p.pid))
+            .filter(|p| p.exec.instance.as_ref().is_some_and(|instance| instance.port.is_some()))
+            .collect()
+    }
+
+    /// Forget groups whose instances were all removed
+    pub fn prune_groups(&mut self) {
+        let list = &self.list;
+        self.groups.retain(|name, _| {
+            list.values()
+                .any(|p| p.exec.instance.as_ref().is_some_and(|instance| instance.group.as_deref() == Some(name)))
+        });
+    }
+
+    /// Add or remove instances of a group until it has `count`, returning how many it had
+    /// The highest positions are removed first and the rest are renumbered, so ports stay contiguous
+    /// An instance whose position or port changed is restarted, new ones are started like the first instance
+    pub fn scale(&mut self, name: &str, count: usize) -> Result<usize, String> {
+        if self.remote.is_some() {
+            return Err("Groups can only be scaled on the local server".to_string());
+        }
+        let Some(mut group) = self.groups.get(name).cloned() else {
+            return Err(format!("Group '{}' does not exist", name));
+        };
+        group.instances = count;
+        group.validate(name)?;
+
+        let instances = self.group_instances(name);
+        let Some(template) = instances.first().and_then(|id| self.info(*id)).cloned() else {
+            return Err(format!("Group '{}' has no instances to copy", name));
+        };
+
+        // Highest id first, removing a process compacts the ids above it
+        let mut surplus: Vec<usize> = instances.iter().skip(count).copied().collect();
+        surplus.sort_unstable_by(|a, b| b.cmp(a));
+        for id in surplus {
+            self.remove(id);
+        }
+
+        self.groups.insert(name.to_string(), group.clone());
+        for (index, id) in self.group_instances(name).into_iter().enumerate() {
+            let instance = group.instance(name, index);
+            let process = self.process(id);
+            let moved = process
+                .exec
+                .instance
+                .as_ref()
+                .is_none_or(|current| current.id != index || current.port != instance.port);
+            let restart = moved && process.running;
+
+            process.env.extend(instance.env());
+            process.exec.instance = Some(instance);
+            if restart {
+                self.restart(id, false, false);
+            }
+        }
+
+        let watch = template.watch.enabled.then(|| template.watch.clone());
+        // Instances keep their names, new ones take the lowest free worker number
+        let mut number = 0;
+        for index in instances.len()..count {
+            while self.list.values().any(|p| p.name == Group::instance_name(name, number)) {
+                number += 1;
+            }
+            let exec = Exec {
+                instance: Some(group.instance(name, index)),
+                ..template.exec.clone()
+            };
+            self.start(
+                &Group::instance_name(name, number),
+                &template.script,
+                template.path.clone(),
+                &watch,
+                template.max_memory,
+                exec,
+            );
+        }
+
+        Ok(instances.len())
+    }
+
+    /// First dependency that is not up yet, dependencies on processes that do not exist are skipped
+    pub fn pending_dependency<'a>(&self, depends_on: &'a [Dependency]) -> Option<&'a Dependency> {
+        depends_on.iter().find(|dependency| {
+            self.list
+                .values()
+                .find(|process| process.name == dependency.name)
+                .is_some_and(|process| !process.satisfies(dependency.condition))
+        })
+    }
+
+    /// The given processes ordered so each comes after its dependencies, an error when they form a cycle
+    pub fn start_order(&self, ids: &[usize]) -> Result<Vec<usize>, String> {
+        let nodes: Vec<(usize, &str, &[Dependency])> = ids
+            .iter()
+            .filter_map(|id| self.list.get(id).map(|p| (*id, p.name.as_str(), p.exec.depends_on.as_slice())))
+            .collect();
+        deps::start_order(&nodes)
+    }
+
+    /// Reject dependencies of a new process that would close a cycle with the existing ones
+    pub fn check_dependencies(&self, name: &str, depends_on: &[Dependency]) -> Result<(), String> {
+        deps::validate(name, depends_on)?;
+        let mut nodes: Vec<(usize, &str, &[Dependency])> = vec![(usize::MAX, name, depends_on)];
+        nodes.extend(
+            self.list
+                .iter()
+                .filter(|(_, p)| p.name != name)
+                .map(|(id, p)| (*id, p.name.as_str(), p.exec.depends_on.as_slice())),
+        );
+        deps::start_order(&nodes).map(|_| ())
     }
 
     /// Helper method to build ProcessItem from Process
@@ -1935,8 +2672,13 @@ impl Runner {
         let process_actually_running = item.running && any_descendant_alive;
 
         let crash_detection_enabled = config::read().daemon.crash_detection;
-        let status = if process_actually_running {
+        let status = if process_actually_running && item.launching.is_some() {
+            // Alive but the readiness check has not passed yet
+            string!("launching")
+        } else if process_actually_running {
             string!("online")
+        } else if let Some(status) = item.job_status() {
+            string!(status)
         } else if item.running {
             // Process is marked as running but PID is not alive.
             // Use longer grace period to account for slow-starting processes
@@ -1995,11 +2737,12 @@ impl Runner {
             restarts: item.restarts,
             name: item.name.clone(),
             start_time: item.started,
-            watch_path: item.watch.path.clone(),
+            watch_path: item.watch.paths.join(", "),
             uptime,
             agent_id: item.agent_id.clone(),
             agent_name: None,
             agent_api_endpoint: None,
+            group: item.exec.instance.as_ref().and_then(|instance| instance.group.clone()),
         }
     }
 
@@ -2047,10 +2790,39 @@ impl LogInfo {
                 self.error
             );
         }
+
+        rotation::remove(Path::new(&self.out));
+        rotation::remove(Path::new(&self.error));
+
+        if Path::new(&self.combined).exists() {
+            if let Err(err) = File::create(&self.combined) {
+                log::error!("{err}");
+                crashln!("{} Failed to purge logs (path={})", *helpers::FAIL, self.combined);
+            }
+            rotation::remove(Path::new(&self.combined));
+        }
     }
 }
 
 impl Process {
+    /// Whether a launching instance passed its readiness check, which runs in the background between calls
+    pub fn is_ready(&self) -> bool {
+        let (Some(ready), Some(launch)) = (&self.exec.ready, &self.launching) else {
+            return true;
+        };
+        if health::finished_readiness(self.id, self.pid) == Some(true) {
+            return true;
+        }
+
+        let mut pids = vec![self.pid];
+        pids.extend(self.shell_pid);
+        pids.extend(&self.children);
+        pids.extend(process_find_children(self.pid));
+
+        ready.spawn(self.id, self.pid, self.logs().out, launch.log_offset, pids);
+        false
+    }
+
     /// Get a log paths of the process item
     pub fn logs(&self) -> LogInfo {
         let name = self.name.replace(" ", "_");
@@ -2058,6 +2830,7 @@ impl Process {
         LogInfo {
             out: global!("opm.logs.out", name.as_str()),
             error: global!("opm.logs.error", name.as_str()),
+            combined: global!("opm.logs.combined", name.as_str()),
         }
     }
 }
@@ -2085,14 +2858,14 @@ impl ProcessWrapper {
         lock!(self.runner).rename(self.id, name);
     }
 
-    /// Enable watching a path on the process item
-    pub fn watch(&mut self, path: &str) {
-        lock!(self.runner).watch(self.id, path, true);
+    /// Enable watching paths on the process item
+    pub fn watch(&mut self, watch: Watch) {
+        lock!(self.runner).watch(self.id, watch);
     }
 
     /// Disable watching on the process item
     pub fn disable_watch(&mut self) {
-        lock!(self.runner).watch(self.id, "", false);
+        lock!(self.runner).watch(self.id, Watch::default());
     }
 
     /// Set the process item as crashed
@@ -2165,8 +2938,12 @@ impl ProcessWrapper {
             }
         }
 
-        let status = if process_actually_running {
+        let status = if process_actually_running && item.launching.is_some() {
+            string!("launching")
+        } else if process_actually_running {
             string!("online")
+        } else if let Some(status) = item.job_status() {
+            string!(status)
         } else if item.running {
             // Process is marked as running but PID is not alive.
             // Use grace period to account for slow-starting processes and restart windows
@@ -2228,6 +3005,13 @@ impl ProcessWrapper {
                     config.args.join(" "),
                     item.script.clone()
                 ),
+                exec: Exec {
+                    cwd: Some(item.exec.working_dir(&item.path)),
+                    ..item.exec.clone()
+                },
+                limits: ternary!(item.running, unix::rlimits::read(item.pid), None),
+                health: item.health.clone(),
+                crash_history: item.crash_history.clone(),
             },
             stats: Stats {
                 cpu_percent,
@@ -2235,11 +3019,7 @@ impl ProcessWrapper {
                 restarts: item.restarts,
                 start_time: item.started.timestamp_millis(),
             },
-            watch: Watch {
-                enabled: item.watch.enabled,
-                hash: item.watch.hash.clone(),
-                path: item.watch.path.clone(),
-            },
+            watch: item.watch.clone(),
             log: Log {
                 out: item.logs().out,
                 error: item.logs().error,
@@ -2859,9 +3639,19 @@ pub fn is_any_descendant_alive(root_pid: i64, children: &[i64]) -> bool {
 /// Enhanced process tree check using sysinfo for more robust detection
 /// This checks if the process or any of its descendants are alive, using sysinfo
 /// for better cross-platform process tree traversal.
-pub fn is_process_or_children_alive_sysinfo(root_pid: i64, tracked_children: &[i64]) -> bool {
+/// When the process has a readable cgroup its membership is the answer, which also
+/// covers descendants that double-forked or reparented away from the tree
+pub fn is_process_or_children_alive_sysinfo(
+    root_pid: i64,
+    tracked_children: &[i64],
+    cgroup: Option<&Cgroup>,
+) -> bool {
     use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
 
+    if let Some(populated) = cgroup.and_then(Cgroup::is_populated) {
+        return populated;
+    }
+
     // Quick check first - if root PID is alive, return immediately
     if is_pid_alive(root_pid) {
         return true;
@@ -3167,62 +3957,206 @@ pub struct ProcessRunResult {
     pub shell_pid: Option<i64>,
     pub session_id: Option<i64>,
     pub start_time: Option<u64>,
+    pub cgroup: Option<Cgroup>,
+    /// Readiness state when the process has a readiness check
+    pub launch: Option<Launch>,
+}
+
+impl ProcessRunResult {
+    /// A process registered without an instance yet, such as a job waiting for its first run
+    pub fn pending() -> Self {
+        ProcessRunResult {
+            pid: 0,
+            shell_pid: None,
+            session_id: None,
+            start_time: None,
+            cgroup: None,
+            launch: None,
+        }
+    }
+}
+
+/// Words of a command line split with POSIX shell quoting rules
+#[derive(Debug, PartialEq)]
+struct ShellWords {
+    /// Words with quotes and escapes removed
+    words: Vec<String>,
+    /// Set when an unquoted operator, expansion or keyword was seen that only a shell can interpret
+    needs_shell: bool,
+}
+
+/// First words the shell treats specially (built-ins that change the shell itself, and keywords)
+const SHELL_BUILTINS: &[&str] = &[
+    "export", "source", ".", "alias", "cd", "eval", "exec", "set", "unset", "ulimit", "if", "for",
+    "while", "until", "case", "{", "!",
+];
+
+/// State while splitting a command into words
+#[derive(Default)]
+struct WordSplitter {
+    words: Vec<String>,
+    word: String,
+    /// A word can be empty ("") and still count, so this is tracked separately from word.is_empty()
+    in_word: bool,
+    /// Unquoted glob character in the current word
+    has_glob: bool,
+    needs_shell: bool,
+}
+
+impl WordSplitter {
+    fn push(&mut self, c: char) {
+        self.in_word = true;
+        self.word.push(c);
+    }
+
+    fn finish_word(&mut self) {
+        if self.in_word {
+            // ? and & are part of the query string in URLs, not patterns
+            if self.has_glob && !self.word.contains("://") {
+                self.needs_shell = true;
+            }
+            self.words.push(std::mem::take(&mut self.word));
+        }
+        self.in_word = false;
+        self.has_glob = false;
+    }
+}
+
+/// Split a command into words the way `sh` would, without performing any expansion
+/// Operators and expansions are kept literally in the words but flag the command as needing a shell
+fn split_shell_words(command: &str) -> ShellWords {
+    let mut splitter = WordSplitter::default();
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => splitter.finish_word(),
+            '\n' => {
+                // An unquoted newline separates commands
+                splitter.needs_shell = true;
+                splitter.finish_word();
+            }
+            '\'' => {
+                splitter.in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => splitter.push(c),
+                        // Unterminated quote, let the shell report it
+                        None => {
+                            splitter.needs_shell = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            '"' => {
+                splitter.in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('$' | '`' | '"' | '\\')) => splitter.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                splitter.push('\\');
+                                splitter.push(c);
+                            }
+                            None => splitter.push('\\'),
+                        },
+                        Some(c @ ('$' | '`')) => {
+                            // Parameter expansion and command substitution still happen inside double quotes
+                            splitter.needs_shell = true;
+                            splitter.push(c);
+                        }
+                        Some(c) => splitter.push(c),
+                        None => {
+                            splitter.needs_shell = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                // Line continuation
+                Some('\n') => {}
+                Some(c) => splitter.push(c),
+                None => splitter.push('\\'),
+            },
+            '|' | '&' | ';' | '<' | '>' | '(' | ')' | '`' => {
+                splitter.needs_shell = true;
+                splitter.push(c);
+            }
+            '$' => {
+                // A lone $ is literal, anything that starts an expansion is not
+                let expands = chars
+                    .peek()
+                    .is_some_and(|next| next.is_ascii_alphanumeric() || "_{(@*#?$!-".contains(*next));
+                splitter.needs_shell |= expands;
+                splitter.push(c);
+            }
+            '#' | '~' if !splitter.in_word => {
+                // Comment or tilde expansion, both only at the start of a word
+                splitter.needs_shell = true;
+                splitter.push(c);
+            }
+            '*' | '?' | '[' => {
+                splitter.has_glob = true;
+                splitter.push(c);
+            }
+            c => splitter.push(c),
+        }
+    }
+    splitter.finish_word();
+
+    let WordSplitter {
+        words,
+        mut needs_shell,
+        ..
+    } = splitter;
+
+    if let Some(first) = words.first() {
+        // Leading NAME=value assignments and built-ins are handled by the shell itself
+        let is_assignment = first.split_once('=').is_some_and(|(name, _)| {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        if is_assignment || SHELL_BUILTINS.contains(&first.as_str()) {
+            needs_shell = true;
+        }
+    }
+
+    ShellWords { words, needs_shell }
 }
 
 /// Check if a command contains shell-specific features that require shell interpretation
 /// Returns true if the command needs to be run through a shell (sh/bash)
 /// Returns false if the command can be spawned directly for PM2-like behavior
 ///
-/// Note: This is a heuristic check and may have false positives for complex edge cases
-/// (e.g., backticks in quoted strings, & in URLs). For critical use cases where direct
-/// execution must be guaranteed, prefer explicit command construction.
+/// Only unquoted operators, expansions and built-ins count: `--title "a | b"` is direct
 fn command_needs_shell(command: &str) -> bool {
-    // Shell operators and features that require shell interpretation
-    let shell_features = [
-        "&&", "||", "|", // Logical operators and pipes
-        ">", ">>", "<", // Redirection
-        ";", // Command separator
-        "`", "$(", // Command substitution
-        "~",  // Home directory expansion
-        "*", "?", "[", // Glob patterns
-        "export ", "source ", "alias ", // Shell built-ins
-    ];
-
-    shell_features
-        .iter()
-        .any(|feature| command.contains(feature))
+    split_shell_words(command).needs_shell
 }
 
 /// Parse command into program and arguments for direct execution
-/// Returns None if parsing fails or command is complex
-///
-/// Note: This uses simple whitespace-based splitting and does NOT handle:
-/// - Quoted arguments with spaces (e.g., program "arg with spaces")
-/// - Escaped characters
-/// - Complex shell quoting rules
-/// For commands requiring such features, they will be detected by command_needs_shell()
-/// and executed through a shell instead.
+/// Quotes and escapes are removed following POSIX shell rules
+/// Returns None if the command has no words
 fn parse_direct_command(command: &str) -> Option<(String, Vec<String>)> {
-    let trimmed = command.trim();
-    if trimmed.is_empty() {
-        return None;
-    }
-
-    // Simple whitespace-based splitting
-    // This handles basic cases like "node server.js" or "python app.py"
-    let mut parts: Vec<String> = trimmed.split_whitespace().map(|s| s.to_string()).collect();
-
-    if parts.is_empty() {
+    let mut words = split_shell_words(command).words;
+    if words.is_empty() {
         return None;
     }
 
-    let program = parts.remove(0);
-    Some((program, parts))
+    let program = words.remove(0);
+    Some((program, words))
 }
 
 /// Run the process
 pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String> {
     use std::fs::{self, OpenOptions};
+    use std::os::fd::AsRawFd;
+    use std::os::unix::ffi::OsStringExt;
     use std::process::{Command, Stdio};
 
     let log_base = format!("{}/{}", metadata.log_path, metadata.name.replace(' ', "_"));
@@ -3268,15 +4202,48 @@ pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String
             )
         })?;
 
+    // Captured output goes through pipes a relay reads, the log files are still created for the readiness check
+    let (stdout, stderr) = match &metadata.exec.log_capture {
+        Some(capture) => {
+            let target = capture::Target {
+                name: metadata.name.clone(),
+                instance: metadata.exec.instance.as_ref().map(|instance| instance.id),
+                out: stdout_path.clone().into(),
+                error: stderr_path.clone().into(),
+                combined: capture.combined.then(|| format!("{}-combined.log", log_base).into()),
+                format: capture.format,
+            };
+            match capture::pipes(&target) {
+                Ok((out, error)) => (Stdio::from(out), Stdio::from(error)),
+                Err(err) => {
+                    log::warn!("Failed to capture the output of '{}' ({}), writing it straight to the logs", metadata.name, err);
+                    (Stdio::from(stdout_file), Stdio::from(stderr_file))
+                }
+            }
+        }
+        None => (Stdio::from(stdout_file), Stdio::from(stderr_file)),
+    };
+
     // PM2-like execution strategy: spawn directly if possible, otherwise use shell
     // This eliminates the intermediate shell PID problem for simple commands
-    let use_direct_spawn = !command_needs_shell(&metadata.command);
+    // exec_mode overrides the detection in either direction
+    let use_direct_spawn = metadata.exec.resolve_mode(&metadata.command) == ExecMode::Direct;
+
+    // With listen sockets a small sh execs the command in its place, the only way to export the pid it will have as LISTEN_PID
+    let new_command = |program: &str| match metadata.exec.listen.is_empty() {
+        true => Command::new(program),
+        false => {
+            let mut command = Command::new("/bin/sh");
+            command.arg("-c").arg(listen::EXEC_WITH_PID).arg("opm").arg(program);
+            command
+        }
+    };
 
     let mut cmd = if use_direct_spawn {
         // Try to parse and spawn directly without shell wrapper
         if let Some((program, args)) = parse_direct_command(&metadata.command) {
             log::debug!("Spawning '{}' directly without shell wrapper", program);
-            let mut command = Command::new(&program);
+            let mut command = new_command(&program);
             command.args(&args);
             command
         } else {
@@ -3285,7 +4252,7 @@ pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String
                 "Direct spawn parsing failed, using shell: {}",
                 metadata.shell
             );
-            let mut command = Command::new(&metadata.shell);
+            let mut command = new_command(&metadata.shell);
             command.args(&metadata.args).arg(&metadata.command);
             command
         }
@@ -3296,23 +4263,96 @@ pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String
             "Using configured shell '{}' for command with shell operators",
             metadata.shell
         );
-        let mut command = Command::new(&metadata.shell);
+        let mut command = new_command(&metadata.shell);
         command.args(&metadata.args).arg(&metadata.command);
         command
     };
 
-    cmd.envs(metadata.env.iter().map(|env_var| {
-        let parts: Vec<&str> = env_var.splitn(2, '=').collect();
-        if parts.len() == 2 {
-            (parts[0], parts[1])
-        } else {
-            (env_var.as_str(), "")
-        }
-    }))
-    .stdout(Stdio::from(stdout_file))
-    .stderr(Stdio::from(stderr_file))
+    // Secrets are decrypted here only, everything stored or shown keeps the secret:// reference
+    cmd.envs(crate::secret::reveal(&metadata.env)?)
+    .stdout(stdout)
+    .stderr(stderr)
     .stdin(Stdio::null());
 
+    // The worker position wins over anything inherited, so a stray PORT cannot override the assigned one
+    if let Some(instance) = &metadata.exec.instance {
+        cmd.envs(instance.env());
+    }
+    if !metadata.exec.listen.is_empty() {
+        cmd.envs(listen::env(&metadata.exec.listen));
+    }
+
+    // Taken before spawning so the log check only sees output from this instance
+    let launch = metadata.exec.ready.as_ref().map(|ready| {
+        if ready.notify {
+            cmd.env("NOTIFY_SOCKET", global!("opm.notify"));
+        }
+        Launch::begin(&stdout_path)
+    });
+
+    if let Some(cwd) = &metadata.exec.cwd {
+        cmd.current_dir(cwd);
+    }
+
+    // Resolve user/group before forking - NSS lookups are not safe in pre_exec
+    let credentials = unix::credentials::resolve(
+        metadata.exec.user.as_deref(),
+        metadata.exec.group.as_deref(),
+    )?;
+
+    // Match what login would set so the child does not inherit the daemon's identity
+    if let Some(user) = &credentials.name {
+        cmd.env("USER", user).env("LOGNAME", user);
+    }
+    if let Some(home) = &credentials.home {
+        cmd.env("HOME", home);
+    }
+
+    // Bound per spawn and dropped once the child holds it, an idle copy here would still be handed connections
+    let shared_socket = match &metadata.exec.instance {
+        Some(Instance { port: Some(port), shared_port: true, .. }) => Some(instance::bind_shared(*port)?),
+        _ => None,
+    };
+    let shared_fd = shared_socket.as_ref().map(|socket| socket.as_raw_fd());
+    // Copies of the sockets the daemon keeps bound across restarts
+    let listen_sockets = listen::sockets(&metadata.exec.listen)?;
+    let listen_fds = listen::raw(&listen_sockets);
+    let umask = metadata.exec.umask;
+    let limits = metadata.exec.limits.clone();
+    limits.check()?;
+    let missing_cwd = metadata.exec.cwd.as_ref().filter(|cwd| !cwd.is_dir());
+    let switches_identity = credentials.uid.is_some() || credentials.gid.is_some();
+
+    // Limits are in place before the child runs its first instruction
+    // Without explicit limits a broken backend only costs us membership tracking
+    let resources = &metadata.exec.resources;
+    let cgroup = match &metadata.cgroup {
+        Some(path) => match unix::cgroup::create(path, resources) {
+            Ok(cgroup) => Some(cgroup),
+            Err(err) if resources.is_empty() => {
+                log::warn!("cgroup backend unavailable for '{}': {}", metadata.name, err);
+                None
+            }
+            Err(err) => return Err(err),
+        },
+        None => {
+            if !resources.is_empty() {
+                log::warn!(
+                    "Ignoring resource limits for '{}': enable daemon.cgroup in config.toml",
+                    metadata.name
+                );
+            }
+            None
+        }
+    };
+    let cgroup_procs = match &cgroup {
+        Some(cgroup) => Some(
+            std::ffi::CString::new(cgroup.path.join("cgroup.procs").into_os_string().into_vec())
+                .map_err(|_| format!("Invalid cgroup path {}", cgroup.path.display()))?,
+        ),
+        None => None,
+    };
+
     // Create a new session for better process tree management
     // This uses setsid() to create a new session where this process is the session leader
     // This ensures all children inherit the same session ID for robust tracking
@@ -3320,9 +4360,25 @@ pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String
     {
         use std::os::unix::process::CommandExt;
         unsafe {
-            cmd.pre_exec(|| {
+            cmd.pre_exec(move || {
                 // Create new session - this process becomes session leader
                 libc::setsid();
+                if let Some(mask) = umask {
+                    libc::umask(mask as libc::mode_t);
+                }
+                if let Some(procs) = &cgroup_procs {
+                    unix::cgroup::join(procs)?;
+                }
+                if let Some(fd) = shared_fd {
+                    instance::inherit(fd)?;
+                }
+                if !listen_fds.is_empty() {
+                    listen::inherit(&listen_fds)?;
+                }
+                // Raising hard limits needs root, so this also happens before the identity switch
+                limits.apply()?;
+                // Drop privileges last so setsid/umask still run with the daemon's rights
+                unix::credentials::apply(&credentials)?;
                 Ok(())
             });
         }
@@ -3331,6 +4387,22 @@ pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String
     let child = cmd.spawn().map_err(|err| {
         // Provide more helpful error messages based on error kind
         match err.kind() {
+            std::io::ErrorKind::PermissionDenied if switches_identity => {
+                format!(
+                    "Failed to spawn process: Cannot switch to user={} group={}. \
+                    The daemon must run as root to change identity. \
+                    Error: {:?}",
+                    metadata.exec.user.as_deref().unwrap_or("-"),
+                    metadata.exec.group.as_deref().unwrap_or("-"),
+                    err
+                )
+            }
+            std::io::ErrorKind::NotFound if missing_cwd.is_some() => {
+                format!(
+                    "Failed to spawn process: Working directory '{}' does not exist",
+                    missing_cwd.unwrap().display()
+                )
+            }
             std::io::ErrorKind::NotFound => {
                 if use_direct_spawn {
                     if let Some((program, _)) = parse_direct_command(&metadata.command) {
@@ -3394,6 +4466,10 @@ pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String
         }
     })?;
 
+    // The child has its own copy of the shared socket now
+    drop(shared_socket);
+    drop(listen_sockets);
+
     // PID of the process spawned by Command::spawn()
     // For direct spawns: this is the application PID
     // For shell-wrapped spawns: this is the shell wrapper PID
@@ -3443,6 +4519,8 @@ pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String
         shell_pid: shell_pid_opt,
         session_id,
         start_time,
+        cgroup,
+        launch,
     })
 }
 
@@ -3458,6 +4536,7 @@ mod tests {
             id: id::Id::new(1),
             list: BTreeMap::new(),
             remote: None,
+            groups: BTreeMap::new(),
         }
     }
 
@@ -3501,11 +4580,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -3519,6 +4594,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         assert_eq!(process.restart_cooldown_remaining_secs(), 0);
@@ -3538,11 +4619,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -3556,11 +4633,17 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         let remaining = process.restart_cooldown_remaining_secs();
         assert!(remaining > 0);
-        assert!(remaining <= FAILED_RESTART_COOLDOWN_SECS);
+        assert!(remaining <= process.restart_delay().as_secs());
         assert!(process.is_in_restart_cooldown());
     }
 
@@ -3577,11 +4660,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -3591,12 +4670,19 @@ mod tests {
             manual_stop: false,
             errored: false,
             last_restart_attempt: Some(
-                Utc::now() - chrono::Duration::seconds(RESTART_COOLDOWN_SECS as i64 + 1),
+                Utc::now()
+                    - chrono::Duration::milliseconds(restart::DEFAULT_RESTART_DELAY_MS as i64 + 1000),
             ),
             failed_restart_attempts: 0,
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         assert_eq!(process.restart_cooldown_remaining_secs(), 0);
@@ -3619,11 +4705,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -3637,6 +4719,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -3676,11 +4764,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -3694,6 +4778,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -3734,6 +4824,8 @@ mod tests {
             log_path: "/tmp".to_string(),
             args: vec!["-c".to_string()],
             env: vec!["TEST_ENV=test_value".to_string()],
+            exec: Exec::default(),
+            cgroup: None,
         };
 
         match process_run(metadata) {
@@ -3770,11 +4862,7 @@ mod tests {
             crash: Crash {
                 crashed: true, // Set to crashed
             },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -3788,6 +4876,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -3879,6 +4973,8 @@ mod tests {
             log_path: "/tmp".to_string(),
             args: vec!["-c".to_string()],
             env: vec![],
+            exec: Exec::default(),
+            cgroup: None,
         };
 
         let result = process_run(metadata);
@@ -3896,6 +4992,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_process_run_applies_exec_context() {
+        let log_dir = std::env::temp_dir().join(format!("opm-exec-test-{}", std::process::id()));
+        let metadata = ProcessMetadata {
+            name: "exec_context".to_string(),
+            shell: "/bin/sh".to_string(),
+            command: "pwd; umask; ulimit -n".to_string(),
+            log_path: log_dir.to_string_lossy().into_owned(),
+            args: vec!["-c".to_string()],
+            env: vec![],
+            exec: Exec {
+                cwd: Some(PathBuf::from("/")),
+                umask: Some(0o027),
+                limits: Limits {
+                    nofile: Some(unix::rlimits::Rlimit::parse("256:512").unwrap()),
+                    ..Limits::default()
+                },
+                ..Exec::default()
+            },
+            cgroup: None,
+        };
+
+        let result = process_run(metadata).expect("process should spawn");
+        let handle_pid = result.shell_pid.unwrap_or(result.pid);
+        if let Some((_, handle)) = PROCESS_HANDLES.remove(&handle_pid) {
+            let _ = handle.lock().unwrap().wait();
+        }
+
+        let output = std::fs::read_to_string(log_dir.join("exec_context-out.log")).unwrap();
+        let _ = std::fs::remove_dir_all(&log_dir);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines, vec!["/", "0027", "256"]);
+    }
+
+    #[test]
+    fn test_exec_working_dir_resolution() {
+        let path = PathBuf::from("/srv/app");
+        assert_eq!(Exec::default().working_dir(&path), path);
+
+        let relative = Exec {
+            cwd: Some(PathBuf::from("current")),
+            ..Exec::default()
+        };
+        assert_eq!(relative.working_dir(&path), PathBuf::from("/srv/app/current"));
+
+        let absolute = Exec {
+            cwd: Some(PathBuf::from("/opt/app")),
+            ..Exec::default()
+        };
+        assert_eq!(absolute.working_dir(&path), PathBuf::from("/opt/app"));
+    }
+
     #[test]
     fn test_error_handling_invalid_log_path() {
         // Test that process_run returns an error for invalid log path
@@ -3906,6 +5054,8 @@ mod tests {
             log_path: "/nonexistent/directory/that/does/not/exist".to_string(),
             args: vec!["-c".to_string()],
             env: vec![],
+            exec: Exec::default(),
+            cgroup: None,
         };
 
         let result = process_run(metadata);
@@ -3938,11 +5088,7 @@ mod tests {
             restarts: 0,
             running: false, // Start with not running
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -3956,6 +5102,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -3992,11 +5144,7 @@ mod tests {
             restarts: 0,
             running: true, // Marked as running
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4010,6 +5158,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4046,11 +5200,7 @@ mod tests {
             restarts: 0,
             running: true, // Marked as running but PID doesn't exist
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: past_time, // Started 5 minutes ago
             max_memory: 0,
@@ -4064,6 +5214,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4102,11 +5258,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now() - chrono::Duration::seconds(20),
             max_memory: 0,
@@ -4120,6 +5272,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4152,11 +5310,7 @@ mod tests {
             restarts: 0,
             running: false, // Explicitly stopped
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: past_time, // Started 10 minutes ago
             max_memory: 0,
@@ -4170,6 +5324,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4210,11 +5370,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4228,6 +5384,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4274,11 +5436,7 @@ mod tests {
             restarts: 9,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4292,6 +5450,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process.clone());
@@ -4347,11 +5511,7 @@ mod tests {
             restarts: 15, // Set to 15 to test display beyond limit
             running: false,
             crash: Crash { crashed: true },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4365,6 +5525,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process.clone());
@@ -4395,11 +5561,7 @@ mod tests {
             restarts: 5, // Start with 5 restarts
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4413,6 +5575,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4451,11 +5619,7 @@ mod tests {
             restarts: 5, // Start with 5 restarts
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4469,6 +5633,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4510,11 +5680,7 @@ mod tests {
             restarts: 2, // Start with 2 restarts already
             running: false,
             crash: Crash { crashed: true },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4528,6 +5694,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4572,11 +5744,7 @@ mod tests {
             restarts: 5, // Start with 5 restarts
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4590,6 +5758,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4633,11 +5807,7 @@ mod tests {
             restarts: 0,
             running: true, // Was running before restore
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4651,6 +5821,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4699,11 +5875,7 @@ mod tests {
             crash: Crash {
                 crashed: false, // Reset by restore command
             },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4717,6 +5889,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4771,7 +5949,7 @@ mod tests {
         // Previously, this would cause a 5-second delay because libc::kill(0, 0)
         // checks the entire process group instead of a specific process
         let start = Instant::now();
-        let result = wait_for_process_termination(0);
+        let result = wait_for_process_termination(0, Duration::from_millis(DEFAULT_KILL_TIMEOUT_MS));
         let duration = start.elapsed();
 
         assert!(
@@ -4786,7 +5964,7 @@ mod tests {
 
         // Test with negative PID
         let start = Instant::now();
-        let result = wait_for_process_termination(-1);
+        let result = wait_for_process_termination(-1, Duration::from_millis(DEFAULT_KILL_TIMEOUT_MS));
         let duration = start.elapsed();
 
         assert!(
@@ -4801,7 +5979,7 @@ mod tests {
 
         // Test with unlikely PID (should also return quickly since process doesn't exist)
         let start = Instant::now();
-        let result = wait_for_process_termination(UNLIKELY_PID);
+        let result = wait_for_process_termination(UNLIKELY_PID, Duration::from_millis(DEFAULT_KILL_TIMEOUT_MS));
         let duration = start.elapsed();
 
         assert!(
@@ -4815,6 +5993,66 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_terminate_escalates_to_sigkill() {
+        // The shell and its sleep ignore SIGTERM, so only SIGKILL after the timeout stops them
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "trap '' TERM; sleep 30; true"])
+            .spawn()
+            .expect("Failed to spawn sh");
+        let pid = child.id() as i64;
+        thread::sleep(Duration::from_millis(200));
+        let children = process_find_children(pid);
+        assert!(!children.is_empty(), "sleep should be a child of the shell");
+
+        let policy = StopPolicy { kill_timeout_ms: Some(300), ..StopPolicy::default() };
+        assert!(terminate(pid, vec![], &policy), "ignoring SIGTERM should escalate to SIGKILL");
+        let _ = child.wait();
+        for child in children {
+            assert!(
+                wait_for_process_termination(child, Duration::from_millis(SIGKILL_WAIT_MS)),
+                "children of the shell should be killed as well"
+            );
+        }
+
+        // SIGINT is delivered instead of SIGTERM and ends the process without escalation
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .expect("Failed to spawn sleep");
+        let policy = StopPolicy {
+            stop_signal: Some("SIGINT".to_string()),
+            kill_timeout_ms: Some(2000),
+            kill_mode: KillMode::Group,
+        };
+        assert!(!terminate(child.id() as i64, vec![], &policy));
+        let status = child.wait().unwrap();
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(status.signal(), Some(libc::SIGINT));
+    }
+
+    #[test]
+    fn test_terminate_kill_mode_main_leaves_children() {
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "sleep 30; true"])
+            .spawn()
+            .expect("Failed to spawn sh");
+        let pid = child.id() as i64;
+        thread::sleep(Duration::from_millis(200));
+        let children = process_find_children(pid);
+        assert_eq!(children.len(), 1, "sleep should be the only child of the shell");
+
+        let policy = StopPolicy { kill_mode: KillMode::Main, ..StopPolicy::default() };
+        assert!(!terminate(pid, vec![], &policy));
+        let _ = child.wait();
+        assert!(is_pid_alive(children[0]), "kill_mode = main must not signal children");
+        signal_pid(children[0], Signal::SIGKILL);
+
+        assert_eq!("mixed".parse::<KillMode>(), Ok(KillMode::Mixed));
+        assert!("tree".parse::<KillMode>().is_err());
+        assert_eq!(policy.describe(), "SIGTERM, 5000ms, main");
+    }
+
     #[test]
     #[ignore = "Requires config file which doesn't exist in test environment"]
     fn test_restart_failure_increments_crash_counter() {
@@ -4844,11 +6082,7 @@ mod tests {
             crash: Crash {
                 crashed: true, // Already marked as crashed, so restart will be attempted
             },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4862,6 +6096,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -4956,11 +6196,7 @@ mod tests {
             crash: Crash {
                 crashed: true, // Marked as crashed
             },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -4974,6 +6210,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -5058,11 +6300,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -5076,6 +6314,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -5237,11 +6481,7 @@ mod tests {
                 restarts: 0,
                 running: true,
                 crash: Crash { crashed: false },
-                watch: Watch {
-                    enabled: false,
-                    path: String::new(),
-                    hash: String::new(),
-                },
+                watch: Watch::default(),
                 children: vec![],
                 started: Utc::now(),
                 max_memory: 0,
@@ -5255,6 +6495,12 @@ mod tests {
                 session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
             };
             runner.list.insert(id, process);
         }
@@ -5345,11 +6591,7 @@ mod tests {
             restarts: 10,
             running: false,
             crash: Crash { crashed: true },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -5363,6 +6605,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process.clone());
@@ -5440,11 +6688,7 @@ mod tests {
                 restarts: 0,
                 running: true,
                 crash: Crash { crashed: false },
-                watch: Watch {
-                    enabled: false,
-                    path: String::new(),
-                    hash: String::new(),
-                },
+                watch: Watch::default(),
                 children: vec![],
                 started: Utc::now(),
                 max_memory: 0,
@@ -5458,6 +6702,12 @@ mod tests {
                 session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
             };
             runner.list.insert(id, process);
         }
@@ -5544,11 +6794,7 @@ mod tests {
             restarts: 0,
             running: true, // Process is running
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -5562,6 +6808,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -5615,11 +6867,7 @@ mod tests {
             crash: Crash {
                 crashed: true, // Marked as crashed
             },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now() - chrono::Duration::seconds(10),
             max_memory: 0,
@@ -5633,6 +6881,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -5683,11 +6937,7 @@ mod tests {
             crash: Crash {
                 crashed: true, // Marked as crashed
             },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -5701,6 +6951,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -5765,11 +7021,7 @@ mod tests {
             crash: Crash {
                 crashed: true, // Was previously marked as crashed
             },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now() - chrono::Duration::seconds(10),
             max_memory: 0,
@@ -5783,6 +7035,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process.clone());
@@ -5850,11 +7108,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -5868,6 +7122,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -5940,11 +7200,7 @@ mod tests {
             restarts: 0,
             running: true, // Marked as running
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -5958,6 +7214,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -6030,11 +7292,7 @@ mod tests {
             restarts: 0,
             running: true, // Process was just started, so running=true
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -6048,6 +7306,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -6095,11 +7359,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![alive_child_pid], // Child is still alive
             started: Utc::now(),
             max_memory: 0,
@@ -6113,6 +7373,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -6162,11 +7428,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![alive_descendant], // But descendant is alive
             started: Utc::now(),
             max_memory: 0,
@@ -6180,6 +7442,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -6213,11 +7481,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![], // No descendants
             started: Utc::now(),
             max_memory: 0,
@@ -6231,6 +7495,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -6269,11 +7539,7 @@ mod tests {
             restarts: 0,
             running: true,
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![], // No tracked descendants
             started: Utc::now(),
             max_memory: 0,
@@ -6287,6 +7553,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -6346,11 +7618,7 @@ mod tests {
             restarts: 0,
             running: true, // Was running before daemon restart
             crash: Crash { crashed: false },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: unix_epoch, // Unix epoch (default)
             max_memory: 0,
@@ -6364,6 +7632,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process_from_dump.clone());
@@ -6479,11 +7753,7 @@ mod tests {
             restarts: 1,   // Counter was already incremented once
             running: true, // Auto-restart enabled
             crash: Crash { crashed: true },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
+            watch: Watch::default(),
             children: vec![],
             started: Utc::now(),
             max_memory: 0,
@@ -6497,6 +7767,12 @@ mod tests {
             session_id: None,
             process_start_time: None,
             is_process_tree: false,
+            exec: Exec::default(),
+            cgroup: None,
+            launching: None,
+            health: Health::default(),
+            crash_history: Vec::new(),
+            runs: Vec::new(),
         };
 
         runner.list.insert(id, process);
@@ -6560,7 +7836,10 @@ mod tests {
             command_needs_shell("echo hello; echo world"),
             "Semicolon should need shell"
         );
-        // Note: Single & removed from detection to avoid false positives with URLs/hex values
+        assert!(
+            command_needs_shell("node worker.js &"),
+            "Unquoted background operator should need shell"
+        );
         assert!(
             command_needs_shell("echo `date`"),
             "Command substitution (backticks) should need shell"
@@ -6599,6 +7878,58 @@ mod tests {
             !command_needs_shell("echo hello"),
             "Simple echo should not need shell"
         );
+
+        // Quoting: operators inside quotes are plain arguments
+        assert!(
+            !command_needs_shell(r#"python app.py --title "hello world | x""#),
+            "Pipe inside double quotes should not need shell"
+        );
+        assert!(
+            !command_needs_shell("node app.js --sep ';' --glob '*.js'"),
+            "Operators and globs inside single quotes should not need shell"
+        );
+        assert!(
+            !command_needs_shell("curl http://example.com/?a=1"),
+            "Query string in a URL should not need shell"
+        );
+        assert!(
+            !command_needs_shell("curl 'http://example.com/?a=1&b=2'"),
+            "Quoted ampersand in a URL should not need shell"
+        );
+        assert!(
+            !command_needs_shell("node app.js --secret '$HOME'"),
+            "Variable in single quotes is literal"
+        );
+
+        // Expansions that only a shell can perform
+        assert!(
+            command_needs_shell("node app.js --home $HOME"),
+            "Variable expansion should need shell"
+        );
+        assert!(
+            command_needs_shell(r#"node app.js --home "$HOME""#),
+            "Variable expansion in double quotes should need shell"
+        );
+        assert!(
+            command_needs_shell("NODE_ENV=production node app.js"),
+            "Leading assignment should need shell"
+        );
+        assert!(
+            command_needs_shell("node app.js 2>&1"),
+            "Descriptor redirect should need shell"
+        );
+        assert!(
+            command_needs_shell("node app.js # comment"),
+            "Comment should need shell"
+        );
+        assert!(
+            command_needs_shell("echo 'unterminated"),
+            "Unterminated quote should be left to the shell"
+        );
+        assert!(
+            !command_needs_shell("node app.js --tag a#b"),
+            "Hash inside a word is not a comment"
+        );
     }
 
     #[test]
@@ -6630,6 +7961,30 @@ mod tests {
         let (program, args) = result.unwrap();
         assert_eq!(program, "node");
         assert_eq!(args.len(), 0);
+
+        // Double quotes keep spaces and operators in one argument
+        let (program, args) =
+            parse_direct_command(r#"python app.py --title "hello world | x""#).unwrap();
+        assert_eq!(program, "python");
+        assert_eq!(args, vec!["app.py", "--title", "hello world | x"]);
+
+        // Single quotes are fully literal
+        let (_, args) = parse_direct_command(r#"node app.js --msg 'say "hi" \n'"#).unwrap();
+        assert_eq!(args, vec!["app.js", "--msg", r#"say "hi" \n"#]);
+
+        // Backslash escapes outside quotes and inside double quotes
+        let (program, args) =
+            parse_direct_command(r#"/opt/my\ app/bin --name "a \"quoted\" \$name""#).unwrap();
+        assert_eq!(program, "/opt/my app/bin");
+        assert_eq!(args, vec!["--name", r#"a "quoted" $name"#]);
+
+        // Empty quoted arguments are preserved, adjacent pieces join into one word
+        let (_, args) = parse_direct_command(r#"node app.js "" --opt='a b'c"#).unwrap();
+        assert_eq!(args, vec!["app.js", "", "--opt=a bc"]);
+
+        // Line continuation is removed and tabs/newlines are separators
+        let (_, args) = parse_direct_command("node app.js \\\n\t--port 3000").unwrap();
+        assert_eq!(args, vec!["app.js", "--port", "3000"]);
     }
 
     #[test]
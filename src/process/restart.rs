@@ -0,0 +1,387 @@
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    os::unix::process::ExitStatusExt,
+    process::ExitStatus,
+    time::Duration,
+};
+use utoipa::ToSchema;
+
+/// An instance that exits sooner than this after starting counts as a failed start
+pub const DEFAULT_MIN_UPTIME_MS: u64 = 10_000;
+
+/// Delay before the first restart attempt
+pub const DEFAULT_RESTART_DELAY_MS: u64 = 2000;
+
+/// Upper bound for the restart delay however many starts failed
+pub const DEFAULT_MAX_RESTART_DELAY_MS: u64 = 60_000;
+
+/// Factor applied to the restart delay for every failed start in a row
+pub const DEFAULT_BACKOFF_MULTIPLIER: f64 = 2.0;
+
+/// Crashes kept in the history of a process, the oldest are dropped first
+pub const CRASH_HISTORY_LIMIT: usize = 10;
+
+/// Exit statuses collected by the daemon's reaper, keyed by the PID of the reaped child
+static EXITS: Lazy<DashMap<i64, Exit>> = Lazy::new(DashMap::new);
+
+/// Which exits the daemon answers with a restart
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Restart after every exit, and start it again on `opm restore` even when it was stopped
+    Always,
+    /// Restart unless the exit code is one of success_exit_codes
+    #[default]
+    OnFailure,
+    /// Never restart, the process is stopped once it exits
+    Never,
+    /// Restart after every exit, but a stopped process stays stopped on `opm restore`
+    UnlessStopped,
+}
+
+impl std::str::FromStr for RestartPolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy.trim().to_lowercase().replace('_', "-").as_str() {
+            "always" => Ok(RestartPolicy::Always),
+            "on-failure" => Ok(RestartPolicy::OnFailure),
+            "never" | "no" => Ok(RestartPolicy::Never),
+            "unless-stopped" => Ok(RestartPolicy::UnlessStopped),
+            _ => Err(format!(
+                "Invalid restart policy: {}. Use 'always', 'on-failure', 'never' or 'unless-stopped'",
+                policy
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RestartPolicy::Always => write!(f, "always"),
+            RestartPolicy::OnFailure => write!(f, "on-failure"),
+            RestartPolicy::Never => write!(f, "never"),
+            RestartPolicy::UnlessStopped => write!(f, "unless-stopped"),
+        }
+    }
+}
+
+/// Exponential delay between restart attempts, growing with each failed start
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Backoff {
+    /// Delay before a restart when the previous start did not fail
+    #[schema(example = 2000)]
+    #[serde(default)]
+    pub initial_ms: Option<u64>,
+    /// Longest delay between attempts
+    #[schema(example = 60000)]
+    #[serde(default)]
+    pub max_ms: Option<u64>,
+    /// Factor applied for every failed start in a row
+    #[schema(example = 2.0)]
+    #[serde(default)]
+    pub multiplier: Option<f64>,
+}
+
+impl Backoff {
+    /// Delay before the next attempt after `failures` failed starts in a row
+    pub fn delay(&self, failures: u32) -> Duration {
+        let initial = self.initial_ms.unwrap_or(DEFAULT_RESTART_DELAY_MS) as f64;
+        let max = self.max_ms.unwrap_or(DEFAULT_MAX_RESTART_DELAY_MS) as f64;
+        let multiplier = self.multiplier.unwrap_or(DEFAULT_BACKOFF_MULTIPLIER);
+
+        let delay = initial * multiplier.powi(failures.min(64) as i32);
+        Duration::from_millis(delay.min(max.max(initial)) as u64)
+    }
+}
+
+/// Restart policy of a process, with the limits that keep a failing process from flapping
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Restart {
+    #[serde(default)]
+    pub policy: RestartPolicy,
+    /// Exit codes that count as a clean exit, only 0 when empty
+    #[serde(default)]
+    pub success_exit_codes: Vec<i32>,
+    /// Restarts before the process is put in the errored state, daemon.restarts when unset
+    #[schema(example = 10)]
+    #[serde(default)]
+    pub max_restarts: Option<u64>,
+    /// Milliseconds an instance must stay up for its start to count as successful
+    #[schema(example = 10000)]
+    #[serde(default)]
+    pub min_uptime_ms: Option<u64>,
+    #[serde(default)]
+    pub backoff: Backoff,
+}
+
+impl Restart {
+    pub fn validate(&self) -> Result<(), String> {
+        let backoff = &self.backoff;
+        if backoff.multiplier.is_some_and(|multiplier| !(multiplier >= 1.0 && multiplier.is_finite())) {
+            return Err("Backoff multiplier must be at least 1".to_string());
+        }
+        if let (Some(initial), Some(max)) = (backoff.initial_ms, backoff.max_ms) {
+            if max < initial {
+                return Err(format!("Backoff max_ms ({}) is below initial_ms ({})", max, initial));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `exit` is a clean exit, a process killed by a signal never is
+    pub fn is_success(&self, exit: &Exit) -> bool {
+        match exit.code {
+            Some(code) if self.success_exit_codes.is_empty() => code == 0,
+            Some(code) => self.success_exit_codes.contains(&code),
+            None => false,
+        }
+    }
+
+    /// Whether the policy restarts a process that exited with `exit` (None when the status is unknown)
+    pub fn should_restart(&self, exit: Option<&Exit>) -> bool {
+        match self.policy {
+            RestartPolicy::Always | RestartPolicy::UnlessStopped => true,
+            RestartPolicy::OnFailure => !exit.is_some_and(|exit| self.is_success(exit)),
+            RestartPolicy::Never => false,
+        }
+    }
+
+    pub fn max_restarts(&self, default: u64) -> u64 {
+        self.max_restarts.unwrap_or(default)
+    }
+
+    pub fn min_uptime(&self) -> Duration {
+        Duration::from_millis(self.min_uptime_ms.unwrap_or(DEFAULT_MIN_UPTIME_MS))
+    }
+
+    /// One-line summary such as "on-failure, exit codes 0, max 10 restarts, min uptime 10000ms, backoff 2000ms x2 up to 60000ms"
+    pub fn describe(&self, default_max_restarts: u64) -> String {
+        let codes = match self.success_exit_codes.is_empty() {
+            true => "0".to_string(),
+            false => self.success_exit_codes.iter().map(i32::to_string).collect::<Vec<_>>().join(","),
+        };
+        format!(
+            "{}, exit codes {}, max {} restarts, min uptime {}ms, backoff {}ms x{} up to {}ms",
+            self.policy,
+            codes,
+            self.max_restarts(default_max_restarts),
+            self.min_uptime().as_millis(),
+            self.backoff.delay(0).as_millis(),
+            self.backoff.multiplier.unwrap_or(DEFAULT_BACKOFF_MULTIPLIER),
+            self.backoff.max_ms.unwrap_or(DEFAULT_MAX_RESTART_DELAY_MS),
+        )
+    }
+}
+
+/// How a process exited
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Exit {
+    /// Exit code, None when the process was killed by a signal
+    pub code: Option<i32>,
+    /// Signal that killed the process
+    pub signal: Option<i32>,
+    #[serde(default)]
+    pub core_dumped: bool,
+}
+
+impl From<ExitStatus> for Exit {
+    fn from(status: ExitStatus) -> Self {
+        Exit {
+            code: status.code(),
+            signal: status.signal(),
+            core_dumped: status.core_dumped(),
+        }
+    }
+}
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exit code {}", code)?,
+            (None, Some(signal)) => match nix::sys::signal::Signal::try_from(signal) {
+                Ok(name) => write!(f, "signal {} ({})", signal, name.as_str())?,
+                Err(_) => write!(f, "signal {}", signal)?,
+            },
+            (None, None) => write!(f, "unknown exit")?,
+        }
+        if self.core_dumped {
+            write!(f, ", core dumped")?;
+        }
+        Ok(())
+    }
+}
+
+/// An unexpected death of a process instance
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct CrashRecord {
+    /// Time of death
+    #[schema(value_type = String, example = "2026-01-01T12:00:00Z")]
+    pub time: DateTime<Utc>,
+    /// None when the exit status could not be collected
+    pub exit: Option<Exit>,
+    /// The cgroup recorded an OOM kill
+    #[serde(default)]
+    pub oom_killed: bool,
+    /// Set when the daemon ended the instance itself, e.g. for a failing liveness probe
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// How long the instance ran
+    #[schema(example = 1500)]
+    #[serde(default)]
+    pub uptime_ms: u64,
+}
+
+impl CrashRecord {
+    pub fn new(started: DateTime<Utc>, exit: Option<Exit>, oom_killed: bool, reason: Option<String>) -> Self {
+        let time = Utc::now();
+        CrashRecord {
+            time,
+            exit,
+            oom_killed,
+            reason,
+            uptime_ms: (time - started).num_milliseconds().max(0) as u64,
+        }
+    }
+}
+
+impl std::fmt::Display for CrashRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (&self.reason, &self.exit) {
+            (Some(reason), Some(exit)) => write!(f, "{}, {}", reason, exit)?,
+            (Some(reason), None) => write!(f, "{}", reason)?,
+            (None, Some(exit)) => write!(f, "{}", exit)?,
+            (None, None) => write!(f, "exit status unknown")?,
+        }
+        if self.oom_killed {
+            write!(f, ", killed by the OOM killer")?;
+        }
+        Ok(())
+    }
+}
+
+/// Keep the exit status of a child reaped before the daemon looked at its process
+pub fn record_exit(pid: i64, exit: Exit) {
+    EXITS.insert(pid, exit);
+}
+
+/// Exit status of a zombie that is not a child of the daemon, from the exit_code field of /proc/<pid>/stat
+/// Only works until its parent reaps it, and not at all without procfs
+pub fn zombie_exit(pid: i64) -> Option<Exit> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // Fields after the command name, which may itself contain spaces and parentheses
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    if fields.first() != Some(&"Z") {
+        return None;
+    }
+    // exit_code is field 52, the state being field 3
+    let status = fields.get(49)?.parse().ok()?;
+    Some(ExitStatus::from_raw(status).into())
+}
+
+/// Exit status of `pid`, from its child handle, an earlier reap or its zombie
+/// None when the process was not spawned by this daemon (e.g. by `opm start`) and is gone, or is still running
+pub fn take_exit(pid: i64) -> Option<Exit> {
+    let exited = super::PROCESS_HANDLES
+        .get(&pid)
+        .and_then(|handle| handle.lock().ok().and_then(|mut child| child.try_wait().ok().flatten()));
+    if let Some(status) = exited {
+        super::PROCESS_HANDLES.remove(&pid);
+        EXITS.remove(&pid);
+        return Some(status.into());
+    }
+    EXITS.remove(&pid).map(|(_, exit)| exit).or_else(|| zombie_exit(pid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exit(code: Option<i32>, signal: Option<i32>) -> Exit {
+        Exit { code, signal, core_dumped: false }
+    }
+
+    #[test]
+    fn test_restart_policy_decisions() {
+        let clean = exit(Some(0), None);
+        let failed = exit(Some(1), None);
+        let killed = exit(None, Some(9));
+
+        let on_failure = Restart::default();
+        assert!(!on_failure.should_restart(Some(&clean)));
+        assert!(on_failure.should_restart(Some(&failed)));
+        assert!(on_failure.should_restart(Some(&killed)));
+        assert!(on_failure.should_restart(None), "an unknown exit counts as a failure");
+
+        let custom = Restart { success_exit_codes: vec![0, 3], ..Default::default() };
+        assert!(!custom.should_restart(Some(&exit(Some(3), None))));
+        assert!(custom.should_restart(Some(&failed)));
+
+        let always = Restart { policy: RestartPolicy::Always, ..Default::default() };
+        assert!(always.should_restart(Some(&clean)));
+        let never = Restart { policy: RestartPolicy::Never, ..Default::default() };
+        assert!(!never.should_restart(Some(&failed)));
+
+        assert_eq!("unless_stopped".parse::<RestartPolicy>(), Ok(RestartPolicy::UnlessStopped));
+        assert_eq!(RestartPolicy::OnFailure.to_string(), "on-failure");
+        assert!("sometimes".parse::<RestartPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff::default();
+        assert_eq!(backoff.delay(0), Duration::from_millis(DEFAULT_RESTART_DELAY_MS));
+        assert_eq!(backoff.delay(1), Duration::from_millis(DEFAULT_RESTART_DELAY_MS * 2));
+        assert_eq!(backoff.delay(3), Duration::from_millis(DEFAULT_RESTART_DELAY_MS * 8));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_millis(DEFAULT_MAX_RESTART_DELAY_MS));
+
+        let fixed = Backoff { initial_ms: Some(500), multiplier: Some(1.0), ..Default::default() };
+        assert_eq!(fixed.delay(10), Duration::from_millis(500));
+
+        assert!(Restart { backoff: Backoff { multiplier: Some(0.5), ..Default::default() }, ..Default::default() }.validate().is_err());
+        assert!(Restart { backoff: Backoff { initial_ms: Some(10), max_ms: Some(5), ..Default::default() }, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_exit_status_capture() {
+        let status = std::process::Command::new("sh").args(["-c", "exit 7"]).status().unwrap();
+        let exit = Exit::from(status);
+        assert_eq!(exit.code, Some(7));
+        assert_eq!(exit.to_string(), "exit code 7");
+
+        let status = std::process::Command::new("sh").args(["-c", "kill -9 $$"]).status().unwrap();
+        assert_eq!(Exit::from(status).to_string(), "signal 9 (SIGKILL)");
+
+        record_exit(-42, exit);
+        assert_eq!(take_exit(-42), Some(exit));
+        assert_eq!(take_exit(-42), None);
+    }
+
+    #[test]
+    fn test_zombie_exit_and_crash_record() {
+        let mut child = std::process::Command::new("sh").args(["-c", "exit 5"]).spawn().unwrap();
+        let pid = child.id() as i64;
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        let mut exit = None;
+        while exit.is_none() && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+            exit = zombie_exit(pid);
+        }
+        child.wait().unwrap();
+        assert_eq!(exit.map(|exit| exit.code), Some(Some(5)));
+        assert_eq!(zombie_exit(pid), None, "a reaped process leaves no zombie");
+
+        let started = Utc::now() - chrono::Duration::seconds(3);
+        let record = CrashRecord::new(started, Some(Exit { code: None, signal: Some(9), core_dumped: false }), true, None);
+        assert!(record.uptime_ms >= 3000);
+        assert_eq!(record.to_string(), "signal 9 (SIGKILL), killed by the OOM killer");
+
+        let record = CrashRecord::new(Utc::now(), None, false, Some("liveness probe failed".to_string()));
+        assert_eq!(record.to_string(), "liveness probe failed");
+        assert_eq!(CrashRecord::new(Utc::now(), None, false, None).to_string(), "exit status unknown");
+    }
+}
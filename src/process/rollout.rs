@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Time a new instance without a readiness check has to stay up before it replaces the old one
+pub const SETTLE_MS: u64 = 1000;
+
+/// Time between readiness probes of a new instance
+pub const POLL_INTERVAL_MS: u64 = 200;
+
+/// Instances replaced at once when neither the group nor the caller sets max_unavailable
+pub const DEFAULT_MAX_UNAVAILABLE: usize = 1;
+
+/// Outcome of a rolling reload, see Runner::rollout
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Rollout {
+    /// Processes now running their new instance
+    pub reloaded: Vec<usize>,
+    /// Processes whose new instance failed, they kept the old one
+    pub rolled_back: Vec<RolledBack>,
+    /// Processes left alone because an earlier batch rolled back
+    pub skipped: Vec<usize>,
+}
+
+/// A process whose new instance was killed in favour of the old one
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct RolledBack {
+    #[schema(example = 2)]
+    pub id: usize,
+    /// Why the new instance failed
+    #[schema(example = "not ready within 30000ms")]
+    pub reason: String,
+}
+
+impl Rollout {
+    /// Processes that did not get a new instance, rolled back or skipped
+    pub fn failed(&self) -> Vec<usize> {
+        self.rolled_back.iter().map(|rolled_back| rolled_back.id).chain(self.skipped.iter().copied()).collect()
+    }
+}
+
+/// The batches `ids` are replaced in, each holds at most `max_unavailable` (and at least one) processes
+pub fn batches(ids: &[usize], max_unavailable: usize) -> std::slice::Chunks<'_, usize> {
+    ids.chunks(max_unavailable.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollout_batches() {
+        let ids = [0, 1, 2, 3, 4];
+        assert_eq!(batches(&ids, 2).collect::<Vec<_>>(), vec![&[0, 1][..], &[2, 3], &[4]]);
+        assert_eq!(batches(&ids, 0).count(), 5);
+        assert_eq!(batches(&ids, 10).count(), 1);
+
+        let rollout = Rollout {
+            reloaded: vec![0, 1],
+            rolled_back: vec![RolledBack { id: 2, reason: "exited after 20ms".to_string() }],
+            skipped: vec![3, 4],
+        };
+        assert_eq!(rollout.failed(), vec![2, 3, 4]);
+        assert!(Rollout::default().failed().is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+use chrono::{DateTime, Local};
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+    time::SystemTime,
+};
+use utoipa::ToSchema;
+
+/// Rotated files kept per log when keep is unset
+pub const DEFAULT_KEEP: usize = 5;
+
+/// Logs being rotated by a background thread
+static ROTATING: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Period each log was last rotated in, for the schedule
+static ROTATED_IN: Lazy<Mutex<HashMap<PathBuf, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Schedule logs are rotated on regardless of their size, in local time
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Schedule {
+    Hourly,
+    Daily,
+}
+
+impl Schedule {
+    fn period(&self, time: DateTime<Local>) -> String {
+        match self {
+            Schedule::Hourly => time.format("%Y-%m-%d %H").to_string(),
+            Schedule::Daily => time.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(schedule: &str) -> Result<Self, Self::Err> {
+        match schedule.trim().to_lowercase().as_str() {
+            "hourly" => Ok(Schedule::Hourly),
+            "daily" => Ok(Schedule::Daily),
+            _ => Err(format!("Invalid log rotation schedule: {}. Use 'hourly' or 'daily'", schedule)),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Schedule::Hourly => write!(f, "hourly"),
+            Schedule::Daily => write!(f, "daily"),
+        }
+    }
+}
+
+/// When the daemon rotates the logs of a process, set per process or as `[daemon.log_rotation]`
+/// Logs are copied and truncated in place, the process keeps writing to the same file
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Rotation {
+    /// Rotate a log once it is larger, in bytes or with a unit like "10M"
+    #[schema(value_type = Option<String>, example = "10M")]
+    #[serde(default, deserialize_with = "size", skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Schedule>,
+    /// Rotated files kept per log, the oldest is deleted
+    #[schema(example = 5)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep: Option<usize>,
+    /// Gzip rotated files
+    #[serde(default)]
+    pub compress: bool,
+}
+
+/// A size written as bytes or with a unit like "10M"
+fn size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Option::<Size>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Size::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(Size::Text(text)) => crate::helpers::parse_memory(&text).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+impl Rotation {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_size.is_none() && self.schedule.is_none() {
+            return Err("Log rotation needs max_size or a schedule".to_string());
+        }
+        if self.max_size == Some(0) {
+            return Err("Log rotation max_size must be above 0".to_string());
+        }
+        if self.keep == Some(0) {
+            return Err("Log rotation must keep at least 1 file".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn keep(&self) -> usize {
+        self.keep.unwrap_or(DEFAULT_KEEP)
+    }
+
+    /// Conditions and retention, e.g. "above 10mb or daily, keep 5 gzipped"
+    pub fn describe(&self) -> String {
+        let conditions: Vec<String> = self
+            .max_size
+            .map(|bytes| format!("above {}", crate::helpers::format_memory(bytes)))
+            .into_iter()
+            .chain(self.schedule.map(|schedule| schedule.to_string()))
+            .collect();
+        format!("{}, keep {}{}", conditions.join(" or "), self.keep(), if self.compress { " gzipped" } else { "" })
+    }
+
+    /// Whether `log` is due, the schedule counts from the last rotation or from when the log was created
+    fn due(&self, log: &Path, now: DateTime<Local>) -> bool {
+        let Ok(metadata) = fs::metadata(log) else {
+            return false;
+        };
+        if metadata.len() == 0 {
+            return false;
+        }
+        if self.max_size.is_some_and(|max_size| metadata.len() > max_size) {
+            return true;
+        }
+        let Some(schedule) = self.schedule else {
+            return false;
+        };
+
+        let mut rotated_in = ROTATED_IN.lock().unwrap_or_else(|err| err.into_inner());
+        let last = rotated_in.entry(log.to_path_buf()).or_insert_with(|| {
+            let since = rotated(log)
+                .first()
+                .and_then(|newest| fs::metadata(newest).and_then(|metadata| metadata.modified()).ok())
+                .or_else(|| metadata.created().ok())
+                .unwrap_or_else(SystemTime::now);
+            schedule.period(since.into())
+        });
+        *last != schedule.period(now)
+    }
+}
+
+/// Rotate the logs that are due in the background, a log being rotated is skipped
+pub fn check(logs: &[&str], rotation: &Rotation) {
+    let now = Local::now();
+    for log in logs {
+        let log = PathBuf::from(log);
+        if !rotation.due(&log, now) || !ROTATING.lock().unwrap_or_else(|err| err.into_inner()).insert(log.clone()) {
+            continue;
+        }
+
+        let (rotation, schedule) = (rotation.clone(), rotation.schedule);
+        std::thread::spawn(move || {
+            match rotate(&log, &rotation) {
+                Ok(()) => log::info!("[rotation] rotated {}", log.display()),
+                Err(err) => log::error!("[rotation] failed to rotate {}: {}", log.display(), err),
+            }
+            if let Some(schedule) = schedule {
+                ROTATED_IN.lock().unwrap_or_else(|err| err.into_inner()).insert(log.clone(), schedule.period(now));
+            }
+            ROTATING.lock().unwrap_or_else(|err| err.into_inner()).remove(&log);
+        });
+    }
+}
+
+/// `<log>.<number>`, with `.gz` when compressed
+fn numbered(log: &Path, number: usize, compressed: bool) -> PathBuf {
+    let mut name = log.as_os_str().to_owned();
+    name.push(format!(".{}{}", number, if compressed { ".gz" } else { "" }));
+    PathBuf::from(name)
+}
+
+/// Rotated files of `log`, newest first
+pub fn rotated(log: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (log.parent(), log.file_name()) else {
+        return vec![];
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut rotated: Vec<(usize, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let suffix = file_name.strip_prefix(&prefix)?;
+            let number = suffix.strip_suffix(".gz").unwrap_or(suffix).parse().ok()?;
+            Some((number, entry.path()))
+        })
+        .collect();
+    rotated.sort();
+    rotated.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Shift the rotated files up by one, dropping those past `keep`, then copy the log to `<log>.1` and truncate it
+fn rotate(log: &Path, rotation: &Rotation) -> io::Result<()> {
+    let keep = rotation.keep();
+    for (index, path) in rotated(log).iter().enumerate().rev() {
+        if index + 1 >= keep {
+            fs::remove_file(path)?;
+        } else {
+            let compressed = path.extension().is_some_and(|extension| extension == "gz");
+            fs::rename(path, numbered(log, index + 2, compressed))?;
+        }
+    }
+
+    // The process writes with O_APPEND, so after the truncate its next write lands at the start again
+    // Lines written between the last copy and the truncate are lost, the copy is repeated to keep that window short
+    let mut source = File::open(log)?;
+    let target = numbered(log, 1, false);
+    let mut copy = File::create(&target)?;
+    let mut copied = 0;
+    loop {
+        let len = source.metadata()?.len();
+        if len <= copied {
+            break;
+        }
+        source.seek(SeekFrom::Start(copied))?;
+        copied += io::copy(&mut (&mut source).take(len - copied), &mut copy)?;
+    }
+    OpenOptions::new().write(true).open(log)?.set_len(0)?;
+
+    if rotation.compress {
+        let mut encoder = GzEncoder::new(File::create(numbered(log, 1, true))?, Compression::default());
+        io::copy(&mut File::open(&target)?, &mut encoder)?;
+        encoder.finish()?;
+        fs::remove_file(&target)?;
+    }
+    Ok(())
+}
+
+/// Lines of one log file, gzipped ones are decompressed
+fn read(path: &Path) -> Vec<String> {
+    let Ok(file) = File::open(path) else {
+        return vec![];
+    };
+    let reader: Box<dyn Read> = match path.extension().is_some_and(|extension| extension == "gz") {
+        true => Box::new(MultiGzDecoder::new(file)),
+        false => Box::new(file),
+    };
+    BufReader::new(reader)
+        .lines()
+        .map(|line| line.unwrap_or_else(|err| format!("error reading line: {err}")))
+        .collect()
+}
+
+/// At least `wanted` of the last lines of `log`, reading the rotated files when the log itself has fewer
+pub fn lines(log: &Path, wanted: usize) -> Vec<String> {
+    let mut lines = read(log);
+    for path in rotated(log) {
+        if lines.len() >= wanted {
+            break;
+        }
+        let mut older = read(&path);
+        older.append(&mut lines);
+        lines = older;
+    }
+    lines
+}
+
+/// Delete the rotated files of `log`
+pub fn remove(log: &Path) {
+    for path in rotated(log) {
+        if let Err(err) = fs::remove_file(&path) {
+            log::warn!("[rotation] failed to delete {}: {}", path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_rotation_settings() {
+        let rotation: Rotation = toml::from_str("max_size = \"1K\"\nschedule = \"daily\"\ncompress = true").unwrap();
+        assert_eq!(rotation.max_size, Some(1024));
+        assert_eq!(rotation.schedule, Some(Schedule::Daily));
+        assert_eq!(rotation.describe(), "above 1kb or daily, keep 5 gzipped");
+        let rotation: Rotation = serde_json::from_str(r#"{"max_size": 2048, "keep": 2}"#).unwrap();
+        assert_eq!(rotation.describe(), "above 2kb, keep 2");
+        assert!(rotation.validate().is_ok());
+
+        assert!(Rotation::default().validate().is_err());
+        assert!(Rotation { keep: Some(0), ..rotation.clone() }.validate().is_err());
+        assert!(Rotation { max_size: Some(0), ..rotation }.validate().is_err());
+        assert!(toml::from_str::<Rotation>("max_size = \"lots\"").is_err());
+        assert!("weekly".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn test_rotate_keeps_and_reads_rotated_files() {
+        let dir = std::env::temp_dir().join(format!("opm-rotation-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("app-out.log");
+        let rotation = Rotation { max_size: Some(3), keep: Some(2), compress: true, ..Default::default() };
+
+        // The handle stays open like the child's, writes after the rotation land in the truncated file
+        let mut writer = OpenOptions::new().create(true).append(true).open(&log).unwrap();
+        for line in ["one", "two", "three"] {
+            writeln!(writer, "{line}").unwrap();
+            assert!(rotation.due(&log, Local::now()));
+            rotate(&log, &rotation).unwrap();
+        }
+        writeln!(writer, "four").unwrap();
+
+        assert_eq!(rotated(&log), [numbered(&log, 1, true), numbered(&log, 2, true)]);
+        assert_eq!(fs::read_to_string(&log).unwrap(), "four\n");
+        assert_eq!(lines(&log, 1), ["four"]);
+        assert_eq!(lines(&log, 2), ["three", "four"]);
+        assert_eq!(lines(&log, 10), ["two", "three", "four"]);
+
+        remove(&log);
+        assert!(rotated(&log).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,45 @@
+use chrono::{DateTime, Local, Utc};
+use croner::Cron;
+
+/// Parse a cron expression, five fields (minute to weekday) or six with leading seconds, in local time
+pub fn parse(expression: &str) -> Result<Cron, String> {
+    Cron::new(expression.trim())
+        .with_seconds_optional()
+        .parse()
+        .map_err(|err| format!("Invalid cron expression '{}': {}", expression, err))
+}
+
+pub fn validate(expression: &str) -> Result<(), String> {
+    parse(expression).map(|_| ())
+}
+
+/// First time after `after` the expression matches, None when it is invalid or never matches again
+pub fn next_after(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let cron = parse(expression).ok()?;
+    cron.find_next_occurrence(&after.with_timezone(&Local), false)
+        .ok()
+        .map(|next| next.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Timelike};
+
+    #[test]
+    fn test_cron_expressions() {
+        assert!(validate("0 3 * * *").is_ok());
+        assert!(validate("*/15 * * * * *").is_ok());
+        assert!(validate("@daily").is_ok());
+        assert!(validate("61 * * * *").is_err());
+        assert!(validate("every night").is_err());
+
+        let now = Utc::now();
+        let next = next_after("*/5 * * * *", now).unwrap();
+        assert!(next > now && next - now <= Duration::minutes(5));
+        assert_eq!(next.with_timezone(&Local).minute() % 5, 0);
+        assert_eq!(next.second(), 0);
+
+        assert_eq!(next_after("not cron", now), None);
+    }
+}
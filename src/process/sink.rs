@@ -0,0 +1,1019 @@
+use super::{
+    entry::{Entry, Level},
+    rotation,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use flate2::read::MultiGzDecoder;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::{TcpStream, ToSocketAddrs, UdpSocket},
+    os::{
+        fd::AsRawFd,
+        unix::{fs::MetadataExt, net::{UnixDatagram, UnixStream}},
+    },
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// Lines buffered per sink when buffer is unset
+pub const DEFAULT_BUFFER: usize = 10_000;
+
+/// Lines sent to a destination at once
+const BATCH: usize = 500;
+
+/// How often the logs are checked for new lines
+const POLL: Duration = Duration::from_millis(500);
+
+/// Delay before the first retry, doubled up to RETRY_MAX while a destination stays unreachable
+const RETRY_MIN: Duration = Duration::from_secs(1);
+const RETRY_MAX: Duration = Duration::from_secs(30);
+
+/// Syslog messages sent as a datagram are cut to this many bytes, the size RFC5426 receivers should accept
+const DATAGRAM_MAX: usize = 2048;
+
+/// Longest line forwarded as one record, the rest of a longer line follows in further records
+const MAX_LINE: usize = 16 * 1024;
+
+/// Most bytes read from a log per poll, a log growing faster is caught up with over the next polls
+const READ_MAX: u64 = 4 * 1024 * 1024;
+
+/// Connect, write and request timeout
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+static HOST: Lazy<String> = Lazy::new(|| hostname::get().ok().and_then(|host| host.into_string().ok()).unwrap_or_else(|| "localhost".to_string()));
+
+/// Processes whose logs are forwarded, set by the daemon every monitoring cycle
+static SOURCES: Lazy<Mutex<Vec<Source>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Where a syslog or JSON sink connects, written as `udp://host:port`, `tcp://host:port` or `unix:///path`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Address {
+    Udp(String),
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec = spec.trim();
+        let host_port = |address: &str| match address.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok_and(|port| port > 0) => Ok(address.to_string()),
+            _ => Err(format!("Invalid log sink address '{}', expected <host>:<port>", spec)),
+        };
+        if let Some(address) = spec.strip_prefix("udp://") {
+            return host_port(address).map(Address::Udp);
+        }
+        if let Some(address) = spec.strip_prefix("tcp://") {
+            return host_port(address).map(Address::Tcp);
+        }
+        if let Some(path) = spec.strip_prefix("unix://") {
+            return match path.starts_with('/') {
+                true => Ok(Address::Unix(PathBuf::from(path))),
+                false => Err(format!("Invalid log sink path '{}', expected unix:///absolute/path", spec)),
+            };
+        }
+        Err(format!("Invalid log sink address '{}', use udp://, tcp:// or unix://", spec))
+    }
+}
+
+impl TryFrom<String> for Address {
+    type Error = String;
+
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        spec.parse()
+    }
+}
+
+impl From<Address> for String {
+    fn from(address: Address) -> Self {
+        address.to_string()
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Udp(address) => write!(f, "udp://{}", address),
+            Address::Tcp(address) => write!(f, "tcp://{}", address),
+            Address::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+/// Syslog facility by name, e.g. "daemon" or "local0"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Facility(u8);
+
+const FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron", "authpriv", "ftp", "ntp", "security", "console", "solaris-cron", "local0", "local1",
+    "local2", "local3", "local4", "local5", "local6", "local7",
+];
+
+impl Default for Facility {
+    fn default() -> Self {
+        Facility(1)
+    }
+}
+
+impl TryFrom<String> for Facility {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        match FACILITIES.iter().position(|facility| facility.eq_ignore_ascii_case(name.trim())) {
+            Some(code) => Ok(Facility(code as u8)),
+            None => Err(format!("Invalid syslog facility '{}', e.g. user, daemon or local0 to local7", name)),
+        }
+    }
+}
+
+impl From<Facility> for String {
+    fn from(facility: Facility) -> Self {
+        FACILITIES[facility.0 as usize].to_string()
+    }
+}
+
+/// Where a sink delivers the lines
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Destination {
+    /// RFC5424 syslog over UDP, TCP (octet counted) or a unix socket
+    Syslog {
+        #[serde(with = "text")]
+        address: Address,
+        #[serde(default, with = "text")]
+        facility: Facility,
+    },
+    /// One JSON object per line over TCP
+    Json {
+        #[serde(with = "text")]
+        address: Address,
+    },
+    /// Loki push API, `url` is the Loki base URL like "http://localhost:3100"
+    Loki {
+        url: String,
+        /// Labels added to every stream, e.g. { env = "prod" }
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        labels: BTreeMap<String, String>,
+        /// Sent as X-Scope-OrgID for a multi-tenant Loki
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+    },
+}
+
+/// Serde for the types written as strings in config.toml
+mod text {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T: Clone + Into<String>, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.clone().into())
+    }
+
+    pub fn deserialize<'de, T: TryFrom<String, Error = String>, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        T::try_from(String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A destination the daemon forwards process logs to, configured as `[[daemon.log_sinks]]`
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Sink {
+    #[serde(flatten)]
+    pub destination: Destination,
+    /// Globs of the process names forwarded, all processes when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub processes: Vec<String>,
+    /// Labels a line must carry: process, id, group, stream, level or host
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub match_labels: BTreeMap<String, String>,
+    /// Lines kept while the destination is unreachable, the oldest are dropped once full
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer: Option<usize>,
+}
+
+impl Sink {
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.destination {
+            Destination::Json { address } if !matches!(address, Address::Tcp(_)) => {
+                return Err(format!("JSON log sink needs a tcp:// address, not {}", address));
+            }
+            Destination::Loki { url, .. } if !url.starts_with("http://") && !url.starts_with("https://") => {
+                return Err(format!("Invalid Loki url '{}', expected http:// or https://", url));
+            }
+            _ => {}
+        }
+        if self.buffer == Some(0) {
+            return Err("Log sink buffer must hold at least 1 line".to_string());
+        }
+        self.globs().map(|_| ())
+    }
+
+    fn globs(&self) -> Result<Option<GlobSet>, String> {
+        if self.processes.is_empty() {
+            return Ok(None);
+        }
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &self.processes {
+            builder.add(Glob::new(pattern).map_err(|err| format!("Invalid process pattern '{}': {}", pattern, err))?);
+        }
+        builder.build().map(Some).map_err(|err| err.to_string())
+    }
+
+    /// e.g. "syslog udp://127.0.0.1:514"
+    pub fn describe(&self) -> String {
+        match &self.destination {
+            Destination::Syslog { address, .. } => format!("syslog {}", address),
+            Destination::Json { address } => format!("json {}", address),
+            Destination::Loki { url, .. } => format!("loki {}", url),
+        }
+    }
+}
+
+/// A process whose logs are forwarded
+#[derive(Clone, Debug, PartialEq)]
+pub struct Source {
+    pub id: usize,
+    pub name: String,
+    pub pid: i64,
+    pub group: Option<String>,
+    pub out: PathBuf,
+    pub error: PathBuf,
+}
+
+impl Source {
+    pub fn new(id: usize, process: &super::Process) -> Self {
+        let logs = process.logs();
+        Source {
+            id,
+            name: process.name.clone(),
+            pid: process.pid,
+            group: process.exec.instance.as_ref().and_then(|instance| instance.group.clone()),
+            out: logs.out.into(),
+            error: logs.error.into(),
+        }
+    }
+}
+
+/// A forwarded line with what is known about it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub time: DateTime<Utc>,
+    pub level: Option<Level>,
+    pub process: String,
+    pub id: usize,
+    pub pid: i64,
+    pub group: Option<String>,
+    pub stream: &'static str,
+    /// Message of a JSON line, the whole text otherwise
+    pub msg: String,
+    /// The line as the process wrote it
+    pub text: String,
+    pub fields: Option<Map<String, Value>>,
+}
+
+impl Record {
+    fn new(source: &Source, stream: &'static str, line: &str) -> Self {
+        let (entry, text) = Entry::written(line);
+        Record {
+            time: entry.time.unwrap_or_else(Utc::now),
+            level: entry.level_in(stream),
+            process: source.name.clone(),
+            id: source.id,
+            pid: source.pid,
+            group: source.group.clone(),
+            stream,
+            msg: entry.msg.unwrap_or_else(|| text.clone()),
+            text,
+            fields: entry.fields,
+        }
+    }
+
+    fn label(&self, key: &str) -> Option<String> {
+        match key {
+            "process" => Some(self.process.clone()),
+            "id" => Some(self.id.to_string()),
+            "group" => self.group.clone(),
+            "stream" => Some(self.stream.to_string()),
+            "level" => self.level.map(|level| level.to_string()),
+            "host" => Some(HOST.clone()),
+            _ => None,
+        }
+    }
+
+    /// RFC5424 message, the severity follows the level and unleveled stdout is informational
+    fn syslog(&self, facility: Facility) -> String {
+        let severity = match self.level {
+            Some(Level::Fatal) => 2,
+            Some(Level::Error) => 3,
+            Some(Level::Warn) => 4,
+            Some(Level::Info) | None => 6,
+            Some(Level::Debug | Level::Trace) => 7,
+        };
+        // Header fields are printable ASCII without spaces, APP-NAME is at most 48 characters
+        let app: String = self.process.chars().map(|char| if char.is_ascii_graphic() { char } else { '_' }).take(48).collect();
+        format!(
+            "<{}>1 {} {} {} {} {} - {}",
+            facility.0 as u16 * 8 + severity,
+            self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            HOST.as_str(),
+            if app.is_empty() { "-" } else { &app },
+            self.pid,
+            self.stream,
+            self.text
+        )
+    }
+
+    fn json(&self) -> String {
+        let mut line = json!({
+            "time": self.time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "host": HOST.as_str(),
+            "process": self.process,
+            "id": self.id,
+            "stream": self.stream,
+            "msg": self.msg,
+        });
+        if let Some(group) = &self.group {
+            line["group"] = json!(group);
+        }
+        if let Some(level) = self.level {
+            line["level"] = json!(level);
+        }
+        if let Some(fields) = &self.fields {
+            line["fields"] = Value::Object(fields.clone());
+        }
+        line.to_string()
+    }
+
+    /// Stream labels of the record in Loki
+    fn loki_labels(&self, extra: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+        let mut labels = extra.clone();
+        for key in ["process", "group", "stream", "level", "host"] {
+            if let Some(value) = self.label(key) {
+                labels.insert(key.to_string(), value);
+            }
+        }
+        labels
+    }
+}
+
+/// Body of a Loki push, records are grouped into streams by their labels
+fn loki_push(records: &VecDeque<Record>, extra: &BTreeMap<String, String>) -> Value {
+    let mut streams: BTreeMap<BTreeMap<String, String>, Vec<Value>> = BTreeMap::new();
+    for record in records {
+        let nanos = record.time.timestamp_nanos_opt().unwrap_or_default().to_string();
+        streams.entry(record.loki_labels(extra)).or_default().push(json!([nanos, record.text]));
+    }
+    let streams: Vec<Value> = streams.into_iter().map(|(stream, values)| json!({ "stream": stream, "values": values })).collect();
+    json!({ "streams": streams })
+}
+
+/// Lines waiting for delivery, bounded by dropping the oldest
+struct Queue {
+    state: Mutex<Pending>,
+    ready: Condvar,
+    capacity: usize,
+}
+
+#[derive(Default)]
+struct Pending {
+    records: VecDeque<Record>,
+    /// Lines dropped since the last delivery
+    dropped: u64,
+}
+
+impl Queue {
+    fn new(capacity: usize) -> Self {
+        Queue { state: Mutex::new(Pending::default()), ready: Condvar::new(), capacity }
+    }
+
+    /// Returns true when the oldest line was dropped to make room, for the first time since the last delivery
+    fn push(&self, record: Record) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let full = state.records.len() >= self.capacity;
+        if full {
+            state.records.pop_front();
+            state.dropped += 1;
+        }
+        state.records.push_back(record);
+        self.ready.notify_one();
+        full && state.dropped == 1
+    }
+
+    /// Up to `max` lines, waiting up to `timeout` for the first
+    fn take(&self, max: usize, timeout: Duration) -> VecDeque<Record> {
+        let state = self.state.lock().unwrap();
+        let (mut state, _) = self.ready.wait_timeout_while(state, timeout, |state| state.records.is_empty()).unwrap();
+        let count = state.records.len().min(max);
+        state.records.drain(..count).collect()
+    }
+
+    fn dropped(&self) -> u64 {
+        std::mem::take(&mut self.state.lock().unwrap().dropped)
+    }
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    UnixDatagram(UnixDatagram),
+    UnixStream(UnixStream),
+}
+
+impl Connection {
+    fn open(address: &Address) -> Result<Self, String> {
+        let failed = |err: std::io::Error| format!("Failed to connect to {}: {}", address, err);
+        match address {
+            Address::Udp(target) => {
+                let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(failed)?;
+                socket.connect(target.as_str()).map_err(failed)?;
+                Ok(Connection::Udp(socket))
+            }
+            Address::Tcp(target) => {
+                let mut last = std::io::Error::new(std::io::ErrorKind::NotFound, "no address resolved");
+                for resolved in target.to_socket_addrs().map_err(failed)? {
+                    match TcpStream::connect_timeout(&resolved, TIMEOUT) {
+                        Ok(stream) => {
+                            stream.set_write_timeout(Some(TIMEOUT)).map_err(failed)?;
+                            return Ok(Connection::Tcp(stream));
+                        }
+                        Err(err) => last = err,
+                    }
+                }
+                Err(failed(last))
+            }
+            // Syslog sockets like /dev/log are datagram sockets, a stream socket is used when that is what listens
+            Address::Unix(path) => {
+                let socket = UnixDatagram::unbound().map_err(failed)?;
+                match socket.connect(path) {
+                    Ok(()) => Ok(Connection::UnixDatagram(socket)),
+                    Err(err) if err.raw_os_error() == Some(libc::EPROTOTYPE) => {
+                        let stream = UnixStream::connect(path).map_err(failed)?;
+                        stream.set_write_timeout(Some(TIMEOUT)).map_err(failed)?;
+                        Ok(Connection::UnixStream(stream))
+                    }
+                    Err(err) => Err(failed(err)),
+                }
+            }
+        }
+    }
+
+    /// A stream closed by the other end still accepts the next write, which is then lost
+    fn closed(&self) -> bool {
+        let fd = match self {
+            Connection::Tcp(stream) => stream.as_raw_fd(),
+            Connection::UnixStream(stream) => stream.as_raw_fd(),
+            Connection::Udp(_) | Connection::UnixDatagram(_) => return false,
+        };
+        let mut byte = 0u8;
+        let read = unsafe { libc::recv(fd, &mut byte as *mut u8 as *mut libc::c_void, 1, libc::MSG_PEEK | libc::MSG_DONTWAIT) };
+        read == 0 || (read < 0 && std::io::Error::last_os_error().kind() != std::io::ErrorKind::WouldBlock)
+    }
+
+    /// Send one message, framed for the transport: a datagram, an octet counted syslog frame or a line
+    fn send(&mut self, message: &str, octet_counted: bool) -> std::io::Result<()> {
+        match self {
+            Connection::Udp(socket) => socket.send(datagram(message)).map(|_| ()),
+            Connection::UnixDatagram(socket) => socket.send(datagram(message)).map(|_| ()),
+            Connection::Tcp(stream) if octet_counted => stream.write_all(format!("{} {}", message.len(), message).as_bytes()),
+            Connection::Tcp(stream) => stream.write_all(format!("{}\n", message).as_bytes()),
+            Connection::UnixStream(stream) => stream.write_all(format!("{}\n", message).as_bytes()),
+        }
+    }
+}
+
+/// `message` cut to DATAGRAM_MAX bytes at a character boundary
+fn datagram(message: &str) -> &[u8] {
+    let mut end = message.len().min(DATAGRAM_MAX);
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+    &message.as_bytes()[..end]
+}
+
+/// Sends the lines of one sink, reconnecting after a failure
+struct Delivery {
+    destination: Destination,
+    connection: Option<Connection>,
+    client: Option<reqwest::blocking::Client>,
+}
+
+impl Delivery {
+    fn new(destination: Destination) -> Self {
+        Delivery { destination, connection: None, client: None }
+    }
+
+    /// Deliver `pending`, removing each line once it is sent so a retry does not repeat it
+    fn deliver(&mut self, pending: &mut VecDeque<Record>) -> Result<(), String> {
+        match &self.destination {
+            Destination::Syslog { address, facility } => {
+                let (address, facility) = (address.clone(), *facility);
+                self.each(&address, pending, |record| (record.syslog(facility), true))
+            }
+            Destination::Json { address } => {
+                let address = address.clone();
+                self.each(&address, pending, |record| (record.json(), false))
+            }
+            Destination::Loki { url, labels, tenant } => {
+                let client = match &self.client {
+                    Some(client) => client.clone(),
+                    None => reqwest::blocking::Client::builder().timeout(TIMEOUT).build().map_err(|err| err.to_string())?,
+                };
+                self.client = Some(client.clone());
+
+                let endpoint = match url.trim_end_matches('/') {
+                    url if url.ends_with("/loki/api/v1/push") => url.to_string(),
+                    url => format!("{}/loki/api/v1/push", url),
+                };
+                let mut request = client.post(&endpoint).json(&loki_push(pending, labels));
+                if let Some(tenant) = tenant {
+                    request = request.header("X-Scope-OrgID", tenant);
+                }
+                let response = request.send().map_err(|err| format!("Failed to push to {}: {}", endpoint, err))?;
+                let status = response.status();
+                // Loki rejects lines it will never accept (too old, too long) with a client error, retrying does not help
+                if status.is_success() || (status.is_client_error() && status.as_u16() != 429) {
+                    if !status.is_success() {
+                        log::warn!("[sink] Loki rejected {} lines ({}): {}", pending.len(), status, response.text().unwrap_or_default().trim());
+                    }
+                    pending.clear();
+                    return Ok(());
+                }
+                Err(format!("Loki push to {} failed with {}", endpoint, status))
+            }
+        }
+    }
+
+    fn each(&mut self, address: &Address, pending: &mut VecDeque<Record>, message: impl Fn(&Record) -> (String, bool)) -> Result<(), String> {
+        if self.connection.as_ref().is_some_and(Connection::closed) {
+            self.connection = None;
+        }
+        while let Some(record) = pending.front() {
+            let connection = match &mut self.connection {
+                Some(connection) => connection,
+                None => self.connection.insert(Connection::open(address)?),
+            };
+            let (message, octet_counted) = message(record);
+            match connection.send(&message, octet_counted) {
+                Ok(()) => {}
+                // Sending the line again fails the same way, it would hold up every line after it
+                Err(err) if err.raw_os_error() == Some(libc::EMSGSIZE) => {
+                    log::warn!("[sink] dropped a line of {} bytes {} does not take: {}", message.len(), address, err);
+                }
+                Err(err) => {
+                    self.connection = None;
+                    return Err(format!("Failed to send to {}: {}", address, err));
+                }
+            }
+            pending.pop_front();
+        }
+        Ok(())
+    }
+}
+
+/// A configured sink with its filters and buffer
+struct Forward {
+    sink: Sink,
+    processes: Option<GlobSet>,
+    queue: Arc<Queue>,
+}
+
+impl Forward {
+    fn wants(&self, process: &str) -> bool {
+        self.processes.as_ref().is_none_or(|globs| globs.is_match(process))
+    }
+
+    fn accepts(&self, record: &Record) -> bool {
+        self.sink.match_labels.iter().all(|(key, value)| record.label(key).as_deref() == Some(value.as_str()))
+    }
+}
+
+/// Start forwarding process logs to the sinks, called once by the daemon
+/// Invalid sinks are logged and left out
+pub fn start(sinks: &[Sink]) {
+    let mut forwards = Vec::new();
+    for sink in sinks {
+        let processes = match sink.validate().and_then(|_| sink.globs()) {
+            Ok(processes) => processes,
+            Err(err) => {
+                log::error!("[sink] ignoring log sink {}: {}", sink.describe(), err);
+                continue;
+            }
+        };
+        let queue = Arc::new(Queue::new(sink.buffer.unwrap_or(DEFAULT_BUFFER)));
+        spawn_delivery(sink.clone(), Arc::clone(&queue));
+        forwards.push(Forward { sink: sink.clone(), processes, queue });
+    }
+
+    if !forwards.is_empty() {
+        std::thread::Builder::new()
+            .name("log-sinks".to_string())
+            .spawn(move || tail(forwards))
+            .map_err(|err| log::error!("[sink] failed to start forwarding logs: {}", err))
+            .ok();
+    }
+}
+
+/// Set the processes whose logs are forwarded
+pub fn track(sources: impl IntoIterator<Item = Source>) {
+    *SOURCES.lock().unwrap() = sources.into_iter().collect();
+}
+
+fn spawn_delivery(sink: Sink, queue: Arc<Queue>) {
+    let name = sink.describe();
+    let spawned = std::thread::Builder::new().name("log-sink".to_string()).spawn(move || {
+        let mut delivery = Delivery::new(sink.destination);
+        let mut pending = VecDeque::new();
+        let mut retry = RETRY_MIN;
+        let mut failing = false;
+
+        loop {
+            if pending.is_empty() {
+                pending = queue.take(BATCH, Duration::from_secs(1));
+                if pending.is_empty() {
+                    continue;
+                }
+            }
+            match delivery.deliver(&mut pending) {
+                Ok(()) => {
+                    let dropped = queue.dropped();
+                    if failing || dropped > 0 {
+                        log::info!("[sink] delivering to {} again, {} lines were dropped from the full buffer", name, dropped);
+                    }
+                    failing = false;
+                    retry = RETRY_MIN;
+                }
+                Err(err) => {
+                    if !failing {
+                        log::warn!("[sink] {}, retrying", err);
+                    }
+                    failing = true;
+                    std::thread::sleep(retry);
+                    retry = (retry * 2).min(RETRY_MAX);
+                }
+            }
+        }
+    });
+    if let Err(err) = spawned {
+        log::error!("[sink] failed to start delivering: {}", err);
+    }
+}
+
+/// Read position in a log
+struct Tail {
+    position: u64,
+    inode: u64,
+    /// Start of a line that is still being written
+    partial: Vec<u8>,
+}
+
+fn tail(forwards: Vec<Forward>) {
+    let started = SystemTime::now();
+    let mut tails: HashMap<PathBuf, Tail> = HashMap::new();
+    loop {
+        let sources: Vec<Source> = SOURCES.lock().unwrap().iter().filter(|source| forwards.iter().any(|forward| forward.wants(&source.name))).cloned().collect();
+        let logs: Vec<(&Source, &'static str, &PathBuf)> = sources.iter().flat_map(|source| [(source, "out", &source.out), (source, "error", &source.error)]).collect();
+        tails.retain(|path, _| logs.iter().any(|(_, _, log)| *log == path));
+
+        for (source, stream, log) in logs {
+            for line in read_new(&mut tails, log, started) {
+                let record = Record::new(source, stream, &line);
+                for forward in forwards.iter().filter(|forward| forward.wants(&source.name) && forward.accepts(&record)) {
+                    if forward.queue.push(record.clone()) {
+                        log::warn!("[sink] buffer of {} is full, dropping the oldest lines", forward.sink.describe());
+                    }
+                }
+            }
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+/// Complete lines added to `log` since the last call, lines written just before a rotation are read from the rotated copy
+/// A log seen for the first time is read from its end, unless it was created after forwarding `started`
+fn read_new(tails: &mut HashMap<PathBuf, Tail>, log: &Path, started: SystemTime) -> Vec<String> {
+    let Ok(metadata) = fs::metadata(log) else {
+        return Vec::new();
+    };
+    let tail = tails.entry(log.to_path_buf()).or_insert_with(|| {
+        let position = match metadata.created() {
+            Ok(created) if created >= started => 0,
+            _ => metadata.len(),
+        };
+        Tail { position, inode: metadata.ino(), partial: Vec::new() }
+    });
+
+    let mut data = std::mem::take(&mut tail.partial);
+    if metadata.ino() != tail.inode || metadata.len() < tail.position {
+        // The newest rotated file is `<log>.1`, or `<log>.1.gz` once compressed
+        if let Some(rotated) = rotation::rotated(log).first().filter(|_| metadata.ino() == tail.inode) {
+            read_from(rotated, tail.position, &mut data);
+        }
+        tail.position = 0;
+        tail.inode = metadata.ino();
+    }
+    tail.position += read_from(log, tail.position, &mut data);
+
+    // A line still being written is kept for the next call, or forwarded in parts once it reaches MAX_LINE
+    let complete = data.iter().rposition(|byte| *byte == b'\n').map_or(0, |end| end + 1);
+    tail.partial = data.split_off(complete + (data.len() - complete) / MAX_LINE * MAX_LINE);
+
+    let mut lines = Vec::new();
+    for line in data.split_inclusive(|byte| *byte == b'\n') {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        match line.is_empty() {
+            true => lines.push(String::new()),
+            false => lines.extend(line.chunks(MAX_LINE).map(|part| String::from_utf8_lossy(part).into_owned())),
+        }
+    }
+    lines
+}
+
+/// Append up to READ_MAX bytes of `path` past `position` to `data`, a gzipped file is read decompressed
+fn read_from(path: &Path, position: u64, data: &mut Vec<u8>) -> u64 {
+    let Ok(mut file) = File::open(path) else {
+        return 0;
+    };
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        let mut decoder = MultiGzDecoder::new(file);
+        return match io::copy(&mut (&mut decoder).take(position), &mut io::sink()) {
+            Ok(skipped) if skipped == position => decoder.take(READ_MAX).read_to_end(data).map_or(0, |read| read as u64),
+            _ => 0,
+        };
+    }
+    if file.seek(SeekFrom::Start(position)).is_err() {
+        return 0;
+    }
+    file.take(READ_MAX).read_to_end(data).map_or(0, |read| read as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    fn source() -> Source {
+        Source { id: 3, name: "api".to_string(), pid: 42, group: Some("web".to_string()), out: PathBuf::new(), error: PathBuf::new() }
+    }
+
+    fn record(line: &str) -> Record {
+        Record::new(&source(), "out", line)
+    }
+
+    #[test]
+    fn test_sink_config() {
+        let config: BTreeMap<String, Vec<Sink>> = toml::from_str(
+            r#"
+            [[log_sinks]]
+            type = "syslog"
+            address = "udp://127.0.0.1:514"
+            facility = "local0"
+            processes = ["api*"]
+
+            [[log_sinks]]
+            type = "loki"
+            url = "http://127.0.0.1:3100"
+            labels = { env = "prod" }
+            match_labels = { stream = "error" }
+            buffer = 100
+            "#,
+        )
+        .unwrap();
+        let sinks = &config["log_sinks"];
+        assert_eq!(sinks[0].destination, Destination::Syslog { address: Address::Udp("127.0.0.1:514".to_string()), facility: Facility(16) });
+        assert_eq!(sinks[0].processes, vec!["api*"]);
+        assert_eq!(sinks[1].match_labels.get("stream").map(String::as_str), Some("error"));
+        assert!(sinks.iter().all(|sink| sink.validate().is_ok()));
+
+        let json = Sink { destination: Destination::Json { address: "udp://127.0.0.1:9000".parse().unwrap() }, processes: vec![], match_labels: BTreeMap::new(), buffer: None };
+        assert!(json.validate().is_err());
+        assert!("tcp://localhost".parse::<Address>().is_err());
+        assert!("unix://dev/log".parse::<Address>().is_err());
+        assert!(Facility::try_from("local9".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_record_formats() {
+        let line = record(r#"2024-01-01T12:00:00.000Z {"level":50,"msg":"db down","code":7}"#);
+        assert_eq!((line.level, line.msg.as_str()), (Some(Level::Error), "db down"));
+        assert_eq!(line.label("group").as_deref(), Some("web"));
+
+        let syslog = line.syslog(Facility(16));
+        let expected = format!("<131>1 2024-01-01T12:00:00.000Z {} api 42 out - {}", *HOST, r#"{"level":50,"msg":"db down","code":7}"#);
+        assert_eq!(syslog, expected);
+
+        let json: Value = serde_json::from_str(&line.json()).unwrap();
+        assert_eq!(json["level"], "error");
+        assert_eq!(json["fields"]["code"], 7);
+        assert_eq!(json["group"], "web");
+
+        let push = loki_push(&VecDeque::from([line, record("plain")]), &BTreeMap::from([("env".to_string(), "prod".to_string())]));
+        let streams = push["streams"].as_array().unwrap();
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0]["stream"]["env"], "prod");
+        assert_eq!(streams[0]["stream"]["level"], "error");
+        assert_eq!(streams[0]["values"][0][0], "1704110400000000000");
+    }
+
+    #[test]
+    fn test_bounded_queue() {
+        let queue = Queue::new(2);
+        assert!(!queue.push(record("one")));
+        assert!(!queue.push(record("two")));
+        assert!(queue.push(record("three")));
+        // Only the first line dropped since the last delivery is reported
+        assert!(!queue.push(record("four")));
+        let taken: Vec<String> = queue.take(10, Duration::ZERO).into_iter().map(|record| record.text).collect();
+        assert_eq!(taken, vec!["three", "four"]);
+        assert_eq!(queue.dropped(), 2);
+        assert_eq!(queue.dropped(), 0);
+        queue.push(record("five"));
+        queue.push(record("six"));
+        assert!(queue.push(record("seven")));
+    }
+
+    #[test]
+    fn test_syslog_udp_and_unix() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = Address::Udp(listener.local_addr().unwrap().to_string());
+        let mut delivery = Delivery::new(Destination::Syslog { address, facility: Facility::default() });
+        let mut pending = VecDeque::from([record("hello")]);
+        delivery.deliver(&mut pending).unwrap();
+        assert!(pending.is_empty());
+
+        let mut buffer = [0u8; 1024];
+        let read = listener.recv(&mut buffer).unwrap();
+        let message = String::from_utf8_lossy(&buffer[..read]).to_string();
+        assert!(message.starts_with("<14>1 "), "{}", message);
+        assert!(message.ends_with(" api 42 out - hello"), "{}", message);
+
+        let path = std::env::temp_dir().join(format!("opm-sink-{}.sock", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let mut delivery = Delivery::new(Destination::Syslog { address: Address::Unix(path.clone()), facility: Facility::default() });
+        delivery.deliver(&mut VecDeque::from([record("local")])).unwrap();
+        let read = socket.recv(&mut buffer).unwrap();
+        assert!(String::from_utf8_lossy(&buffer[..read]).ends_with("- local"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_oversized_syslog_datagram() {
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = Address::Udp(listener.local_addr().unwrap().to_string());
+        let mut delivery = Delivery::new(Destination::Syslog { address, facility: Facility::default() });
+        let mut pending = VecDeque::from([record(&"é".repeat(40_000)), record("after")]);
+        delivery.deliver(&mut pending).unwrap();
+        assert!(pending.is_empty());
+
+        let mut buffer = vec![0u8; 65536];
+        let read = listener.recv(&mut buffer).unwrap();
+        assert!(read <= DATAGRAM_MAX && read > DATAGRAM_MAX - 2);
+        assert!(std::str::from_utf8(&buffer[..read]).unwrap().starts_with("<14>1 "));
+        let read = listener.recv(&mut buffer).unwrap();
+        assert!(String::from_utf8_lossy(&buffer[..read]).ends_with("- after"));
+    }
+
+    #[test]
+    fn test_tcp_retry() {
+        // Nothing listens yet, the line stays pending until the listener is back on the same port
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let mut delivery = Delivery::new(Destination::Json { address: Address::Tcp(format!("127.0.0.1:{}", port)) });
+        let mut pending = VecDeque::from([record(r#"{"level":"warn","msg":"first"}"#), record("second")]);
+        assert!(delivery.deliver(&mut pending).is_err());
+        assert_eq!(pending.len(), 2);
+
+        let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+        delivery.deliver(&mut pending).unwrap();
+        assert!(pending.is_empty());
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        let first: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!((first["msg"].as_str(), first["level"].as_str()), (Some("first"), Some("warn")));
+        let second: Value = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(second["msg"], "second");
+
+        // The listener restarted, the line goes to the new connection rather than the closed one
+        drop((lines, listener));
+        let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        delivery.deliver(&mut VecDeque::from([record("third")])).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let accept = || {
+            std::thread::sleep(Duration::from_millis(20));
+            listener.accept().ok()
+        };
+        let (stream, _) = (0..100).find_map(|_| accept()).expect("no connection to the restarted listener");
+        stream.set_nonblocking(false).unwrap();
+        let third: Value = serde_json::from_str(&BufReader::new(stream).lines().next().unwrap().unwrap()).unwrap();
+        assert_eq!(third["msg"], "third");
+    }
+
+    #[test]
+    fn test_syslog_tcp_octet_counting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = Address::Tcp(listener.local_addr().unwrap().to_string());
+        let mut delivery = Delivery::new(Destination::Syslog { address, facility: Facility::default() });
+        delivery.deliver(&mut VecDeque::from([record("framed")])).unwrap();
+        drop(delivery);
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        let (length, message) = received.split_once(' ').unwrap();
+        assert_eq!(length.parse::<usize>().unwrap(), message.len());
+        assert!(message.ends_with("- framed"));
+    }
+
+    #[test]
+    fn test_loki_push() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                request.push_str(&line);
+            }
+            let mut body = vec![0u8; length];
+            reader.read_exact(&mut body).unwrap();
+            stream.write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n").unwrap();
+            (request, String::from_utf8(body).unwrap())
+        });
+
+        let mut delivery = Delivery::new(Destination::Loki { url, labels: BTreeMap::new(), tenant: Some("team".to_string()) });
+        let mut pending = VecDeque::from([record("pushed")]);
+        delivery.deliver(&mut pending).unwrap();
+        assert!(pending.is_empty());
+
+        let (request, body) = server.join().unwrap();
+        assert!(request.starts_with("POST /loki/api/v1/push "), "{}", request);
+        assert!(request.to_lowercase().contains("x-scope-orgid: team"));
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["streams"][0]["stream"]["process"], "api");
+        assert_eq!(body["streams"][0]["values"][0][1], "pushed");
+    }
+
+    #[test]
+    fn test_read_new_lines() {
+        let dir = std::env::temp_dir().join(format!("opm-sink-tail-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("api-out.log");
+        fs::write(&log, "old\n").unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        let started = SystemTime::now();
+
+        let mut tails = HashMap::new();
+        assert!(read_new(&mut tails, &log, started).is_empty());
+        fs::OpenOptions::new().append(true).open(&log).unwrap().write_all(b"one\ntw").unwrap();
+        assert_eq!(read_new(&mut tails, &log, started), vec!["one"]);
+
+        // Rotated by copy and truncate before the rest of the line was read
+        fs::OpenOptions::new().append(true).open(&log).unwrap().write_all(b"o\n").unwrap();
+        fs::copy(&log, dir.join("api-out.log.1")).unwrap();
+        fs::write(&log, "three\n").unwrap();
+        assert_eq!(read_new(&mut tails, &log, started), vec!["two", "three"]);
+
+        // With compress the rotated copy is only left as `.1.gz`
+        let mut encoder = flate2::write::GzEncoder::new(File::create(dir.join("api-out.log.1.gz")).unwrap(), flate2::Compression::default());
+        encoder.write_all(b"three\nfour\nfive\n").unwrap();
+        encoder.finish().unwrap();
+        fs::remove_file(dir.join("api-out.log.1")).unwrap();
+        fs::write(&log, "six\n").unwrap();
+        assert_eq!(read_new(&mut tails, &log, started), vec!["four", "five", "six"]);
+
+        // The log of a process started later is read from its first line
+        let error = dir.join("api-error.log");
+        fs::write(&error, "starting\n").unwrap();
+        assert_eq!(read_new(&mut tails, &error, started), vec!["starting"]);
+
+        // A line without an end is forwarded in parts instead of growing without bound
+        fs::OpenOptions::new().append(true).open(&error).unwrap().write_all("x".repeat(MAX_LINE * 2 + 10).as_bytes()).unwrap();
+        let parts: Vec<usize> = read_new(&mut tails, &error, started).iter().map(String::len).collect();
+        assert_eq!(parts, vec![MAX_LINE, MAX_LINE]);
+        fs::OpenOptions::new().append(true).open(&error).unwrap().write_all(b"\n\ndone\n").unwrap();
+        assert_eq!(read_new(&mut tails, &error, started), vec!["x".repeat(10), String::new(), "done".to_string()]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
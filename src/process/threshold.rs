@@ -0,0 +1,304 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use utoipa::ToSchema;
+
+/// Window the CPU usage is averaged over when cpu_window_ms is unset
+pub const DEFAULT_CPU_WINDOW_MS: u64 = 60_000;
+
+/// What the daemon does once a process stays above one of its thresholds
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ThresholdAction {
+    /// Restart the process, the breach is recorded in its crash history
+    Restart,
+    /// Stop the process, it stays stopped until started again
+    /// The default, as a process leaking memory from the start would otherwise restart in a loop
+    #[default]
+    Stop,
+    /// Send a signal to the main process, once per breach
+    Signal(String),
+    /// Only emit the event and notification, once per breach
+    NotifyOnly,
+}
+
+impl ThresholdAction {
+    /// Whether the action leaves the process running, so it is repeated only after usage went back under the threshold
+    pub fn keeps_running(&self) -> bool {
+        matches!(self, ThresholdAction::Signal(_) | ThresholdAction::NotifyOnly)
+    }
+}
+
+impl FromStr for ThresholdAction {
+    type Err = String;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        let action = action.trim();
+        if let Some(signal) = action.strip_prefix("signal:") {
+            return crate::helpers::parse_signal(signal).map(ThresholdAction::Signal);
+        }
+        match action.to_lowercase().replace('_', "-").as_str() {
+            "restart" => Ok(ThresholdAction::Restart),
+            "stop" => Ok(ThresholdAction::Stop),
+            "notify-only" | "notify" => Ok(ThresholdAction::NotifyOnly),
+            _ => Err(format!(
+                "Invalid threshold action: {}. Use 'restart', 'stop', 'signal:<SIGNAL>' or 'notify-only'",
+                action
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for ThresholdAction {
+    type Error = String;
+
+    fn try_from(action: String) -> Result<Self, Self::Error> {
+        action.parse()
+    }
+}
+
+impl From<ThresholdAction> for String {
+    fn from(action: ThresholdAction) -> Self {
+        action.to_string()
+    }
+}
+
+impl fmt::Display for ThresholdAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThresholdAction::Restart => write!(f, "restart"),
+            ThresholdAction::Stop => write!(f, "stop"),
+            ThresholdAction::Signal(signal) => write!(f, "signal:{}", signal),
+            ThresholdAction::NotifyOnly => write!(f, "notify-only"),
+        }
+    }
+}
+
+/// When max_memory or max_cpu count as exceeded, and what happens then
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Thresholds {
+    /// restart, stop, signal:<SIGNAL> or notify-only
+    #[schema(value_type = String, example = "signal:SIGUSR2")]
+    #[serde(default)]
+    pub action: ThresholdAction,
+    /// Milliseconds memory must stay above max_memory before the action, the first sample above it acts when unset
+    #[schema(example = 30000)]
+    #[serde(default)]
+    pub memory_for_ms: Option<u64>,
+    /// CPU usage in percent, as shown by `opm list`, averaged over cpu_window_ms
+    #[schema(example = 90.0)]
+    #[serde(default)]
+    pub max_cpu: Option<f64>,
+    /// Milliseconds the CPU usage is averaged over
+    #[schema(example = 60000)]
+    #[serde(default)]
+    pub cpu_window_ms: Option<u64>,
+}
+
+/// Parse a CPU percentage like "90" or "90%"
+pub fn parse_percent(percent: &str) -> Result<f64, String> {
+    match percent.trim().trim_end_matches('%').parse::<f64>() {
+        Ok(value) if value > 0.0 && value.is_finite() => Ok(value),
+        _ => Err(format!("Invalid CPU percentage: {}. Use a number above 0 like 90 or 90%", percent)),
+    }
+}
+
+impl Thresholds {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(max_cpu) = self.max_cpu {
+            parse_percent(&max_cpu.to_string())?;
+        }
+        if self.cpu_window_ms == Some(0) {
+            return Err("cpu_window_ms must be above 0".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn memory_for(&self) -> Duration {
+        Duration::from_millis(self.memory_for_ms.unwrap_or(0))
+    }
+
+    pub fn cpu_window(&self) -> Duration {
+        Duration::from_millis(self.cpu_window_ms.unwrap_or(DEFAULT_CPU_WINDOW_MS))
+    }
+
+    /// Conditions and action, e.g. "memory above 512mb for 30000ms or cpu above 90% over 60000ms: restart"
+    pub fn describe(&self, max_memory: u64) -> String {
+        let mut conditions = vec![];
+        if max_memory > 0 {
+            let memory = format!("memory above {}", crate::helpers::format_memory(max_memory));
+            conditions.push(match self.memory_for_ms {
+                Some(ms) if ms > 0 => format!("{} for {}ms", memory, ms),
+                _ => memory,
+            });
+        }
+        if let Some(max_cpu) = self.max_cpu {
+            conditions.push(format!("cpu above {}% over {}ms", max_cpu, self.cpu_window().as_millis()));
+        }
+        match conditions.is_empty() {
+            true => "none".to_string(),
+            false => format!("{}: {}", conditions.join(" or "), self.action),
+        }
+    }
+
+    /// How often CPU is sampled, every measurement blocks the daemon for a moment
+    pub fn cpu_sample_interval(&self) -> Duration {
+        (self.cpu_window() / 6).clamp(Duration::from_secs(1), Duration::from_secs(10))
+    }
+}
+
+/// Usage of one instance of a process, kept by the daemon between monitoring cycles
+#[derive(Debug)]
+pub struct Usage {
+    pid: i64,
+    memory_above: Option<Instant>,
+    memory_reported: bool,
+    cpu_since: Instant,
+    cpu_samples: VecDeque<(Instant, f64)>,
+    cpu_reported: bool,
+}
+
+impl Usage {
+    pub fn new(pid: i64, now: Instant) -> Self {
+        Usage {
+            pid,
+            memory_above: None,
+            memory_reported: false,
+            cpu_since: now,
+            cpu_samples: VecDeque::new(),
+            cpu_reported: false,
+        }
+    }
+
+    /// Whether this usage was collected for the instance with `pid`
+    pub fn is_for(&self, pid: i64) -> bool {
+        self.pid == pid
+    }
+
+    /// Record a memory sample, returns true when memory stayed above `limit` for `sustained`
+    /// An action that keeps the process running is reported once until memory drops under the limit again
+    pub fn memory(&mut self, now: Instant, rss: u64, limit: u64, sustained: Duration, action: &ThresholdAction) -> bool {
+        if rss <= limit {
+            self.memory_above = None;
+            self.memory_reported = false;
+            return false;
+        }
+
+        let above = *self.memory_above.get_or_insert(now);
+        if now.duration_since(above) < sustained || self.memory_reported {
+            return false;
+        }
+        match action.keeps_running() {
+            true => self.memory_reported = true,
+            false => self.memory_above = None,
+        }
+        true
+    }
+
+    /// Whether the next CPU sample is due
+    pub fn cpu_due(&self, now: Instant, interval: Duration) -> bool {
+        self.cpu_samples.back().is_none_or(|(last, _)| now.duration_since(*last) >= interval)
+    }
+
+    /// Record a CPU sample, returns the average once samples cover `window` and it is above `max`
+    /// An action that keeps the process running is reported once until the average drops under `max` again
+    pub fn cpu(&mut self, now: Instant, percent: f64, max: f64, window: Duration, action: &ThresholdAction) -> Option<f64> {
+        self.cpu_samples.push_back((now, percent));
+        while self.cpu_samples.front().is_some_and(|(time, _)| now.duration_since(*time) > window) {
+            self.cpu_samples.pop_front();
+        }
+        if now.duration_since(self.cpu_since) < window {
+            return None;
+        }
+
+        let average = self.cpu_samples.iter().map(|(_, percent)| percent).sum::<f64>() / self.cpu_samples.len() as f64;
+        if average <= max {
+            self.cpu_reported = false;
+            return None;
+        }
+        if self.cpu_reported {
+            return None;
+        }
+        match action.keeps_running() {
+            true => self.cpu_reported = true,
+            false => {
+                self.cpu_since = now;
+                self.cpu_samples.clear();
+            }
+        }
+        Some(average)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_action_default() {
+        assert_eq!(ThresholdAction::default(), ThresholdAction::Stop);
+        // Processes saved before threshold actions existed keep stopping on a breach
+        let thresholds: Thresholds = serde_json::from_str("{}").unwrap();
+        assert_eq!(thresholds.action, ThresholdAction::Stop);
+    }
+
+    #[test]
+    fn test_threshold_actions() {
+        assert_eq!("restart".parse::<ThresholdAction>().unwrap(), ThresholdAction::Restart);
+        assert_eq!("Stop".parse::<ThresholdAction>().unwrap(), ThresholdAction::Stop);
+        assert_eq!("notify_only".parse::<ThresholdAction>().unwrap(), ThresholdAction::NotifyOnly);
+        let signal = "signal:usr2".parse::<ThresholdAction>().unwrap();
+        assert_eq!(signal, ThresholdAction::Signal("SIGUSR2".to_string()));
+        assert_eq!(signal.to_string(), "signal:SIGUSR2");
+        assert!("signal:SIGNOPE".parse::<ThresholdAction>().is_err());
+        assert!("reload".parse::<ThresholdAction>().is_err());
+
+        assert_eq!(parse_percent("90%"), Ok(90.0));
+        assert!(parse_percent("-5").is_err());
+        assert!(Thresholds { max_cpu: Some(0.0), ..Default::default() }.validate().is_err());
+        assert!(Thresholds { cpu_window_ms: Some(0), ..Default::default() }.validate().is_err());
+        let thresholds = Thresholds { max_cpu: Some(80.0), memory_for_ms: Some(30000), ..Default::default() };
+        assert!(thresholds.validate().is_ok());
+        assert_eq!(thresholds.describe(0), "cpu above 80% over 60000ms: stop");
+        assert_eq!(Thresholds::default().describe(0), "none");
+    }
+
+    #[test]
+    fn test_sustained_breach() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let sustained = Duration::from_secs(10);
+        let mut usage = Usage::new(1, start);
+
+        // A spike that drops back under the limit does not count
+        assert!(!usage.memory(at(0), 200, 100, sustained, &ThresholdAction::Restart));
+        assert!(!usage.memory(at(5), 50, 100, sustained, &ThresholdAction::Restart));
+        assert!(!usage.memory(at(6), 200, 100, sustained, &ThresholdAction::Restart));
+        assert!(!usage.memory(at(15), 200, 100, sustained, &ThresholdAction::Restart));
+        assert!(usage.memory(at(16), 200, 100, sustained, &ThresholdAction::Restart));
+
+        // Notify only reports a breach once until memory went back under the limit
+        let notify = ThresholdAction::NotifyOnly;
+        let mut usage = Usage::new(1, start);
+        assert!(usage.memory(at(0), 200, 100, Duration::ZERO, &notify));
+        assert!(!usage.memory(at(1), 200, 100, Duration::ZERO, &notify));
+        assert!(!usage.memory(at(2), 100, 100, Duration::ZERO, &notify));
+        assert!(usage.memory(at(3), 200, 100, Duration::ZERO, &notify));
+
+        // CPU is averaged over the window, and only once the window is covered
+        let window = Duration::from_secs(30);
+        let mut usage = Usage::new(1, start);
+        assert_eq!(usage.cpu(at(0), 100.0, 50.0, window, &notify), None);
+        assert_eq!(usage.cpu(at(20), 100.0, 50.0, window, &notify), None);
+        assert_eq!(usage.cpu(at(30), 10.0, 50.0, window, &notify), Some(70.0));
+        assert_eq!(usage.cpu(at(40), 100.0, 50.0, window, &notify), None);
+        assert_eq!(usage.cpu(at(61), 0.0, 50.0, window, &notify), None);
+        assert!(usage.cpu(at(62), 100.0, 50.0, window, &notify).is_some_and(|average| average > 66.0));
+        assert!(usage.cpu_due(at(72), Duration::from_secs(10)));
+        assert!(!usage.cpu_due(at(63), Duration::from_secs(10)));
+    }
+}
@@ -0,0 +1,248 @@
+use crate::process::Resources;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Controllers opm needs delegated to the parent cgroup
+const CONTROLLERS: &[&str] = &["memory", "cpu", "pids"];
+
+/// Scheduler period written to cpu.max, quota is derived from the percentage
+pub const CPU_PERIOD_US: u64 = 100_000;
+
+/// cgroup v2 directory a process was placed in
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Cgroup {
+    pub path: PathBuf,
+    /// Value of the oom_kill counter when the process was started
+    /// The directory outlives restarts, so only increases past this point belong to the current run
+    #[serde(default)]
+    pub oom_kills: u64,
+}
+
+fn memory_value(resources: &Resources) -> String {
+    resources
+        .memory_max
+        .map_or_else(|| "max".to_string(), |bytes| bytes.to_string())
+}
+
+/// cpu.max takes "$QUOTA $PERIOD"; a percentage of 150 means 1.5 CPUs
+fn cpu_value(resources: &Resources) -> String {
+    match resources.cpu_max {
+        Some(percent) => format!("{} {}", percent as u64 * CPU_PERIOD_US / 100, CPU_PERIOD_US),
+        None => format!("max {}", CPU_PERIOD_US),
+    }
+}
+
+fn pids_value(resources: &Resources) -> String {
+    resources
+        .pids_max
+        .map_or_else(|| "max".to_string(), |pids| pids.to_string())
+}
+
+/// Turn a process name into a directory name that cannot collide with cgroup interface files
+pub fn dir_name(name: &str, id: usize) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect();
+
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    format!("opm-{}-{}-{}", id, name, &suffix[..8])
+}
+
+/// Best-effort delegation of the controllers to the children of `dir`
+fn enable_controllers(dir: &Path) {
+    let control = dir.join("cgroup.subtree_control");
+    for controller in CONTROLLERS {
+        if let Err(err) = std::fs::write(&control, format!("+{}", controller)) {
+            log::debug!("Failed to enable {} controller in {}: {}", controller, dir.display(), err);
+        }
+    }
+}
+
+fn write_limit(path: &Path, file: &str, value: String, required: bool) -> Result<(), String> {
+    let target = path.join(file);
+    if !required && !target.exists() {
+        return Ok(());
+    }
+
+    std::fs::write(&target, &value).map_err(|err| {
+        format!(
+            "Failed to write '{}' to {}: {}. Check that the controller is delegated to the parent cgroup",
+            value,
+            target.display(),
+            err
+        )
+    })
+}
+
+/// Create (or reuse) the cgroup at `path` and apply the limits
+pub fn create(path: &Path, resources: &Resources) -> Result<Cgroup, String> {
+    let parent = path
+        .parent()
+        .ok_or_else(|| format!("Invalid cgroup path {}", path.display()))?;
+
+    if !parent.exists() {
+        std::fs::create_dir_all(parent).map_err(|err| {
+            format!("Failed to create parent cgroup {}: {}", parent.display(), err)
+        })?;
+        if let Some(grandparent) = parent.parent() {
+            enable_controllers(grandparent);
+        }
+    }
+    enable_controllers(parent);
+
+    if !path.exists() {
+        std::fs::create_dir(path)
+            .map_err(|err| format!("Failed to create cgroup {}: {}", path.display(), err))?;
+    }
+
+    write_limit(path, "memory.max", memory_value(resources), resources.memory_max.is_some())?;
+    write_limit(path, "cpu.max", cpu_value(resources), resources.cpu_max.is_some())?;
+    write_limit(path, "pids.max", pids_value(resources), resources.pids_max.is_some())?;
+
+    Ok(Cgroup {
+        path: path.to_path_buf(),
+        oom_kills: oom_kills(path),
+    })
+}
+
+/// Move the calling process into the cgroup whose cgroup.procs file is `procs`
+/// Runs in the forked child, so only open/write/close are used
+pub fn join(procs: &std::ffi::CStr) -> std::io::Result<()> {
+    unsafe {
+        let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // Writing 0 migrates the writer itself
+        let written = libc::write(fd, b"0".as_ptr() as *const libc::c_void, 1);
+        let err = std::io::Error::last_os_error();
+        libc::close(fd);
+        if written != 1 {
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+/// PIDs currently in the cgroup, None when it cannot be read (removed or not ours)
+pub fn procs(path: &Path) -> Option<Vec<i64>> {
+    let contents = std::fs::read_to_string(path.join("cgroup.procs")).ok()?;
+    Some(contents.lines().filter_map(|line| line.trim().parse().ok()).collect())
+}
+
+fn parse_oom_kills(events: &str) -> u64 {
+    events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Cumulative number of processes in the cgroup killed by the OOM killer
+pub fn oom_kills(path: &Path) -> u64 {
+    std::fs::read_to_string(path.join("memory.events"))
+        .map(|events| parse_oom_kills(&events))
+        .unwrap_or(0)
+}
+
+/// Kill every member of the cgroup, including processes that escaped the session
+pub fn kill(path: &Path) {
+    if std::fs::write(path.join("cgroup.kill"), "1").is_ok() {
+        return;
+    }
+
+    // cgroup.kill needs Linux 5.14, fall back to signalling each member
+    for pid in procs(path).unwrap_or_default() {
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+}
+
+/// Remove the cgroup directory once its members are gone, cgroupfs only allows rmdir
+pub fn remove(path: &Path) {
+    // Killed members take a moment to leave, rmdir fails with EBUSY until then
+    for _ in 0..20 {
+        match std::fs::remove_dir(path) {
+            Ok(()) => return,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+            Err(_) => std::thread::sleep(std::time::Duration::from_millis(25)),
+        }
+    }
+    log::warn!("Failed to remove cgroup {}: still populated", path.display());
+}
+
+impl Cgroup {
+    /// Whether any process is left in the cgroup, None when membership cannot be read
+    pub fn is_populated(&self) -> Option<bool> {
+        let events = std::fs::read_to_string(self.path.join("cgroup.events")).ok()?;
+        match events.lines().find_map(|line| line.strip_prefix("populated ")) {
+            Some(populated) => Some(populated.trim() == "1"),
+            None => procs(&self.path).map(|pids| !pids.is_empty()),
+        }
+    }
+
+    /// Whether the OOM killer fired since this cgroup handle was created
+    pub fn oom_killed(&self) -> bool {
+        oom_kills(&self.path) > self.oom_kills
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_values() {
+        let resources = Resources {
+            memory_max: Some(512 * 1024 * 1024),
+            cpu_max: Some(150),
+            pids_max: None,
+        };
+        assert_eq!(memory_value(&resources), "536870912");
+        assert_eq!(cpu_value(&resources), "150000 100000");
+        assert_eq!(pids_value(&resources), "max");
+        assert_eq!(cpu_value(&Resources::default()), "max 100000");
+    }
+
+    #[test]
+    fn test_parse_oom_kills() {
+        let events = "low 0\nhigh 0\nmax 12\noom 3\noom_kill 2\noom_group_kill 0\n";
+        assert_eq!(parse_oom_kills(events), 2);
+        assert_eq!(parse_oom_kills(""), 0);
+    }
+
+    #[test]
+    fn test_dir_name_is_sanitized() {
+        let name = dir_name("api/server.js", 3);
+        assert!(name.starts_with("opm-3-api_server_js-"), "unexpected name: {}", name);
+        assert!(!name.contains('/'));
+    }
+
+    #[test]
+    fn test_membership_and_oom_from_files() {
+        let dir = std::env::temp_dir().join(format!("opm-cgroup-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cgroup.events"), "populated 0\nfrozen 0\n").unwrap();
+        std::fs::write(dir.join("memory.events"), "oom 1\noom_kill 1\n").unwrap();
+
+        let cgroup = Cgroup { path: dir.clone(), oom_kills: oom_kills(&dir) };
+        assert_eq!(cgroup.is_populated(), Some(false));
+        assert!(!cgroup.oom_killed());
+
+        std::fs::write(dir.join("cgroup.events"), "populated 1\nfrozen 0\n").unwrap();
+        std::fs::write(dir.join("cgroup.procs"), "42\n43\n").unwrap();
+        assert_eq!(cgroup.is_populated(), Some(true));
+        std::fs::write(dir.join("memory.events"), "oom 2\noom_kill 2\n").unwrap();
+        assert_eq!(procs(&dir), Some(vec![42, 43]));
+        assert!(cgroup.oom_killed());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(cgroup.is_populated(), None);
+    }
+}
@@ -0,0 +1,139 @@
+use nix::unistd::{Gid, Group, Uid, User};
+
+/// Resolved identity a child process should switch to before exec
+/// All lookups happen in the parent because NSS calls are not safe after fork()
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Supplementary groups, only populated when switching user
+    pub groups: Vec<u32>,
+    /// User name and home directory, used to fix up USER/LOGNAME/HOME
+    pub name: Option<String>,
+    pub home: Option<String>,
+}
+
+fn lookup_user(user: &str) -> Result<User, String> {
+    let found = match user.parse::<u32>() {
+        Ok(uid) => User::from_uid(Uid::from_raw(uid)),
+        Err(_) => User::from_name(user),
+    };
+
+    match found {
+        Ok(Some(user)) => Ok(user),
+        Ok(None) => Err(format!("User '{}' does not exist", user)),
+        Err(err) => Err(format!("Failed to look up user '{}': {}", user, err)),
+    }
+}
+
+fn lookup_group(group: &str) -> Result<u32, String> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Ok(gid);
+    }
+
+    match Group::from_name(group) {
+        Ok(Some(group)) => Ok(group.gid.as_raw()),
+        Ok(None) => Err(format!("Group '{}' does not exist", group)),
+        Err(err) => Err(format!("Failed to look up group '{}': {}", group, err)),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn supplementary_groups(user: &User, gid: u32) -> Vec<u32> {
+    use std::ffi::CString;
+
+    let Ok(name) = CString::new(user.name.clone()) else {
+        return vec![gid];
+    };
+
+    match nix::unistd::getgrouplist(&name, Gid::from_raw(gid)) {
+        Ok(groups) => groups.into_iter().map(|g| g.as_raw()).collect(),
+        Err(err) => {
+            log::warn!(
+                "Failed to read supplementary groups for '{}': {}",
+                user.name,
+                err
+            );
+            vec![gid]
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn supplementary_groups(_user: &User, gid: u32) -> Vec<u32> {
+    vec![gid]
+}
+
+/// Resolve a user and/or group (name or numeric id) into raw ids
+/// When only a user is given, its primary group is used
+pub fn resolve(user: Option<&str>, group: Option<&str>) -> Result<Credentials, String> {
+    let user = user.map(lookup_user).transpose()?;
+    let explicit_gid = group.map(lookup_group).transpose()?;
+
+    let gid = explicit_gid.or_else(|| user.as_ref().map(|u| u.gid.as_raw()));
+    let groups = match (&user, gid) {
+        (Some(user), Some(gid)) => supplementary_groups(user, gid),
+        _ => vec![],
+    };
+
+    Ok(Credentials {
+        uid: user.as_ref().map(|u| u.uid.as_raw()),
+        gid,
+        groups,
+        name: user.as_ref().map(|u| u.name.clone()),
+        home: user.as_ref().map(|u| u.dir.to_string_lossy().into_owned()),
+    })
+}
+
+/// Apply the resolved identity in the forked child
+/// Only async-signal-safe libc calls (no allocation) are used here; groups and gid are dropped before uid
+pub fn apply(credentials: &Credentials) -> std::io::Result<()> {
+    unsafe {
+        if let Some(gid) = credentials.gid {
+            if credentials.uid.is_some() && libc::getuid() == 0 {
+                let groups = &credentials.groups;
+                if libc::setgroups(groups.len() as _, groups.as_ptr() as *const libc::gid_t) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if libc::setgid(gid as libc::gid_t) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        if let Some(uid) = credentials.uid {
+            if libc::setuid(uid as libc::uid_t) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_numeric_ids() {
+        let credentials = resolve(None, Some("0")).unwrap();
+        assert_eq!(credentials.uid, None);
+        assert_eq!(credentials.gid, Some(0));
+        assert!(credentials.groups.is_empty());
+    }
+
+    #[test]
+    fn test_resolve_user_uses_primary_group() {
+        let credentials = resolve(Some("root"), None).unwrap();
+        assert_eq!(credentials.uid, Some(0));
+        assert_eq!(credentials.gid, Some(0));
+        assert_eq!(credentials.name.as_deref(), Some("root"));
+    }
+
+    #[test]
+    fn test_resolve_unknown_user() {
+        let err = resolve(Some("opm-user-that-does-not-exist"), None).unwrap_err();
+        assert!(err.contains("does not exist"), "unexpected error: {}", err);
+    }
+}
@@ -2,10 +2,13 @@ use std::thread;
 use std::time::{Duration, SystemTime};
 
 pub mod cpu;
+pub mod cgroup;
+pub mod credentials;
 pub mod env;
 pub mod memory;
 pub mod process_info;
 pub mod process_list;
+pub mod rlimits;
 
 pub use cpu::{get_cpu_percent, get_cpu_percent_fast, get_effective_cpu_count};
 pub use env::{env, Vars};
@@ -0,0 +1,274 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::ToSchema;
+
+/// Soft and hard value of a single resource limit (None = unlimited)
+/// Written as "unlimited", a single value for both, or "soft:hard"
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "RlimitValue", into = "String")]
+pub struct Rlimit {
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+}
+
+/// HCL allows `nofile = 65536` as well as `nofile = "4096:65536"`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RlimitValue {
+    Number(u64),
+    Text(String),
+}
+
+impl TryFrom<RlimitValue> for Rlimit {
+    type Error = String;
+
+    fn try_from(value: RlimitValue) -> Result<Self, Self::Error> {
+        match value {
+            RlimitValue::Number(value) => Ok(Rlimit { soft: Some(value), hard: Some(value) }),
+            RlimitValue::Text(value) => Rlimit::parse(&value),
+        }
+    }
+}
+
+impl From<Rlimit> for String {
+    fn from(limit: Rlimit) -> Self {
+        limit.to_string()
+    }
+}
+
+fn parse_value(value: &str) -> Result<Option<u64>, String> {
+    match value.trim() {
+        "unlimited" | "infinity" => Ok(None),
+        value => value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Invalid limit value '{}'", value)),
+    }
+}
+
+fn format_value(value: Option<u64>) -> String {
+    value.map_or_else(|| "unlimited".to_string(), |value| value.to_string())
+}
+
+impl Rlimit {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (soft, hard) = match value.split_once(':') {
+            Some((soft, hard)) => (parse_value(soft)?, parse_value(hard)?),
+            None => {
+                let value = parse_value(value)?;
+                (value, value)
+            }
+        };
+
+        match (soft, hard) {
+            (Some(soft), Some(hard)) if soft > hard => Err(format!(
+                "Invalid limit '{}': soft limit is above the hard limit",
+                value
+            )),
+            (None, Some(_)) => Err(format!(
+                "Invalid limit '{}': soft limit is above the hard limit",
+                value
+            )),
+            _ => Ok(Rlimit { soft, hard }),
+        }
+    }
+
+    fn to_raw(value: Option<u64>) -> libc::rlim_t {
+        value.unwrap_or(libc::RLIM_INFINITY)
+    }
+
+    fn from_raw(value: libc::rlim_t) -> Option<u64> {
+        (value != libc::RLIM_INFINITY).then_some(value)
+    }
+}
+
+impl fmt::Display for Rlimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.soft == self.hard {
+            write!(f, "{}", format_value(self.soft))
+        } else {
+            write!(f, "{}:{}", format_value(self.soft), format_value(self.hard))
+        }
+    }
+}
+
+/// Resource limits applied with setrlimit before exec
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Limits {
+    /// RLIMIT_NOFILE, open file descriptors
+    #[schema(value_type = Option<String>, example = "65536")]
+    #[serde(default)]
+    pub nofile: Option<Rlimit>,
+    /// RLIMIT_NPROC, processes for the user
+    #[schema(value_type = Option<String>, example = "4096")]
+    #[serde(default)]
+    pub nproc: Option<Rlimit>,
+    /// RLIMIT_CORE, core dump size in bytes
+    #[schema(value_type = Option<String>, example = "unlimited")]
+    #[serde(default)]
+    pub core: Option<Rlimit>,
+    /// RLIMIT_AS, address space in bytes
+    #[schema(value_type = Option<String>, example = "unlimited")]
+    #[serde(default, rename = "as")]
+    pub address_space: Option<Rlimit>,
+}
+
+#[cfg(target_os = "linux")]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(target_os = "linux"))]
+type Resource = libc::c_int;
+
+impl Limits {
+    pub fn is_empty(&self) -> bool {
+        self.entries().iter().all(|(_, _, limit)| limit.is_none())
+    }
+
+    fn entries(&self) -> [(&'static str, Resource, Option<Rlimit>); 4] {
+        [
+            ("nofile", libc::RLIMIT_NOFILE as Resource, self.nofile),
+            ("nproc", libc::RLIMIT_NPROC as Resource, self.nproc),
+            ("core", libc::RLIMIT_CORE as Resource, self.core),
+            ("as", libc::RLIMIT_AS as Resource, self.address_space),
+        ]
+    }
+
+    /// Reject limits the child could not set, so the error surfaces before forking
+    /// Raising a hard limit needs CAP_SYS_RESOURCE, which in practice means root
+    pub fn check(&self) -> Result<(), String> {
+        if unsafe { libc::geteuid() } == 0 {
+            return Ok(());
+        }
+
+        for (name, resource, limit) in self.entries() {
+            let Some(limit) = limit else { continue };
+            let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+            if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+                continue;
+            }
+
+            let allowed = match (Rlimit::from_raw(current.rlim_max), limit.hard) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(max), Some(hard)) => hard <= max,
+            };
+            if !allowed {
+                return Err(format!(
+                    "Cannot raise {} hard limit to {}: the daemon's limit is {} and only root can raise it",
+                    name,
+                    format_value(limit.hard),
+                    format_value(Rlimit::from_raw(current.rlim_max))
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the limits in the forked child, setrlimit is async-signal-safe
+    pub fn apply(&self) -> std::io::Result<()> {
+        for (_, resource, limit) in self.entries() {
+            let Some(limit) = limit else { continue };
+            let raw = libc::rlimit {
+                rlim_cur: Rlimit::to_raw(limit.soft),
+                rlim_max: Rlimit::to_raw(limit.hard),
+            };
+            if unsafe { libc::setrlimit(resource, &raw) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// One-line summary such as "nofile=65536 core=unlimited"
+    pub fn describe(&self) -> String {
+        self.entries()
+            .iter()
+            .filter_map(|(name, _, limit)| limit.map(|limit| format!("{}={}", name, limit)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn parse_proc_limits(contents: &str) -> Limits {
+    let find = |label: &str| {
+        contents.lines().find_map(|line| {
+            let mut values = line.strip_prefix(label)?.split_whitespace();
+            let soft = parse_value(values.next()?).ok()?;
+            let hard = parse_value(values.next()?).ok()?;
+            Some(Rlimit { soft, hard })
+        })
+    };
+
+    Limits {
+        nofile: find("Max open files"),
+        nproc: find("Max processes"),
+        core: find("Max core file size"),
+        address_space: find("Max address space"),
+    }
+}
+
+/// Limits actually in effect for a running process, read from /proc/<pid>/limits
+pub fn read(pid: i64) -> Option<Limits> {
+    if pid <= 0 {
+        return None;
+    }
+
+    let contents = std::fs::read_to_string(format!("/proc/{}/limits", pid)).ok()?;
+    Some(parse_proc_limits(&contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rlimit() {
+        assert_eq!(
+            Rlimit::parse("65536").unwrap(),
+            Rlimit { soft: Some(65536), hard: Some(65536) }
+        );
+        assert_eq!(
+            Rlimit::parse("1024:unlimited").unwrap(),
+            Rlimit { soft: Some(1024), hard: None }
+        );
+        assert_eq!(Rlimit::parse("unlimited").unwrap(), Rlimit { soft: None, hard: None });
+        assert!(Rlimit::parse("4096:1024").is_err());
+        assert!(Rlimit::parse("unlimited:1024").is_err());
+        assert!(Rlimit::parse("lots").is_err());
+    }
+
+    #[test]
+    fn test_rlimit_accepts_numbers_and_strings() {
+        let limits: Limits = serde_json::from_str(r#"{"nofile": 4096, "core": "unlimited"}"#).unwrap();
+        assert_eq!(limits.nofile, Some(Rlimit { soft: Some(4096), hard: Some(4096) }));
+        assert_eq!(limits.core, Some(Rlimit { soft: None, hard: None }));
+        assert_eq!(limits.describe(), "nofile=4096 core=unlimited");
+
+        let json = serde_json::to_string(&limits).unwrap();
+        assert_eq!(serde_json::from_str::<Limits>(&json).unwrap(), limits);
+    }
+
+    #[test]
+    fn test_parse_proc_limits() {
+        let contents = "\
+Limit                     Soft Limit           Hard Limit           Units
+Max core file size        0                    unlimited            bytes
+Max processes             24002                24002                processes
+Max open files            1024                 1048576              files
+Max address space         unlimited            unlimited            bytes
+";
+        let limits = parse_proc_limits(contents);
+        assert_eq!(limits.nofile, Some(Rlimit { soft: Some(1024), hard: Some(1048576) }));
+        assert_eq!(limits.nproc, Some(Rlimit { soft: Some(24002), hard: Some(24002) }));
+        assert_eq!(limits.core, Some(Rlimit { soft: Some(0), hard: None }));
+        assert_eq!(limits.address_space, Some(Rlimit { soft: None, hard: None }));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_read_own_limits() {
+        let limits = read(std::process::id() as i64).expect("should read /proc/self/limits");
+        assert!(limits.nofile.is_some());
+    }
+}
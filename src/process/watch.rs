@@ -0,0 +1,543 @@
+use chrono::{DateTime, Utc};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use utoipa::ToSchema;
+
+/// Quiet time after the last change before the process is restarted
+pub const DEFAULT_DEBOUNCE_MS: u64 = 500;
+
+/// Ignored when no ignore globs are given
+pub const DEFAULT_IGNORE: &[&str] = &["node_modules", ".git", "logs", "*.log"];
+
+/// Watchers run by the daemon, by process id
+static WATCHERS: Lazy<Mutex<HashMap<usize, Watcher>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// What a change in a watched path does to the process
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchAction {
+    /// Stop the process and start it again
+    #[default]
+    Restart,
+    /// Start a new instance before stopping the old one, see `opm reload`
+    Reload,
+}
+
+impl FromStr for WatchAction {
+    type Err = String;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action.trim().to_lowercase().as_str() {
+            "restart" => Ok(WatchAction::Restart),
+            "reload" => Ok(WatchAction::Reload),
+            _ => Err(format!("Invalid watch action: {}. Use 'restart' or 'reload'", action)),
+        }
+    }
+}
+
+impl fmt::Display for WatchAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WatchAction::Restart => write!(f, "restart"),
+            WatchAction::Reload => write!(f, "reload"),
+        }
+    }
+}
+
+/// Paths the daemon watches for changes, relative to the process path
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct Watch {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Files or directories, a single `path` in dumps of older versions
+    #[schema(example = json!(["src", "config"]))]
+    #[serde(default, alias = "path", deserialize_with = "one_or_many")]
+    pub paths: Vec<String>,
+    /// Globs of changes to ignore, a glob without `/` matches any file or directory name
+    #[schema(example = json!(["node_modules", "*.tmp"]))]
+    #[serde(default)]
+    pub ignore: Option<Vec<String>>,
+    /// Milliseconds without changes before the process is restarted
+    #[schema(example = 500)]
+    #[serde(default)]
+    pub debounce_ms: Option<u64>,
+    #[serde(default)]
+    pub action: WatchAction,
+}
+
+/// Paths written as a single string or a list, empty strings are dropped
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Paths {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    let paths = match Paths::deserialize(deserializer)? {
+        Paths::One(path) => vec![path],
+        Paths::Many(paths) => paths,
+    };
+    Ok(paths.into_iter().filter(|path| !path.is_empty()).collect())
+}
+
+/// Watch settings given as a path, a list of paths or the full settings, enabled when paths are given
+pub fn deserialize_optional<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Watch>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Given {
+        Path(String),
+        Paths(Vec<String>),
+        Watch(Watch),
+    }
+
+    let watch = match Option::<Given>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(Given::Path(path)) => Watch::new(vec![path]),
+        Some(Given::Paths(paths)) => Watch::new(paths),
+        Some(Given::Watch(watch)) => Watch {
+            enabled: !watch.paths.is_empty(),
+            ..watch
+        },
+    };
+    Ok(watch.enabled.then_some(watch))
+}
+
+impl Watch {
+    /// Watch `paths` with the default settings, disabled when there are none
+    pub fn new(paths: Vec<String>) -> Self {
+        Watch {
+            enabled: !paths.is_empty(),
+            paths,
+            ..Watch::default()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.enabled && self.paths.is_empty() {
+            return Err("Watch needs at least one path".to_string());
+        }
+        Ignore::new(&self.ignore()).map(|_| ())
+    }
+
+    /// Ignore globs, the defaults when none are given
+    pub fn ignore(&self) -> Vec<String> {
+        match &self.ignore {
+            Some(ignore) => ignore.clone(),
+            None => DEFAULT_IGNORE.iter().map(|glob| glob.to_string()).collect(),
+        }
+    }
+
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms.unwrap_or(DEFAULT_DEBOUNCE_MS))
+    }
+
+    /// Paths, ignored globs and action, e.g. "src, config (ignoring node_modules, .git), restart after 500ms"
+    pub fn describe(&self) -> String {
+        if !self.enabled {
+            return "disabled".to_string();
+        }
+        format!(
+            "{} (ignoring {}), {} after {}ms",
+            self.paths.join(", "),
+            self.ignore().join(", "),
+            self.action,
+            self.debounce().as_millis()
+        )
+    }
+}
+
+/// Globs matched against paths relative to the watched path
+struct Ignore(GlobSet);
+
+impl Ignore {
+    fn new(patterns: &[String]) -> Result<Self, String> {
+        let mut set = GlobSetBuilder::new();
+        for pattern in patterns {
+            let trimmed = pattern.trim().trim_matches('/');
+            let base = match trimmed.contains('/') {
+                true => trimmed.to_string(),
+                false => format!("**/{}", trimmed),
+            };
+            for glob in [base.clone(), format!("{}/**", base)] {
+                let glob = GlobBuilder::new(&glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|err| format!("Invalid watch ignore glob '{}': {}", pattern, err))?;
+                set.add(glob);
+            }
+        }
+        set.build().map(Ignore).map_err(|err| err.to_string())
+    }
+
+    fn matches(&self, relative: &Path) -> bool {
+        !relative.as_os_str().is_empty() && self.0.is_match(relative)
+    }
+}
+
+/// Last change the watcher of a process saw
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct Change {
+    #[schema(example = "/srv/app/src/index.js")]
+    pub path: String,
+    pub time: DateTime<Utc>,
+}
+
+/// State of the watcher of a process, for `/process/<id>/watch`
+#[derive(Clone, Debug, Default, Deserialize, Serialize, ToSchema)]
+pub struct Status {
+    /// Directories with an inotify watch
+    pub directories: usize,
+    /// A change waits for the debounce window to pass
+    pub pending: bool,
+    pub last_change: Option<Change>,
+    /// Last error of the watcher, such as reaching fs.inotify.max_user_watches
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct State {
+    status: Status,
+    changed_at: Option<Instant>,
+}
+
+impl State {
+    fn record(&mut self, path: &Path) {
+        self.changed_at = Some(Instant::now());
+        self.status.pending = true;
+        self.status.last_change = Some(Change {
+            path: path.to_string_lossy().into_owned(),
+            time: Utc::now(),
+        });
+    }
+}
+
+struct Watcher {
+    roots: Vec<PathBuf>,
+    ignore: Vec<String>,
+    stop: Arc<AtomicBool>,
+    state: Arc<Mutex<State>>,
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+/// Start the watcher of a process, or replace it when its paths or ignore globs changed
+pub fn sync(id: usize, path: &Path, watch: &Watch) {
+    let roots: Vec<PathBuf> = watch.paths.iter().map(|watched| path.join(watched).components().collect()).collect();
+    let ignore = watch.ignore();
+
+    let mut watchers = WATCHERS.lock().unwrap_or_else(|err| err.into_inner());
+    if watchers.get(&id).is_some_and(|watcher| watcher.roots == roots && watcher.ignore == ignore) {
+        return;
+    }
+
+    let state = Arc::new(Mutex::new(State::default()));
+    let stop = Arc::new(AtomicBool::new(false));
+    match Ignore::new(&ignore) {
+        Ok(matcher) => spawn(id, roots.clone(), matcher, stop.clone(), state.clone()),
+        Err(err) => state.lock().unwrap().status.error = Some(err),
+    }
+    watchers.insert(id, Watcher { roots, ignore, stop, state });
+}
+
+/// Stop the watchers of every process not listed
+pub fn retain(ids: impl IntoIterator<Item = usize>) {
+    let ids: std::collections::HashSet<usize> = ids.into_iter().collect();
+    WATCHERS.lock().unwrap_or_else(|err| err.into_inner()).retain(|id, _| ids.contains(id));
+}
+
+/// Path of the last change once nothing changed for `debounce`, the change is consumed
+pub fn changed(id: usize, debounce: Duration) -> Option<String> {
+    let watchers = WATCHERS.lock().unwrap_or_else(|err| err.into_inner());
+    let mut state = watchers.get(&id)?.state.lock().unwrap_or_else(|err| err.into_inner());
+    if state.changed_at.is_none_or(|at| at.elapsed() < debounce) {
+        return None;
+    }
+    state.changed_at = None;
+    state.status.pending = false;
+    state.status.last_change.as_ref().map(|change| change.path.clone())
+}
+
+/// State of the watcher of a process, None when the daemon does not watch it
+pub fn status(id: usize) -> Option<Status> {
+    let watchers = WATCHERS.lock().unwrap_or_else(|err| err.into_inner());
+    let status = watchers.get(&id)?.state.lock().unwrap_or_else(|err| err.into_inner()).status.clone();
+    Some(status)
+}
+
+#[cfg(target_os = "linux")]
+fn spawn(id: usize, roots: Vec<PathBuf>, ignore: Ignore, stop: Arc<AtomicBool>, state: Arc<Mutex<State>>) {
+    let spawned = std::thread::Builder::new()
+        .name(format!("opm-watch-{}", id))
+        .spawn(move || inotify::run(roots, ignore, stop, state.clone()));
+    if let Err(err) = spawned {
+        log::error!("Failed to start the watcher of process {}: {}", id, err);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn spawn(_: usize, _: Vec<PathBuf>, _: Ignore, _: Arc<AtomicBool>, state: Arc<Mutex<State>>) {
+    state.lock().unwrap().status.error = Some("Watching is only supported on Linux".to_string());
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use super::{Ignore, State};
+    use nix::{
+        errno::Errno,
+        sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor},
+    };
+    use std::{
+        collections::HashMap,
+        ffi::OsString,
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    /// How long the watcher sleeps when no events are queued
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// A watched directory, `only` limits it to one file when a file was given as path
+    struct Dir {
+        root: PathBuf,
+        path: PathBuf,
+        only: Option<OsString>,
+    }
+
+    fn mask() -> AddWatchFlags {
+        AddWatchFlags::IN_MODIFY
+            | AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_ONLYDIR
+    }
+
+    struct Tree<'w> {
+        inotify: &'w Inotify,
+        ignore: &'w Ignore,
+        state: &'w Mutex<State>,
+        dirs: HashMap<WatchDescriptor, Dir>,
+    }
+
+    impl Tree<'_> {
+        fn error(&self, message: String) {
+            log::warn!("{}", message);
+            self.state.lock().unwrap_or_else(|err| err.into_inner()).status.error = Some(message);
+        }
+
+        fn add(&mut self, root: &Path, path: &Path, only: Option<OsString>) -> bool {
+            match self.inotify.add_watch(path, mask()) {
+                Ok(wd) => {
+                    self.dirs.insert(wd, Dir { root: root.to_path_buf(), path: path.to_path_buf(), only });
+                    true
+                }
+                Err(Errno::ENOSPC) => {
+                    self.error(format!(
+                        "Reached fs.inotify.max_user_watches after {} directories, {} is not watched",
+                        self.dirs.len(),
+                        path.display()
+                    ));
+                    false
+                }
+                Err(err) => {
+                    self.error(format!("Failed to watch {}: {}", path.display(), err));
+                    true
+                }
+            }
+        }
+
+        /// Watch a directory and every directory below it that is not ignored
+        fn add_tree(&mut self, root: &Path, path: &Path) {
+            if !self.add(root, path, None) {
+                return;
+            }
+            let Ok(entries) = std::fs::read_dir(path) else {
+                return;
+            };
+            for entry in entries.flatten() {
+                let child = entry.path();
+                let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+                if is_dir && !self.ignore.matches(child.strip_prefix(root).unwrap_or(&child)) {
+                    self.add_tree(root, &child);
+                }
+            }
+        }
+
+        fn add_root(&mut self, root: &Path) {
+            if root.is_dir() {
+                return self.add_tree(root, root);
+            }
+            // A file is watched through its directory, so editors replacing it are still seen
+            match (root.parent(), root.file_name()) {
+                (Some(parent), Some(name)) if parent.is_dir() => {
+                    self.add(root, parent, Some(name.to_os_string()));
+                }
+                _ => self.error(format!("Watch path {} does not exist", root.display())),
+            }
+        }
+
+        fn sync(&mut self) {
+            self.state.lock().unwrap_or_else(|err| err.into_inner()).status.directories = self.dirs.len();
+        }
+    }
+
+    pub(super) fn run(roots: Vec<PathBuf>, ignore: Ignore, stop: Arc<AtomicBool>, state: Arc<Mutex<State>>) {
+        let inotify = match Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC) {
+            Ok(inotify) => inotify,
+            Err(err) => {
+                state.lock().unwrap_or_else(|err| err.into_inner()).status.error = Some(format!("Failed to start inotify: {}", err));
+                return;
+            }
+        };
+
+        let mut tree = Tree {
+            inotify: &inotify,
+            ignore: &ignore,
+            state: &state,
+            dirs: HashMap::new(),
+        };
+        roots.iter().for_each(|root| tree.add_root(root));
+        tree.sync();
+
+        while !stop.load(Ordering::Acquire) {
+            let events = match inotify.read_events() {
+                Ok(events) => events,
+                Err(Errno::EAGAIN) => {
+                    std::thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+                Err(err) => {
+                    tree.error(format!("Failed to read watch events: {}", err));
+                    std::thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+            };
+
+            for event in events {
+                if event.mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+                    if let Some(root) = roots.first() {
+                        state.lock().unwrap_or_else(|err| err.into_inner()).record(root);
+                    }
+                    continue;
+                }
+                if event.mask.contains(AddWatchFlags::IN_IGNORED) {
+                    tree.dirs.remove(&event.wd);
+                    tree.sync();
+                    continue;
+                }
+
+                let Some(dir) = tree.dirs.get(&event.wd) else {
+                    continue;
+                };
+                if dir.only.is_some() && event.name != dir.only {
+                    continue;
+                }
+                let path = match &event.name {
+                    Some(name) => dir.path.join(name),
+                    None => dir.path.clone(),
+                };
+                let root = dir.root.clone();
+                if ignore.matches(path.strip_prefix(&root).unwrap_or(&path)) {
+                    continue;
+                }
+
+                let created = AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO;
+                if event.mask.contains(AddWatchFlags::IN_ISDIR) && event.mask.intersects(created) {
+                    tree.add_tree(&root, &path);
+                    tree.sync();
+                }
+                state.lock().unwrap_or_else(|err| err.into_inner()).record(&path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_settings() {
+        let watch: Watch = ron::from_str(r#"(enabled: true, path: "src", hash: "abc")"#).unwrap();
+        assert_eq!(watch.paths, ["src"]);
+        let watch: Watch = ron::from_str(r#"(enabled: false, path: "", hash: "")"#).unwrap();
+        assert!(watch.paths.is_empty());
+        let watch: Watch = serde_json::from_str(r#"{"enabled": true, "paths": ["src", "config"], "action": "reload"}"#).unwrap();
+        assert_eq!(watch.paths, ["src", "config"]);
+        assert_eq!(watch.action, WatchAction::Reload);
+        assert_eq!(watch.describe(), "src, config (ignoring node_modules, .git, logs, *.log), reload after 500ms");
+
+        assert!(Watch::new(vec![]).validate().is_ok());
+        assert!(Watch { enabled: true, ..Watch::default() }.validate().is_err());
+        assert!(Watch { ignore: Some(vec!["a[".to_string()]), ..Watch::new(vec![".".to_string()]) }.validate().is_err());
+
+        let ignore = Ignore::new(&Watch::new(vec![]).ignore()).unwrap();
+        assert!(ignore.matches(Path::new("node_modules")));
+        assert!(ignore.matches(Path::new("packages/web/node_modules/react/index.js")));
+        assert!(ignore.matches(Path::new(".git/HEAD")));
+        assert!(ignore.matches(Path::new("server.log")));
+        assert!(!ignore.matches(Path::new("src/logger.js")));
+        assert!(!ignore.matches(Path::new("")));
+
+        let ignore = Ignore::new(&["src/generated".to_string(), "*.tmp".to_string()]).unwrap();
+        assert!(ignore.matches(Path::new("src/generated/api.rs")));
+        assert!(!ignore.matches(Path::new("lib/src/generated")));
+        assert!(ignore.matches(Path::new("a/b/c.tmp")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_watcher_reports_changes() {
+        let dir = std::env::temp_dir().join(format!("opm-watch-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::create_dir_all(dir.join("node_modules/pkg")).unwrap();
+
+        let watch = Watch { debounce_ms: Some(50), ..Watch::new(vec![".".to_string()]) };
+        sync(usize::MAX, &dir, &watch);
+        let wait = || {
+            for _ in 0..50 {
+                if let Some(path) = changed(usize::MAX, watch.debounce()) {
+                    return Some(path);
+                }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            None
+        };
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(status(usize::MAX).unwrap().directories, 2);
+
+        std::fs::write(dir.join("node_modules/pkg/index.js"), "ignored").unwrap();
+        assert_eq!(wait(), None);
+
+        std::fs::create_dir(dir.join("src/new")).unwrap();
+        assert!(wait().is_some());
+        std::fs::write(dir.join("src/new/app.js"), "changed").unwrap();
+        assert!(wait().is_some_and(|path| path.ends_with("src/new/app.js")));
+
+        retain([]);
+        assert!(status(usize::MAX).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,246 @@
+//! Secrets encrypted at rest in `~/.opm/secrets`, with the key in `~/.opm/secret.key`
+//!
+//! Processes refer to a secret as `secret://name` in any environment variable, the value is only
+//! decrypted when the process is spawned. Anything shown or stored elsewhere keeps the reference.
+
+use crate::process::Env;
+use global_placeholders::global;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+/// Prefix of a variable value that refers to a secret
+pub const SCHEME: &str = "secret://";
+
+/// Written before the nonce, authenticated along with the secrets
+const HEADER: &[u8] = b"opm-secrets-v1\n";
+const KEY_LEN: usize = 32;
+
+/// Decrypted secrets by name
+#[derive(Default)]
+pub struct Secrets {
+    values: BTreeMap<String, String>,
+    key: PathBuf,
+    store: PathBuf,
+}
+
+impl Secrets {
+    /// Secrets of this user, an empty store when none were set yet
+    pub fn load() -> Result<Self, String> {
+        Self::open(global!("opm.secret.key"), global!("opm.secrets"))
+    }
+
+    /// Secrets to redact output with, empty when the store cannot be read
+    pub fn redactor() -> Self {
+        Self::load().unwrap_or_else(|err| {
+            log::warn!("Secret values are not redacted: {}", err);
+            Self::default()
+        })
+    }
+
+    pub fn open(key: impl Into<PathBuf>, store: impl Into<PathBuf>) -> Result<Self, String> {
+        let (key, store) = (key.into(), store.into());
+        let values = match fs::read(&store) {
+            Ok(data) => decrypt(&read_key(&key)?, &data)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(format!("Failed to read {}: {}", store.display(), err)),
+        };
+        Ok(Self { values, key, store })
+    }
+
+    /// Encrypt the secrets with a new nonce, creating the key on first use
+    pub fn save(&self) -> Result<(), String> {
+        let key = match self.key.exists() {
+            true => read_key(&self.key)?,
+            false => create_key(&self.key)?,
+        };
+        let data = encrypt(&key, &self.values)?;
+
+        let temp = self.store.with_extension("tmp");
+        write_private(&temp, &data, false).and_then(|_| fs::rename(&temp, &self.store)).map_err(|err| {
+            let _ = fs::remove_file(&temp);
+            format!("Failed to write {}: {}", self.store.display(), err)
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.values.get(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: String) -> Result<(), String> {
+        validate(name)?;
+        self.values.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.values.remove(name).is_some()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.values.keys()
+    }
+
+    /// Show every value equal to a secret as its reference, for output only: a variable that merely has the
+    /// same value is rewritten too, so what is stored must never go through this
+    pub fn redact(&self, env: &mut Env) {
+        if self.values.is_empty() {
+            return;
+        }
+        for value in env.values_mut() {
+            let secret = self.values.iter().find(|(_, secret)| !secret.is_empty() && *secret == value);
+            if let Some((name, _)) = secret {
+                *value = reference(name);
+            }
+        }
+    }
+}
+
+/// `secret://<name>`
+pub fn reference(name: &str) -> String {
+    format!("{SCHEME}{name}")
+}
+
+/// Name of the secret a value refers to
+pub fn referenced(value: &str) -> Option<&str> {
+    value.strip_prefix(SCHEME)
+}
+
+/// Names are letters, digits, `_`, `-` and `.`
+pub fn validate(name: &str) -> Result<(), String> {
+    match !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+        true => Ok(()),
+        false => Err(format!("Invalid secret name '{}', use letters, digits, '_', '-' and '.'", name)),
+    }
+}
+
+/// Split `KEY=value` pairs for a new process, decrypting the values that refer to secrets
+/// The store is only opened when a reference is found
+pub fn reveal(env: &[String]) -> Result<Vec<(String, String)>, String> {
+    let mut secrets: Option<Secrets> = None;
+    env.iter()
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let Some(name) = referenced(value) else {
+                return Ok((key.to_string(), value.to_string()));
+            };
+            if secrets.is_none() {
+                secrets = Some(Secrets::load()?);
+            }
+            match secrets.as_ref().and_then(|secrets| secrets.get(name)) {
+                Some(secret) => Ok((key.to_string(), secret.clone())),
+                None => Err(format!("Secret '{}' used by {} is not set, add it with `opm secret set {}`", name, key, name)),
+            }
+        })
+        .collect()
+}
+
+fn read_key(path: &Path) -> Result<LessSafeKey, String> {
+    let bytes = fs::read(path).map_err(|err| format!("Failed to read secret key {}: {}", path.display(), err))?;
+    UnboundKey::new(&AES_256_GCM, &bytes)
+        .map(LessSafeKey::new)
+        .map_err(|_| format!("Secret key {} is not a {} byte key", path.display(), KEY_LEN))
+}
+
+fn create_key(path: &Path) -> Result<LessSafeKey, String> {
+    let mut bytes = [0u8; KEY_LEN];
+    SystemRandom::new().fill(&mut bytes).map_err(|_| "Failed to generate a secret key".to_string())?;
+    write_private(path, &bytes, true).map_err(|err| format!("Failed to create secret key {}: {}", path.display(), err))?;
+    read_key(path)
+}
+
+/// Write a file only the owner can read, `new` refuses to replace an existing one
+fn write_private(path: &Path, data: &[u8], new: bool) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(!new)
+        .create_new(new)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(data)?;
+    file.sync_all()
+}
+
+fn encrypt(key: &LessSafeKey, values: &BTreeMap<String, String>) -> Result<Vec<u8>, String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).map_err(|_| "Failed to generate a nonce".to_string())?;
+
+    let mut sealed = ron::ser::to_string(values).map_err(|err| err.to_string())?.into_bytes();
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(HEADER), &mut sealed)
+        .map_err(|_| "Failed to encrypt secrets".to_string())?;
+
+    Ok([HEADER, &nonce, &sealed].concat())
+}
+
+fn decrypt(key: &LessSafeKey, data: &[u8]) -> Result<BTreeMap<String, String>, String> {
+    let invalid = || "Secrets store is not readable with the secret key, it was changed or replaced".to_string();
+    let data = data.strip_prefix(HEADER).ok_or_else(invalid)?;
+    if data.len() < NONCE_LEN {
+        return Err(invalid());
+    }
+
+    let (nonce, sealed) = data.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| invalid())?;
+    let mut sealed = sealed.to_vec();
+    let plain = key.open_in_place(nonce, Aad::from(HEADER), &mut sealed).map_err(|_| invalid())?;
+
+    let plain = std::str::from_utf8(plain).map_err(|_| invalid())?;
+    ron::from_str(plain).map_err(|err| format!("Secrets store is corrupted: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secrets_store() {
+        let dir = std::env::temp_dir().join(format!("opm-secret-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (key, store) = (dir.join("secret.key"), dir.join("secrets"));
+
+        let mut secrets = Secrets::open(&key, &store).unwrap();
+        secrets.set("db_password", "hunter2".to_string()).unwrap();
+        secrets.set("api.token", "abc".to_string()).unwrap();
+        assert!(secrets.set("bad name", "x".to_string()).is_err());
+        secrets.save().unwrap();
+
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(fs::metadata(&key).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::metadata(&store).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(!String::from_utf8_lossy(&fs::read(&store).unwrap()).contains("hunter2"));
+
+        let mut secrets = Secrets::open(&key, &store).unwrap();
+        assert_eq!(secrets.get("db_password").map(String::as_str), Some("hunter2"));
+        assert_eq!(secrets.names().collect::<Vec<_>>(), ["api.token", "db_password"]);
+        assert!(secrets.remove("api.token"));
+        assert!(!secrets.remove("api.token"));
+
+        let mut env = Env::from([
+            ("DB_PASSWORD".to_string(), "hunter2".to_string()),
+            ("USER".to_string(), "app".to_string()),
+        ]);
+        secrets.redact(&mut env);
+        assert_eq!(env["DB_PASSWORD"], "secret://db_password");
+        assert_eq!(env["USER"], "app");
+
+        // Tampering with the store or a different key is refused
+        let mut data = fs::read(&store).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        fs::write(&store, data).unwrap();
+        assert!(Secrets::open(&key, &store).is_err());
+        fs::write(&key, [7u8; KEY_LEN]).unwrap();
+        secrets.save().unwrap();
+        assert!(Secrets::open(dir.join("other.key"), &store).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -18,6 +18,7 @@ use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::{BufRead, BufReader, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::Path;
 use std::thread;
@@ -49,6 +50,10 @@ pub enum SocketRequest {
         name: Option<String>,
         command: Option<String>,
     },
+    /// Consume a READY=1 notification sent by any of the PIDs, only the daemon receives them
+    TakeNotification(Vec<i64>),
+    /// Copies of the listen sockets the daemon holds, passed with SCM_RIGHTS
+    Listen(Vec<process::listen::Listen>),
     /// Ping to check if daemon is responsive
     Ping,
 }
@@ -64,6 +69,10 @@ pub enum SocketResponse {
     Error(String),
     /// Pong response to Ping
     Pong,
+    /// Whether a READY=1 notification was taken
+    Notified(bool),
+    /// Number of descriptors attached to the response
+    Sockets(usize),
 }
 
 /// Start the Unix socket server in the daemon
@@ -216,6 +225,9 @@ fn handle_client(mut stream: UnixStream) -> Result<()> {
         })?
     }; // BufReader is dropped here, releasing the mutable borrow on stream
 
+    // Descriptors attached to the response, only Listen requests pass any
+    let mut passed: Vec<OwnedFd> = Vec::new();
+
     // Process request
     let response = match request {
         SocketRequest::GetState => {
@@ -335,6 +347,10 @@ fn handle_client(mut stream: UnixStream) -> Result<()> {
                         current.list.insert(id, process);
                     }
 
+                    // Groups are added or updated the same way, then dropped once no process belongs to them
+                    current.groups.extend(runner.groups);
+                    current.prune_groups();
+
                     // Update the ID counter to the maximum of both
                     // Use Relaxed ordering since socket handler is single-threaded and sequential
                     let provided_counter =
@@ -386,6 +402,8 @@ fn handle_client(mut stream: UnixStream) -> Result<()> {
                     .info(id)
                     .map(|p| p.children.clone())
                     .unwrap_or_default();
+                let name = runner.info(id).map(|p| p.name.clone()).unwrap_or_default();
+                let stop = runner.info(id).map(|p| p.exec.stop.clone()).unwrap_or_default();
 
                 // IMPORTANT: Mark process as stopped BEFORE removing from list
                 // This prevents race condition where daemon's restart_process() loop
@@ -414,23 +432,9 @@ fn handle_client(mut stream: UnixStream) -> Result<()> {
                 // Write to memory cache only - don't persist until save
                 dump::write_memory_direct(&runner);
 
-                // Now kill the process
+                // Now kill the process, waiting for it with its stop policy
                 if pid > 0 {
-                    use crate::process::process_stop;
-
-                    // Kill children
-                    for child_pid in children {
-                        let _ = nix::sys::signal::kill(
-                            nix::unistd::Pid::from_raw(child_pid as i32),
-                            nix::sys::signal::Signal::SIGTERM,
-                        );
-                    }
-
-                    // Kill main process
-                    let _ = process_stop(pid);
-
-                    // Wait for termination (simple version without accessing private function)
-                    std::thread::sleep(std::time::Duration::from_millis(500));
+                    process::terminate_and_record(id, &name, pid, children, &stop, "remove");
                 }
 
                 SocketResponse::Success
@@ -450,6 +454,8 @@ fn handle_client(mut stream: UnixStream) -> Result<()> {
                     .info(id)
                     .map(|p| p.children.clone())
                     .unwrap_or_default();
+                let name = runner.info(id).map(|p| p.name.clone()).unwrap_or_default();
+                let stop = runner.info(id).map(|p| p.exec.stop.clone()).unwrap_or_default();
 
                 // Mark as stopped and clear crashed flag
                 runner.process(id).running = false;
@@ -460,20 +466,7 @@ fn handle_client(mut stream: UnixStream) -> Result<()> {
 
                 // Kill the process
                 if pid > 0 {
-                    use crate::process::process_stop;
-
-                    // Kill children
-                    for child_pid in children {
-                        let _ = nix::sys::signal::kill(
-                            nix::unistd::Pid::from_raw(child_pid as i32),
-                            nix::sys::signal::Signal::SIGTERM,
-                        );
-                    }
-
-                    // Kill main process
-                    let _ = process_stop(pid);
-
-                    std::thread::sleep(std::time::Duration::from_millis(500));
+                    process::terminate_and_record(id, &name, pid, children, &stop, "stop");
                 }
 
                 SocketResponse::Success
@@ -516,21 +509,12 @@ fn handle_client(mut stream: UnixStream) -> Result<()> {
                     .info(id)
                     .map(|p| p.children.clone())
                     .unwrap_or_default();
+                let name = runner.info(id).map(|p| p.name.clone()).unwrap_or_default();
+                let stop = runner.info(id).map(|p| p.exec.stop.clone()).unwrap_or_default();
 
                 // Kill existing process
                 if pid > 0 {
-                    use crate::process::process_stop;
-
-                    // Kill children
-                    for child_pid in children {
-                        let _ = nix::sys::signal::kill(
-                            nix::unistd::Pid::from_raw(child_pid as i32),
-                            nix::sys::signal::Signal::SIGTERM,
-                        );
-                    }
-
-                    // Kill main process
-                    let _ = process_stop(pid);
+                    process::terminate_and_record(id, &name, pid, children, &stop, "restart");
 
                     // Remove process handle if it exists
                     let handle_pid = shell_pid.unwrap_or(pid);
@@ -539,8 +523,6 @@ fn handle_client(mut stream: UnixStream) -> Result<()> {
                             let _ = child.wait();
                         }
                     }
-
-                    std::thread::sleep(std::time::Duration::from_millis(500));
                 }
 
                 // Mark for restart - daemon will spawn the process
@@ -585,6 +567,14 @@ fn handle_client(mut stream: UnixStream) -> Result<()> {
                 SocketResponse::Error(format!("Process {} not found", id))
             }
         }
+        SocketRequest::TakeNotification(pids) => SocketResponse::Notified(process::health::take_notification(&pids)),
+        SocketRequest::Listen(listen) => match process::listen::sockets(&listen) {
+            Ok(sockets) => {
+                passed = sockets;
+                SocketResponse::Sockets(passed.len())
+            }
+            Err(err) => SocketResponse::Error(err),
+        },
         SocketRequest::Ping => SocketResponse::Pong,
     };
 
@@ -597,8 +587,17 @@ fn handle_client(mut stream: UnixStream) -> Result<()> {
     let response_len = response_json.len();
     log::debug!("[socket] Sending response ({} bytes)", response_len);
 
+    // The descriptors travel with the first bytes of the response
+    let sent = match passed.is_empty() {
+        true => 0,
+        false => send_with_fds(&stream, response_json.as_bytes(), &passed).map_err(|e| {
+            log::error!("[socket] Failed to pass sockets: {}", e);
+            anyhow!("Failed to pass sockets: {}", e)
+        })?,
+    };
+
     // Write response with error handling
-    if let Err(e) = stream.write_all(response_json.as_bytes()) {
+    if let Err(e) = stream.write_all(&response_json.as_bytes()[sent..]) {
         log::error!("[socket] Failed to write response body: {}", e);
         return Err(anyhow!("Failed to write response: {}", e));
     }
@@ -785,6 +784,103 @@ pub(crate) fn send_request_once(
     Ok(response)
 }
 
+/// Ask the daemon for copies of the listen sockets it holds, binding them on first use
+pub fn request_sockets(socket_path: &str, listen: &[process::listen::Listen]) -> Result<Vec<OwnedFd>> {
+    request_fds(socket_path, &SocketRequest::Listen(listen.to_vec()), listen.len())
+}
+
+/// Send a request answered with up to `max` descriptors
+fn request_fds(socket_path: &str, request: &SocketRequest, max: usize) -> Result<Vec<OwnedFd>> {
+    let mut stream = UnixStream::connect(socket_path)
+        .map_err(|e| anyhow!("Failed to connect to daemon socket: {}. Is the daemon running?", e))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(30)))?;
+    stream.set_write_timeout(Some(std::time::Duration::from_secs(30)))?;
+
+    let request_json = serde_json::to_string(request)?;
+    stream.write_all(request_json.as_bytes())?;
+    stream.write_all(b"\n")?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+
+    // The descriptors arrive with the first bytes, the rest of the line is read normally
+    let mut buffer = vec![0u8; 4096];
+    let (read, fds) = recv_with_fds(&stream, &mut buffer, max)?;
+    let mut line = buffer[..read].to_vec();
+    if !line.contains(&b'\n') {
+        BufReader::new(&stream).read_until(b'\n', &mut line)?;
+    }
+
+    match serde_json::from_slice::<SocketResponse>(&line)? {
+        SocketResponse::Sockets(count) if count == fds.len() => Ok(fds),
+        SocketResponse::Sockets(count) => Err(anyhow!("Daemon passed {} of {} descriptors", fds.len(), count)),
+        SocketResponse::Error(err) => Err(anyhow!(err)),
+        response => Err(anyhow!("Unexpected response: {:?}", response)),
+    }
+}
+
+/// Send `data` with the descriptors attached as SCM_RIGHTS, returns how many bytes went out
+fn send_with_fds(stream: &UnixStream, data: &[u8], fds: &[OwnedFd]) -> std::io::Result<usize> {
+    let raw: Vec<RawFd> = fds.iter().map(AsRawFd::as_raw_fd).collect();
+    let payload = std::mem::size_of_val(raw.as_slice()) as libc::c_uint;
+    let mut control = vec![0u8; unsafe { libc::CMSG_SPACE(payload) } as usize];
+    let mut iov = libc::iovec { iov_base: data.as_ptr() as *mut libc::c_void, iov_len: data.len() };
+
+    let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = control.len() as _;
+
+    let sent = unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(payload) as _;
+        std::ptr::copy_nonoverlapping(raw.as_ptr(), libc::CMSG_DATA(header) as *mut RawFd, raw.len());
+        libc::sendmsg(stream.as_raw_fd(), &message, libc::MSG_NOSIGNAL)
+    };
+    match sent < 0 {
+        true => Err(std::io::Error::last_os_error()),
+        false => Ok(sent as usize),
+    }
+}
+
+/// Receive into `buffer` along with up to `max` descriptors, returns the bytes read and the descriptors
+fn recv_with_fds(stream: &UnixStream, buffer: &mut [u8], max: usize) -> std::io::Result<(usize, Vec<OwnedFd>)> {
+    let payload = (max.max(1) * std::mem::size_of::<RawFd>()) as libc::c_uint;
+    let mut control = vec![0u8; unsafe { libc::CMSG_SPACE(payload) } as usize];
+    let mut iov = libc::iovec { iov_base: buffer.as_mut_ptr() as *mut libc::c_void, iov_len: buffer.len() };
+
+    let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = control.len() as _;
+
+    let read = unsafe { libc::recvmsg(stream.as_raw_fd(), &mut message, libc::MSG_CMSG_CLOEXEC) };
+    if read < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut fds = Vec::new();
+    unsafe {
+        let mut header = libc::CMSG_FIRSTHDR(&message);
+        while !header.is_null() {
+            if (*header).cmsg_level == libc::SOL_SOCKET && (*header).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(header) as *const RawFd;
+                let count = ((*header).cmsg_len as usize - libc::CMSG_LEN(0) as usize) / std::mem::size_of::<RawFd>();
+                for index in 0..count {
+                    fds.push(OwnedFd::from_raw_fd(std::ptr::read_unaligned(data.add(index))));
+                }
+            }
+            header = libc::CMSG_NXTHDR(&message, header);
+        }
+    }
+    if message.msg_flags & libc::MSG_CTRUNC != 0 {
+        return Err(std::io::Error::other("descriptors were truncated"));
+    }
+    Ok((read as usize, fds))
+}
+
 /// Check if daemon is running by attempting to ping via socket
 pub fn is_daemon_running(socket_path: &str) -> bool {
     match send_request(socket_path, SocketRequest::Ping) {
@@ -5,7 +5,7 @@ import Header from '@/components/react/header';
 import ToastContainer from '@/components/react/toast';
 import { useToast } from '@/components/react/useToast';
 
-type EventType = 'agentconnect' | 'agentdisconnect' | 'processstart' | 'processstop' | 'processcrash' | 'processrestart' | 'processdelete';
+type EventType = 'agentconnect' | 'agentdisconnect' | 'processstart' | 'processstop' | 'processcrash' | 'processrestart' | 'processdelete' | 'processkilled' | 'processunhealthy' | 'processscheduledrestart' | 'processthresholdexceeded';
 
 interface Event {
 	id: string;
@@ -32,6 +32,10 @@ const EventsPage = (props: { base: string }) => {
 		processcrash: 'bg-red-500/10 text-red-400 border-red-500/20',
 		processrestart: 'bg-purple-500/10 text-purple-400 border-purple-500/20',
 		processdelete: 'bg-orange-500/10 text-orange-400 border-orange-500/20',
+		processkilled: 'bg-red-500/10 text-red-400 border-red-500/20',
+		processunhealthy: 'bg-amber-500/10 text-amber-400 border-amber-500/20',
+		processscheduledrestart: 'bg-sky-500/10 text-sky-400 border-sky-500/20',
+		processthresholdexceeded: 'bg-yellow-500/10 text-yellow-400 border-yellow-500/20',
 	};
 
 	const eventIcons: Record<EventType, string> = {
@@ -42,6 +46,10 @@ const EventsPage = (props: { base: string }) => {
 		processcrash: '💥',
 		processrestart: '🔄',
 		processdelete: '🗑️',
+		processkilled: '☠️',
+		processunhealthy: '🩺',
+		processscheduledrestart: '⏰',
+		processthresholdexceeded: '📈',
 	};
 
 	const formatEventType = (type: EventType): string => {
@@ -53,6 +61,10 @@ const EventsPage = (props: { base: string }) => {
 			processcrash: 'Process Crash',
 			processrestart: 'Process Restart',
 			processdelete: 'Process Delete',
+			processkilled: 'Process Killed',
+			processunhealthy: 'Process Unhealthy',
+			processscheduledrestart: 'Process Scheduled Restart',
+			processthresholdexceeded: 'Process Threshold Exceeded',
 		};
 		return typeMap[type] || type;
 	};
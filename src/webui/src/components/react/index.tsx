@@ -66,6 +66,10 @@ const Index = (props: { base: string }) => {
 
 	const badge = {
 		online: 'bg-emerald-400',
+		launching: 'bg-sky-400',
+		scheduled: 'bg-sky-400',
+		completed: 'bg-emerald-400',
+		failed: 'bg-red-500',
 		stopped: 'bg-red-500',
 		crashed: 'bg-amber-400'
 	};
@@ -462,6 +466,8 @@ const Index = (props: { base: string }) => {
 							className="w-full sm:w-auto px-4 py-2.5 bg-white dark:bg-zinc-900/50 border border-gray-300 dark:border-zinc-700/50 rounded-lg text-gray-700 dark:text-zinc-200 focus:outline-none focus:ring-2 focus:ring-blue-500/50 focus:border-blue-500 transition-all">
 							<option value="all">All Status</option>
 							<option value="online">Online</option>
+							<option value="launching">Launching</option>
+							<option value="scheduled">Scheduled</option>
 							<option value="stopped">Stopped</option>
 							<option value="crashed">Crashed</option>
 						</select>
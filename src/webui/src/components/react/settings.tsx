@@ -20,6 +20,7 @@ interface NotificationConfig {
 		process_crash: boolean;
 		process_restart: boolean;
 		process_delete: boolean;
+		process_threshold_exceeded: boolean;
 	};
 	channels: string[];
 }
@@ -46,6 +47,7 @@ const SettingsPage = (props: { base: string }) => {
 			process_crash: false,
 			process_restart: false,
 			process_delete: false,
+			process_threshold_exceeded: false,
 		},
 		channels: [],
 	});
@@ -326,6 +326,10 @@ const View = (props: { id: string; base: string }) => {
 
 	const badge = {
 		online: 'bg-emerald-400/10 text-emerald-400',
+		launching: 'bg-sky-400/10 text-sky-400',
+		scheduled: 'bg-sky-400/10 text-sky-400',
+		completed: 'bg-emerald-400/10 text-emerald-400',
+		failed: 'bg-red-500/10 text-red-500',
 		stopped: 'bg-red-500/10 text-red-500',
 		crashed: 'bg-amber-400/10 text-amber-400'
 	};